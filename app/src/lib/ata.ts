@@ -11,13 +11,14 @@ const encoder = getAddressEncoder();
 
 export async function getAssociatedTokenAddress(
   wallet: Address,
-  mint: Address
+  mint: Address,
+  tokenProgram: Address = TOKEN_PROGRAM
 ): Promise<Address> {
   const [ata] = await getProgramDerivedAddress({
     programAddress: ATA_PROGRAM,
     seeds: [
       encoder.encode(wallet),
-      encoder.encode(TOKEN_PROGRAM),
+      encoder.encode(tokenProgram),
       encoder.encode(mint),
     ],
   });
@@ -18,9 +18,18 @@ export const PROGRAM_ID = address(
 export const TOKEN_PROGRAM_ID = address(
   "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
 );
+export const TOKEN_2022_PROGRAM_ID = address(
+  "TokenzQdBNbLqP5VEhdkAS6EPFLC8PHnSnTg9gj8zfQ"
+);
 export const SYSTEM_PROGRAM_ID = address(
   "11111111111111111111111111111111"
 );
+export const INSTRUCTIONS_SYSVAR_ID = address(
+  "Sysvar1nstructions1111111111111111111111111"
+);
+export const SLOT_HASHES_SYSVAR_ID = address(
+  "SysvarS1otHashes111111111111111111111111111"
+);
 export const ADMIN_ADDRESS = address(
   "HyBxuaafzKP6k4zkEDUp4LrZctS9mJVNUEEJBmp9cp7L"
 );
@@ -29,6 +38,7 @@ const SEED_PREFIX = new TextEncoder().encode("redpacket");
 const VAULT_SEED = new TextEncoder().encode("vault");
 const TREASURY_SEED = new TextEncoder().encode("treasury");
 const TREASURY_VAULT_SEED = new TextEncoder().encode("treasury_vault");
+const RECEIPT_SEED = new TextEncoder().encode("receipt");
 
 const NATIVE_SOL_MINT = new Uint8Array(32).fill(0xff);
 
@@ -36,9 +46,56 @@ export const TOKEN_TYPE_SPL = 0;
 export const TOKEN_TYPE_SOL = 1;
 export const SPLIT_EVEN = 0;
 export const SPLIT_RANDOM = 1;
-export const MAX_RECIPIENTS = 20;
+// Fixed unequal amounts: create ends with one u16 weight per slot
+export const SPLIT_WEIGHTED = 2;
+export const WEIGHT_TOTAL_BPS = 10_000; // weighted splits' weights sum to this
+// Random draws, plus a 60% jackpot finalize hands one claimed slot
+export const SPLIT_LUCKY = 3;
+// Claims only enter; finalize pays the whole pot to one drawn entrant
+export const SPLIT_RAFFLE = 4;
+export const MAX_RECIPIENTS = 100;
+export const MAX_RECIPIENTS_LARGE = 255;
+// RedPacket flags byte (offset 80)
+export const FLAG_ALLOW_REPEAT = 1 << 0;
+// Create sets it on every split mode unless FLAG_CREATOR_CAN_CLAIM is passed
+export const FLAG_NO_SELF_CLAIM = 1 << 1;
+// Large packets dedupe claims by receipt PDA and have no claimers array
+export const FLAG_LARGE = 1 << 2;
+// Set by the program on random packets: each claim draws its own amount
+export const FLAG_RANDOM_ONCHAIN = 1 << 3;
+// Set by the program once a commit-reveal packet's creator reveals its secret
+export const FLAG_REVEALED = 1 << 4;
+// Create only: lets the creator claim their own packet
+export const FLAG_CREATOR_CAN_CLAIM = 1 << 5;
+// Set by the program once finalize pays a lucky packet's jackpot
+export const FLAG_LUCKY_PAID = 1 << 6;
+// Each slot unlocks linearly from vestingStart to expiresAt; claimers claim
+// again as it does
+export const FLAG_VESTING = 1 << 7;
+// Create options byte, right after the flags (never stored as flags)
+// SPL only (stored as burnUnclaimed): close and partial withdraw burn the
+// leftover tokens instead of refunding them
+export const CREATE_BURN_UNCLAIMED = 1 << 0;
+// Stored as donateUnclaimed: close and partial withdraw pay leftovers to the
+// treasury instead of refunding them
+export const CREATE_DONATE_UNCLAIMED = 1 << 1;
+// Claim options byte
+export const CLAIM_TO_DESTINATION = 1 << 0;
+export const CLAIM_EXACT_SLOT = 1 << 1;
+// Red packet layout version (offset 79); older packets need migrate
+export const REDPACKET_VERSION = 16;
+// Longest claim password the program accepts (bytes)
+export const MAX_SECRET_LEN = 64;
+// Longest creator memo the program stores (UTF-8 bytes)
+export const MAX_MEMO_LEN = 64;
 
-const FEE_RATE_BPS = 10n;
+// Default treasury fee; each treasury stores its own fee_bps
+export const FEE_RATE_BPS = 10;
+export const MAX_FEE_BPS = 500;
+// Default treasury expiry bounds (seconds from now); set_expiry_bounds may
+// narrow them, never past MAX_EXPIRY_SECONDS
+export const MIN_EXPIRY_SECONDS = 60n;
+export const MAX_EXPIRY_SECONDS = 365n * 24n * 60n * 60n;
 const FEE_DENOMINATOR = 10_000n;
 
 // ============================================================
@@ -74,6 +131,16 @@ export async function findVaultPDA(
   });
 }
 
+export async function findReceiptPDA(
+  redPacket: Address,
+  claimer: Address
+) {
+  return getProgramDerivedAddress({
+    programAddress: PROGRAM_ID,
+    seeds: [RECEIPT_SEED, addressEncoder.encode(redPacket), addressEncoder.encode(claimer)],
+  });
+}
+
 export async function findTreasuryPDA(
   mint: Address | "SOL"
 ) {
@@ -98,8 +165,12 @@ export async function findTreasuryVaultPDA(
 // Fee computation
 // ============================================================
 
-export function computeFee(totalAmount: bigint): bigint {
-  const fee = (totalAmount * FEE_RATE_BPS) / FEE_DENOMINATOR;
+export function computeFee(
+  totalAmount: bigint,
+  feeBps: number = FEE_RATE_BPS
+): bigint {
+  if (feeBps === 0) return 0n;
+  const fee = (totalAmount * BigInt(feeBps)) / FEE_DENOMINATOR;
   return fee > 0n ? fee : 1n;
 }
 
@@ -109,7 +180,10 @@ export function computeFee(totalAmount: bigint): bigint {
 
 // Disc 0: create
 // Data: [disc=0][token_type:u8][id:u64][total_amount:u64][num_recipients:u8]
-//       [split_mode:u8][expires_at:i64][rp_bump:u8][vault_bump:u8][amounts?:u64*N]
+//       [split_mode:u8][expires_at:i64][rp_bump:u8][vault_bump:u8]
+//       [flags?:u8][secret_hash?:32][voucher_authority?:32]
+//       [gate_mint?:32][gate_min_amount?:u64][recipients?:32*N]
+//       [memo_len?:u8][memo?:64][weights?:u16*N]
 export interface CreateParams {
   tokenType: number;
   id: bigint;
@@ -119,7 +193,19 @@ export interface CreateParams {
   expiresAt: bigint;
   rpBump: number;
   vaultBump: number;
-  amounts?: bigint[]; // required for SPLIT_RANDOM
+  allowRepeat?: boolean; // one wallet may claim several slots
+  noSelfClaim?: boolean; // the creator may not claim their own packet
+  creatorCanClaim?: boolean; // opt out of the default NO_SELF_CLAIM
+  large?: boolean; // up to MAX_RECIPIENTS_LARGE slots; no repeats or recipients
+  burnUnclaimed?: boolean; // SPL only: leftovers are burned at close instead of refunded
+  donateUnclaimed?: boolean; // leftovers go to the treasury at close; not with burnUnclaimed
+  secretHash?: Uint8Array; // sha256 of the claim password (32 bytes)
+  voucherAuthority?: Address; // key that must sign each claim's voucher
+  gate?: { mint: Address; minAmount: bigint }; // claimers must hold this mint
+  recipients?: Address[]; // one wallet per slot: only it may claim that slot
+  memo?: string; // greeting shown to claimers, up to MAX_MEMO_LEN UTF-8 bytes
+  weights?: number[]; // SPLIT_WEIGHTED only: basis points per slot, summing to WEIGHT_TOTAL_BPS
+  tokenProgram?: Address; // SPL only; TOKEN_2022_PROGRAM_ID for Token-2022 mints
   // Accounts
   creator: Address;
   creatorTokenAccount?: Address; // SPL only
@@ -135,9 +221,31 @@ export function buildCreateInstruction(p: CreateParams): Instruction {
 
   // Build data
   const baseLen = 1 + 1 + 8 + 8 + 1 + 1 + 8 + 1 + 1; // 30 bytes
-  const amountsLen =
-    p.splitMode === SPLIT_RANDOM ? 8 * p.numRecipients : 0;
-  const data = new Uint8Array(baseLen + amountsLen);
+  // Trailing fields are positional: each needs those before it (zeros = unset)
+  const hasGate = p.gate !== undefined || p.recipients !== undefined;
+  const hasVoucher = p.voucherAuthority !== undefined || hasGate;
+  const hasHash = p.secretHash !== undefined || hasVoucher;
+  const flags =
+    (p.allowRepeat ? FLAG_ALLOW_REPEAT : 0) |
+    (p.noSelfClaim ? FLAG_NO_SELF_CLAIM : 0) |
+    (p.creatorCanClaim ? FLAG_CREATOR_CAN_CLAIM : 0) |
+    (p.large ? FLAG_LARGE : 0);
+  const options =
+    (p.burnUnclaimed ? CREATE_BURN_UNCLAIMED : 0) | (p.donateUnclaimed ? CREATE_DONATE_UNCLAIMED : 0);
+  const hasOptions = options !== 0 || hasHash || p.memo !== undefined;
+  const hasFlag = flags !== 0 || hasOptions;
+  const memo = p.memo !== undefined ? new TextEncoder().encode(p.memo) : null;
+  const data = new Uint8Array(
+    baseLen +
+      (hasFlag ? 1 : 0) +
+      (hasOptions ? 1 : 0) +
+      (hasHash ? 32 : 0) +
+      (hasVoucher ? 32 : 0) +
+      (hasGate ? 40 : 0) +
+      32 * (p.recipients?.length ?? 0) +
+      (memo ? 1 + MAX_MEMO_LEN : 0) +
+      2 * (p.weights?.length ?? 0)
+  );
   const view = new DataView(data.buffer);
 
   let offset = 0;
@@ -154,10 +262,55 @@ export function buildCreateInstruction(p: CreateParams): Instruction {
   data[offset++] = p.rpBump;
   data[offset++] = p.vaultBump;
 
-  if (p.splitMode === SPLIT_RANDOM && p.amounts) {
-    for (const amt of p.amounts) {
-      view.setBigUint64(offset, amt, true);
-      offset += 8;
+  if (hasFlag) {
+    data[offset++] = flags; // omitted = one claim per wallet, creator included
+  }
+  if (hasOptions) {
+    data[offset++] = options; // omitted = leftovers are refunded
+  }
+  if (hasHash) {
+    if (p.secretHash && p.secretHash.length !== 32) {
+      throw new Error("secretHash must be 32 bytes");
+    }
+    if (p.secretHash) data.set(p.secretHash, offset);
+    offset += 32;
+  }
+  if (hasVoucher) {
+    if (p.voucherAuthority) data.set(addressEncoder.encode(p.voucherAuthority), offset);
+    offset += 32;
+  }
+  if (hasGate) {
+    if (p.gate) data.set(addressEncoder.encode(p.gate.mint), offset);
+    offset += 32;
+    view.setBigUint64(offset, p.gate?.minAmount ?? 0n, true);
+    offset += 8;
+  }
+  if (p.recipients) {
+    if (p.recipients.length !== p.numRecipients) {
+      throw new Error("recipients must list one wallet per slot");
+    }
+    for (const recipient of p.recipients) {
+      data.set(addressEncoder.encode(recipient), offset);
+      offset += 32;
+    }
+  }
+  // The memo is zero-padded to MAX_MEMO_LEN
+  if (memo) {
+    if (memo.length > MAX_MEMO_LEN) {
+      throw new Error(`memo must be at most ${MAX_MEMO_LEN} bytes`);
+    }
+    data[offset] = memo.length;
+    data.set(memo, offset + 1);
+    offset += 1 + MAX_MEMO_LEN;
+  }
+  // Weights are always the final bytes, after whatever optional fields precede them
+  if (p.weights) {
+    if (p.weights.length !== p.numRecipients) {
+      throw new Error("weights must list one weight per slot");
+    }
+    for (const weight of p.weights) {
+      view.setUint16(offset, weight, true);
+      offset += 2;
     }
   }
 
@@ -187,7 +340,7 @@ export function buildCreateInstruction(p: CreateParams): Instruction {
       { address: p.treasury, role: AccountRole.READONLY },
       { address: p.treasuryVault!, role: AccountRole.WRITABLE },
       { address: p.mint!, role: AccountRole.READONLY },
-      { address: TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
+      { address: p.tokenProgram ?? TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
       { address: SYSTEM_PROGRAM_ID, role: AccountRole.READONLY },
     ],
     data,
@@ -195,35 +348,73 @@ export function buildCreateInstruction(p: CreateParams): Instruction {
 }
 
 // Disc 1: claim
-// Data: [disc=1][token_type:u8][slot_index:u8]
+// Data: [disc=1][token_type:u8][slot_index:u8][options:u8][secret?:bytes]
 export interface ClaimParams {
   tokenType: number;
   slotIndex: number;
+  // Take exactly slotIndex, which must be unclaimed (and on assigned packets
+  // assigned to the claimer), instead of the lowest unclaimed slot
+  exactSlot?: boolean;
+  secret?: Uint8Array; // password preimage, for password-protected packets
+  // Voucher packets: the backend's ed25519 instruction, signing slotIndex,
+  // must come right before this one (with exactSlot set), and the
+  // instructions sysvar is appended
+  voucher?: boolean;
+  // Gated packets: the claimer's token account for the gate mint (after the
+  // sysvar when both apply)
+  gateAccount?: Address;
+  // Pay this account instead of the claimer (a wallet for SOL, a token
+  // account of the packet's mint for SPL); the claimer is still recorded
+  destination?: Address;
+  // Large packets: the claimer's findReceiptPDA (the claimer pays its rent)
+  receipt?: Address;
+  tokenProgram?: Address; // SPL only; TOKEN_2022_PROGRAM_ID for Token-2022 mints
   // Accounts
   claimer: Address;
   claimerTokenAccount?: Address; // SPL only
   redPacket: Address;
   vault: Address;
+  treasury: Address; // the packet's mint treasury (checked for pause)
 }
 
 export function buildClaimInstruction(p: ClaimParams): Instruction {
-  const data = new Uint8Array([1, p.tokenType, p.slotIndex]);
+  const secret = p.secret ?? new Uint8Array();
+  if (secret.length > MAX_SECRET_LEN) {
+    throw new Error(`Password is longer than ${MAX_SECRET_LEN} bytes`);
+  }
+  const data = new Uint8Array(4 + secret.length);
+  const options = (p.destination ? CLAIM_TO_DESTINATION : 0) | (p.exactSlot ? CLAIM_EXACT_SLOT : 0);
+  data.set([1, p.tokenType, p.slotIndex, options]);
+  data.set(secret, 4);
   const isSol = p.tokenType === TOKEN_TYPE_SOL;
+  const extraAccounts = [
+    ...(p.voucher ? [{ address: INSTRUCTIONS_SYSVAR_ID, role: AccountRole.READONLY }] : []),
+    ...(p.gateAccount ? [{ address: p.gateAccount, role: AccountRole.READONLY }] : []),
+    ...(p.destination ? [{ address: p.destination, role: AccountRole.WRITABLE }] : []),
+    ...(p.receipt
+      ? [
+          { address: p.receipt, role: AccountRole.WRITABLE },
+          { address: SYSTEM_PROGRAM_ID, role: AccountRole.READONLY },
+        ]
+      : []),
+  ];
 
   if (isSol) {
-    // SOL: claimer, red_packet, vault (3)
+    // SOL: claimer, red_packet, vault, treasury (4)
     return {
       programAddress: PROGRAM_ID,
       accounts: [
         { address: p.claimer, role: AccountRole.WRITABLE_SIGNER },
         { address: p.redPacket, role: AccountRole.WRITABLE },
         { address: p.vault, role: AccountRole.WRITABLE },
+        { address: p.treasury, role: AccountRole.READONLY },
+        ...extraAccounts,
       ],
       data,
     };
   }
 
-  // SPL: claimer, claimer_ta, red_packet, vault, token_program (5)
+  // SPL: claimer, claimer_ta, red_packet, vault, token_program, treasury (6)
   return {
     programAddress: PROGRAM_ID,
     accounts: [
@@ -231,41 +422,51 @@ export function buildClaimInstruction(p: ClaimParams): Instruction {
       { address: p.claimerTokenAccount!, role: AccountRole.WRITABLE },
       { address: p.redPacket, role: AccountRole.WRITABLE },
       { address: p.vault, role: AccountRole.WRITABLE },
-      { address: TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
+      { address: p.tokenProgram ?? TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
+      { address: p.treasury, role: AccountRole.READONLY },
+      ...extraAccounts,
     ],
     data,
   };
 }
 
-// Disc 2: close
-// Data: [disc=2][token_type:u8]
+// Disc 2: close / Disc 7: cancel (same accounts; cancel needs zero claims)
+// Data: [disc][token_type:u8]
 export interface CloseParams {
   tokenType: number;
+  cancel?: boolean;
+  tokenProgram?: Address; // SPL only; TOKEN_2022_PROGRAM_ID for Token-2022 mints
   // Accounts
   creator: Address;
   creatorTokenAccount?: Address; // SPL only
   redPacket: Address;
   vault: Address;
+  burnMint?: Address; // burnUnclaimed SPL packets: the vault's mint, burned from
+  // donateUnclaimed packets: the treasury leftovers go to (SPL: and its vault)
+  donateTreasury?: Address;
+  donateTreasuryVault?: Address;
 }
 
 export function buildCloseInstruction(p: CloseParams): Instruction {
-  const data = new Uint8Array([2, p.tokenType]);
+  const data = new Uint8Array([p.cancel ? 7 : 2, p.tokenType]);
   const isSol = p.tokenType === TOKEN_TYPE_SOL;
 
   if (isSol) {
-    // SOL: creator, red_packet, vault (3)
+    // SOL: creator, red_packet, vault, [treasury] (3-4)
     return {
       programAddress: PROGRAM_ID,
       accounts: [
         { address: p.creator, role: AccountRole.WRITABLE_SIGNER },
         { address: p.redPacket, role: AccountRole.WRITABLE },
         { address: p.vault, role: AccountRole.WRITABLE },
+        ...(p.donateTreasury ? [{ address: p.donateTreasury, role: AccountRole.WRITABLE }] : []),
       ],
       data,
     };
   }
 
-  // SPL: creator, creator_ta, red_packet, vault, token_program (5)
+  // SPL: creator, creator_ta, red_packet, vault, token_program,
+  // [mint | treasury, treasury_vault] (5-7)
   return {
     programAddress: PROGRAM_ID,
     accounts: [
@@ -273,18 +474,58 @@ export function buildCloseInstruction(p: CloseParams): Instruction {
       { address: p.creatorTokenAccount!, role: AccountRole.WRITABLE },
       { address: p.redPacket, role: AccountRole.WRITABLE },
       { address: p.vault, role: AccountRole.WRITABLE },
-      { address: TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
+      { address: p.tokenProgram ?? TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
+      ...(p.burnMint ? [{ address: p.burnMint, role: AccountRole.WRITABLE }] : []),
+      ...(p.donateTreasury
+        ? [
+            { address: p.donateTreasury, role: AccountRole.READONLY },
+            { address: p.donateTreasuryVault!, role: AccountRole.WRITABLE },
+          ]
+        : []),
     ],
     data,
   };
 }
 
+// Disc 9: partial_withdraw (close accounts; after expiry, keeps the packet)
+// Data: [disc=9][token_type:u8]
+export function buildPartialWithdrawInstruction(p: CloseParams): Instruction {
+  return {
+    ...buildCloseInstruction(p),
+    data: new Uint8Array([9, p.tokenType]),
+  };
+}
+
+// Disc 10: migrate (upgrade a v0 packet in place; creator or admin pays the extra rent)
+// Data: [disc=10]
+export interface MigrateParams {
+  authority: Address;
+  redPacket: Address;
+  treasury?: Address; // required when authority is a treasury admin, not the creator
+}
+
+export function buildMigrateInstruction(p: MigrateParams): Instruction {
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.authority, role: AccountRole.WRITABLE_SIGNER },
+      { address: p.redPacket, role: AccountRole.WRITABLE },
+      { address: SYSTEM_PROGRAM_ID, role: AccountRole.READONLY },
+      ...(p.treasury ? [{ address: p.treasury, role: AccountRole.READONLY }] : []),
+    ],
+    data: new Uint8Array([10]),
+  };
+}
+
 // Disc 3: init_treasury
-// Data: [disc=3][token_type:u8][treasury_bump:u8][vault_bump:u8]
+// Data: [disc=3][token_type:u8][treasury_bump:u8][vault_bump:u8][fee_bps?:u16][if_missing?:u8]
 export interface InitTreasuryParams {
   tokenType: number;
   treasuryBump: number;
   vaultBump: number;
+  feeBps?: number; // admin only; defaults to FEE_RATE_BPS
+  ifMissing?: boolean; // succeed without changes if the treasury already exists
+  tokenProgram?: Address; // SPL only; TOKEN_2022_PROGRAM_ID for Token-2022 mints
   // Accounts
   payer: Address;
   treasury: Address;
@@ -295,7 +536,15 @@ export interface InitTreasuryParams {
 export function buildInitTreasuryInstruction(
   p: InitTreasuryParams
 ): Instruction {
-  const data = new Uint8Array([3, p.tokenType, p.treasuryBump, p.vaultBump]);
+  // if_missing follows fee_bps, so it sends the default rate explicitly
+  const data = new Uint8Array(p.ifMissing ? 7 : p.feeBps === undefined ? 4 : 6);
+  data.set([3, p.tokenType, p.treasuryBump, p.vaultBump]);
+  if (p.feeBps !== undefined || p.ifMissing) {
+    new DataView(data.buffer).setUint16(4, p.feeBps ?? FEE_RATE_BPS, true);
+  }
+  if (p.ifMissing) {
+    data[6] = 1;
+  }
   const isSol = p.tokenType === TOKEN_TYPE_SOL;
 
   if (isSol) {
@@ -319,7 +568,7 @@ export function buildInitTreasuryInstruction(
       { address: p.treasury, role: AccountRole.WRITABLE },
       { address: p.treasuryVault!, role: AccountRole.WRITABLE },
       { address: p.mint!, role: AccountRole.READONLY },
-      { address: TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
+      { address: p.tokenProgram ?? TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
       { address: SYSTEM_PROGRAM_ID, role: AccountRole.READONLY },
     ],
     data,
@@ -327,35 +576,40 @@ export function buildInitTreasuryInstruction(
 }
 
 // Disc 4: withdraw_fees
-// Data: [disc=4][token_type:u8][amount:u64]
+// Data: [disc=4][token_type:u8][amount:u64][strict:u8]? (strict omitted = 0)
 export interface WithdrawFeesParams {
   tokenType: number;
   amount: bigint; // 0 = withdraw all
+  strict?: boolean; // destination must belong to the admin
+  tokenProgram?: Address; // SPL only; TOKEN_2022_PROGRAM_ID for Token-2022 mints
   // Accounts
   admin: Address;
-  adminTokenAccount?: Address; // SPL only
+  adminTokenAccount?: Address; // SPL only; any token account of the treasury's mint
   treasury: Address;
   treasuryVault?: Address; // SPL only
+  destination?: Address; // SOL only; defaults to admin
 }
 
 export function buildWithdrawFeesInstruction(
   p: WithdrawFeesParams
 ): Instruction {
-  const data = new Uint8Array(1 + 1 + 8);
+  const data = new Uint8Array(1 + 1 + 8 + (p.strict === undefined ? 0 : 1));
   const view = new DataView(data.buffer);
   data[0] = 4; // discriminator
   data[1] = p.tokenType;
   view.setBigUint64(2, p.amount, true);
+  if (p.strict !== undefined) data[10] = p.strict ? 1 : 0;
 
   const isSol = p.tokenType === TOKEN_TYPE_SOL;
 
   if (isSol) {
-    // SOL: admin, treasury (2)
+    // SOL: admin, treasury, [destination] (2-3)
     return {
       programAddress: PROGRAM_ID,
       accounts: [
         { address: p.admin, role: AccountRole.WRITABLE_SIGNER },
         { address: p.treasury, role: AccountRole.WRITABLE },
+        ...(p.destination ? [{ address: p.destination, role: AccountRole.WRITABLE }] : []),
       ],
       data,
     };
@@ -369,12 +623,302 @@ export function buildWithdrawFeesInstruction(
       { address: p.adminTokenAccount!, role: AccountRole.WRITABLE },
       { address: p.treasury, role: AccountRole.READONLY },
       { address: p.treasuryVault!, role: AccountRole.WRITABLE },
-      { address: TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
+      { address: p.tokenProgram ?? TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
+    ],
+    data,
+  };
+}
+
+// Disc 8: update_fee (admin only)
+// Data: [disc=8][fee_bps:u16]
+export interface UpdateFeeParams {
+  feeBps: number;
+  // Accounts
+  admin: Address;
+  treasury: Address;
+}
+
+export function buildUpdateFeeInstruction(p: UpdateFeeParams): Instruction {
+  const data = new Uint8Array(3);
+  data[0] = 8;
+  new DataView(data.buffer).setUint16(1, p.feeBps, true);
+
+  // admin, treasury, system_program (3) — system program pays to grow legacy treasuries
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.admin, role: AccountRole.WRITABLE_SIGNER },
+      { address: p.treasury, role: AccountRole.WRITABLE },
+      { address: SYSTEM_PROGRAM_ID, role: AccountRole.READONLY },
+    ],
+    data,
+  };
+}
+
+// Disc 11: propose_admin (current treasury admin nominates a successor)
+// Data: [disc=11][new_admin:32]
+export interface ProposeAdminParams {
+  newAdmin: Address;
+  // Accounts
+  admin: Address;
+  treasury: Address;
+}
+
+export function buildProposeAdminInstruction(p: ProposeAdminParams): Instruction {
+  const data = new Uint8Array(33);
+  data[0] = 11;
+  data.set(addressEncoder.encode(p.newAdmin), 1);
+
+  // admin, treasury, system_program (3) — system program pays to grow legacy treasuries
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.admin, role: AccountRole.WRITABLE_SIGNER },
+      { address: p.treasury, role: AccountRole.WRITABLE },
+      { address: SYSTEM_PROGRAM_ID, role: AccountRole.READONLY },
+    ],
+    data,
+  };
+}
+
+// Disc 12: accept_admin (pending admin signs to take over the treasury)
+// Data: [disc=12]
+export interface AcceptAdminParams {
+  pendingAdmin: Address;
+  treasury: Address;
+}
+
+export function buildAcceptAdminInstruction(p: AcceptAdminParams): Instruction {
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.pendingAdmin, role: AccountRole.READONLY_SIGNER },
+      { address: p.treasury, role: AccountRole.WRITABLE },
+    ],
+    data: new Uint8Array([12]),
+  };
+}
+
+// Disc 13: claim_batch (creator hands even-split slots to several recipients)
+// Data: [disc=13][token_type:u8][slot:u8 per recipient]
+export interface ClaimBatchParams {
+  tokenType: number;
+  firstSlot: number; // the packet's current num_claimed
+  tokenProgram?: Address; // SPL only; TOKEN_2022_PROGRAM_ID for Token-2022 mints
+  // Accounts
+  creator: Address;
+  redPacket: Address;
+  vault: Address;
+  treasury: Address;
+  recipients: Address[]; // wallets (SOL) or token accounts (SPL), one per slot
+}
+
+export function buildClaimBatchInstruction(p: ClaimBatchParams): Instruction {
+  const data = new Uint8Array(2 + p.recipients.length);
+  data[0] = 13;
+  data[1] = p.tokenType;
+  p.recipients.forEach((_, i) => (data[2 + i] = p.firstSlot + i));
+
+  const isSol = p.tokenType === TOKEN_TYPE_SOL;
+
+  // SOL: creator, red_packet, vault, treasury, recipients... (4 + n)
+  // SPL: creator, red_packet, vault, token_program, treasury, recipient_tas... (5 + n)
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.creator, role: AccountRole.WRITABLE_SIGNER },
+      { address: p.redPacket, role: AccountRole.WRITABLE },
+      { address: p.vault, role: AccountRole.WRITABLE },
+      ...(isSol ? [] : [{ address: p.tokenProgram ?? TOKEN_PROGRAM_ID, role: AccountRole.READONLY }]),
+      { address: p.treasury, role: AccountRole.READONLY },
+      ...p.recipients.map((address) => ({ address, role: AccountRole.WRITABLE })),
+    ],
+    data,
+  };
+}
+
+// Disc 14: pause / Disc 15: unpause (admin only; halts creates and claims for the treasury's mint)
+// Data: [disc]
+export interface SetPausedParams {
+  paused: boolean;
+  // Accounts
+  admin: Address;
+  treasury: Address;
+}
+
+export function buildSetPausedInstruction(p: SetPausedParams): Instruction {
+  // admin, treasury, system_program (3) — system program pays to grow legacy treasuries
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.admin, role: AccountRole.WRITABLE_SIGNER },
+      { address: p.treasury, role: AccountRole.WRITABLE },
+      { address: SYSTEM_PROGRAM_ID, role: AccountRole.READONLY },
+    ],
+    data: new Uint8Array([p.paused ? 14 : 15]),
+  };
+}
+
+// Disc 21: set_expiry_bounds (admin only; how soon and how far out creates may expire)
+// Data: [disc=21][min_expiry_secs:i64][max_expiry_secs:i64]
+export interface SetExpiryBoundsParams {
+  minExpirySecs: bigint;
+  maxExpirySecs: bigint; // at most MAX_EXPIRY_SECONDS
+  // Accounts
+  admin: Address;
+  treasury: Address;
+}
+
+export function buildSetExpiryBoundsInstruction(p: SetExpiryBoundsParams): Instruction {
+  const data = new Uint8Array(17);
+  data[0] = 21;
+  const view = new DataView(data.buffer);
+  view.setBigInt64(1, p.minExpirySecs, true);
+  view.setBigInt64(9, p.maxExpirySecs, true);
+
+  // admin, treasury, system_program (3) — system program pays to grow legacy treasuries
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.admin, role: AccountRole.WRITABLE_SIGNER },
+      { address: p.treasury, role: AccountRole.WRITABLE },
+      { address: SYSTEM_PROGRAM_ID, role: AccountRole.READONLY },
+    ],
+    data,
+  };
+}
+
+// Disc 16: close_treasury (admin only; treasury must have no fees left)
+// Data: [disc, token_type]
+export interface CloseTreasuryParams {
+  tokenType: number;
+  tokenProgram?: Address; // SPL only; TOKEN_2022_PROGRAM_ID for Token-2022 mints
+  // Accounts
+  admin: Address;
+  treasury: Address;
+  treasuryVault?: Address; // SPL only
+}
+
+export function buildCloseTreasuryInstruction(p: CloseTreasuryParams): Instruction {
+  const isSol = p.tokenType === TOKEN_TYPE_SOL;
+  // SOL: admin, treasury (2)
+  // SPL: admin, treasury, treasury_vault, token_program (4)
+  const accounts = isSol
+    ? [
+        { address: p.admin, role: AccountRole.WRITABLE_SIGNER },
+        { address: p.treasury, role: AccountRole.WRITABLE },
+      ]
+    : [
+        { address: p.admin, role: AccountRole.WRITABLE_SIGNER },
+        { address: p.treasury, role: AccountRole.WRITABLE },
+        { address: p.treasuryVault!, role: AccountRole.WRITABLE },
+        { address: p.tokenProgram ?? TOKEN_PROGRAM_ID, role: AccountRole.READONLY },
+      ];
+  return {
+    programAddress: PROGRAM_ID,
+    accounts,
+    data: new Uint8Array([16, p.tokenType]),
+  };
+}
+
+// Disc 17: migrate_treasury (admin only; grows an older treasury to the current layout)
+// Data: [disc]
+export interface MigrateTreasuryParams {
+  // Accounts
+  admin: Address;
+  treasury: Address;
+}
+
+export function buildMigrateTreasuryInstruction(p: MigrateTreasuryParams): Instruction {
+  // admin, treasury, system_program (3) — admin pays the extra rent
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.admin, role: AccountRole.WRITABLE_SIGNER },
+      { address: p.treasury, role: AccountRole.WRITABLE },
+      { address: SYSTEM_PROGRAM_ID, role: AccountRole.READONLY },
+    ],
+    data: new Uint8Array([17]),
+  };
+}
+
+// Disc 18: close_receipt (large packets, once closed or fully claimed)
+// Data: [disc=18]
+export interface CloseReceiptParams {
+  // Accounts
+  claimer: Address;
+  receipt: Address;
+  redPacket: Address;
+}
+
+export function buildCloseReceiptInstruction(p: CloseReceiptParams): Instruction {
+  // claimer, receipt, red_packet (3) — the receipt's rent goes to the claimer
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.claimer, role: AccountRole.WRITABLE_SIGNER },
+      { address: p.receipt, role: AccountRole.WRITABLE },
+      { address: p.redPacket, role: AccountRole.READONLY },
+    ],
+    data: new Uint8Array([18]),
+  };
+}
+
+// Disc 19: reveal (commit-reveal packets; opens claims)
+// Data: [disc=19][secret:32] — sha256(secret) must be the committed hash
+export interface RevealParams {
+  // Accounts
+  creator: Address;
+  redPacket: Address;
+  // Data
+  secret: Uint8Array;
+}
+
+export function buildRevealInstruction(p: RevealParams): Instruction {
+  // creator, red_packet (2)
+  const data = new Uint8Array(33);
+  data[0] = 19;
+  data.set(p.secret, 1);
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.creator, role: AccountRole.READONLY_SIGNER },
+      { address: p.redPacket, role: AccountRole.WRITABLE },
     ],
     data,
   };
 }
 
+// Disc 20: finalize (raffles and lucky splits; anyone may call once expired
+// or full). Data: [disc=20][token_type:u8]. The first call draws and records
+// the winner; the pot (lucky: the jackpot) is paid when `winner` is (SPL: is
+// a token account of) it
+export interface FinalizeParams {
+  tokenType: number;
+  tokenProgram?: Address; // SPL only; TOKEN_2022_PROGRAM_ID for Token-2022 mints
+  // Accounts
+  redPacket: Address;
+  vault: Address;
+  winner: Address; // SPL: the winner's token account
+}
+
+export function buildFinalizeInstruction(p: FinalizeParams): Instruction {
+  const isSol = p.tokenType === TOKEN_TYPE_SOL;
+  // SOL: red_packet, vault, winner, slot_hashes (4)
+  // SPL: red_packet, vault, winner_ta, token_program, slot_hashes (5)
+  return {
+    programAddress: PROGRAM_ID,
+    accounts: [
+      { address: p.redPacket, role: AccountRole.WRITABLE },
+      { address: p.vault, role: AccountRole.WRITABLE },
+      { address: p.winner, role: AccountRole.WRITABLE },
+      ...(isSol ? [] : [{ address: p.tokenProgram ?? TOKEN_PROGRAM_ID, role: AccountRole.READONLY }]),
+      { address: SLOT_HASHES_SYSVAR_ID, role: AccountRole.READONLY },
+    ],
+    data: new Uint8Array([20, p.tokenType]),
+  };
+}
+
 // ============================================================
 // Account deserialization
 // ============================================================
@@ -394,8 +938,32 @@ export interface RedPacketAccount {
   vaultBump: number;
   tokenType: number;
   expiresAt: bigint;
+  feePaid: bigint;
+  version: number;
+  allowRepeat: boolean;
+  noSelfClaim: boolean;
+  large: boolean; // claims are receipted; claimers stays empty
+  randomOnchain: boolean; // amounts are drawn per claim; unclaimed slots read 0
+  secretHash: Uint8Array | null; // null = no password
+  voucherAuthority: Address | null; // null = no voucher needed
+  gateMint: Address | null; // null = ungated
+  gateMinAmount: bigint;
+  assignedTo: Address[]; // assigned packets: wallets still due a slot
+  createdAt: bigint; // unix time of the create; 0 = migrated, unknown
+  revealPending: boolean; // commit-reveal packet not yet revealed: claims fail
+  revealDeadline: bigint; // past it, an unrevealed packet can only be closed
+  winner: Address | null; // raffles: the entrant finalize drew; null = not drawn
+  vesting: boolean;
+  vestingStart: bigint; // vesting packets unlock from here until expiresAt
+  memo: string | null; // the creator's greeting; null = none
+  burnUnclaimed: boolean; // close and partial withdraw burn leftovers instead of refunding
+  donateUnclaimed: boolean; // close and partial withdraw pay leftovers to the treasury
+  originalCreator: Address; // PDAs derive from it; creator differs after transfer_creator
   amounts: bigint[];
+  claimedSlots: number[]; // slot of each entry in claimers, claimedAt and taken
   claimers: Address[];
+  claimedAt: bigint[]; // unix time of each claim in claimers; 0 = claimed before v11
+  taken: bigint[]; // vesting packets: what each claimed slot has paid so far
 }
 
 export function decodeRedPacket(data: Uint8Array): RedPacketAccount {
@@ -413,23 +981,83 @@ export function decodeRedPacket(data: Uint8Array): RedPacketAccount {
   const vaultBump = data[61];
   const tokenType = data[62];
   const expiresAt = view.getBigInt64(63, true);
+  const feePaid = view.getBigUint64(71, true);
+  const version = data[79];
+  if (version !== REDPACKET_VERSION) {
+    throw new Error("Red packet uses an old account layout; migrate it first");
+  }
+  const allowRepeat = (data[80] & FLAG_ALLOW_REPEAT) !== 0;
+  const noSelfClaim = (data[80] & FLAG_NO_SELF_CLAIM) !== 0;
+  const large = (data[80] & FLAG_LARGE) !== 0;
+  const randomOnchain = (data[80] & FLAG_RANDOM_ONCHAIN) !== 0;
+  const hashBytes = data.slice(81, 113);
+  const secretHash = hashBytes.some((b) => b !== 0) ? hashBytes : null;
+  const authorityBytes = data.slice(113, 145);
+  const voucherAuthority = authorityBytes.some((b) => b !== 0)
+    ? addressDecoder.decode(authorityBytes)
+    : null;
+  const gateBytes = data.slice(145, 177);
+  const gateMint = gateBytes.some((b) => b !== 0) ? addressDecoder.decode(gateBytes) : null;
+  const gateMinAmount = view.getBigUint64(177, true);
+  const assigned = data[185] !== 0;
+  const createdAt = view.getBigInt64(186, true);
+  const revealPending =
+    (data[80] & FLAG_REVEALED) === 0 && data.slice(194, 226).some((b) => b !== 0);
+  const revealDeadline = view.getBigInt64(226, true);
+  const winnerBytes = data.slice(234, 266);
+  const winner = winnerBytes.some((b) => b !== 0) ? addressDecoder.decode(winnerBytes) : null;
+  const vesting = (data[80] & FLAG_VESTING) !== 0;
+  const vestingStart = view.getBigInt64(266, true);
+
+  // Bit i of the claimed_slots bitmap is set once slot i is claimed; claims
+  // may land out of order, so the claimed slots can have gaps
+  const claimedSlots: number[] = [];
+  for (let i = 0; i < numRecipients; i++) {
+    if ((data[274 + (i >> 3)] & (1 << (i & 7))) !== 0) {
+      claimedSlots.push(i);
+    }
+  }
+
+  const memoLen = Math.min(data[306], MAX_MEMO_LEN);
+  const memo = memoLen > 0 ? new TextDecoder().decode(data.slice(307, 307 + memoLen)) : null;
+  const burnUnclaimed = data[371] !== 0;
+  const donateUnclaimed = data[372] !== 0;
+  const originalCreator = addressDecoder.decode(data.slice(373, 405));
 
   const amounts: bigint[] = [];
   for (let i = 0; i < numRecipients; i++) {
-    amounts.push(view.getBigUint64(71 + i * 8, true));
+    amounts.push(view.getBigUint64(405 + i * 8, true));
   }
 
-  const claimersOffset = 71 + numRecipients * 8;
+  // Claimed slots hold their claimer; assigned packets keep the wallet still
+  // due each unclaimed slot
+  const claimersOffset = 405 + numRecipients * 8;
   const claimers: Address[] = [];
-  for (let i = 0; i < numRecipients; i++) {
+  const assignedTo: Address[] = [];
+  for (let i = 0; i < (large ? 0 : numRecipients); i++) {
     const start = claimersOffset + i * 32;
-    const bytes = data.slice(start, start + 32);
-    // Only include non-zero addresses (claimed slots)
-    if (bytes.some((b) => b !== 0)) {
-      claimers.push(addressDecoder.decode(bytes));
+    const wallet = addressDecoder.decode(data.slice(start, start + 32));
+    if (claimedSlots.includes(i)) {
+      claimers.push(wallet);
+    } else if (assigned) {
+      assignedTo.push(wallet);
     }
   }
 
+  // When each claimed slot was claimed (large packets don't record it)
+  const claimedAtOffset = claimersOffset + numRecipients * 32;
+  const claimedAt: bigint[] = [];
+  for (const slot of large ? [] : claimedSlots) {
+    claimedAt.push(view.getBigInt64(claimedAtOffset + slot * 8, true));
+  }
+
+  // Vesting packets end with what each slot has paid out so far
+  const takenOffset = claimedAtOffset + numRecipients * 8;
+  const taken: bigint[] = [];
+  for (const slot of vesting ? claimedSlots : []) {
+    taken.push(view.getBigUint64(takenOffset + slot * 8, true));
+  }
+
   return {
     discriminator,
     creator,
@@ -443,8 +1071,32 @@ export function decodeRedPacket(data: Uint8Array): RedPacketAccount {
     vaultBump,
     tokenType,
     expiresAt,
+    feePaid,
+    version,
+    allowRepeat,
+    noSelfClaim,
+    large,
+    randomOnchain,
+    secretHash,
+    voucherAuthority,
+    gateMint,
+    gateMinAmount,
+    assignedTo,
+    createdAt,
+    revealPending,
+    revealDeadline,
+    winner,
+    vesting,
+    vestingStart,
+    memo,
+    burnUnclaimed,
+    donateUnclaimed,
+    originalCreator,
     amounts,
+    claimedSlots,
     claimers,
+    claimedAt,
+    taken,
   };
 }
 
@@ -454,6 +1106,13 @@ export interface TreasuryAccount {
   vaultBump: number;
   mint: Address;
   solFeesCollected: bigint;
+  feeBps: number;
+  admin: Address;
+  pendingAdmin: Address | null;
+  paused: boolean;
+  version: number; // 1 = pre-versioned layout (migrate_treasury upgrades it)
+  minExpirySecs: bigint;
+  maxExpirySecs: bigint;
 }
 
 export function decodeTreasury(data: Uint8Array): TreasuryAccount {
@@ -465,6 +1124,19 @@ export function decodeTreasury(data: Uint8Array): TreasuryAccount {
     vaultBump: data[2],
     mint: addressDecoder.decode(data.slice(3, 35)),
     solFeesCollected: view.getBigUint64(35, true),
+    // Legacy 43-byte treasuries predate fee_bps and charge the default
+    feeBps: data.length >= 45 ? view.getUint16(43, true) : FEE_RATE_BPS,
+    // Treasuries shorter than 109 bytes predate the stored admin
+    admin: data.length >= 109 ? addressDecoder.decode(data.slice(45, 77)) : ADMIN_ADDRESS,
+    pendingAdmin:
+      data.length >= 109 && data.slice(77, 109).some((b) => b !== 0)
+        ? addressDecoder.decode(data.slice(77, 109))
+        : null,
+    paused: data.length >= 110 && data[109] === 1,
+    version: data.length >= 111 ? data[110] : 1,
+    // Treasuries before v3 use the default bounds
+    minExpirySecs: data.length >= 127 ? view.getBigInt64(111, true) : MIN_EXPIRY_SECONDS,
+    maxExpirySecs: data.length >= 127 ? view.getBigInt64(119, true) : MAX_EXPIRY_SECONDS,
   };
 }
 
@@ -472,39 +1144,6 @@ export function decodeTreasury(data: Uint8Array): TreasuryAccount {
 // Helpers
 // ============================================================
 
-/** Generate random split amounts that sum to totalAmount */
-export function generateRandomSplit(
-  totalAmount: bigint,
-  numRecipients: number
-): bigint[] {
-  if (numRecipients <= 0) return [];
-  if (numRecipients === 1) return [totalAmount];
-
-  // Generate random cut points, then compute differences
-  const cuts: number[] = [];
-  for (let i = 0; i < numRecipients - 1; i++) {
-    cuts.push(Math.random());
-  }
-  cuts.sort((a, b) => a - b);
-
-  const totalNum = Number(totalAmount);
-  const rawAmounts: number[] = [];
-  let prev = 0;
-  for (const cut of cuts) {
-    rawAmounts.push(Math.floor(cut * totalNum) - Math.floor(prev * totalNum));
-    prev = cut;
-  }
-  rawAmounts.push(totalNum - Math.floor(prev * totalNum));
-
-  // Ensure no zero amounts (minimum 1 per slot)
-  const amounts = rawAmounts.map((a) => (a < 1 ? 1 : a));
-  // Adjust last slot to make sum exact
-  const sum = amounts.reduce((s, a) => s + a, 0);
-  amounts[amounts.length - 1] += totalNum - sum;
-
-  return amounts.map((a) => BigInt(a));
-}
-
 /** Red packet status derived from on-chain data */
 export type RedPacketStatus = "active" | "expired" | "fully_claimed";
 
@@ -517,6 +1156,13 @@ export function getRedPacketStatus(
   return "active";
 }
 
+/** The slot a claim that doesn't name one takes: the lowest unclaimed */
+export function nextSlot(rp: RedPacketAccount): number {
+  let slot = 0;
+  while (rp.claimedSlots.includes(slot)) slot++;
+  return slot;
+}
+
 /** Format lamports/micro-units to human-readable string */
 export function formatAmount(
   amount: bigint,
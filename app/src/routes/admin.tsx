@@ -21,6 +21,9 @@ import {
   TOKEN_TYPE_SOL,
   findTreasuryPDA,
   decodeTreasury,
+  buildAcceptAdminInstruction,
+  buildProposeAdminInstruction,
+  buildSetPausedInstruction,
   buildWithdrawFeesInstruction,
   formatAmount,
 } from "@/lib/program";
@@ -33,6 +36,27 @@ export const Route = createFileRoute("/admin")({
 
 function AdminPage() {
   const [selectedAccount] = useSelectedWalletAccount();
+  // The SOL treasury's stored admin; ADMIN_ADDRESS until it has been handed over
+  const [admin, setAdmin] = useState<string>(ADMIN_ADDRESS);
+  const [pendingAdmin, setPendingAdmin] = useState<string | null>(null);
+
+  const fetchAdmin = useCallback(async () => {
+    try {
+      const [solTreasuryPDA] = await findTreasuryPDA("SOL");
+      const solAccount = await fetchEncodedAccount(rpc, solTreasuryPDA);
+      if (solAccount.exists) {
+        const treasury = decodeTreasury(new Uint8Array(solAccount.data));
+        setAdmin(treasury.admin);
+        setPendingAdmin(treasury.pendingAdmin);
+      }
+    } catch {
+      toast.error("Failed to fetch treasury admin");
+    }
+  }, []);
+
+  useEffect(() => {
+    fetchAdmin();
+  }, [fetchAdmin]);
 
   if (!selectedAccount) {
     return (
@@ -47,7 +71,11 @@ function AdminPage() {
     );
   }
 
-  const isAdmin = selectedAccount.address === ADMIN_ADDRESS;
+  const isAdmin = selectedAccount.address === admin;
+
+  if (selectedAccount.address === pendingAdmin) {
+    return <AcceptAdminPanel account={selectedAccount} onAccepted={fetchAdmin} />;
+  }
 
   if (!isAdmin) {
     return (
@@ -57,7 +85,7 @@ function AdminPage() {
           <CardContent className="py-12 text-center text-muted-foreground">
             Connected wallet is not the admin. Only{" "}
             <code className="text-xs">
-              {String(ADMIN_ADDRESS).slice(0, 8)}...
+              {admin.slice(0, 8)}...
             </code>{" "}
             can access this page.
           </CardContent>
@@ -66,13 +94,76 @@ function AdminPage() {
     );
   }
 
-  return <AdminPanel account={selectedAccount} />;
+  return (
+    <AdminPanel
+      account={selectedAccount}
+      pendingAdmin={pendingAdmin}
+      onAdminChanged={fetchAdmin}
+    />
+  );
+}
+
+function AcceptAdminPanel({
+  account,
+  onAccepted,
+}: {
+  account: NonNullable<ReturnType<typeof useSelectedWalletAccount>[0]>;
+  onAccepted: () => void;
+}) {
+  const signer = useWalletAccountTransactionSendingSigner(
+    account,
+    "solana:devnet"
+  );
+  const [isAccepting, setIsAccepting] = useState(false);
+
+  async function handleAccept() {
+    setIsAccepting(true);
+    try {
+      const [treasuryPDA] = await findTreasuryPDA("SOL");
+      const ix = buildAcceptAdminInstruction({
+        pendingAdmin: address(account.address),
+        treasury: treasuryPDA,
+      });
+
+      await sendTransaction(signer, [ix]);
+      toast.success("You are now the treasury admin!");
+      onAccepted();
+    } catch (e: unknown) {
+      const msg = e instanceof Error ? e.message : "Accept failed";
+      toast.error(msg);
+    } finally {
+      setIsAccepting(false);
+    }
+  }
+
+  return (
+    <div className="space-y-6">
+      <h1 className="text-2xl font-bold">Admin</h1>
+      <Card>
+        <CardHeader>
+          <CardTitle>Admin nomination</CardTitle>
+          <CardDescription>
+            This wallet has been nominated as the SOL treasury admin.
+          </CardDescription>
+        </CardHeader>
+        <CardContent>
+          <Button onClick={handleAccept} disabled={isAccepting}>
+            {isAccepting ? "Accepting..." : "Accept Admin Role"}
+          </Button>
+        </CardContent>
+      </Card>
+    </div>
+  );
 }
 
 function AdminPanel({
   account,
+  pendingAdmin,
+  onAdminChanged,
 }: {
   account: NonNullable<ReturnType<typeof useSelectedWalletAccount>[0]>;
+  pendingAdmin: string | null;
+  onAdminChanged: () => void;
 }) {
   const signer = useWalletAccountTransactionSendingSigner(
     account,
@@ -81,8 +172,12 @@ function AdminPanel({
 
   const [solFees, setSolFees] = useState<bigint | null>(null);
   const [solTreasuryExists, setSolTreasuryExists] = useState(false);
+  const [paused, setPaused] = useState(false);
+  const [isTogglingPause, setIsTogglingPause] = useState(false);
   const [withdrawAmount, setWithdrawAmount] = useState("");
   const [isWithdrawing, setIsWithdrawing] = useState(false);
+  const [newAdmin, setNewAdmin] = useState("");
+  const [isProposing, setIsProposing] = useState(false);
   const [loading, setLoading] = useState(true);
 
   const fetchTreasury = useCallback(async () => {
@@ -94,6 +189,7 @@ function AdminPanel({
       if (solAccount.exists) {
         const treasury = decodeTreasury(new Uint8Array(solAccount.data));
         setSolFees(treasury.solFeesCollected);
+        setPaused(treasury.paused);
         setSolTreasuryExists(true);
       } else {
         setSolTreasuryExists(false);
@@ -142,6 +238,57 @@ function AdminPanel({
     }
   }
 
+  async function handleTogglePause() {
+    setIsTogglingPause(true);
+    try {
+      const [treasuryPDA] = await findTreasuryPDA("SOL");
+      const ix = buildSetPausedInstruction({
+        paused: !paused,
+        admin: address(account.address),
+        treasury: treasuryPDA,
+      });
+
+      await sendTransaction(signer, [ix]);
+      toast.success(paused ? "SOL packets unpaused" : "SOL packets paused");
+      fetchTreasury();
+    } catch (e: unknown) {
+      const msg = e instanceof Error ? e.message : "Pause toggle failed";
+      toast.error(msg);
+    } finally {
+      setIsTogglingPause(false);
+    }
+  }
+
+  async function handleProposeAdmin() {
+    setIsProposing(true);
+    try {
+      let nominee;
+      try {
+        nominee = address(newAdmin.trim());
+      } catch {
+        toast.error("Invalid address");
+        return;
+      }
+
+      const [treasuryPDA] = await findTreasuryPDA("SOL");
+      const ix = buildProposeAdminInstruction({
+        newAdmin: nominee,
+        admin: address(account.address),
+        treasury: treasuryPDA,
+      });
+
+      await sendTransaction(signer, [ix]);
+      toast.success("Admin nominated! They must accept from their wallet.");
+      setNewAdmin("");
+      onAdminChanged();
+    } catch (e: unknown) {
+      const msg = e instanceof Error ? e.message : "Nomination failed";
+      toast.error(msg);
+    } finally {
+      setIsProposing(false);
+    }
+  }
+
   return (
     <div className="space-y-6">
       <h1 className="text-2xl font-bold">Admin Panel</h1>
@@ -180,10 +327,54 @@ function AdminPanel({
               >
                 {isWithdrawing ? "Withdrawing..." : "Withdraw SOL Fees"}
               </Button>
+              <div className="flex items-center justify-between border-t pt-4">
+                <p className="text-sm text-muted-foreground">
+                  {paused
+                    ? "Paused: no new SOL packets or claims. Closes and withdrawals still work."
+                    : "SOL packets are live."}
+                </p>
+                <Button
+                  variant={paused ? "default" : "destructive"}
+                  onClick={handleTogglePause}
+                  disabled={isTogglingPause}
+                >
+                  {isTogglingPause ? "Updating..." : paused ? "Unpause" : "Pause"}
+                </Button>
+              </div>
             </CardContent>
           )}
         </Card>
       )}
+
+      {solTreasuryExists && (
+        <Card>
+          <CardHeader>
+            <CardTitle>Transfer Admin</CardTitle>
+            <CardDescription>
+              {pendingAdmin
+                ? `Pending: ${pendingAdmin.slice(0, 8)}... (nominating again replaces it)`
+                : "The nominee becomes admin once they accept from their wallet."}
+            </CardDescription>
+          </CardHeader>
+          <CardContent className="space-y-4">
+            <div className="space-y-2">
+              <Label htmlFor="new-admin">New admin address</Label>
+              <Input
+                id="new-admin"
+                placeholder="Wallet address"
+                value={newAdmin}
+                onChange={(e) => setNewAdmin(e.target.value)}
+              />
+            </div>
+            <Button
+              onClick={handleProposeAdmin}
+              disabled={isProposing || newAdmin.trim() === ""}
+            >
+              {isProposing ? "Nominating..." : "Nominate Admin"}
+            </Button>
+          </CardContent>
+        </Card>
+      )}
     </div>
   );
 }
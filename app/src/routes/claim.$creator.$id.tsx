@@ -18,12 +18,16 @@ import {
 import {
   type RedPacketAccount,
   TOKEN_TYPE_SOL,
+  SPLIT_RAFFLE,
   findRedPacketPDA,
   findVaultPDA,
+  findTreasuryPDA,
+  findReceiptPDA,
   decodeRedPacket,
   buildClaimInstruction,
   formatAmount,
   getRedPacketStatus,
+  nextSlot,
 } from "@/lib/program";
 import { getAssociatedTokenAddress } from "@/lib/ata";
 import { sendTransaction } from "@/lib/transaction";
@@ -86,12 +90,21 @@ function ClaimPage() {
   const nowUnix = Math.floor(Date.now() / 1000);
   const status = getRedPacketStatus(redPacket, nowUnix);
 
+  // Allow-repeat packets let the same wallet take another slot
   const alreadyClaimed =
     selectedAccount &&
+    !redPacket.allowRepeat &&
     redPacket.claimers.some((c) => c === selectedAccount.address);
 
+  const isCreatorBlocked =
+    selectedAccount && redPacket.noSelfClaim && selectedAccount.address === redPacket.creator;
+
   const canClaim =
-    selectedAccount && status === "active" && !alreadyClaimed;
+    selectedAccount &&
+    status === "active" &&
+    !alreadyClaimed &&
+    !isCreatorBlocked &&
+    !redPacket.revealPending;
 
   return (
     <div className="space-y-6">
@@ -100,6 +113,7 @@ function ClaimPage() {
         <p className="text-muted-foreground">
           From {creator.slice(0, 8)}...{creator.slice(-4)}
         </p>
+        {redPacket.memo && <p className="mt-2 italic">“{redPacket.memo}”</p>}
       </div>
 
       <Card>
@@ -127,7 +141,7 @@ function ClaimPage() {
           <CardDescription>
             {redPacket.numClaimed} / {redPacket.numRecipients} claimed
             &middot;{" "}
-            {redPacket.splitMode === 0 ? "Even split" : "Random split"}
+            {["Even split", "Random split", "Weighted split", "Lucky split", "Raffle"][redPacket.splitMode] ?? "Split"}
           </CardDescription>
         </CardHeader>
         <CardContent className="space-y-4">
@@ -135,7 +149,6 @@ function ClaimPage() {
           {redPacket.numClaimed > 0 && (
             <div className="space-y-1">
               {redPacket.claimers
-                .filter((_, i) => i < redPacket.numClaimed)
                 .map((claimer, i) => (
                   <div
                     key={i}
@@ -145,7 +158,11 @@ function ClaimPage() {
                       {claimer.slice(0, 4)}...{claimer.slice(-4)}
                     </span>
                     <span>
-                      {formatAmount(redPacket.amounts[i], redPacket.tokenType, decimals)}
+                      {formatAmount(
+                        redPacket.amounts[redPacket.claimedSlots[i]],
+                        redPacket.tokenType,
+                        decimals
+                      )}
                     </span>
                   </div>
                 ))}
@@ -159,6 +176,14 @@ function ClaimPage() {
             </p>
           )}
 
+          {/* Created (migrated packets don't know) */}
+          {redPacket.createdAt !== 0n && (
+            <p className="text-xs text-muted-foreground">
+              Created:{" "}
+              {new Date(Number(redPacket.createdAt) * 1000).toLocaleString()}
+            </p>
+          )}
+
           {/* Expiry */}
           <p className="text-xs text-muted-foreground">
             Expires:{" "}
@@ -174,6 +199,14 @@ function ClaimPage() {
             <p className="text-center text-sm text-green-600">
               You already claimed this red packet!
             </p>
+          ) : isCreatorBlocked ? (
+            <p className="text-center text-sm text-muted-foreground">
+              You created this red packet, so it's only for others to claim
+            </p>
+          ) : status === "active" && redPacket.revealPending ? (
+            <p className="text-center text-sm text-muted-foreground">
+              Claims open once the creator reveals this red packet's randomness
+            </p>
           ) : canClaim ? (
             <ClaimButton
               account={selectedAccount}
@@ -217,13 +250,16 @@ function ClaimButton({
     try {
       const claimerAddress = address(account.address);
       const isSol = redPacket.tokenType === TOKEN_TYPE_SOL;
-      const slotIndex = redPacket.numClaimed;
+      const slotIndex = nextSlot(redPacket);
 
       const [rpPDA] = await findRedPacketPDA(creatorAddress, packetId);
       const [vaultPDA] = await findVaultPDA(creatorAddress, packetId);
 
       let claimerTokenAccount: Address | undefined;
-      if (!isSol) {
+      let treasuryPDA: Address | undefined;
+      if (isSol) {
+        [treasuryPDA] = await findTreasuryPDA("SOL");
+      } else {
         // Need to get the mint from the red packet data — not stored directly,
         // but we can derive it from the treasury
         // For simplicity, the claimer needs their ATA for the token
@@ -240,23 +276,40 @@ function ClaimButton({
             claimerAddress,
             mintAddr
           );
+          [treasuryPDA] = await findTreasuryPDA(mintAddr);
         }
       }
+      if (!treasuryPDA) {
+        throw new Error("Red packet vault not found");
+      }
+
+      // Large packets record the claim as a receipt PDA the claimer pays for
+      const receipt = redPacket.large
+        ? (await findReceiptPDA(rpPDA, claimerAddress))[0]
+        : undefined;
 
       const ix = buildClaimInstruction({
         tokenType: redPacket.tokenType,
         slotIndex,
+        receipt,
         claimer: claimerAddress,
         claimerTokenAccount,
         redPacket: rpPDA,
         vault: vaultPDA,
+        treasury: treasuryPDA,
       });
 
       await sendTransaction(signer, [ix]);
 
       const decimals = isSol ? 9 : 6;
+      // On-chain random slots are drawn by the claim itself; the claimed list
+      // shows the amount once it refreshes
       toast.success(
-        `Claimed ${formatAmount(redPacket.amounts[slotIndex], redPacket.tokenType, decimals)}!`
+        redPacket.splitMode === SPLIT_RAFFLE
+          ? "Entered the draw!"
+          : redPacket.randomOnchain
+          ? "Claimed your share!"
+          : `Claimed ${formatAmount(redPacket.amounts[slotIndex], redPacket.tokenType, decimals)}!`
       );
       onSuccess();
     } catch (e: unknown) {
@@ -268,8 +321,9 @@ function ClaimButton({
   }
 
   const decimals = redPacket.tokenType === TOKEN_TYPE_SOL ? 9 : 6;
-  const isRandom = redPacket.splitMode === 1;
-  const claimAmount = redPacket.amounts[redPacket.numClaimed];
+  const isRandom = redPacket.splitMode === 1 || redPacket.splitMode === 3;
+  const isRaffle = redPacket.splitMode === SPLIT_RAFFLE;
+  const claimAmount = redPacket.amounts[nextSlot(redPacket)];
 
   return (
     <Button
@@ -279,7 +333,9 @@ function ClaimButton({
     >
       {isClaiming
         ? "Claiming..."
-        : isRandom
+        : isRaffle
+          ? "Enter Draw"
+          : isRandom
           ? "Claim Your Share"
           : `Claim ${claimAmount ? formatAmount(claimAmount, redPacket.tokenType, decimals) : ""}`}
     </Button>
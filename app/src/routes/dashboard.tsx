@@ -26,6 +26,8 @@ import {
   decodeRedPacket,
   findRedPacketPDA,
   findVaultPDA,
+  findTreasuryPDA,
+  findTreasuryVaultPDA,
   buildCloseInstruction,
   formatAmount,
   getRedPacketStatus,
@@ -174,17 +176,30 @@ function PacketCard({
   const nowUnix = Math.floor(Date.now() / 1000);
   const status = getRedPacketStatus(rp, nowUnix);
   const canClose = status === "expired" || status === "fully_claimed";
+  // Active packets can still be cancelled until the first claim
+  const canCancel = status === "active" && rp.numClaimed === 0;
 
-  const claimUrl = `${window.location.origin}/claim/${rp.creator}/${rp.id}`;
+  // Packets handed over with transfer_creator keep their original address
+  const claimUrl = `${window.location.origin}/claim/${rp.originalCreator}/${rp.id}`;
 
   async function handleClose() {
     setIsClosing(true);
     try {
       const creatorAddress = address(account.address);
-      const [rpPDA] = await findRedPacketPDA(creatorAddress, rp.id);
-      const [vaultPDA] = await findVaultPDA(creatorAddress, rp.id);
+      const [rpPDA] = await findRedPacketPDA(rp.originalCreator, rp.id);
+      const [vaultPDA] = await findVaultPDA(rp.originalCreator, rp.id);
 
+      // Cancelling before expiry refunds even a burnUnclaimed or
+      // donateUnclaimed packet
+      const burn = rp.burnUnclaimed && !canCancel;
+      const donate = rp.donateUnclaimed && !canCancel;
       let creatorTokenAccount: Address | undefined;
+      let burnMint: Address | undefined;
+      let donateTreasury: Address | undefined;
+      let donateTreasuryVault: Address | undefined;
+      if (isSol && donate) {
+        [donateTreasury] = await findTreasuryPDA("SOL");
+      }
       if (!isSol) {
         const vaultAccount = await fetchEncodedAccount(rpc, vaultPDA);
         if (vaultAccount.exists) {
@@ -195,19 +210,36 @@ function PacketCard({
             creatorAddress,
             mintAddr
           );
+          if (burn) burnMint = mintAddr;
+          if (donate) {
+            [donateTreasury] = await findTreasuryPDA(mintAddr);
+            [donateTreasuryVault] = await findTreasuryVaultPDA(mintAddr);
+          }
         }
       }
 
       const ix = buildCloseInstruction({
         tokenType: rp.tokenType,
+        cancel: canCancel,
         creator: creatorAddress,
         creatorTokenAccount,
         redPacket: rpPDA,
         vault: vaultPDA,
+        burnMint,
+        donateTreasury,
+        donateTreasuryVault,
       });
 
       await sendTransaction(signer, [ix]);
-      toast.success("Red packet closed, funds reclaimed!");
+      toast.success(
+        canCancel
+          ? "Red packet cancelled, funds reclaimed!"
+          : burn
+            ? "Red packet closed, leftover tokens burned!"
+            : donate
+              ? "Red packet closed, leftovers donated to the treasury!"
+              : "Red packet closed, funds reclaimed!"
+      );
       onClose();
     } catch (e: unknown) {
       const msg = e instanceof Error ? e.message : "Close failed";
@@ -243,7 +275,7 @@ function PacketCard({
         <CardDescription>
           ID: {String(rp.id)} &middot; {rp.numClaimed}/{rp.numRecipients}{" "}
           claimed &middot;{" "}
-          {rp.splitMode === 0 ? "Even" : "Random"}
+          {["Even", "Random", "Weighted", "Lucky", "Raffle"][rp.splitMode] ?? "Unknown"}
         </CardDescription>
       </CardHeader>
       <CardContent className="flex gap-2">
@@ -257,14 +289,20 @@ function PacketCard({
         >
           Copy Link
         </Button>
-        {canClose && (
+        {(canClose || canCancel) && (
           <Button
             variant="destructive"
             size="sm"
             onClick={handleClose}
             disabled={isClosing}
           >
-            {isClosing ? "Closing..." : "Close & Reclaim"}
+            {isClosing
+              ? canCancel
+                ? "Cancelling..."
+                : "Closing..."
+              : canCancel
+                ? "Cancel & Reclaim"
+                : "Close & Reclaim"}
           </Button>
         )}
       </CardContent>
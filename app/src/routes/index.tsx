@@ -28,7 +28,6 @@ import {
   findTreasuryVaultPDA,
   buildCreateInstruction,
   computeFee,
-  generateRandomSplit,
   formatAmount,
   BLINKS_BASE_URL,
 } from "@/lib/program";
@@ -104,11 +103,6 @@ function CreateForm({
       const [rpPDA, rpBump] = await findRedPacketPDA(creatorAddress, id);
       const [vaultPDA, vaultBump] = await findVaultPDA(creatorAddress, id);
 
-      const amounts =
-        splitMode === SPLIT_RANDOM
-          ? generateRandomSplit(totalLamports, numRecipients)
-          : undefined;
-
       let mint: Address | undefined;
       let treasuryVault: Address | undefined;
       let creatorTokenAccount: Address | undefined;
@@ -135,7 +129,6 @@ function CreateForm({
         expiresAt,
         rpBump,
         vaultBump,
-        amounts,
         creator: creatorAddress,
         creatorTokenAccount,
         redPacket: rpPDA,
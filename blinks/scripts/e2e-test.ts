@@ -160,10 +160,10 @@ function buildCreateInstruction(
   });
 }
 
-function extractId(message: string): bigint {
-  const match = message.match(/id=(\d+)/);
-  if (!match) throw new Error("No id in message");
-  return BigInt(match[1]);
+function extractId(createResp: any): bigint {
+  const id = createResp.redPacket?.id;
+  if (typeof id !== "string") throw new Error("No redPacket.id in create response");
+  return BigInt(id);
 }
 
 // ============================================================
@@ -184,7 +184,7 @@ async function test1_EvenSplit2Recipients(
   assert(!!createResp.transaction, "Create returns transaction");
   assert(!!createResp.message, "Create returns message");
 
-  const redPacketId = extractId(createResp.message);
+  const redPacketId = extractId(createResp);
   console.log(`  Red Packet ID: ${redPacketId}`);
 
   const createSig = await signAndSend(connection, createResp.transaction, [creator]);
@@ -276,7 +276,7 @@ async function test2_RandomSplit2Recipients(
   );
   assert(!!createResp.transaction, "Create returns transaction");
 
-  const redPacketId = extractId(createResp.message);
+  const redPacketId = extractId(createResp);
   console.log(`  Red Packet ID: ${redPacketId}`);
 
   const createSig = await signAndSend(connection, createResp.transaction, [creator]);
@@ -291,9 +291,9 @@ async function test2_RandomSplit2Recipients(
   const rpData = rpAccount!.data;
   assert(rpData[59] === 1, "split_mode = 1 (random)");
 
-  // Read amounts at offset 71
-  const amt0 = rpData.readBigUInt64LE(71);
-  const amt1 = rpData.readBigUInt64LE(79);
+  // Read amounts right after the 81-byte header
+  const amt0 = rpData.readBigUInt64LE(81);
+  const amt1 = rpData.readBigUInt64LE(89);
   const totalAmount = rpData.readBigUInt64LE(41);
   assert(amt0 + amt1 === totalAmount, `Amounts sum to total (${amt0} + ${amt1} = ${totalAmount})`);
   assert(amt0 > 0n && amt1 > 0n, "Both amounts > 0");
@@ -353,7 +353,7 @@ async function test3_SingleRecipient(
   );
   assert(!!createResp.transaction, "Create returns transaction");
 
-  const redPacketId = extractId(createResp.message);
+  const redPacketId = extractId(createResp);
   const createSig = await signAndSend(connection, createResp.transaction, [creator]);
   console.log(`  Create tx: ${createSig}`);
 
@@ -405,7 +405,7 @@ async function test4_MaxRecipients20(
   );
   assert(!!createResp.transaction, "Create returns transaction");
 
-  const redPacketId = extractId(createResp.message);
+  const redPacketId = extractId(createResp);
   const createSig = await signAndSend(connection, createResp.transaction, [creator]);
   console.log(`  Create tx: ${createSig}`);
 
@@ -414,7 +414,7 @@ async function test4_MaxRecipients20(
   const [rpAddr] = findRedPacketPDA(creator.publicKey, redPacketId);
   const rpAccount = await connection.getAccountInfo(rpAddr);
   assert(rpAccount !== null, "Red packet exists");
-  assert(rpAccount!.data.length === 71 + 40 * 20, `Account size = ${71 + 40 * 20} (got ${rpAccount!.data.length})`);
+  assert(rpAccount!.data.length === 81 + 40 * 20, `Account size = ${81 + 40 * 20} (got ${rpAccount!.data.length})`);
   assert(rpAccount!.data[57] === 20, "num_recipients = 20");
 
   // Claim 1 slot to verify it works
@@ -539,7 +539,7 @@ async function test6_DoubleClaimRejection(
     `/api/actions/create?amount=0.01&recipients=2&split_mode=0&expiry_hours=1`,
     creator.publicKey.toBase58()
   );
-  const redPacketId = extractId(createResp.message);
+  const redPacketId = extractId(createResp);
   await signAndSend(connection, createResp.transaction, [creator]);
 
   await sleep(3000);
@@ -595,7 +595,7 @@ async function test7_CloseByNonCreator(
     `/api/actions/create?amount=0.005&recipients=1&split_mode=0&expiry_hours=1`,
     creator.publicKey.toBase58()
   );
-  const redPacketId = extractId(createResp.message);
+  const redPacketId = extractId(createResp);
   await signAndSend(connection, createResp.transaction, [creator]);
 
   await sleep(3000);
@@ -647,6 +647,43 @@ async function test8_CloseActivePacket(
   );
 }
 
+async function test12_CancelUnclaimedPacket(
+  connection: Connection,
+  creator: Keypair,
+) {
+  console.log("\n=== TEST 12: Cancel Active Packet With Zero Claims ===");
+
+  const createResp = await blinksPost(
+    `/api/actions/create?amount=0.005&recipients=3&split_mode=0&expiry_hours=1`,
+    creator.publicKey.toBase58()
+  );
+  assert(!!createResp.transaction, "Create returns transaction");
+  const redPacketId = extractId(createResp);
+  await signAndSend(connection, createResp.transaction, [creator]);
+
+  await sleep(3000);
+
+  const closeUrl = `/api/actions/close?creator=${creator.publicKey.toBase58()}&id=${redPacketId}`;
+  const meta = await blinksGet(closeUrl);
+  assert(meta.title === "Cancel Red Packet", `Title offers cancel (got: ${meta.title})`);
+  assert(meta.label === "Cancel & Reclaim SOL", `Label offers cancel (got: ${meta.label})`);
+  assert(!meta.disabled, "Cancel is enabled");
+
+  const cancelResp = await blinksPost(closeUrl, creator.publicKey.toBase58());
+  assert(!!cancelResp.transaction, "Cancel returns transaction");
+  assert(
+    cancelResp.message?.includes("cancelled") ?? false,
+    `Message says cancelled (got: ${cancelResp.message})`
+  );
+  await signAndSend(connection, cancelResp.transaction, [creator]);
+
+  await sleep(2000);
+
+  const [rpAddr] = findRedPacketPDA(creator.publicKey, redPacketId);
+  const rpCancelled = await connection.getAccountInfo(rpAddr);
+  assert(rpCancelled === null, "Red packet closed by cancel before expiry");
+}
+
 async function test9_FeeAccumulation(
   connection: Connection,
   treasuryBalBefore: number,
@@ -843,6 +880,13 @@ async function main() {
     failed++;
   }
 
+  try {
+    await test12_CancelUnclaimedPacket(connection, creator);
+  } catch (e: any) {
+    console.error(`  TEST 12 CRASHED: ${e.message}`);
+    failed++;
+  }
+
   try {
     await test9_FeeAccumulation(connection, treasuryBalBefore);
   } catch (e: any) {
@@ -1,33 +1,65 @@
 use async_trait::async_trait;
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::hash::{hash, Hash};
 use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::message::Message;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
 use solana_sdk::transaction::Transaction;
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::{get_param, serialize_tx, Action};
+use super::{
+    account_exists, create, fetch_account, fetch_packet_token, get_id_param, get_param, serialize_tx, simulate_tx, Action,
+};
+use crate::cache::RedPacketCache;
 use crate::consts::*;
 use crate::error::AppError;
 use crate::program;
 use crate::spec::*;
 
-pub struct ClaimAction;
+pub struct ClaimAction {
+    cache: Arc<RedPacketCache>,
+    /// Simulate each transaction before returning it
+    simulate: bool,
+    /// Compute unit price, in micro-lamports, each transaction offers
+    priority_fee: u64,
+    /// Pays the fee of claims sent with `relay=1`, if configured
+    relayer: Option<Arc<Keypair>>,
+}
 
-/// Fetch and decode a red packet from chain.
-async fn fetch_red_packet(
-    rpc: &RpcClient,
-    creator: &Pubkey,
-    id: u64,
-) -> Result<program::RedPacketAccount, AppError> {
-    let (red_packet_addr, _) = program::find_red_packet_pda(creator, id);
-    let account = rpc
-        .get_account(&red_packet_addr)
-        .await
-        .map_err(|_| AppError::NotFound("Red packet not found on chain".into()))?;
-
-    program::decode_red_packet(&account.data)
+impl ClaimAction {
+    pub fn new(
+        cache: Arc<RedPacketCache>,
+        simulate: bool,
+        priority_fee: u64,
+        relayer: Option<Arc<Keypair>>,
+    ) -> Self {
+        Self { cache, simulate, priority_fee, relayer }
+    }
+
+    /// The `relay` choice, offered when a relayer is configured and the
+    /// claim costs the claimer no rent of its own
+    fn relay_parameter(
+        &self,
+        rp: &program::RedPacketAccount,
+        href: &mut String,
+        parameters: &mut Vec<ActionParameter>,
+    ) {
+        if self.relayer.is_none() || rp.large {
+            return;
+        }
+        href.push_str("&relay={relay}");
+        parameters.push(ActionParameter::radio(
+            "relay",
+            "Network fee",
+            vec![
+                ActionParameterOption::new("Paid by my wallet", "0"),
+                ActionParameterOption::new("Sponsored (for wallets without SOL)", "1"),
+            ],
+        ));
+    }
 }
 
 #[async_trait]
@@ -43,22 +75,76 @@ impl Action for ClaimAction {
         params: HashMap<String, String>,
     ) -> Result<ActionGetResponse, AppError> {
         let creator: Pubkey = get_param(&params, "creator")?;
-        let id: u64 = get_param(&params, "id")?;
+        let id = get_id_param(&params)?;
 
-        let rp = fetch_red_packet(rpc, &creator, id).await?;
+        let rp = self.cache.fetch(rpc, &creator, id, false).await?;
+        let spl = fetch_packet_token(rpc, &self.cache, &rp).await?;
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64;
         let status = program::get_status(&rp, now);
 
-        let total_sol = program::lamports_to_sol(rp.total_amount);
-        let remaining_sol = program::lamports_to_sol(rp.remaining_amount);
+        let (total, unit) = program::display_amount(rp.total_amount, spl.as_ref());
+        let (remaining, _) = program::display_amount(rp.remaining_amount, spl.as_ref());
 
-        let description = format!(
-            "{total_sol} SOL red packet — {}/{} claimed, {remaining_sol} SOL remaining (devnet)",
-            rp.num_claimed, rp.num_recipients
+        let mut description = format!(
+            "{total} {unit} red packet — {}/{} claimed, {remaining} {unit} remaining{}",
+            rp.num_claimed,
+            rp.num_recipients,
+            CLUSTER.label()
         );
+        // The creator's greeting leads, as it would on a paper red packet
+        if let Some(memo) = &rp.memo {
+            description = format!("“{memo}” — {description}");
+        }
+        // Wallets can't tell why a gated claim fails, so state the rule up front
+        if let Some(mint) = &rp.gate_mint {
+            let mint_account = fetch_account(rpc, mint, "Gate token mint not found on chain").await?;
+            let decimals = program::decode_mint_decimals(&mint_account.data)?;
+            description.push_str(&gate_requirement(&rp, decimals));
+        }
+        if rp.assigned {
+            description.push_str(" — reserved for wallets chosen by the creator");
+        }
+        if rp.no_self_claim {
+            description.push_str(" — the creator can't claim it");
+        }
+        if rp.split_mode == SPLIT_RAFFLE {
+            description.push_str(" — a raffle: one entrant wins the whole pot");
+        }
+        if rp.vesting {
+            description.push_str(" — each share unlocks gradually until it expires; claim again as it does");
+        }
+        description.push_str(&program::packet_age(&rp, now));
+        description.push_str(&program::last_claim_age(&rp, now));
+        // GET carries no wallet; clients that pass the viewer as `account` get
+        // a disabled button instead of a claim that fails on-chain
+        let viewer: Option<Pubkey> = params.get("account").and_then(|raw| raw.parse().ok());
+
+        // A vesting claimer comes back for its own slot, even once the packet
+        // is full or expired
+        if let Some(slot) = viewer.and_then(|viewer| program::vesting_slot(&rp, &viewer)) {
+            let unlocked = program::claimable(&rp, slot, now);
+            if unlocked == 0 {
+                return Ok(
+                    ActionGetResponse::new(ICON_URL, "Red Packet", &description, "Nothing Unlocked")
+                        .with_error("Nothing more of your share has unlocked yet"),
+                );
+            }
+            let (unlocked, _) = program::display_amount(unlocked, spl.as_ref());
+            let label = format!("Claim {unlocked:.4} {unit} Unlocked");
+            let mut href = format!("/api/actions/claim?creator={creator}&id={id}&destination={{destination}}");
+            let mut parameters = vec![ActionParameter::text("destination", "Send to another wallet (optional)", false)];
+            self.relay_parameter(&rp, &mut href, &mut parameters);
+            return Ok(ActionGetResponse::new(ICON_URL, "Red Packet", &description, &label).with_links(vec![
+                LinkedAction {
+                    href,
+                    label,
+                    parameters: Some(parameters),
+                },
+            ]));
+        }
 
         match status {
             "fully_claimed" => Ok(
@@ -69,27 +155,73 @@ impl Action for ClaimAction {
                 ActionGetResponse::new(ICON_URL, "Red Packet", &description, "Expired")
                     .with_error("This red packet has expired"),
             ),
+            _ if rp.no_self_claim && viewer == Some(rp.creator) => Ok(
+                ActionGetResponse::new(ICON_URL, "Red Packet", &description, "Your Red Packet")
+                    .with_error("You created this red packet, so it's only for others to claim"),
+            ),
+            _ if rp.reveal_pending => Ok(
+                ActionGetResponse::new(ICON_URL, "Red Packet", &description, "Not Open Yet")
+                    .with_error("The creator hasn't revealed this red packet's randomness yet"),
+            ),
+            // The voucher comes from the campaign's backend, which blinks can't reach
+            _ if rp.voucher_authority.is_some() => Ok(
+                ActionGetResponse::new(ICON_URL, "Red Packet", &description, "Voucher Required")
+                    .with_error("This red packet is claimed through its campaign site"),
+            ),
             _ => {
                 // Active — show claim button
-                let next_slot = rp.num_claimed as usize;
-                let slot_amount = if next_slot < rp.amounts.len() {
-                    program::lamports_to_sol(rp.amounts[next_slot])
-                } else {
-                    remaining_sol / (rp.num_recipients - rp.num_claimed) as f64
+                let slot_amount = match program::next_slot(&rp) {
+                    Some(slot) if slot < rp.amounts.len() => {
+                        program::display_amount(rp.amounts[slot], spl.as_ref()).0
+                    }
+                    _ => remaining / (rp.num_recipients - rp.num_claimed) as f64,
                 };
 
-                let label = if rp.split_mode == SPLIT_EVEN {
-                    format!("Claim {slot_amount:.4} SOL")
+                let label = if rp.split_mode == SPLIT_RAFFLE {
+                    "Enter Draw".into()
+                } else if !rp.random_onchain && rp.split_mode != SPLIT_RANDOM {
+                    format!("Claim {slot_amount:.4} {unit}")
                 } else {
                     "Claim (Random Amount)".into()
                 };
 
-                Ok(ActionGetResponse::new(
-                    ICON_URL,
-                    "Red Packet",
-                    &description,
-                    &label,
-                ))
+                // Password-protected packets ask for it; any claim but a raffle
+                // entry (the pot goes to the drawn signer) may name another
+                // wallet to receive the payout
+                let mut href = format!("/api/actions/claim?creator={creator}&id={id}");
+                let mut parameters = Vec::with_capacity(4);
+                if rp.secret_hash.is_some() {
+                    href.push_str("&password={password}");
+                    parameters.push(ActionParameter::text("password", "Password", true));
+                }
+                if rp.split_mode != SPLIT_RAFFLE {
+                    href.push_str("&destination={destination}");
+                    parameters.push(ActionParameter::text(
+                        "destination",
+                        "Send to another wallet (optional)",
+                        false,
+                    ));
+                }
+                if rp.split_mode != SPLIT_RAFFLE && spl.as_ref().is_some_and(|token| token.mint == *NATIVE_MINT) {
+                    href.push_str("&unwrap={unwrap}");
+                    parameters.push(ActionParameter::radio(
+                        "unwrap",
+                        "Receive as",
+                        vec![
+                            ActionParameterOption::new("wSOL", "0"),
+                            ActionParameterOption::new("Native SOL (pays your own wallet)", "1"),
+                        ],
+                    ));
+                }
+                self.relay_parameter(&rp, &mut href, &mut parameters);
+
+                Ok(ActionGetResponse::new(ICON_URL, "Red Packet", &description, &label).with_links(vec![
+                    LinkedAction {
+                        href,
+                        label,
+                        parameters: Some(parameters),
+                    },
+                ]))
             }
         }
     }
@@ -102,55 +234,776 @@ impl Action for ClaimAction {
         params: HashMap<String, String>,
     ) -> Result<ActionPostResponse, AppError> {
         let creator: Pubkey = get_param(&params, "creator")?;
-        let id: u64 = get_param(&params, "id")?;
+        let id = get_id_param(&params)?;
 
-        // Fetch current state to get slot index and verify claimable
-        let rp = fetch_red_packet(rpc, &creator, id).await?;
+        // Fresh read: the paid slot must still be unclaimed on-chain
+        let rp = self.cache.fetch(rpc, &creator, id, true).await?;
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64;
         let status = program::get_status(&rp, now);
 
-        if status == "fully_claimed" {
+        // A vesting claimer's repeat claim skips the first claim's checks (and
+        // so carries no password or gate account)
+        let held = program::vesting_slot(&rp, &account);
+        if let Some(slot) = held {
+            if program::claimable(&rp, slot, now) == 0 {
+                return Err(AppError::BadRequest("Nothing more of your share has unlocked yet".into()));
+            }
+        } else if status == "fully_claimed" {
             return Err(AppError::BadRequest("Red packet is fully claimed".into()));
-        }
-        if status == "expired" {
+        } else if status == "expired" {
             return Err(AppError::BadRequest("Red packet has expired".into()));
+        } else if rp.reveal_pending {
+            return Err(AppError::BadRequest(
+                "Red packet opens once its creator reveals the randomness".into(),
+            ));
+        } else if rp.voucher_authority.is_some() {
+            return Err(AppError::BadRequest(
+                "Red packet needs a signed voucher; claim it through its campaign site".into(),
+            ));
+        } else if rp.no_self_claim && account == rp.creator {
+            return Err(AppError::BadRequest("The creator can't claim this red packet".into()));
+        }
+        // Large packets have no claimers list: the program would refuse a
+        // wallet whose receipt exists
+        if rp.large {
+            let (red_packet, _) = program::find_red_packet_pda(&creator, id);
+            let (receipt, _) = program::find_receipt_pda(&red_packet, &account);
+            let receipted = rpc
+                .get_account_with_commitment(&receipt, rpc.commitment())
+                .await?
+                .value
+                .is_some_and(|receipt| receipt.owner == *PROGRAM_ID);
+            if receipted {
+                return Err(AppError::BadRequest("Your wallet already claimed this red packet".into()));
+            }
         }
 
-        let slot_index = rp.num_claimed;
-        let (red_packet_addr, _) = program::find_red_packet_pda(&creator, id);
-        let (vault_addr, _) = program::find_vault_pda(&creator, id);
+        // Assigned packets pay the wallet's own slot, not the next one
+        let paid_slot = if let Some(slot) = held {
+            slot
+        } else if rp.assigned {
+            program::assigned_slot(&rp, &account).ok_or_else(|| {
+                AppError::BadRequest(
+                    "This red packet is reserved for other wallets, or yours already claimed".into(),
+                )
+            })?
+        } else {
+            program::next_slot(&rp).unwrap_or(rp.amounts.len())
+        };
 
-        let data = program::build_claim_data(slot_index);
+        let secret = if held.is_some() { None } else { claim_secret(&rp, &params)? };
+        let destination = claim_destination(&params)?;
+        let slot_index = paid_slot as u8;
+        let spl = fetch_packet_token(rpc, &self.cache, &rp).await?;
 
-        // SOL claim: claimer, red_packet, vault (3)
-        let ix = Instruction {
-            program_id: *PROGRAM_ID,
-            accounts: vec![
-                AccountMeta::new(account, true),
-                AccountMeta::new(red_packet_addr, false),
-                AccountMeta::new(vault_addr, false),
-            ],
-            data,
+        // Gated packets check the claimer's ATA for the gate mint
+        let gate_account = match &rp.gate_mint {
+            _ if held.is_some() => None,
+            None => None,
+            Some(mint) => {
+                // The gate mint may live under either token program
+                let gate_program = rpc
+                    .get_account(mint)
+                    .await
+                    .map(|mint_account| mint_account.owner)
+                    .unwrap_or(*TOKEN_PROGRAM_ID);
+                let ata = program::find_associated_token_address(&account, mint, &gate_program);
+                let balance = match rpc.get_account_with_commitment(&ata, rpc.commitment()).await?.value {
+                    Some(ata_account) if program::is_token_program(&ata_account.owner) => {
+                        Some(program::decode_token_account_amount(&ata_account.data)?)
+                    }
+                    _ => None,
+                };
+                if balance.is_none_or(|balance| balance < rp.gate_min_amount) {
+                    return Err(AppError::BadRequest(format!(
+                        "This red packet is for holders only: your wallet needs at least {} base units of token {mint}",
+                        rp.gate_min_amount
+                    )));
+                }
+                Some(ata)
+            }
         };
 
+        let unwrap = claim_unwrap(&params, spl.as_ref(), destination.as_ref())?;
+        let relayer = claim_relay(&params, self.relayer.as_deref(), rp.large, unwrap)?;
+
+        // SPL payouts land in the payee's ATA; create it first if missing
+        // (the claimer pays the rent either way)
+        let payee = destination.unwrap_or(account);
+        let mut instructions = Vec::with_capacity(3);
+        let mut unwrap_ata = None;
+        if let Some(token) = &spl {
+            let ata = program::find_associated_token_address(&payee, &token.mint, &token.token_program);
+            if !account_exists(rpc, &ata).await? {
+                // The payee could close a relayer-funded ATA and keep its
+                // rent, so sponsored claims only pay into existing ones
+                if relayer.is_some() {
+                    return Err(AppError::BadRequest(
+                        "Sponsored claims can't create your token account; pay the fee from your wallet".into(),
+                    ));
+                }
+                instructions.push(program::create_ata_idempotent_instruction(
+                    &account,
+                    &payee,
+                    &token.mint,
+                    &token.token_program,
+                ));
+            } else if unwrap {
+                return Err(AppError::BadRequest(
+                    "You already have a wSOL account; claim without unwrapping, or close it first".into(),
+                ));
+            }
+            if unwrap {
+                unwrap_ata = Some((ata, token.token_program));
+            }
+        }
+        instructions.push(build_claim_instruction(
+            &account,
+            &creator,
+            id,
+            slot_index,
+            spl.as_ref(),
+            secret,
+            gate_account.as_ref(),
+            destination.as_ref(),
+            rp.large,
+        ));
+        // Closing the fresh wSOL account pays its balance and rent to the
+        // claimer as native SOL
+        if let Some((ata, token_program)) = unwrap_ata {
+            instructions.push(program::close_account_instruction(&ata, &account, &account, &token_program));
+        }
+
+        let unit_limit = CLAIM_COMPUTE_UNITS + ATA_COMPUTE_UNITS * (instructions.len() as u32 - 1);
+        let instructions = program::with_compute_budget(instructions, unit_limit, self.priority_fee);
+
         let blockhash = rpc.get_latest_blockhash().await?;
-        let msg = Message::new_with_blockhash(&[ix], Some(&account), &blockhash);
-        let tx = Transaction::new_unsigned(msg);
+        let tx = claim_transaction(&instructions, &account, relayer, blockhash);
         let transaction = serialize_tx(&tx)?;
+        // A relayed claim always simulates: the relayer's signature must
+        // never go out on a transaction that would fail at its expense
+        let simulated = if self.simulate || relayer.is_some() {
+            simulate_tx(rpc, &tx).await?
+        } else if rp.random_onchain {
+            // Only for the message: a failed simulation here is the
+            // wallet's to report
+            simulate_tx(rpc, &tx).await.ok().flatten()
+        } else {
+            None
+        };
 
-        let claim_amount = if (slot_index as usize) < rp.amounts.len() {
-            program::lamports_to_sol(rp.amounts[slot_index as usize])
+        // On-chain random slots are only drawn when the claim lands, so the
+        // simulated draw is an estimate unless the packet was revealed
+        let claimed = if rp.random_onchain {
+            match simulated.as_deref().and_then(claimed_amount) {
+                Some((amount, _)) => {
+                    let (claim_amount, unit) = program::display_amount(amount, spl.as_ref());
+                    format!("about {claim_amount:.4} {unit}")
+                }
+                None => "a random amount".to_string(),
+            }
         } else {
-            0.0
+            let slot_amount = program::claimable(&rp, paid_slot, now);
+            let (claim_amount, unit) = program::display_amount(slot_amount, spl.as_ref());
+            format!("{claim_amount:.4} {unit}")
         };
 
         Ok(ActionPostResponse {
             transaction,
-            message: Some(format!("Claimed {claim_amount:.4} SOL from red packet!")),
-            links: None,
+            message: Some(match destination {
+                _ if rp.split_mode == SPLIT_RAFFLE => {
+                    "Entered the draw! One entrant wins the whole pot once it's full or expired.".into()
+                }
+                None => format!("Claimed {claimed} from red packet!"),
+                Some(destination) => format!("Claimed {claimed} from red packet, sent to {destination}!"),
+            }),
+            links: Some(pass_it_on()),
+            red_packet: None,
         })
     }
 }
+
+/// The claim transaction, paid by the claimer or, when relayed, by the
+/// relayer: it signs for the fee payer slot and leaves the claimer's
+/// signature to the wallet.
+pub fn claim_transaction(
+    instructions: &[Instruction],
+    claimer: &Pubkey,
+    relayer: Option<&Keypair>,
+    blockhash: Hash,
+) -> Transaction {
+    let fee_payer = relayer.map_or(*claimer, |relayer| relayer.pubkey());
+    let msg = Message::new_with_blockhash(instructions, Some(&fee_payer), &blockhash);
+    let mut tx = Transaction::new_unsigned(msg);
+    if let Some(relayer) = relayer {
+        tx.partial_sign(&[relayer], blockhash);
+    }
+    tx
+}
+
+/// Amount paid and slot from a claim's return data: the program sets the
+/// amount (u64 LE) then the slot
+pub fn claimed_amount(return_data: &[u8]) -> Option<(u64, u8)> {
+    let data: &[u8; 9] = return_data.try_into().ok()?;
+    Some((u64::from_le_bytes(data[..8].try_into().unwrap()), data[8]))
+}
+
+/// Chained after every claim: once it confirms, the wallet renders the
+/// create action so the claimer can send a packet of their own
+fn pass_it_on() -> NextActionLinks {
+    let mut action = create::create_action();
+    action.title = "Pass It On".into();
+    action.description = format!("Now create a red packet of your own for friends to claim{}", CLUSTER.label());
+    NextActionLinks {
+        next: NextActionLink::Inline {
+            action: NextAction::Action(action),
+        },
+    }
+}
+
+/// The password bytes to claim `rp` with, or None for open packets. Checked
+/// against the stored hash here so a wrong password fails before signing.
+pub fn claim_secret<'a>(
+    rp: &program::RedPacketAccount,
+    params: &'a HashMap<String, String>,
+) -> Result<Option<&'a [u8]>, AppError> {
+    let Some(secret_hash) = rp.secret_hash else {
+        return Ok(None);
+    };
+    let password = params
+        .get("password")
+        .filter(|p| !p.is_empty())
+        .ok_or_else(|| AppError::BadRequest("This red packet needs a password".into()))?;
+    if password.len() > MAX_SECRET_LEN {
+        return Err(AppError::BadRequest(format!(
+            "Password is longer than {MAX_SECRET_LEN} bytes"
+        )));
+    }
+    if hash(password.as_bytes()).to_bytes() != secret_hash {
+        return Err(AppError::BadRequest("Wrong password".into()));
+    }
+    Ok(Some(password.as_bytes()))
+}
+
+/// The optional `destination` wallet to pay instead of the signer; blank
+/// means the signer. The signer is still the one recorded as claimer.
+pub fn claim_destination(params: &HashMap<String, String>) -> Result<Option<Pubkey>, AppError> {
+    let Some(raw) = params.get("destination").map(|raw| raw.trim()).filter(|raw| !raw.is_empty()) else {
+        return Ok(None);
+    };
+    raw.parse()
+        .map(Some)
+        .map_err(|_| AppError::BadRequest(format!("Invalid destination wallet: {raw:?}")))
+}
+
+/// The optional `unwrap` flag: 1 closes the claimer's wSOL account after a
+/// wrapped-SOL packet's claim, so it receives native SOL. It must pay the
+/// signer, who owns the account, into one this transaction creates:
+/// closing an existing one would sweep the rest of its wSOL too.
+pub fn claim_unwrap(
+    params: &HashMap<String, String>,
+    spl: Option<&program::SplToken>,
+    destination: Option<&Pubkey>,
+) -> Result<bool, AppError> {
+    match params.get("unwrap").map(|raw| raw.trim()) {
+        None | Some("") | Some("0") => return Ok(false),
+        Some("1") => {}
+        Some(_) => {
+            return Err(AppError::BadRequest(
+                "Unwrap must be 0 (keep wSOL) or 1 (receive native SOL)".into(),
+            ))
+        }
+    }
+    if spl.is_none_or(|token| token.mint != *NATIVE_MINT) {
+        return Err(AppError::BadRequest("Only wrapped SOL packets can be unwrapped".into()));
+    }
+    if destination.is_some() {
+        return Err(AppError::BadRequest(
+            "Unwrapped claims pay your own wallet; leave the destination blank".into(),
+        ));
+    }
+    Ok(true)
+}
+
+/// The optional `relay` flag: 1 has the server's relayer pay the claim's
+/// fee, for wallets holding no SOL. The relayer pays no rent: large packets
+/// charge the claimer a receipt and unwrapping creates a wSOL account, so
+/// neither is relayed (nor, in execute, a claim whose payee lacks its ATA).
+pub fn claim_relay<'a>(
+    params: &HashMap<String, String>,
+    relayer: Option<&'a Keypair>,
+    large: bool,
+    unwrap: bool,
+) -> Result<Option<&'a Keypair>, AppError> {
+    match params.get("relay").map(|raw| raw.trim()) {
+        None | Some("") | Some("0") => return Ok(None),
+        Some("1") => {}
+        Some(_) => {
+            return Err(AppError::BadRequest(
+                "Relay must be 0 (your wallet pays) or 1 (sponsored)".into(),
+            ))
+        }
+    }
+    let Some(relayer) = relayer else {
+        return Err(AppError::BadRequest("This server doesn't sponsor claims; pay the fee from your wallet".into()));
+    };
+    if large {
+        return Err(AppError::BadRequest(
+            "Large red packets charge the claimer receipt rent, so their claims can't be sponsored".into(),
+        ));
+    }
+    if unwrap {
+        return Err(AppError::BadRequest("Sponsored claims can't unwrap; receive wSOL instead".into()));
+    }
+    Ok(Some(relayer))
+}
+
+/// " — holders only: ..." suffix for a gated packet's description, with the
+/// minimum in whole tokens of the gate mint (`decimals`); empty when ungated.
+pub fn gate_requirement(rp: &program::RedPacketAccount, decimals: u8) -> String {
+    let Some(mint) = &rp.gate_mint else {
+        return String::new();
+    };
+    let min = rp.gate_min_amount as f64 / 10f64.powi(decimals as i32);
+    format!(" — holders only: claimers need at least {min} of token {mint}")
+}
+
+/// Claim instruction for `claimer` taking `slot_index`; `spl` is None for
+/// SOL packets, `secret` None for packets without a password,
+/// `gate_account` None for ungated packets and `destination` None to pay the
+/// claimer itself. A destination is a wallet: SPL payouts go to its ATA.
+/// `large` packets end with the claimer's receipt PDA and the system program.
+///
+/// SOL: claimer, red_packet, vault, treasury (4)
+/// SPL: claimer, claimer_ata, red_packet, vault, token_program, treasury (6)
+///
+/// The treasury is the packet mint's, which the program checks for pause.
+#[allow(clippy::too_many_arguments)]
+pub fn build_claim_instruction(
+    claimer: &Pubkey,
+    creator: &Pubkey,
+    id: u64,
+    slot_index: u8,
+    spl: Option<&program::SplToken>,
+    secret: Option<&[u8]>,
+    gate_account: Option<&Pubkey>,
+    destination: Option<&Pubkey>,
+    large: bool,
+) -> Instruction {
+    let (red_packet, _) = program::find_red_packet_pda(creator, id);
+    let (vault, _) = program::find_vault_pda(creator, id);
+
+    let (mut accounts, token_type) = match spl {
+        None => (
+            vec![
+                AccountMeta::new(*claimer, true),
+                AccountMeta::new(red_packet, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(program::find_treasury_pda_sol().0, false),
+            ],
+            TOKEN_TYPE_SOL,
+        ),
+        Some(token) => (
+            vec![
+                AccountMeta::new(*claimer, true),
+                AccountMeta::new(
+                    program::find_associated_token_address(claimer, &token.mint, &token.token_program),
+                    false,
+                ),
+                AccountMeta::new(red_packet, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(token.token_program, false),
+                AccountMeta::new_readonly(program::find_treasury_pda(&token.mint).0, false),
+            ],
+            TOKEN_TYPE_SPL,
+        ),
+    };
+
+    // Blinks never serves voucher packets, so the gate account comes straight
+    // after the fixed accounts, then the destination
+    if let Some(gate_account) = gate_account {
+        accounts.push(AccountMeta::new_readonly(*gate_account, false));
+    }
+    if let Some(destination) = destination {
+        let payee = match spl {
+            None => *destination,
+            Some(token) => program::find_associated_token_address(destination, &token.mint, &token.token_program),
+        };
+        accounts.push(AccountMeta::new(payee, false));
+    }
+    if large {
+        accounts.push(AccountMeta::new(program::find_receipt_pda(&red_packet, claimer).0, false));
+        accounts.push(AccountMeta::new_readonly(solana_sdk::system_program::id(), false));
+    }
+
+    Instruction {
+        program_id: *PROGRAM_ID,
+        accounts,
+        data: program::build_claim_data(token_type, slot_index, destination.is_some(), secret),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usdc() -> program::SplToken {
+        program::SplToken {
+            mint: Pubkey::new_unique(),
+            token_program: *TOKEN_PROGRAM_ID,
+            decimals: 6,
+        }
+    }
+
+    #[test]
+    fn claimed_amount_reads_the_claim_return_data() {
+        let mut data = 333_333_334u64.to_le_bytes().to_vec();
+        data.push(2);
+        assert_eq!(claimed_amount(&data), Some((333_333_334, 2)));
+        assert_eq!(claimed_amount(&data[..8]), None);
+        assert_eq!(claimed_amount(&[]), None);
+    }
+
+    #[test]
+    fn sol_claim_uses_four_accounts() {
+        let (claimer, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = build_claim_instruction(&claimer, &creator, 7, 2, None, None, None, None, false);
+
+        assert_eq!(ix.data, vec![1, TOKEN_TYPE_SOL, 2]);
+        let keys: Vec<Pubkey> = ix.accounts.iter().map(|m| m.pubkey).collect();
+        assert_eq!(
+            keys,
+            vec![
+                claimer,
+                program::find_red_packet_pda(&creator, 7).0,
+                program::find_vault_pda(&creator, 7).0,
+                program::find_treasury_pda_sol().0,
+            ]
+        );
+    }
+
+    #[test]
+    fn spl_claim_pays_claimer_ata_and_checks_mint_treasury() {
+        let (claimer, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let token = usdc();
+        let ix = build_claim_instruction(&claimer, &creator, 7, 0, Some(&token), None, None, None, false);
+
+        assert_eq!(ix.data, vec![1, TOKEN_TYPE_SPL, 0]);
+        let ata = program::find_associated_token_address(&claimer, &token.mint, &token.token_program);
+        let metas: Vec<(Pubkey, bool, bool)> = ix
+            .accounts
+            .iter()
+            .map(|m| (m.pubkey, m.is_signer, m.is_writable))
+            .collect();
+        assert_eq!(
+            metas,
+            vec![
+                (claimer, true, true),
+                (ata, false, true),
+                (program::find_red_packet_pda(&creator, 7).0, false, true),
+                (program::find_vault_pda(&creator, 7).0, false, true),
+                (*TOKEN_PROGRAM_ID, false, false),
+                (program::find_treasury_pda(&token.mint).0, false, false),
+            ]
+        );
+    }
+
+    fn packet(secret_hash: Option<[u8; 32]>) -> program::RedPacketAccount {
+        program::RedPacketAccount {
+            secret_hash,
+            amounts: vec![500, 500],
+            ..program::RedPacketAccount::mock()
+        }
+    }
+
+    #[test]
+    fn packet_age_rounds_down_and_skips_migrated_packets() {
+        let mut rp = packet(None);
+        assert_eq!(program::packet_age(&rp, 1_700_000_000), "");
+
+        rp.created_at = 1_700_000_000;
+        let age = |secs: i64| program::packet_age(&rp, 1_700_000_000 + secs);
+        assert_eq!(age(-5), " — created just now");
+        assert_eq!(age(59), " — created just now");
+        assert_eq!(age(60), " — created 1 minute ago");
+        assert_eq!(age(3 * 3_600 + 59), " — created 3 hours ago");
+        assert_eq!(age(2 * 86_400), " — created 2 days ago");
+    }
+
+    #[test]
+    fn last_claim_age_uses_the_newest_recorded_claim() {
+        let mut rp = packet(None);
+        assert_eq!(program::last_claim_age(&rp, 1_700_000_000), "");
+
+        // Slots claimed before migration read 0 and are skipped
+        rp.claimed_at = vec![0, 0];
+        assert_eq!(program::last_claim_age(&rp, 1_700_000_000), "");
+
+        rp.claimed_at = vec![1_700_000_000 - 7_200, 1_700_000_000 - 300];
+        assert_eq!(program::last_claim_age(&rp, 1_700_000_000), " — last claimed 5 minutes ago");
+    }
+
+    #[test]
+    fn password_is_checked_and_appended_to_claim_data() {
+        let rp = packet(Some(hash(b"hunter2").to_bytes()));
+        let with = |password: &str| HashMap::from([("password".to_string(), password.to_string())]);
+
+        assert!(claim_secret(&rp, &HashMap::new()).is_err());
+        assert!(claim_secret(&rp, &with("")).is_err());
+        assert!(claim_secret(&rp, &with("hunter3")).is_err());
+        assert!(claim_secret(&rp, &with(&"x".repeat(MAX_SECRET_LEN + 1))).is_err());
+
+        let params = with("hunter2");
+        let secret = claim_secret(&rp, &params).unwrap();
+        assert_eq!(secret, Some(b"hunter2".as_ref()));
+
+        let ix = build_claim_instruction(&Pubkey::new_unique(), &rp.creator, 1, 0, None, secret, None, None, false);
+        assert_eq!(ix.data, [&[1, TOKEN_TYPE_SOL, 0, 0][..], b"hunter2"].concat());
+    }
+
+    #[test]
+    fn assigned_packets_pay_the_wallets_own_unclaimed_slot() {
+        let (first, second, stranger) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut rp = packet(None);
+        rp.claimers = vec![first, second];
+        rp.claimed = vec![false, false];
+        assert_eq!(program::assigned_slot(&rp, &second), None);
+
+        rp.assigned = true;
+        assert_eq!(program::assigned_slot(&rp, &second), Some(1));
+        assert_eq!(program::assigned_slot(&rp, &stranger), None);
+        rp.num_claimed = 1;
+        rp.claimed = vec![true, false];
+        assert_eq!(program::assigned_slot(&rp, &first), None);
+    }
+
+    #[test]
+    fn claims_may_leave_gaps_that_later_claims_fill() {
+        let wallet = Pubkey::new_unique();
+        let mut rp = packet(None);
+        rp.num_recipients = 3;
+        rp.amounts = vec![100, 200, 300];
+        rp.claimers = vec![Pubkey::default(), wallet, Pubkey::default()];
+        rp.claimed = vec![false, true, false];
+        rp.num_claimed = 1;
+        assert_eq!(program::next_slot(&rp), Some(0));
+
+        rp.claimed = vec![true, true, false];
+        rp.num_claimed = 2;
+        assert_eq!(program::next_slot(&rp), Some(2));
+
+        rp.vesting = true;
+        assert_eq!(program::vesting_slot(&rp, &wallet), Some(1));
+        rp.claimed = vec![true; 3];
+        assert_eq!(program::next_slot(&rp), None);
+    }
+
+    #[test]
+    fn vesting_claimers_come_back_for_what_unlocked_since() {
+        let (first, stranger) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut rp = packet(None);
+        rp.claimers = vec![first, Pubkey::default()];
+        rp.num_claimed = 1;
+        rp.claimed = vec![true, false];
+        rp.expires_at = 2_000;
+        rp.vesting_start = 1_000;
+        rp.taken = vec![100, 0];
+        assert_eq!(program::vesting_slot(&rp, &first), None);
+        assert_eq!(program::claimable(&rp, 0, 1_500), 500);
+
+        rp.vesting = true;
+        assert_eq!(program::vesting_slot(&rp, &first), Some(0));
+        assert_eq!(program::vesting_slot(&rp, &stranger), None);
+        assert_eq!(program::claimable(&rp, 0, 900), 0);
+        assert_eq!(program::claimable(&rp, 0, 1_333), 66);
+        assert_eq!(program::claimable(&rp, 0, 5_000), 400);
+        assert_eq!(program::claimable(&rp, 1, 1_500), 250);
+    }
+
+    #[test]
+    fn destination_is_validated_and_appended_last() {
+        let with = |destination: &str| HashMap::from([("destination".to_string(), destination.to_string())]);
+        assert_eq!(claim_destination(&HashMap::new()).unwrap(), None);
+        assert_eq!(claim_destination(&with(" ")).unwrap(), None);
+        assert!(claim_destination(&with("not-a-wallet")).is_err());
+
+        let (claimer, creator, wallet) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let destination = claim_destination(&with(&wallet.to_string())).unwrap();
+        assert_eq!(destination, Some(wallet));
+
+        // SOL pays the wallet; the signer stays first and is the claimer
+        let ix = build_claim_instruction(&claimer, &creator, 7, 0, None, None, None, destination.as_ref(), false);
+        assert_eq!(ix.data, vec![1, TOKEN_TYPE_SOL, 0, 1]);
+        assert_eq!(ix.accounts.len(), 5);
+        assert_eq!(ix.accounts[0].pubkey, claimer);
+        assert_eq!((ix.accounts[4].pubkey, ix.accounts[4].is_writable), (wallet, true));
+
+        // SPL pays the wallet's ATA, after the gate account
+        let token = usdc();
+        let gate_ata = Pubkey::new_unique();
+        let ix = build_claim_instruction(
+            &claimer, &creator, 7, 0, Some(&token), None, Some(&gate_ata), destination.as_ref(), false,
+        );
+        assert_eq!(ix.data, vec![1, TOKEN_TYPE_SPL, 0, 1]);
+        assert_eq!(ix.accounts[6].pubkey, gate_ata);
+        assert_eq!(
+            ix.accounts[7].pubkey,
+            program::find_associated_token_address(&wallet, &token.mint, &TOKEN_PROGRAM_ID)
+        );
+    }
+
+    #[test]
+    fn open_packets_ignore_the_password_param() {
+        let params = HashMap::from([("password".to_string(), "anything".to_string())]);
+        assert_eq!(claim_secret(&packet(None), &params).unwrap(), None);
+    }
+
+    #[test]
+    fn unwrap_is_only_for_wrapped_sol_paid_to_the_signer() {
+        let with = |unwrap: &str| HashMap::from([("unwrap".to_string(), unwrap.to_string())]);
+        let wsol = program::SplToken {
+            mint: *NATIVE_MINT,
+            token_program: *TOKEN_PROGRAM_ID,
+            decimals: 9,
+        };
+        assert!(!claim_unwrap(&HashMap::new(), Some(&wsol), None).unwrap());
+        assert!(!claim_unwrap(&with(""), Some(&wsol), None).unwrap());
+        assert!(!claim_unwrap(&with("0"), Some(&usdc()), None).unwrap());
+        assert!(claim_unwrap(&with("1"), Some(&wsol), None).unwrap());
+        assert!(claim_unwrap(&with("2"), Some(&wsol), None).is_err());
+        assert!(claim_unwrap(&with("1"), Some(&usdc()), None).is_err());
+        assert!(claim_unwrap(&with("1"), None, None).is_err());
+        assert!(claim_unwrap(&with("1"), Some(&wsol), Some(&Pubkey::new_unique())).is_err());
+
+        let (claimer, ata) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let ix = program::close_account_instruction(&ata, &claimer, &claimer, &TOKEN_PROGRAM_ID);
+        assert_eq!(ix.program_id, *TOKEN_PROGRAM_ID);
+        assert_eq!(ix.data, vec![9]);
+        assert_eq!(ix.accounts[0], AccountMeta::new(ata, false));
+        assert_eq!(ix.accounts[1], AccountMeta::new(claimer, false));
+        assert_eq!(ix.accounts[2], AccountMeta::new_readonly(claimer, true));
+    }
+
+    #[test]
+    fn relayed_claims_need_a_relayer_and_no_claimer_rent() {
+        let with = |relay: &str| HashMap::from([("relay".to_string(), relay.to_string())]);
+        let relayer = Keypair::new();
+        assert!(claim_relay(&HashMap::new(), Some(&relayer), false, false).unwrap().is_none());
+        assert!(claim_relay(&with("0"), Some(&relayer), true, true).unwrap().is_none());
+        assert_eq!(claim_relay(&with("1"), Some(&relayer), false, false).unwrap().unwrap().pubkey(), relayer.pubkey());
+        assert!(claim_relay(&with("2"), Some(&relayer), false, false).is_err());
+        assert!(claim_relay(&with("1"), None, false, false).is_err());
+        assert!(claim_relay(&with("1"), Some(&relayer), true, false).is_err());
+        assert!(claim_relay(&with("1"), Some(&relayer), false, true).is_err());
+    }
+
+    #[test]
+    fn relayer_pays_the_fee_and_the_claimer_still_signs() {
+        use solana_sdk::signature::Signature;
+        let (claimer, relayer) = (Pubkey::new_unique(), Keypair::new());
+        let claim_ix = build_claim_instruction(&claimer, &Pubkey::new_unique(), 1, 0, None, None, None, None, false);
+        let blockhash = Hash::new_unique();
+
+        let tx = claim_transaction(std::slice::from_ref(&claim_ix), &claimer, None, blockhash);
+        assert_eq!(tx.message.account_keys[0], claimer);
+        assert_eq!(tx.signatures, vec![Signature::default()]);
+
+        let tx = claim_transaction(&[claim_ix], &claimer, Some(&relayer), blockhash);
+        assert_eq!(tx.message.header.num_required_signatures, 2);
+        assert_eq!(&tx.message.account_keys[..2], &[relayer.pubkey(), claimer]);
+        assert!(tx.signatures[0].verify(relayer.pubkey().as_ref(), &tx.message_data()));
+        assert_eq!(tx.signatures[1], Signature::default());
+        assert!(!tx.is_signed());
+    }
+
+    #[test]
+    fn gated_claim_appends_gate_account_and_describes_the_rule() {
+        let mint = Pubkey::new_unique();
+        let mut rp = packet(None);
+        assert_eq!(gate_requirement(&rp, 6), "");
+
+        rp.gate_mint = Some(mint);
+        rp.gate_min_amount = 2_500_000;
+        assert_eq!(
+            gate_requirement(&rp, 6),
+            format!(" — holders only: claimers need at least 2.5 of token {mint}")
+        );
+
+        let claimer = Pubkey::new_unique();
+        let gate_ata = program::find_associated_token_address(&claimer, &mint, &TOKEN_PROGRAM_ID);
+        let ix = build_claim_instruction(&claimer, &rp.creator, 1, 0, None, None, Some(&gate_ata), None, false);
+        assert_eq!(ix.accounts.len(), 5);
+        let last = ix.accounts.last().unwrap();
+        assert_eq!((last.pubkey, last.is_signer, last.is_writable), (gate_ata, false, false));
+    }
+
+    #[test]
+    fn ata_prelude_creates_the_claimed_ata() {
+        let claimer = Pubkey::new_unique();
+        let token = usdc();
+        let ata_ix = program::create_ata_idempotent_instruction(&claimer, &claimer, &token.mint, &token.token_program);
+        let claim_ix = build_claim_instruction(&claimer, &Pubkey::new_unique(), 1, 0, Some(&token), None, None, None, false);
+
+        assert_eq!(ata_ix.program_id, *ASSOCIATED_TOKEN_PROGRAM_ID);
+        assert_eq!(ata_ix.data, vec![1]);
+        assert_eq!(ata_ix.accounts[1].pubkey, claim_ix.accounts[1].pubkey);
+    }
+
+    #[test]
+    fn budget_instructions_lead_the_claim_message() {
+        use solana_compute_budget_interface::{self as compute_budget, ComputeBudgetInstruction};
+        let claimer = Pubkey::new_unique();
+        let token = usdc();
+        let ata_ix = program::create_ata_idempotent_instruction(&claimer, &claimer, &token.mint, &token.token_program);
+        let claim_ix = build_claim_instruction(&claimer, &Pubkey::new_unique(), 1, 0, Some(&token), None, None, None, false);
+        let unit_limit = CLAIM_COMPUTE_UNITS + ATA_COMPUTE_UNITS;
+
+        let instructions = program::with_compute_budget(vec![ata_ix, claim_ix], unit_limit, 5_000);
+        let msg = Message::new(&instructions, Some(&claimer));
+        let programs: Vec<_> = msg.instructions.iter().map(|ix| msg.account_keys[ix.program_id_index as usize]).collect();
+        assert_eq!(
+            programs,
+            [compute_budget::id(), compute_budget::id(), *ASSOCIATED_TOKEN_PROGRAM_ID, *PROGRAM_ID]
+        );
+        assert_eq!(msg.instructions[0].data, ComputeBudgetInstruction::set_compute_unit_limit(unit_limit).data);
+        assert_eq!(msg.instructions[1].data, ComputeBudgetInstruction::set_compute_unit_price(5_000).data);
+        assert_eq!(msg.instructions[3].data[0], 1);
+
+        // No priority fee leaves out the price, but still caps the units
+        let instructions = program::with_compute_budget(instructions[3..].to_vec(), CLAIM_COMPUTE_UNITS, 0);
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(instructions[0], ComputeBudgetInstruction::set_compute_unit_limit(CLAIM_COMPUTE_UNITS));
+    }
+
+    #[test]
+    fn large_claims_append_the_claimers_receipt_and_system_program() {
+        let (claimer, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let wallet = Pubkey::new_unique();
+        let ix = build_claim_instruction(&claimer, &creator, 7, 0, None, None, None, Some(&wallet), true);
+
+        let red_packet = program::find_red_packet_pda(&creator, 7).0;
+        let metas: Vec<(Pubkey, bool)> = ix.accounts[4..].iter().map(|m| (m.pubkey, m.is_writable)).collect();
+        assert_eq!(
+            metas,
+            vec![
+                (wallet, true),
+                (program::find_receipt_pda(&red_packet, &claimer).0, true),
+                (solana_sdk::system_program::id(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn claims_chain_to_the_create_action() {
+        let json = serde_json::to_value(pass_it_on()).unwrap();
+        let action = &json["next"]["action"];
+        assert_eq!(json["next"]["type"], "inline");
+        assert_eq!(action["type"], "action");
+        assert_eq!(action["title"], "Pass It On");
+        let href = action["links"]["actions"][0]["href"].as_str().unwrap();
+        assert!(href.starts_with("/api/actions/create?amount={amount}"));
+    }
+}
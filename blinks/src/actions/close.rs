@@ -1,19 +1,42 @@
 use async_trait::async_trait;
+use axum::extract::{Query, State};
+use axum::Json;
+use serde::Serialize;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::message::Message;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::transaction::Transaction;
-use std::collections::HashMap;
+use solana_sdk::hash::Hash;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::{get_param, serialize_tx, Action};
+use super::{
+    account_exists, fetch_packet_token, get_id_param, get_param, serialize_tx, simulate_tx, Action,
+};
+use crate::cache::RedPacketCache;
 use crate::consts::*;
 use crate::error::AppError;
-use crate::program;
+use crate::program::{self, RedPacketAccount};
+use crate::query::{fetch_creator_packets, CreatorPackets};
+use crate::router::AppState;
 use crate::spec::*;
 
-pub struct CloseAction;
+pub struct CloseAction {
+    cache: Arc<RedPacketCache>,
+    /// Simulate each transaction before returning it
+    simulate: bool,
+    /// Compute unit price, in micro-lamports, each transaction offers
+    priority_fee: u64,
+}
+
+impl CloseAction {
+    pub fn new(cache: Arc<RedPacketCache>, simulate: bool, priority_fee: u64) -> Self {
+        Self { cache, simulate, priority_fee }
+    }
+}
 
 #[async_trait]
 impl Action for CloseAction {
@@ -28,26 +51,32 @@ impl Action for CloseAction {
         params: HashMap<String, String>,
     ) -> Result<ActionGetResponse, AppError> {
         let creator: Pubkey = get_param(&params, "creator")?;
-        let id: u64 = get_param(&params, "id")?;
+        let id = get_id_param(&params)?;
 
-        let (red_packet_addr, _) = program::find_red_packet_pda(&creator, id);
-        let account = rpc
-            .get_account(&red_packet_addr)
-            .await
-            .map_err(|_| AppError::NotFound("Red packet not found on chain".into()))?;
-
-        let rp = program::decode_red_packet(&account.data)?;
+        let rp = self.cache.fetch(rpc, &creator, id, false).await?;
+        let spl = fetch_packet_token(rpc, &self.cache, &rp).await?;
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64;
         let status = program::get_status(&rp, now);
 
-        let remaining_sol = program::lamports_to_sol(rp.remaining_amount);
-        let description = format!(
-            "{}/{} claimed — {remaining_sol} SOL remaining (devnet)",
-            rp.num_claimed, rp.num_recipients
+        let (remaining, unit) = program::display_amount(rp.remaining_amount, spl.as_ref());
+        let (fee, _) = program::display_amount(rp.fee_paid, spl.as_ref());
+        let mut description = format!(
+            "{}/{} claimed — {remaining} {unit} remaining, {fee} {unit} fee paid{}{}",
+            rp.num_claimed,
+            rp.num_recipients,
+            program::packet_age(&rp, now),
+            CLUSTER.label()
         );
+        if rp.burn_unclaimed {
+            description.push_str(". Closing burns what's left; cancelling before expiry refunds it");
+        } else if rp.donate_unclaimed {
+            description.push_str(
+                ". Closing donates what's left to the treasury; cancelling before expiry refunds it",
+            );
+        }
 
         let can_close = status == "expired" || status == "fully_claimed";
 
@@ -56,7 +85,15 @@ impl Action for CloseAction {
                 ICON_URL,
                 "Close Red Packet",
                 &description,
-                "Close & Reclaim SOL",
+                &format!("Close & Reclaim {unit}"),
+            ))
+        } else if rp.num_claimed == 0 {
+            // Active but untouched — the creator can cancel early
+            Ok(ActionGetResponse::new(
+                ICON_URL,
+                "Cancel Red Packet",
+                &description,
+                &format!("Cancel & Reclaim {unit}"),
             ))
         } else {
             Ok(
@@ -73,63 +110,511 @@ impl Action for CloseAction {
         account: Pubkey,
         params: HashMap<String, String>,
     ) -> Result<ActionPostResponse, AppError> {
-        let creator: Pubkey = get_param(&params, "creator")?;
-        let id: u64 = get_param(&params, "id")?;
+        // The packet's address keeps deriving from its original creator,
+        // which the blink link names, after a transfer_creator
+        let original_creator: Pubkey = get_param(&params, "creator")?;
+        let id = get_id_param(&params)?;
+
+        // Fresh read: close vs cancel depends on the current num_claimed
+        let rp = self.cache.fetch(rpc, &original_creator, id, true).await?;
 
         // Verify the signer is the creator
+        let creator = rp.creator;
         if account != creator {
             return Err(AppError::BadRequest(
                 "Only the red packet creator can close it".into(),
             ));
         }
-
-        // Fetch state to verify closeable
-        let (red_packet_addr, _) = program::find_red_packet_pda(&creator, id);
-        let rpc_account = rpc
-            .get_account(&red_packet_addr)
-            .await
-            .map_err(|_| AppError::NotFound("Red packet not found on chain".into()))?;
-
-        let rp = program::decode_red_packet(&rpc_account.data)?;
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs() as i64;
         let status = program::get_status(&rp, now);
 
-        if status == "active" {
+        // Active packets can only be cancelled, and only before any claim
+        let cancel = status == "active";
+        if cancel && rp.num_claimed > 0 {
             return Err(AppError::BadRequest(
                 "Cannot close an active red packet".into(),
             ));
         }
 
-        let (vault_addr, _) = program::find_vault_pda(&creator, id);
-        let data = program::build_close_data();
+        let spl = fetch_packet_token(rpc, &self.cache, &rp).await?;
+        // Blinks only cancels before expiry, which always refunds
+        let leftover = if cancel { Leftover::Refund } else { Leftover::of(&rp) };
 
-        // SOL close: creator, red_packet, vault (3)
-        let ix = Instruction {
-            program_id: *PROGRAM_ID,
-            accounts: vec![
-                AccountMeta::new(account, true),
-                AccountMeta::new(red_packet_addr, false),
-                AccountMeta::new(vault_addr, false),
-            ],
-            data,
-        };
+        // Leftover SPL goes to the creator's ATA; create it first if missing
+        let mut instructions = Vec::with_capacity(2);
+        if let Some(token) = spl.as_ref().filter(|_| leftover == Leftover::Refund) {
+            let ata = program::find_associated_token_address(&creator, &token.mint, &token.token_program);
+            if rp.remaining_amount > 0 && !account_exists(rpc, &ata).await? {
+                instructions.push(program::create_ata_idempotent_instruction(
+                    &creator,
+                    &creator,
+                    &token.mint,
+                    &token.token_program,
+                ));
+            }
+        }
+        instructions.push(build_close_instruction(&creator, &original_creator, id, cancel, spl.as_ref(), leftover));
+
+        let unit_limit = CLOSE_COMPUTE_UNITS + ATA_COMPUTE_UNITS * (instructions.len() as u32 - 1);
+        let instructions = program::with_compute_budget(instructions, unit_limit, self.priority_fee);
 
         let blockhash = rpc.get_latest_blockhash().await?;
-        let msg = Message::new_with_blockhash(&[ix], Some(&account), &blockhash);
+        let msg = Message::new_with_blockhash(&instructions, Some(&account), &blockhash);
         let tx = Transaction::new_unsigned(msg);
         let transaction = serialize_tx(&tx)?;
+        if self.simulate {
+            simulate_tx(rpc, &tx).await?;
+        }
 
-        let remaining_sol = program::lamports_to_sol(rp.remaining_amount);
+        let (remaining, unit) = program::display_amount(rp.remaining_amount, spl.as_ref());
+
+        let verb = if cancel { "cancelled" } else { "closed" };
+        let fate = match leftover {
+            Leftover::Refund => "reclaimed",
+            Leftover::Burn => "will be burned",
+            Leftover::Donate => "donated to the treasury",
+        };
 
         Ok(ActionPostResponse {
             transaction,
             message: Some(format!(
-                "Red packet closed. {remaining_sol} SOL reclaimed."
+                "Red packet {verb}. {remaining} {unit} {fate}."
             )),
             links: None,
+            red_packet: None,
         })
     }
 }
+
+/// Where a close sends the packet's unclaimed balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Leftover {
+    Refund,
+    Burn,
+    Donate,
+}
+
+impl Leftover {
+    /// What the packet chose at create
+    pub fn of(rp: &program::RedPacketAccount) -> Self {
+        if rp.burn_unclaimed {
+            Leftover::Burn
+        } else if rp.donate_unclaimed {
+            Leftover::Donate
+        } else {
+            Leftover::Refund
+        }
+    }
+}
+
+/// Close (or, with `cancel`, cancel) instruction for the creator's packet,
+/// whose addresses derive from `original_creator` (the creator itself unless
+/// the packet was handed over); `spl` is None for SOL packets. Burning passes the mint the leftover
+/// tokens are burned from, donating the treasury (and vault) they go to.
+///
+/// SOL: creator, red_packet, vault, [treasury] (3-4)
+/// SPL: creator, creator_ata, red_packet, vault, token_program,
+///      [mint | treasury, treasury_vault] (5-7)
+pub fn build_close_instruction(
+    creator: &Pubkey,
+    original_creator: &Pubkey,
+    id: u64,
+    cancel: bool,
+    spl: Option<&program::SplToken>,
+    leftover: Leftover,
+) -> Instruction {
+    let (red_packet, _) = program::find_red_packet_pda(original_creator, id);
+    let (vault, _) = program::find_vault_pda(original_creator, id);
+
+    let (mut accounts, token_type) = match spl {
+        None => (
+            vec![
+                AccountMeta::new(*creator, true),
+                AccountMeta::new(red_packet, false),
+                AccountMeta::new(vault, false),
+            ],
+            TOKEN_TYPE_SOL,
+        ),
+        Some(token) => (
+            vec![
+                AccountMeta::new(*creator, true),
+                AccountMeta::new(
+                    program::find_associated_token_address(creator, &token.mint, &token.token_program),
+                    false,
+                ),
+                AccountMeta::new(red_packet, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(token.token_program, false),
+            ],
+            TOKEN_TYPE_SPL,
+        ),
+    };
+    match (leftover, spl) {
+        (Leftover::Refund, _) | (Leftover::Burn, None) => {}
+        (Leftover::Burn, Some(token)) => accounts.push(AccountMeta::new(token.mint, false)),
+        (Leftover::Donate, None) => accounts.push(AccountMeta::new(program::find_treasury_pda_sol().0, false)),
+        (Leftover::Donate, Some(token)) => {
+            accounts.push(AccountMeta::new_readonly(program::find_treasury_pda(&token.mint).0, false));
+            accounts.push(AccountMeta::new(program::find_treasury_vault_pda(&token.mint).0, false));
+        }
+    }
+
+    let data = if cancel {
+        program::build_cancel_data(token_type)
+    } else {
+        program::build_close_data(token_type)
+    };
+
+    Instruction {
+        program_id: *PROGRAM_ID,
+        accounts,
+        data,
+    }
+}
+
+/// Packets one close-expired call closes at most; call again for the rest
+pub const MAX_EXPIRED_CLOSES: usize = 100;
+
+/// Closes per transaction at most, well inside the compute budget
+const MAX_CLOSES_PER_TX: usize = 8;
+
+#[derive(Debug, Serialize)]
+pub struct CloseBatch {
+    /// Base64 unsigned transaction, for the creator to sign
+    pub transaction: String,
+    /// Decimal ids of the packets it closes
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloseExpiredResponse {
+    pub batches: Vec<CloseBatch>,
+    /// More expired packets remain past MAX_EXPIRED_CLOSES
+    pub more: bool,
+    /// The creator's packets on another layout version, which can't be
+    /// checked or closed until migrated
+    pub unmigrated: Vec<String>,
+}
+
+/// Whether a plain close of this packet would succeed now: past expiry,
+/// with no raffle pot or lucky jackpot owed to entrants and no vested amount
+/// owed to claimers
+pub fn closable_expired(rp: &RedPacketAccount, now_unix: i64) -> bool {
+    let draw_unpaid = rp.num_claimed > 0
+        && match rp.split_mode {
+            SPLIT_RAFFLE => rp.remaining_amount > 0,
+            SPLIT_LUCKY => !rp.lucky_paid,
+            _ => false,
+        };
+    let vesting_owed = rp.vesting
+        && (0..rp.claimed.len()).any(|slot| {
+            rp.claimed[slot] && rp.amounts.get(slot).copied().unwrap_or(0) > rp.taken.get(slot).copied().unwrap_or(0)
+        });
+    now_unix >= rp.expires_at && !draw_unpaid && !vesting_owed
+}
+
+/// Pack each packet's instructions (an optional ATA create, then its close)
+/// into as few transactions as fit PACKET_DATA_SIZE and MAX_CLOSES_PER_TX,
+/// never splitting one packet's instructions across transactions.
+pub fn pack_closes(
+    creator: &Pubkey,
+    closes: Vec<(u64, Vec<Instruction>)>,
+    blockhash: &Hash,
+) -> Result<Vec<(Transaction, Vec<u64>)>, AppError> {
+    let build = |instructions: &[Instruction]| {
+        Transaction::new_unsigned(Message::new_with_blockhash(instructions, Some(creator), blockhash))
+    };
+    let fits = |tx: &Transaction| -> Result<bool, AppError> {
+        Ok(bincode::serialized_size(tx)? as usize <= PACKET_DATA_SIZE)
+    };
+
+    let mut batches = Vec::new();
+    let mut instructions: Vec<Instruction> = Vec::new();
+    let mut ids = Vec::new();
+    for (id, close) in closes {
+        let mut candidate = instructions.clone();
+        candidate.extend(close.iter().cloned());
+        if ids.len() < MAX_CLOSES_PER_TX && fits(&build(&candidate))? {
+            instructions = candidate;
+            ids.push(id);
+            continue;
+        }
+        if !ids.is_empty() {
+            batches.push((build(&instructions), std::mem::take(&mut ids)));
+        }
+        if !fits(&build(&close))? {
+            return Err(AppError::BadRequest(format!(
+                "Closing red packet {id} doesn't fit in one transaction"
+            )));
+        }
+        instructions = close;
+        ids.push(id);
+    }
+    if !ids.is_empty() {
+        batches.push((build(&instructions), ids));
+    }
+    Ok(batches)
+}
+
+/// GET /api/actions/close/expired?account=: unsigned transactions closing
+/// the creator's expired packets, oldest first, to reclaim their rent.
+/// Each transaction is independent, so they may be signed and sent in any
+/// order; once they land, calling again picks up any past
+/// MAX_EXPIRED_CLOSES.
+pub async fn close_expired(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<CloseExpiredResponse>, AppError> {
+    let creator: Pubkey = get_param(&params, "account")?;
+    let rpc = &state.rpc;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    let CreatorPackets { packets, unmigrated } = fetch_creator_packets(rpc, &creator, false).await?;
+    let mut expired: Vec<RedPacketAccount> = packets
+        .into_iter()
+        .map(|(_, rp)| rp)
+        .filter(|rp| closable_expired(rp, now))
+        .collect();
+    expired.sort_unstable_by_key(|rp| rp.id);
+    let more = expired.len() > MAX_EXPIRED_CLOSES;
+    expired.truncate(MAX_EXPIRED_CLOSES);
+
+    // Refunds to an ATA that doesn't exist yet create it first, in every
+    // transaction that needs it (idempotently), so the batches stay
+    // independent
+    let mut missing_atas = HashSet::new();
+    let mut closes = Vec::with_capacity(expired.len());
+    for rp in &expired {
+        let spl = fetch_packet_token(rpc, &state.red_packet_cache, rp).await?;
+        let leftover = Leftover::of(rp);
+        let mut instructions = Vec::with_capacity(2);
+        if let Some(token) = spl.as_ref().filter(|_| leftover == Leftover::Refund && rp.remaining_amount > 0) {
+            let ata = program::find_associated_token_address(&creator, &token.mint, &token.token_program);
+            if missing_atas.contains(&ata) || !account_exists(rpc, &ata).await? {
+                missing_atas.insert(ata);
+                instructions.push(program::create_ata_idempotent_instruction(
+                    &creator,
+                    &creator,
+                    &token.mint,
+                    &token.token_program,
+                ));
+            }
+        }
+        instructions.push(build_close_instruction(&creator, &rp.original_creator, rp.id, false, spl.as_ref(), leftover));
+        closes.push((rp.id, instructions));
+    }
+
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let batches = pack_closes(&creator, closes, &blockhash)?
+        .into_iter()
+        .map(|(tx, ids)| {
+            Ok(CloseBatch {
+                transaction: serialize_tx(&tx)?,
+                ids: ids.iter().map(u64::to_string).collect(),
+            })
+        })
+        .collect::<Result<_, AppError>>()?;
+
+    Ok(Json(CloseExpiredResponse {
+        batches,
+        more,
+        unmigrated: unmigrated.iter().map(Pubkey::to_string).collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sol_close_and_cancel_use_three_accounts() {
+        let creator = Pubkey::new_unique();
+        let close = build_close_instruction(&creator, &creator, 3, false, None, Leftover::Refund);
+        let cancel = build_close_instruction(&creator, &creator, 3, true, None, Leftover::Refund);
+
+        assert_eq!(close.data, vec![2, TOKEN_TYPE_SOL]);
+        assert_eq!(cancel.data, vec![7, TOKEN_TYPE_SOL]);
+        assert_eq!(close.accounts, cancel.accounts);
+        assert_eq!(close.accounts.len(), 3);
+    }
+
+    #[test]
+    fn handed_over_packets_close_at_the_original_address() {
+        let creator = Pubkey::new_unique();
+        let original_creator = Pubkey::new_unique();
+        let ix = build_close_instruction(&creator, &original_creator, 3, false, None, Leftover::Refund);
+        assert_eq!(ix.accounts[0].pubkey, creator);
+        assert!(ix.accounts[0].is_signer);
+        assert_eq!(ix.accounts[1].pubkey, program::find_red_packet_pda(&original_creator, 3).0);
+        assert_eq!(ix.accounts[2].pubkey, program::find_vault_pda(&original_creator, 3).0);
+    }
+
+    #[test]
+    fn spl_close_refunds_to_creator_ata() {
+        let creator = Pubkey::new_unique();
+        let token = program::SplToken {
+            mint: Pubkey::new_unique(),
+            token_program: *TOKEN_PROGRAM_ID,
+            decimals: 6,
+        };
+        let ix = build_close_instruction(&creator, &creator, 3, false, Some(&token), Leftover::Refund);
+
+        assert_eq!(ix.data, vec![2, TOKEN_TYPE_SPL]);
+        let metas: Vec<(Pubkey, bool, bool)> = ix
+            .accounts
+            .iter()
+            .map(|m| (m.pubkey, m.is_signer, m.is_writable))
+            .collect();
+        assert_eq!(
+            metas,
+            vec![
+                (creator, true, true),
+                (
+                    program::find_associated_token_address(&creator, &token.mint, &token.token_program),
+                    false,
+                    true,
+                ),
+                (program::find_red_packet_pda(&creator, 3).0, false, true),
+                (program::find_vault_pda(&creator, 3).0, false, true),
+                (*TOKEN_PROGRAM_ID, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn burning_close_passes_the_mint() {
+        let creator = Pubkey::new_unique();
+        let token = program::SplToken {
+            mint: Pubkey::new_unique(),
+            token_program: *TOKEN_2022_PROGRAM_ID,
+            decimals: 6,
+        };
+        let ix = build_close_instruction(&creator, &creator, 3, false, Some(&token), Leftover::Burn);
+
+        assert_eq!(ix.accounts.len(), 6);
+        let mint = &ix.accounts[5];
+        assert_eq!((mint.pubkey, mint.is_signer, mint.is_writable), (token.mint, false, true));
+        // SOL packets have nothing to burn
+        assert_eq!(build_close_instruction(&creator, &creator, 3, false, None, Leftover::Burn).accounts.len(), 3);
+    }
+
+    #[test]
+    fn donating_close_passes_the_treasury() {
+        let creator = Pubkey::new_unique();
+        let sol = build_close_instruction(&creator, &creator, 3, false, None, Leftover::Donate);
+        assert_eq!(sol.accounts.len(), 4);
+        let treasury = &sol.accounts[3];
+        assert_eq!(
+            (treasury.pubkey, treasury.is_signer, treasury.is_writable),
+            (program::find_treasury_pda_sol().0, false, true)
+        );
+
+        let token = program::SplToken {
+            mint: Pubkey::new_unique(),
+            token_program: *TOKEN_PROGRAM_ID,
+            decimals: 6,
+        };
+        let spl = build_close_instruction(&creator, &creator, 3, false, Some(&token), Leftover::Donate);
+        let metas: Vec<(Pubkey, bool, bool)> = spl.accounts[5..]
+            .iter()
+            .map(|m| (m.pubkey, m.is_signer, m.is_writable))
+            .collect();
+        assert_eq!(
+            metas,
+            vec![
+                (program::find_treasury_pda(&token.mint).0, false, false),
+                (program::find_treasury_vault_pda(&token.mint).0, false, true),
+            ]
+        );
+    }
+
+    fn expired_packet(id: u64) -> RedPacketAccount {
+        RedPacketAccount {
+            id,
+            remaining_amount: 400,
+            num_claimed: 1,
+            expires_at: 100,
+            fee_paid: 1,
+            claimed: vec![true, false],
+            amounts: vec![600, 400],
+            claimers: vec![Pubkey::new_unique(), Pubkey::default()],
+            claimed_at: vec![50, 0],
+            ..RedPacketAccount::mock()
+        }
+    }
+
+    #[test]
+    fn only_expired_packets_nobody_is_owed_are_closable() {
+        let rp = expired_packet(1);
+        assert!(closable_expired(&rp, 100));
+        assert!(!closable_expired(&rp, 99));
+
+        let mut raffle = expired_packet(1);
+        raffle.split_mode = SPLIT_RAFFLE;
+        assert!(!closable_expired(&raffle, 100));
+        raffle.remaining_amount = 0;
+        assert!(closable_expired(&raffle, 100));
+
+        let mut lucky = expired_packet(1);
+        lucky.split_mode = SPLIT_LUCKY;
+        assert!(!closable_expired(&lucky, 100));
+        lucky.lucky_paid = true;
+        assert!(closable_expired(&lucky, 100));
+        let unclaimed = RedPacketAccount { split_mode: SPLIT_LUCKY, num_claimed: 0, ..expired_packet(1) };
+        assert!(closable_expired(&unclaimed, 100));
+
+        let mut vesting = expired_packet(1);
+        vesting.vesting = true;
+        vesting.taken = vec![599, 0];
+        assert!(!closable_expired(&vesting, 100));
+        vesting.taken = vec![600, 0];
+        assert!(closable_expired(&vesting, 100));
+    }
+
+    #[test]
+    fn closes_pack_into_bounded_transactions() {
+        let creator = Pubkey::new_unique();
+        let closes = (0..20)
+            .map(|id| (id, vec![build_close_instruction(&creator, &creator, id, false, None, Leftover::Refund)]))
+            .collect();
+        let batches = pack_closes(&creator, closes, &Hash::default()).unwrap();
+
+        let ids: Vec<Vec<u64>> = batches.iter().map(|(_, ids)| ids.clone()).collect();
+        assert_eq!(ids, vec![(0..8).collect::<Vec<_>>(), (8..16).collect(), (16..20).collect()]);
+        for (tx, ids) in &batches {
+            assert_eq!(tx.message.instructions.len(), ids.len());
+            assert!(serialize_tx(tx).is_ok());
+        }
+
+        // SPL refunds creating the ATA first keep both instructions together
+        let token = program::SplToken {
+            mint: Pubkey::new_unique(),
+            token_program: *TOKEN_PROGRAM_ID,
+            decimals: 6,
+        };
+        let closes = (0..MAX_CLOSES_PER_TX as u64)
+            .map(|id| {
+                let ata = program::create_ata_idempotent_instruction(&creator, &creator, &token.mint, &token.token_program);
+                (id, vec![ata, build_close_instruction(&creator, &creator, id, false, Some(&token), Leftover::Refund)])
+            })
+            .collect();
+        for (tx, ids) in pack_closes(&creator, closes, &Hash::default()).unwrap() {
+            assert_eq!(tx.message.instructions.len(), 2 * ids.len());
+            assert!(serialize_tx(&tx).is_ok());
+        }
+
+        let oversized = Instruction {
+            program_id: *PROGRAM_ID,
+            accounts: vec![],
+            data: vec![0; PACKET_DATA_SIZE],
+        };
+        assert!(pack_closes(&creator, vec![(1, vec![oversized])], &Hash::default()).is_err());
+    }
+}
@@ -1,19 +1,35 @@
 use async_trait::async_trait;
+use axum::extract::Query;
+use axum::Json;
+use base64::Engine;
+use serde::Serialize;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::message::Message;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::transaction::Transaction;
 use std::collections::HashMap;
+use std::future::Future;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::{get_param, serialize_tx, Action};
+use super::{account_exists, get_id_param, get_param, serialize_tx, simulate_tx, Action};
 use crate::consts::*;
 use crate::error::AppError;
 use crate::program;
 use crate::spec::*;
 
-pub struct CreateAction;
+pub struct CreateAction {
+    /// Simulate each transaction before returning it
+    simulate: bool,
+    /// Compute unit price, in micro-lamports, each transaction offers
+    priority_fee: u64,
+}
+
+impl CreateAction {
+    pub fn new(simulate: bool, priority_fee: u64) -> Self {
+        Self { simulate, priority_fee }
+    }
+}
 
 #[async_trait]
 impl Action for CreateAction {
@@ -27,34 +43,7 @@ impl Action for CreateAction {
         _base_url: &str,
         _params: HashMap<String, String>,
     ) -> Result<ActionGetResponse, AppError> {
-        let resp = ActionGetResponse::new(
-            ICON_URL,
-            "Create Red Packet",
-            "Create a shareable SOL red packet that friends can claim (devnet)",
-            "Create",
-        )
-        .with_links(vec![LinkedAction {
-            href: "/api/actions/create?amount={amount}&recipients={recipients}&split_mode={split_mode}&expiry_hours={expiry_hours}".into(),
-            label: "Create Red Packet".into(),
-            parameters: Some(vec![
-                ActionParameter::number("amount", "Amount (SOL)", true).with_min(0.001),
-                ActionParameter::number("recipients", "Number of Recipients (1-20)", true)
-                    .with_min(1.0)
-                    .with_max(20.0),
-                ActionParameter::radio(
-                    "split_mode",
-                    "Split Mode",
-                    vec![
-                        ActionParameterOption::new("Even", "0"),
-                        ActionParameterOption::new("Random", "1"),
-                    ],
-                ),
-                ActionParameter::number("expiry_hours", "Hours until expiry", true)
-                    .with_min(1.0),
-            ]),
-        }]);
-
-        Ok(resp)
+        Ok(create_action())
     }
 
     async fn execute(
@@ -64,95 +53,930 @@ impl Action for CreateAction {
         account: Pubkey,
         params: HashMap<String, String>,
     ) -> Result<ActionPostResponse, AppError> {
-        // Parse parameters
-        let amount_sol: f64 = get_param(&params, "amount")?;
-        let num_recipients: u8 = get_param(&params, "recipients")?;
-        let split_mode: u8 = get_param(&params, "split_mode")?;
-        let expiry_hours: u64 = get_param(&params, "expiry_hours")?;
-
-        // Validate
-        if amount_sol < 0.001 {
-            return Err(AppError::BadRequest("Amount must be at least 0.001 SOL".into()));
+        let id = free_id(rpc, &account, &params).await?;
+        let now = now_unix();
+        let plan = build_create_instruction(account, &params, id, now)?;
+        let created = created_red_packet(&account, id);
+
+        // The packet doesn't exist yet, so quote the fee the program will
+        // charge at the treasury's rate; once created it's recorded on-chain
+        // as fee_paid.
+        let (treasury, _) = program::find_treasury_pda_sol();
+        let treasury_account = rpc
+            .get_account_with_commitment(&treasury, rpc.commitment())
+            .await?
+            .value;
+        let treasury = create_treasury(treasury_account.as_ref().map(|account| account.data.as_slice()))?;
+        check_expiry_bounds(plan.expires_at - now, &treasury)?;
+        let fee = program::compute_fee(plan.total_lamports, treasury.fee_bps)
+            .ok_or_else(|| AppError::BadRequest("The fee on that amount overflows".into()))?;
+        check_creator_balance(rpc.get_balance(&account).await?, &plan, fee)?;
+
+        let blockhash = rpc.get_latest_blockhash().await?;
+        let instructions = program::with_compute_budget(vec![plan.instruction], CREATE_COMPUTE_UNITS, self.priority_fee);
+        let msg = Message::new_with_blockhash(&instructions, Some(&account), &blockhash);
+        let tx = Transaction::new_unsigned(msg);
+        let transaction = serialize_tx(&tx)?;
+        if self.simulate {
+            simulate_tx(rpc, &tx).await?;
+        }
+
+        let amount_display = program::lamports_to_sol(plan.total_lamports);
+        let fee_display = program::lamports_to_sol(fee);
+        let num_recipients = plan.num_recipients;
+        let rent_display = program::lamports_to_sol(program::sol_create_rent(num_recipients));
+        let claim_url = claim_url(base_url, &account, id);
+
+        Ok(ActionPostResponse {
+            transaction,
+            message: Some(format!(
+                "Red packet created! {amount_display} SOL for {num_recipients} recipients (fee: {fee_display} SOL, plus {rent_display} SOL of rent returned at close).\n\nShare this claim link:\n{claim_url}"
+            )),
+            links: None,
+            red_packet: Some(created),
+        })
+    }
+}
+
+/// Create's blink metadata, also chained after claims so claimers can pass
+/// a packet on
+pub fn create_action() -> ActionGetResponse {
+    ActionGetResponse::new(
+        ICON_URL,
+        "Create Red Packet",
+        &format!("Create a shareable SOL red packet that friends can claim{}", CLUSTER.label()),
+        "Create",
+    )
+    .with_links(vec![LinkedAction {
+        href: "/api/actions/create?amount={amount}&recipients={recipients}&split_mode={split_mode}&allow_repeat={allow_repeat}&self_claim={self_claim}&expiry_hours={expiry_hours}&assigned_to={assigned_to}&weights={weights}&memo={memo}".into(),
+        label: "Create Red Packet".into(),
+        parameters: Some(vec![
+            ActionParameter::number("amount", "Amount (SOL)", true).with_min(0.001),
+            ActionParameter::number(
+                "recipients",
+                &format!("Number of Recipients (1-{MAX_RECIPIENTS})"),
+                true,
+            )
+            .with_min(1.0)
+            .with_max(MAX_RECIPIENTS as f64),
+            ActionParameter::radio(
+                "split_mode",
+                "Split Mode",
+                vec![
+                    ActionParameterOption::new("Even", "0"),
+                    ActionParameterOption::new("Random", "1"),
+                    ActionParameterOption::new("Weighted", "2"),
+                    ActionParameterOption::new("Lucky (one slot wins 60%)", "3"),
+                    ActionParameterOption::new("Raffle (one winner takes all)", "4"),
+                ],
+            ),
+            ActionParameter::radio(
+                "allow_repeat",
+                "Claims per Wallet",
+                vec![
+                    ActionParameterOption::new("One per wallet", "0"),
+                    ActionParameterOption::new("Grab as many as you can", "1"),
+                ],
+            ),
+            ActionParameter::radio(
+                "self_claim",
+                "Can You Claim Your Own Packet?",
+                vec![
+                    ActionParameterOption::new("No, it's only for others", "0"),
+                    ActionParameterOption::new("Yes", "1"),
+                ],
+            ),
+            ActionParameter::number("expiry_hours", "Hours until expiry (within the treasury's limits, at most 1 year)", true)
+                .with_min(1.0)
+                .with_max((MAX_EXPIRY_SECONDS / 3600) as f64),
+            ActionParameter::text(
+                "assigned_to",
+                "Only these wallets may claim (optional, comma-separated, one per recipient)",
+                false,
+            ),
+            ActionParameter::text(
+                "weights",
+                "Weighted only: each slot's share in basis points (comma-separated, summing to 10000)",
+                false,
+            ),
+            ActionParameter::text(
+                "memo",
+                &format!("Greeting for claimers (optional, up to {MAX_MEMO_LEN} bytes)"),
+                false,
+            ),
+        ]),
+    }])
+}
+
+/// The id and PDAs of the packet `creator` opens with `id`; the id is
+/// otherwise only known inside execute, so it's echoed back for tracking.
+pub fn created_red_packet(creator: &Pubkey, id: u64) -> CreatedRedPacket {
+    let (red_packet, _) = program::find_red_packet_pda(creator, id);
+    let (vault, _) = program::find_vault_pda(creator, id);
+    CreatedRedPacket {
+        id: id.to_string(),
+        red_packet: red_packet.to_string(),
+        vault: vault.to_string(),
+    }
+}
+
+/// The claim blink for `creator`'s packet `id`, as shared after a create
+pub fn claim_url(base_url: &str, creator: &Pubkey, id: u64) -> String {
+    format!("{base_url}/api/actions/claim?creator={creator}&id={id}")
+}
+
+/// Id for a create that didn't pin one: the millisecond timestamp in the
+/// high bits and a random nonce in the low 16, so two creates by one wallet
+/// in the same millisecond still get different PDAs. Stays below 2^57.
+pub fn generate_id(now_millis: u64, nonce: u16) -> u64 {
+    (now_millis << 16) | nonce as u64
+}
+
+/// The caller's `id` param if present, else a freshly generated one.
+fn resolve_id(params: &HashMap<String, String>) -> Result<u64, AppError> {
+    if params.contains_key("id") {
+        return get_id_param(params);
+    }
+    Ok(fresh_id())
+}
+
+fn fresh_id() -> u64 {
+    let now_millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    generate_id(now_millis, rand::random())
+}
+
+/// Generated ids tried before giving up on finding a free one
+const MAX_ID_ATTEMPTS: usize = 5;
+
+/// The id the create will use. A caller's `id` must be free (a collision is
+/// a 400 here instead of RedPacketAlreadyExists on-chain); a generated one
+/// that's taken is regenerated.
+async fn free_id(
+    rpc: &RpcClient,
+    creator: &Pubkey,
+    params: &HashMap<String, String>,
+) -> Result<u64, AppError> {
+    let taken = |id| async move {
+        let (red_packet, _) = program::find_red_packet_pda(creator, id);
+        account_exists(rpc, &red_packet).await
+    };
+    if params.contains_key("id") {
+        let id = get_id_param(params)?;
+        if taken(id).await? {
+            return Err(AppError::BadRequest(format!(
+                "Red packet id {id} is already in use for this creator"
+            )));
         }
-        if num_recipients < 1 || num_recipients > MAX_RECIPIENTS {
-            return Err(AppError::BadRequest(
-                format!("Recipients must be 1-{MAX_RECIPIENTS}"),
-            ));
+        return Ok(id);
+    }
+    first_free_id(fresh_id, taken).await
+}
+
+/// The first of up to MAX_ID_ATTEMPTS ids from `next_id` that isn't `taken`
+async fn first_free_id<F, Fut>(
+    mut next_id: impl FnMut() -> u64,
+    mut taken: F,
+) -> Result<u64, AppError>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<bool, AppError>>,
+{
+    for _ in 0..MAX_ID_ATTEMPTS {
+        let id = next_id();
+        if !taken(id).await? {
+            return Ok(id);
         }
-        if split_mode != SPLIT_EVEN && split_mode != SPLIT_RANDOM {
-            return Err(AppError::BadRequest("Split mode must be 0 (even) or 1 (random)".into()));
+    }
+    Err(AppError::BadRequest(
+        "Couldn't find a free red packet id, try again".into(),
+    ))
+}
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// A validated create instruction plus the values derived while building it
+pub struct CreatePlan {
+    pub id: u64,
+    pub total_lamports: u64,
+    pub num_recipients: u8,
+    pub expires_at: i64,
+    pub instruction: Instruction,
+}
+
+/// The SOL treasury the create pays its fee into, from its account data if
+/// it exists. The program fails a create without one (TreasuryNotInitialized),
+/// so a missing or closed treasury is refused before the creator signs.
+pub fn create_treasury(data: Option<&[u8]>) -> Result<program::TreasuryAccount, AppError> {
+    data.and_then(|data| program::decode_treasury(data).ok())
+        .ok_or_else(|| AppError::NotFound("Treasury not set up yet — contact the operator.".into()))
+}
+
+/// The treasury's expiry bounds, which the program enforces at create
+/// (ExpiryTooSoon / ExpiryTooFar); `lifetime` is expires_at minus now.
+/// build_create_instruction only knows the MAX_EXPIRY_SECONDS cap.
+pub fn check_expiry_bounds(lifetime: i64, treasury: &program::TreasuryAccount) -> Result<(), AppError> {
+    if lifetime < treasury.min_expiry_secs {
+        return Err(AppError::BadRequest(format!(
+            "Expiry must be at least {} hours away",
+            (treasury.min_expiry_secs + 3599) / 3600
+        )));
+    }
+    if lifetime > treasury.max_expiry_secs {
+        return Err(AppError::BadRequest(format!(
+            "Expiry must be at most {} hours away",
+            treasury.max_expiry_secs / 3600
+        )));
+    }
+    Ok(())
+}
+
+/// Refuse a creator whose `balance` (lamports) can't fund the pot, the fee
+/// and both rent deposits, which the program would fail with
+/// InsufficientForRent only after the wallet signed.
+pub fn check_creator_balance(balance: u64, plan: &CreatePlan, fee: u64) -> Result<(), AppError> {
+    let needed = plan
+        .total_lamports
+        .saturating_add(fee)
+        .saturating_add(program::sol_create_rent(plan.num_recipients));
+    if balance < needed {
+        return Err(AppError::BadRequest(format!(
+            "You need {} SOL (the packet, its fee and refundable rent) but only have {} SOL",
+            program::lamports_to_sol(needed),
+            program::lamports_to_sol(balance)
+        )));
+    }
+    Ok(())
+}
+
+/// Validate the create params and build the SOL create instruction.
+///
+/// `id` and `now` are passed in so the POST action and the instruction
+/// endpoint share one code path, and tests can reproduce the exact bytes.
+pub fn build_create_instruction(
+    account: Pubkey,
+    params: &HashMap<String, String>,
+    id: u64,
+    now: i64,
+) -> Result<CreatePlan, AppError> {
+    // Parse parameters
+    let amount_sol: f64 = get_param(params, "amount")?;
+    let num_recipients: u8 = get_param(params, "recipients")?;
+    let split_mode: u8 = get_param(params, "split_mode")?;
+    let expiry_hours: u64 = get_param(params, "expiry_hours")?;
+    // Optional so links built before the flag existed keep working
+    let allow_repeat: u8 = if params.contains_key("allow_repeat") {
+        get_param(params, "allow_repeat")?
+    } else {
+        0
+    };
+    // Unset leaves it to the program, which keeps creators out of their own
+    // packets
+    let self_claim: Option<u8> = if params.contains_key("self_claim") {
+        Some(get_param(params, "self_claim")?)
+    } else {
+        None
+    };
+
+    // Validate
+    if amount_sol < 0.001 {
+        return Err(AppError::BadRequest("Amount must be at least 0.001 SOL".into()));
+    }
+    if !(1..=MAX_RECIPIENTS).contains(&num_recipients) {
+        return Err(AppError::BadRequest(
+            format!("Recipients must be 1-{MAX_RECIPIENTS}"),
+        ));
+    }
+    if ![SPLIT_EVEN, SPLIT_RANDOM, SPLIT_WEIGHTED, SPLIT_LUCKY, SPLIT_RAFFLE].contains(&split_mode) {
+        return Err(AppError::BadRequest(
+            "Split mode must be 0 (even), 1 (random), 2 (weighted), 3 (lucky) or 4 (raffle)".into(),
+        ));
+    }
+    if expiry_hours > (MAX_EXPIRY_SECONDS / 3600) as u64 {
+        return Err(AppError::BadRequest(format!(
+            "Expiry must be at most {} hours (one year) away, or what the treasury's set_expiry_bounds allows",
+            MAX_EXPIRY_SECONDS / 3600
+        )));
+    }
+    if allow_repeat > 1 {
+        return Err(AppError::BadRequest(
+            "Allow repeat must be 0 (one claim per wallet) or 1 (repeat claims)".into(),
+        ));
+    }
+    if self_claim.is_some_and(|v| v > 1) {
+        return Err(AppError::BadRequest(
+            "Self claim must be 0 (the creator can't claim) or 1 (the creator can claim)".into(),
+        ));
+    }
+    let assigned_to = parse_assigned_to(params, num_recipients)?;
+    let memo = parse_memo(params)?;
+
+    let total_lamports = program::sol_to_lamports(amount_sol);
+    if total_lamports > MAX_TOTAL_AMOUNT {
+        return Err(AppError::BadRequest(format!(
+            "Amount must be at most {} SOL",
+            program::lamports_to_sol(MAX_TOTAL_AMOUNT)
+        )));
+    }
+    if split_mode == SPLIT_EVEN && total_lamports < num_recipients as u64 {
+        return Err(AppError::BadRequest("Every even slot must pay at least 1 lamport".into()));
+    }
+    let weights = parse_weights(params, num_recipients, split_mode, total_lamports)?;
+
+    // Calculate expiry
+    let expires_at = now + (expiry_hours as i64) * 3600;
+
+    // Derive PDAs
+    let (red_packet, rp_bump) = program::find_red_packet_pda(&account, id);
+    let (vault, vault_bump) = program::find_vault_pda(&account, id);
+    let (treasury, _) = program::find_treasury_pda_sol();
+
+    let mut flags = 0;
+    if allow_repeat == 1 {
+        flags |= FLAG_ALLOW_REPEAT;
+    }
+    match self_claim {
+        Some(0) => flags |= FLAG_NO_SELF_CLAIM,
+        Some(_) => flags |= FLAG_CREATOR_CAN_CLAIM,
+        None => {}
+    }
+
+    // Build instruction data
+    let data = program::build_create_data(
+        id,
+        total_lamports,
+        num_recipients,
+        split_mode,
+        expires_at,
+        rp_bump,
+        vault_bump,
+        flags,
+        assigned_to.as_deref(),
+        memo,
+        weights.as_deref(),
+    );
+
+    // SOL create: creator, red_packet, vault, treasury, system_program (5)
+    let instruction = Instruction {
+        program_id: *PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(account, true),
+            AccountMeta::new(red_packet, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(treasury, false),
+            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+        ],
+        data,
+    };
+
+    Ok(CreatePlan {
+        id,
+        total_lamports,
+        num_recipients,
+        expires_at,
+        instruction,
+    })
+}
+
+/// The optional `assigned_to` wallets, one per slot in slot order. Absent or
+/// blank means anyone may claim.
+fn parse_assigned_to(
+    params: &HashMap<String, String>,
+    num_recipients: u8,
+) -> Result<Option<Vec<Pubkey>>, AppError> {
+    let Some(raw) = params.get("assigned_to").map(|raw| raw.trim()).filter(|raw| !raw.is_empty()) else {
+        return Ok(None);
+    };
+    let wallets = raw
+        .split(',')
+        .map(|wallet| {
+            wallet
+                .trim()
+                .parse::<Pubkey>()
+                .map_err(|_| AppError::BadRequest(format!("Invalid assigned wallet: {wallet:?}")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if wallets.len() != num_recipients as usize {
+        return Err(AppError::BadRequest(format!(
+            "Assigned wallets must list exactly {num_recipients} wallets, one per recipient"
+        )));
+    }
+    Ok(Some(wallets))
+}
+
+/// The optional `memo` greeting, trimmed; absent or blank means none
+fn parse_memo(params: &HashMap<String, String>) -> Result<Option<&str>, AppError> {
+    let memo = params.get("memo").map(|raw| raw.trim()).filter(|raw| !raw.is_empty());
+    if memo.is_some_and(|memo| memo.len() > MAX_MEMO_LEN) {
+        return Err(AppError::BadRequest(format!("Memo must be at most {MAX_MEMO_LEN} bytes")));
+    }
+    Ok(memo)
+}
+
+/// The `weights` of a weighted split, one per slot in basis points. The
+/// program rejects zero weights, sums other than WEIGHT_TOTAL_BPS and slots
+/// that round down to nothing; checking here turns those into a 400.
+fn parse_weights(
+    params: &HashMap<String, String>,
+    num_recipients: u8,
+    split_mode: u8,
+    total_lamports: u64,
+) -> Result<Option<Vec<u16>>, AppError> {
+    let raw = params.get("weights").map(|raw| raw.trim()).filter(|raw| !raw.is_empty());
+    let raw = match (split_mode == SPLIT_WEIGHTED, raw) {
+        (false, None) => return Ok(None),
+        (false, Some(_)) => {
+            return Err(AppError::BadRequest("Weights only apply to weighted splits".into()))
+        }
+        (true, None) => {
+            return Err(AppError::BadRequest("Weighted splits need one weight per recipient".into()))
+        }
+        (true, Some(raw)) => raw,
+    };
+    let weights = raw
+        .split(',')
+        .map(|weight| {
+            weight
+                .trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|&weight| weight > 0)
+                .ok_or_else(|| AppError::BadRequest(format!("Invalid weight: {weight:?}")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if weights.len() != num_recipients as usize {
+        return Err(AppError::BadRequest(format!(
+            "Weights must list exactly {num_recipients} values, one per recipient"
+        )));
+    }
+    if weights.iter().map(|&weight| weight as u32).sum::<u32>() != WEIGHT_TOTAL_BPS {
+        return Err(AppError::BadRequest(format!("Weights must sum to {WEIGHT_TOTAL_BPS}")));
+    }
+    let smallest = weights.iter().min().copied().unwrap_or_default();
+    if (total_lamports as u128 * smallest as u128 / WEIGHT_TOTAL_BPS as u128) == 0 {
+        return Err(AppError::BadRequest("Every weighted slot must pay at least 1 lamport".into()));
+    }
+    Ok(Some(weights))
+}
+
+// ============================================================
+// GET /api/actions/create/instruction
+// ============================================================
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstructionAccount {
+    pub pubkey: String,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// Just the create instruction, for clients that want to rebuild and compare
+/// it against the transaction returned by the POST action.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateInstructionResponse {
+    /// Decimal string, like the `id` query param, so JS clients keep precision
+    pub id: String,
+    pub expires_at: i64,
+    pub program_id: String,
+    pub accounts: Vec<InstructionAccount>,
+    pub data_hex: String,
+    pub data_base64: String,
+}
+
+impl CreateInstructionResponse {
+    fn new(plan: &CreatePlan) -> Self {
+        let ix = &plan.instruction;
+        Self {
+            id: plan.id.to_string(),
+            expires_at: plan.expires_at,
+            program_id: ix.program_id.to_string(),
+            accounts: ix
+                .accounts
+                .iter()
+                .map(|meta| InstructionAccount {
+                    pubkey: meta.pubkey.to_string(),
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data_hex: ix.data.iter().map(|b| format!("{b:02x}")).collect(),
+            data_base64: base64::engine::general_purpose::STANDARD.encode(&ix.data),
         }
+    }
+}
+
+/// Same params as the create action plus `account` (the creator). An
+/// optional `id` pins the packet id; otherwise it's generated the same way
+/// the POST action does.
+pub async fn create_instruction(
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<CreateInstructionResponse>, AppError> {
+    let account: Pubkey = get_param(&params, "account")?;
+    let id = resolve_id(&params)?;
+
+    let plan = build_create_instruction(account, &params, id, now_unix())?;
+    Ok(Json(CreateInstructionResponse::new(&plan)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(
+        amount: &str,
+        recipients: &str,
+        split_mode: &str,
+        expiry_hours: &str,
+    ) -> HashMap<String, String> {
+        HashMap::from([
+            ("amount".to_string(), amount.to_string()),
+            ("recipients".to_string(), recipients.to_string()),
+            ("split_mode".to_string(), split_mode.to_string()),
+            ("expiry_hours".to_string(), expiry_hours.to_string()),
+        ])
+    }
+
+    #[test]
+    fn instruction_bytes_match_build_create_data() {
+        let account = Pubkey::new_unique();
+        let (id, now) = (1_700_000_000_000, 1_700_000_000);
+
+        let plan = build_create_instruction(account, &params("1.5", "3", "0", "2"), id, now).unwrap();
 
-        let total_lamports = program::sol_to_lamports(amount_sol);
-        let fee = program::compute_fee(total_lamports);
-        // Generate unique ID from timestamp
-        let id = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-
-        // Calculate expiry
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        let expires_at = now + (expiry_hours as i64) * 3600;
-
-        // Derive PDAs
         let (red_packet, rp_bump) = program::find_red_packet_pda(&account, id);
         let (vault, vault_bump) = program::find_vault_pda(&account, id);
         let (treasury, _) = program::find_treasury_pda_sol();
-
-        // Generate random amounts if needed
-        let amounts = if split_mode == SPLIT_RANDOM {
-            Some(program::generate_random_split(total_lamports, num_recipients as usize))
-        } else {
-            None
-        };
-
-        // Build instruction data
-        let data = program::build_create_data(
+        let expected = program::build_create_data(
             id,
-            total_lamports,
-            num_recipients,
-            split_mode,
-            expires_at,
+            1_500_000_000,
+            3,
+            SPLIT_EVEN,
+            now + 2 * 3600,
             rp_bump,
             vault_bump,
-            amounts.as_deref(),
+            0,
+            None,
+            None,
+            None,
         );
+        assert_eq!(plan.instruction.data, expected);
 
-        // SOL create: creator, red_packet, vault, treasury, system_program (5)
-        let ix = Instruction {
-            program_id: *PROGRAM_ID,
-            accounts: vec![
-                AccountMeta::new(account, true),
-                AccountMeta::new(red_packet, false),
-                AccountMeta::new(vault, false),
-                AccountMeta::new(treasury, false),
-                AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
-            ],
-            data,
-        };
-
-        let blockhash = rpc.get_latest_blockhash().await?;
-        let msg = Message::new_with_blockhash(&[ix], Some(&account), &blockhash);
-        let tx = Transaction::new_unsigned(msg);
-        let transaction = serialize_tx(&tx)?;
+        let resp = CreateInstructionResponse::new(&plan);
+        let hex: String = expected.iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(resp.data_hex, hex);
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD.decode(&resp.data_base64).unwrap(),
+            expected
+        );
+        assert_eq!(resp.program_id, PROGRAM_ID.to_string());
 
-        let amount_display = program::lamports_to_sol(total_lamports);
-        let fee_display = program::lamports_to_sol(fee);
-        let claim_url = format!(
-            "{base_url}/api/actions/claim?creator={}&id={id}",
-            account
+        let order: Vec<_> = resp
+            .accounts
+            .iter()
+            .map(|a| (a.pubkey.clone(), a.is_signer, a.is_writable))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (account.to_string(), true, true),
+                (red_packet.to_string(), false, true),
+                (vault.to_string(), false, true),
+                (treasury.to_string(), false, true),
+                (solana_sdk::system_program::id().to_string(), false, false),
+            ]
         );
+    }
 
-        Ok(ActionPostResponse {
-            transaction,
-            message: Some(format!(
-                "Red packet created! {amount_display} SOL for {num_recipients} recipients (fee: {fee_display} SOL).\n\nShare this claim link:\n{claim_url}"
-            )),
+    #[test]
+    fn created_red_packet_matches_instruction_accounts() {
+        let account = Pubkey::new_unique();
+        let id = u64::MAX - 1; // beyond f64 precision: must survive as a string
+        let plan = build_create_instruction(account, &params("1", "2", "0", "1"), id, 0).unwrap();
+
+        let created = created_red_packet(&account, id);
+        assert_eq!(created.id, "18446744073709551614");
+        assert_eq!(created.red_packet, plan.instruction.accounts[1].pubkey.to_string());
+        assert_eq!(created.vault, plan.instruction.accounts[2].pubkey.to_string());
+
+        let resp = ActionPostResponse {
+            transaction: String::new(),
+            message: None,
             links: None,
-        })
+            red_packet: Some(created.clone()),
+        };
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["redPacket"]["id"], created.id);
+        assert_eq!(json["redPacket"]["redPacket"], created.red_packet);
+        assert_eq!(json["redPacket"]["vault"], created.vault);
+    }
+
+    #[test]
+    fn random_split_sends_no_amounts() {
+        // The program draws each slot's amount at claim time, so a random
+        // create is the bare header, even at MAX_RECIPIENTS
+        let plan =
+            build_create_instruction(Pubkey::new_unique(), &params("0.01", "100", "1", "1"), 7, 0)
+                .unwrap();
+        let data = &plan.instruction.data;
+        assert_eq!(data.len(), 30);
+        assert_eq!(data[19], SPLIT_RANDOM);
+    }
+
+    #[test]
+    fn allow_repeat_appends_flag_byte() {
+        let mut p = params("1", "3", "0", "1");
+        p.insert("allow_repeat".into(), "1".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+        assert_eq!(plan.instruction.data.len(), 31);
+        assert_eq!(plan.instruction.data[30], 1);
+
+        p.insert("allow_repeat".into(), "0".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+        assert_eq!(plan.instruction.data.len(), 30);
+
+        p.insert("allow_repeat".into(), "2".into());
+        assert!(build_create_instruction(Pubkey::new_unique(), &p, 7, 0).is_err());
+    }
+
+    #[test]
+    fn self_claim_off_sets_no_self_claim_flag() {
+        let mut p = params("1", "3", "0", "1");
+        p.insert("self_claim".into(), "0".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+        assert_eq!(plan.instruction.data[30], FLAG_NO_SELF_CLAIM);
+
+        p.insert("allow_repeat".into(), "1".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+        assert_eq!(plan.instruction.data[30], FLAG_ALLOW_REPEAT | FLAG_NO_SELF_CLAIM);
+
+        // Every split mode needs the opt-in
+        p.insert("self_claim".into(), "1".into());
+        p.insert("allow_repeat".into(), "0".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+        assert_eq!(plan.instruction.data[30], FLAG_CREATOR_CAN_CLAIM);
+        p.insert("split_mode".into(), "1".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+        assert_eq!(plan.instruction.data[30], FLAG_CREATOR_CAN_CLAIM);
+
+        p.insert("self_claim".into(), "2".into());
+        assert!(build_create_instruction(Pubkey::new_unique(), &p, 7, 0).is_err());
+    }
+
+    #[test]
+    fn assigned_wallets_follow_the_zeroed_claim_rules() {
+        let wallets = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut p = params("1", "2", "0", "1");
+        p.insert("assigned_to".into(), format!("{}, {}", wallets[0], wallets[1]));
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+
+        let data = &plan.instruction.data;
+        assert_eq!(data.len(), 30 + 2 + 104 + 64);
+        assert_eq!(data[30..32], [0, 0]); // flags, create options
+        assert!(data[32..136].iter().all(|&b| b == 0));
+        assert_eq!(&data[136..168], wallets[0].as_ref());
+        assert_eq!(&data[168..200], wallets[1].as_ref());
+
+        // Blank means unassigned; the count must match and wallets must parse
+        p.insert("assigned_to".into(), " ".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+        assert_eq!(plan.instruction.data.len(), 30);
+        p.insert("assigned_to".into(), wallets[0].to_string());
+        assert!(build_create_instruction(Pubkey::new_unique(), &p, 7, 0).is_err());
+        p.insert("assigned_to".into(), format!("{},nope", wallets[0]));
+        assert!(build_create_instruction(Pubkey::new_unique(), &p, 7, 0).is_err());
+    }
+
+    #[test]
+    fn weighted_split_appends_one_weight_per_slot() {
+        let mut p = params("1", "3", "2", "1");
+        p.insert("weights".into(), "5000, 3000,2000".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+
+        let data = &plan.instruction.data;
+        assert_eq!(data.len(), 30 + 6);
+        assert_eq!(data[19], SPLIT_WEIGHTED);
+        assert_eq!(&data[30..], &[0x88, 0x13, 0xb8, 0x0b, 0xd0, 0x07]);
+
+        // Weighted needs weights, whole and nonzero, one per slot, summing to
+        // 10_000; other modes take none
+        for bad in ["", "5000,5000", "5000,5000,0", "5000,3000,1999", "5000,3000,x"] {
+            p.insert("weights".into(), bad.into());
+            assert!(build_create_instruction(Pubkey::new_unique(), &p, 7, 0).is_err(), "{bad:?}");
+        }
+        let mut p = params("1", "3", "0", "1");
+        p.insert("weights".into(), "5000,3000,2000".into());
+        assert!(build_create_instruction(Pubkey::new_unique(), &p, 7, 0).is_err());
+        p.insert("weights".into(), " ".into());
+        assert!(build_create_instruction(Pubkey::new_unique(), &p, 7, 0).is_ok());
+    }
+
+    #[test]
+    fn memo_is_a_padded_block_after_the_options() {
+        let mut p = params("1", "2", "0", "1");
+        p.insert("memo".into(), "  恭喜发财 ".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+
+        let data = &plan.instruction.data;
+        let memo = "恭喜发财".as_bytes();
+        assert_eq!(data.len(), 30 + 2 + 1 + MAX_MEMO_LEN);
+        assert_eq!(data[30..32], [0, 0]); // flags, create options
+        assert_eq!(data[32] as usize, memo.len());
+        assert_eq!(&data[33..33 + memo.len()], memo);
+        assert!(data[33 + memo.len()..].iter().all(|&b| b == 0));
+
+        // Blank means no memo; longer than MAX_MEMO_LEN bytes is refused
+        p.insert("memo".into(), " ".into());
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 7, 0).unwrap();
+        assert_eq!(plan.instruction.data.len(), 30);
+        p.insert("memo".into(), "x".repeat(MAX_MEMO_LEN + 1));
+        assert!(build_create_instruction(Pubkey::new_unique(), &p, 7, 0).is_err());
+    }
+
+    #[test]
+    fn generated_ids_differ_within_a_millisecond() {
+        let now_millis = 1_700_000_000_000;
+        assert_ne!(generate_id(now_millis, 1), generate_id(now_millis, 2));
+        // Later milliseconds always sort after earlier ones
+        assert!(generate_id(now_millis + 1, 0) > generate_id(now_millis, u16::MAX));
+    }
+
+    #[test]
+    fn resolve_id_prefers_caller_id() {
+        let mut p = params("1", "2", "0", "1");
+        p.insert("id".into(), "42".into());
+        assert_eq!(resolve_id(&p).unwrap(), 42);
+        p.insert("id".into(), "4.2".into());
+        assert!(resolve_id(&p).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_params() {
+        let account = Pubkey::new_unique();
+        assert!(build_create_instruction(account, &params("0.0001", "3", "0", "1"), 1, 0).is_err());
+        assert!(build_create_instruction(account, &params("1", "101", "0", "1"), 1, 0).is_err());
+        assert!(build_create_instruction(account, &params("1", "101", "1", "1"), 1, 0).is_err());
+        assert!(build_create_instruction(account, &params("1", "100", "1", "1"), 1, 0).is_ok());
+        assert!(build_create_instruction(account, &params("1", "100", "0", "1"), 1, 0).is_ok());
+        assert!(build_create_instruction(account, &params("1", "3", "2", "1"), 1, 0).is_err());
+        assert!(build_create_instruction(account, &params("1", "3", "3", "1"), 1, 0).is_ok());
+        assert!(build_create_instruction(account, &params("1", "3", "4", "1"), 1, 0).is_ok());
+        assert!(build_create_instruction(account, &params("1", "3", "5", "1"), 1, 0).is_err());
+        assert!(build_create_instruction(account, &params("1", "3", "0", "8761"), 1, 0).is_err());
+        assert!(build_create_instruction(account, &params("1", "3", "0", "8760"), 1, 0).is_ok());
+    }
+
+    #[test]
+    fn sol_create_rent_covers_the_packet_and_vault() {
+        // 3 slots: 405 + 3 * 48 = 549 bytes of packet, plus a 0-byte vault
+        assert_eq!(program::sol_create_rent(3), (549 + 128) * 2 * 3480 + 128 * 2 * 3480);
+        assert!(program::sol_create_rent(MAX_RECIPIENTS) > program::sol_create_rent(1));
+    }
+
+    #[test]
+    fn fee_matches_the_program_across_totals_and_rates() {
+        // Reference: floor(total * bps / 10_000) in exact arithmetic, at
+        // least 1 unless fee-free
+        let expected = |total: u64, bps: u16| -> Option<u64> {
+            if bps == 0 {
+                return Some(0);
+            }
+            let fee = total as u128 * bps as u128 / 10_000;
+            (fee <= u64::MAX as u128).then(|| (fee as u64).max(1))
+        };
+        let mut totals = vec![0, 1, 9_999, 10_000, 10_001, u64::MAX / 500, u64::MAX / 10, u64::MAX];
+        let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+        for _ in 0..2_000 {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+            // Spread over every magnitude, not just huge values
+            totals.push(seed >> (seed % 64));
+        }
+        for &total in &totals {
+            for bps in [0, 1, 10, 25, 499, 500, 9_999, 10_000, u16::MAX] {
+                assert_eq!(program::compute_fee(total, bps), expected(total, bps), "{total} at {bps} bps");
+            }
+        }
+
+        // Past ~3.7e16 the old u64 product overflowed at the 5% cap
+        assert_eq!(program::compute_fee(100_000_000_000_000_000, 500), Some(5_000_000_000_000_000));
+        assert_eq!(program::compute_fee(u64::MAX, 10_000), Some(u64::MAX));
+        assert_eq!(program::compute_fee(u64::MAX, 10_001), None);
+        assert_eq!(program::compute_fee(1, 10), Some(1));
+    }
+
+    #[test]
+    fn amounts_above_the_program_ceiling_are_rejected() {
+        let account = Pubkey::new_unique();
+        let (id, now) = (1, 1_700_000_000);
+
+        // u64::MAX lamports and beyond (sol_to_lamports saturates)
+        for amount in ["18446744073.709551615", "1e12"] {
+            let err = build_create_instruction(account, &params(amount, "3", "0", "2"), id, now).err().unwrap();
+            assert!(err.to_string().contains("at most"), "{amount}: {err}");
+        }
+
+        // Just below MAX_TOTAL_AMOUNT, whose fee at the 5% cap still fits
+        let plan = build_create_instruction(account, &params("9000000000", "3", "0", "2"), id, now).unwrap();
+        assert_eq!(plan.total_lamports, 9_000_000_000_000_000_000);
+        assert!(plan.total_lamports < MAX_TOTAL_AMOUNT);
+        let fee = program::compute_fee(plan.total_lamports, 500).unwrap();
+        assert!(plan.total_lamports.checked_add(fee).is_some());
+    }
+
+    #[test]
+    fn expiry_must_fall_within_the_treasury_bounds() {
+        let mut treasury = program::TreasuryAccount {
+            bump: 0,
+            vault_bump: 0,
+            mint: Pubkey::new_from_array(NATIVE_SOL_MINT),
+            sol_fees_collected: 0,
+            fee_bps: FEE_RATE_BPS,
+            paused: false,
+            min_expiry_secs: MIN_EXPIRY_SECONDS,
+            max_expiry_secs: MAX_EXPIRY_SECONDS,
+        };
+        assert!(check_expiry_bounds(3_600, &treasury).is_ok());
+        assert!(check_expiry_bounds(MAX_EXPIRY_SECONDS, &treasury).is_ok());
+
+        treasury.min_expiry_secs = 2 * 3_600;
+        treasury.max_expiry_secs = 24 * 3_600;
+        assert!(check_expiry_bounds(3_600, &treasury).is_err());
+        assert!(check_expiry_bounds(2 * 3_600, &treasury).is_ok());
+        assert!(check_expiry_bounds(24 * 3_600, &treasury).is_ok());
+        assert!(check_expiry_bounds(25 * 3_600, &treasury).is_err());
+    }
+
+    #[test]
+    fn creator_balance_must_cover_the_pot_fee_and_rent() {
+        let p = params("1", "3", "0", "24");
+        let plan = build_create_instruction(Pubkey::new_unique(), &p, 1, 0).unwrap();
+        let fee = program::compute_fee(plan.total_lamports, FEE_RATE_BPS).unwrap();
+        let needed = plan.total_lamports + fee + program::sol_create_rent(3);
+
+        assert!(check_creator_balance(needed, &plan, fee).is_ok());
+        match check_creator_balance(needed - 1, &plan, fee) {
+            Err(AppError::BadRequest(msg)) => assert!(msg.starts_with("You need 1.00")),
+            other => panic!("expected BadRequest, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn taken_generated_ids_are_regenerated() {
+        let mut ids = [7, 8, 9].into_iter();
+        let id = first_free_id(|| ids.next().unwrap(), |id| async move { Ok(id < 9) })
+            .await
+            .unwrap();
+        assert_eq!(id, 9);
+
+        let mut calls = 0;
+        let never_free = first_free_id(
+            || {
+                calls += 1;
+                calls
+            },
+            |_| async { Ok(true) },
+        )
+        .await;
+        assert!(never_free.is_err());
+        assert_eq!(calls, MAX_ID_ATTEMPTS as u64);
+    }
+
+    #[test]
+    fn creates_need_an_initialized_treasury() {
+        for data in [None, Some(&[][..]), Some(&[0u8; TREASURY_LEGACY_SIZE][..])] {
+            match create_treasury(data) {
+                Err(AppError::NotFound(msg)) => assert!(msg.starts_with("Treasury not set up yet")),
+                other => panic!("expected NotFound, got {other:?}"),
+            }
+        }
+
+        let mut data = vec![0u8; TREASURY_LEGACY_SIZE];
+        data[0] = TREASURY_DISCRIMINATOR;
+        assert_eq!(create_treasury(Some(&data)).unwrap().fee_bps, FEE_RATE_BPS);
+    }
+
+    #[test]
+    fn builders_match_the_program_reference_vectors() {
+        // The program's ix_data tests parse these same bytes
+        assert_eq!(
+            program::build_create_data(7, 1_500_000_000, 3, SPLIT_EVEN, 1_700_000_000, 254, 253, 0, None, None, None),
+            [0, 1, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0x2f, 0x68, 0x59, 0, 0, 0, 0, 3, 0, 0, 0xf1, 0x53, 0x65, 0, 0, 0, 0, 254, 253]
+        );
+        assert_eq!(program::build_claim_data(TOKEN_TYPE_SOL, 2, false, None), [1, 1, 2]);
+        assert_eq!(
+            program::build_claim_data(TOKEN_TYPE_SPL, 0, true, Some(b"pwd")),
+            [1, 0, 0, 1, b'p', b'w', b'd']
+        );
+        assert_eq!(program::build_close_data(TOKEN_TYPE_SOL), [2, 1]);
+        assert_eq!(program::build_cancel_data(TOKEN_TYPE_SPL), [7, 0]);
+        assert_eq!(program::build_withdraw_fees_data(TOKEN_TYPE_SOL, 0), [4, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
     }
 }
@@ -1,8 +1,12 @@
 pub mod claim;
 pub mod close;
 pub mod create;
+pub mod withdraw_fees;
 mod registry;
 mod utils;
 
 pub use registry::{Action, ActionRegistry};
-pub use utils::{get_param, serialize_tx};
+pub use utils::{
+    account_exists, fetch_account, fetch_packet_token, get_id_param, get_optional_id_param, get_param, parse_priority_fee,
+    parse_relayer_keypair, parse_simulate, serialize_tx, simulate_tx,
+};
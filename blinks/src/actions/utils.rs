@@ -1,9 +1,19 @@
 use base64::Engine;
-use solana_sdk::transaction::Transaction;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+use solana_sdk::account::Account;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair};
+use solana_sdk::transaction::{Transaction, TransactionError};
 use std::collections::HashMap;
 use std::str::FromStr;
 
+use crate::cache::RedPacketCache;
+use crate::consts::{PROGRAM_ID, TOKEN_TYPE_SOL};
 use crate::error::AppError;
+use crate::program::{self, RedPacketAccount, SplToken};
+use crate::program_error;
 
 pub fn get_param<T: FromStr>(params: &HashMap<String, String>, key: &str) -> Result<T, AppError> {
     params
@@ -13,7 +23,226 @@ pub fn get_param<T: FromStr>(params: &HashMap<String, String>, key: &str) -> Res
         .map_err(|_| AppError::BadRequest(format!("Invalid '{key}' parameter")))
 }
 
+/// Parse a red packet `id` (u64). Clients must send it as a plain decimal
+/// string: ids above 2^53 lose precision as JS numbers, so scientific
+/// notation, floats and signs are rejected rather than coerced.
+pub fn get_id_param(params: &HashMap<String, String>) -> Result<u64, AppError> {
+    let raw = params
+        .get("id")
+        .ok_or_else(|| AppError::BadRequest("Missing 'id' parameter".into()))?;
+    parse_id("id", raw)
+}
+
+/// Like `get_id_param` for an optional id-valued param such as a `before`
+/// pagination cursor.
+pub fn get_optional_id_param(
+    params: &HashMap<String, String>,
+    key: &str,
+) -> Result<Option<u64>, AppError> {
+    params.get(key).map(|raw| parse_id(key, raw)).transpose()
+}
+
+fn parse_id(key: &str, raw: &str) -> Result<u64, AppError> {
+    if raw.is_empty() || !raw.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(AppError::BadRequest(format!(
+            "Invalid '{key}' parameter: {raw:?} must be a decimal integer string (no floats or exponents)"
+        )));
+    }
+    raw.parse()
+        .map_err(|_| AppError::BadRequest(format!("Invalid '{key}' parameter: {raw:?} exceeds u64")))
+}
+
+/// Mint, token program and decimals of an SPL packet, read via its vault;
+/// None for SOL packets.
+pub async fn fetch_packet_token(
+    rpc: &RpcClient,
+    cache: &RedPacketCache,
+    rp: &RedPacketAccount,
+) -> Result<Option<SplToken>, AppError> {
+    if rp.token_type == TOKEN_TYPE_SOL {
+        return Ok(None);
+    }
+    let (vault, _) = program::find_vault_pda(&rp.original_creator, rp.id);
+    let vault_account = fetch_account(rpc, &vault, "Red packet vault not found on chain").await?;
+    // The vault lives under whichever token program the mint uses
+    if !program::is_token_program(&vault_account.owner) {
+        return Err(AppError::BadRequest("Unsupported token program for red packet vault".into()));
+    }
+    let mint = program::decode_token_account_mint(&vault_account.data)?;
+    let token = cache.fetch_mint(rpc, &mint).await?;
+    if token.token_program != vault_account.owner {
+        return Err(AppError::BadRequest("Red packet vault and mint use different token programs".into()));
+    }
+    Ok(Some(token))
+}
+
+/// The account at `address`, or NotFound with `missing` if there is none.
+/// RPC failures stay AppError::Rpc (a 502), so clients can tell a missing
+/// account from a flaky node and know when a retry may help.
+pub async fn fetch_account(rpc: &RpcClient, address: &Pubkey, missing: &str) -> Result<Account, AppError> {
+    rpc.get_account_with_commitment(address, rpc.commitment())
+        .await?
+        .value
+        .ok_or_else(|| AppError::NotFound(missing.into()))
+}
+
+/// Whether `address` exists, e.g. to skip creating a token account.
+pub async fn account_exists(rpc: &RpcClient, address: &Pubkey) -> Result<bool, AppError> {
+    Ok(rpc
+        .get_account_with_commitment(address, rpc.commitment())
+        .await?
+        .value
+        .is_some())
+}
+
+/// Base64 wire form of `tx`. Over the packet limit (e.g. an assigned create
+/// listing too many wallets) it's a 400, since no wallet could send it.
 pub fn serialize_tx(tx: &Transaction) -> Result<String, AppError> {
     let bytes = bincode::serialize(tx)?;
+    if bytes.len() > PACKET_DATA_SIZE {
+        return Err(AppError::BadRequest(format!(
+            "Transaction is {} bytes, over the {PACKET_DATA_SIZE}-byte limit",
+            bytes.len()
+        )));
+    }
     Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
 }
+
+/// Parse SIMULATE_TRANSACTIONS. Off unless set to 1 or true.
+pub fn parse_simulate(raw: Option<&str>) -> Result<bool, String> {
+    match raw.map(str::trim) {
+        None | Some("") | Some("0") | Some("false") => Ok(false),
+        Some("1") | Some("true") => Ok(true),
+        Some(raw) => Err(format!("Invalid SIMULATE_TRANSACTIONS: {raw:?}")),
+    }
+}
+
+/// Parse PRIORITY_FEE_MICRO_LAMPORTS. Unset uses `default`; 0 sends no
+/// compute unit price.
+pub fn parse_priority_fee(raw: Option<&str>, default: u64) -> Result<u64, String> {
+    match raw.map(str::trim) {
+        None | Some("") => Ok(default),
+        Some(raw) => raw
+            .parse()
+            .map_err(|_| format!("Invalid PRIORITY_FEE_MICRO_LAMPORTS: {raw:?}")),
+    }
+}
+
+/// Load RELAYER_KEYPAIR, a path to a Solana CLI keypair file. Unset turns
+/// relayed claims off.
+pub fn parse_relayer_keypair(raw: Option<&str>) -> Result<Option<Keypair>, String> {
+    match raw.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(path) => read_keypair_file(path)
+            .map(Some)
+            .map_err(|err| format!("Invalid RELAYER_KEYPAIR {path:?}: {err}")),
+    }
+}
+
+/// Simulate `tx` as its fee payer would send it (unsigned, so without
+/// signature checks). A failure is a 400 naming the program error, so the
+/// wallet never prompts for a transaction that can't land. Returns the
+/// return data the red packet program left, if any.
+pub async fn simulate_tx(rpc: &RpcClient, tx: &Transaction) -> Result<Option<Vec<u8>>, AppError> {
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        commitment: Some(rpc.commitment()),
+        ..Default::default()
+    };
+    let result = rpc.simulate_transaction_with_config(tx, config).await?.value;
+    if let Some(err) = result.err {
+        return Err(simulation_error(&err));
+    }
+    Ok(result
+        .return_data
+        .filter(|return_data| return_data.program_id == PROGRAM_ID.to_string())
+        .and_then(|return_data| base64::engine::general_purpose::STANDARD.decode(return_data.data.0).ok()))
+}
+
+fn simulation_error(err: &TransactionError) -> AppError {
+    let reason = program_error::describe_transaction_error(err).unwrap_or_else(|| err.to_string());
+    AppError::BadRequest(format!("Transaction simulation failed: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_above_js_safe_integer_parses_exactly() {
+        assert_eq!(parse_id("id", "9007199254740993").unwrap(), 9_007_199_254_740_993);
+        assert_eq!(parse_id("id", "18446744073709551615").unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn oversized_transactions_are_rejected() {
+        use solana_sdk::instruction::Instruction;
+        use solana_sdk::message::Message;
+
+        let payer = Pubkey::new_unique();
+        let tx = |data_len: usize| {
+            let ix = Instruction::new_with_bytes(Pubkey::new_unique(), &vec![0; data_len], vec![]);
+            Transaction::new_unsigned(Message::new(&[ix], Some(&payer)))
+        };
+        assert!(serialize_tx(&tx(1_000)).is_ok());
+        assert!(serialize_tx(&tx(1_200)).is_err());
+    }
+
+    #[test]
+    fn simulate_flag_is_off_by_default() {
+        assert_eq!(parse_simulate(None), Ok(false));
+        assert_eq!(parse_simulate(Some("0")), Ok(false));
+        assert_eq!(parse_simulate(Some("1")), Ok(true));
+        assert_eq!(parse_simulate(Some("true")), Ok(true));
+        assert!(parse_simulate(Some("yes")).is_err());
+    }
+
+    #[test]
+    fn priority_fee_defaults_and_zero_turns_it_off() {
+        assert_eq!(parse_priority_fee(None, 1_000), Ok(1_000));
+        assert_eq!(parse_priority_fee(Some(" "), 1_000), Ok(1_000));
+        assert_eq!(parse_priority_fee(Some("0"), 1_000), Ok(0));
+        assert_eq!(parse_priority_fee(Some("250000"), 1_000), Ok(250_000));
+        assert!(parse_priority_fee(Some("-1"), 1_000).is_err());
+        assert!(parse_priority_fee(Some("0.5"), 1_000).is_err());
+    }
+
+    #[test]
+    fn relayer_keypair_is_optional_and_read_from_file() {
+        use solana_sdk::signature::{write_keypair_file, Signer};
+        assert!(parse_relayer_keypair(None).unwrap().is_none());
+        assert!(parse_relayer_keypair(Some(" ")).unwrap().is_none());
+        assert!(parse_relayer_keypair(Some("/nonexistent/relayer.json")).is_err());
+
+        let relayer = Keypair::new();
+        let path = std::env::temp_dir().join(format!("relayer-{}.json", relayer.pubkey()));
+        write_keypair_file(&relayer, &path).unwrap();
+        let loaded = parse_relayer_keypair(path.to_str()).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.pubkey(), relayer.pubkey());
+    }
+
+    #[test]
+    fn simulation_failures_name_the_program_error() {
+        use solana_sdk::instruction::InstructionError;
+
+        let custom = TransactionError::InstructionError(0, InstructionError::Custom(53));
+        assert_eq!(
+            simulation_error(&custom).to_string(),
+            "Bad request: Transaction simulation failed: \
+             The wallet can't cover the packet, its rent and the fee (InsufficientForRent, error 53)"
+        );
+        let other = TransactionError::InsufficientFundsForFee;
+        assert_eq!(
+            simulation_error(&other).to_string(),
+            format!("Bad request: Transaction simulation failed: {other}")
+        );
+    }
+
+    #[test]
+    fn id_rejects_non_decimal_forms() {
+        for raw in ["1.5e3", "1500.0", "1e3", "+1500", "-1", " 1500", "", "18446744073709551616"] {
+            assert!(parse_id("id", raw).is_err(), "{raw:?} should be rejected");
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use async_trait::async_trait;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+use std::collections::HashMap;
+
+use super::{account_exists, fetch_account, get_param, serialize_tx, Action};
+use crate::consts::*;
+use crate::error::AppError;
+use crate::program;
+use crate::spec::*;
+
+pub struct WithdrawFeesAction;
+
+#[async_trait]
+impl Action for WithdrawFeesAction {
+    fn path(&self) -> &'static str {
+        "withdraw-fees"
+    }
+
+    async fn metadata(
+        &self,
+        _rpc: &RpcClient,
+        _base_url: &str,
+        _params: HashMap<String, String>,
+    ) -> Result<ActionGetResponse, AppError> {
+        let resp = ActionGetResponse::new(
+            ICON_URL,
+            "Withdraw Fees",
+            &format!("Withdraw collected red packet fees to the admin wallet (admin only{})", CLUSTER.label()),
+            "Withdraw",
+        )
+        .with_links(vec![LinkedAction {
+            href: "/api/actions/withdraw-fees?token_type={token_type}&mint={mint}&amount={amount}".into(),
+            label: "Withdraw Fees".into(),
+            parameters: Some(vec![
+                ActionParameter::radio(
+                    "token_type",
+                    "Treasury",
+                    vec![
+                        ActionParameterOption::new("SOL", "1"),
+                        ActionParameterOption::new("SPL token", "0"),
+                    ],
+                ),
+                ActionParameter::text("mint", "Token mint (SPL only)", false),
+                ActionParameter::number("amount", "Amount (0 = everything collected)", true)
+                    .with_min(0.0),
+            ]),
+        }]);
+
+        Ok(resp)
+    }
+
+    async fn execute(
+        &self,
+        rpc: &RpcClient,
+        _base_url: &str,
+        account: Pubkey,
+        params: HashMap<String, String>,
+    ) -> Result<ActionPostResponse, AppError> {
+        // The program checks the treasury's stored admin; this keeps the
+        // action from building transactions for anyone else
+        if account != *ADMIN {
+            return Err(AppError::Unauthorized("Only the treasury admin can withdraw fees".into()));
+        }
+
+        let token_type: u8 = get_param(&params, "token_type")?;
+        let amount: f64 = get_param(&params, "amount")?;
+        if !amount.is_finite() || amount < 0.0 {
+            return Err(AppError::BadRequest("Amount must be 0 or more".into()));
+        }
+
+        let spl = match token_type {
+            TOKEN_TYPE_SOL => None,
+            TOKEN_TYPE_SPL => Some(fetch_mint(rpc, &get_param(&params, "mint")?).await?),
+            _ => return Err(AppError::BadRequest("Invalid 'token_type' parameter".into())),
+        };
+        let amount = program::base_amount(amount, spl.as_ref());
+
+        // Quote what the treasury holds so an overdraw is a 400 here
+        let available = match &spl {
+            None => {
+                let (treasury, _) = program::find_treasury_pda_sol();
+                let treasury_account = fetch_account(rpc, &treasury, "SOL treasury not initialized").await?;
+                program::decode_treasury(&treasury_account.data)?.sol_fees_collected
+            }
+            Some(token) => {
+                let (treasury_vault, _) = program::find_treasury_vault_pda(&token.mint);
+                let vault_account = fetch_account(rpc, &treasury_vault, "Treasury not initialized for this mint").await?;
+                program::decode_token_account_amount(&vault_account.data)?
+            }
+        };
+        if available == 0 || amount > available {
+            let (available, unit) = program::display_amount(available, spl.as_ref());
+            return Err(AppError::BadRequest(format!(
+                "Only {available} {unit} of fees available to withdraw"
+            )));
+        }
+
+        // SPL fees go to the admin's ATA; create it first if missing
+        let mut instructions = Vec::with_capacity(2);
+        if let Some(token) = &spl {
+            let ata = program::find_associated_token_address(&account, &token.mint, &token.token_program);
+            if !account_exists(rpc, &ata).await? {
+                instructions.push(program::create_ata_idempotent_instruction(
+                    &account,
+                    &account,
+                    &token.mint,
+                    &token.token_program,
+                ));
+            }
+        }
+        instructions.push(build_withdraw_fees_instruction(&account, amount, spl.as_ref()));
+
+        let blockhash = rpc.get_latest_blockhash().await?;
+        let msg = Message::new_with_blockhash(&instructions, Some(&account), &blockhash);
+        let tx = Transaction::new_unsigned(msg);
+        let transaction = serialize_tx(&tx)?;
+
+        let withdrawn = if amount == 0 { available } else { amount };
+        let (withdrawn, unit) = program::display_amount(withdrawn, spl.as_ref());
+
+        Ok(ActionPostResponse {
+            transaction,
+            message: Some(format!("Withdrawing {withdrawn} {unit} of fees to the admin wallet.")),
+            links: None,
+            red_packet: None,
+        })
+    }
+}
+
+/// Token program and decimals of `mint`, for an SPL treasury
+async fn fetch_mint(rpc: &RpcClient, mint: &Pubkey) -> Result<program::SplToken, AppError> {
+    let mint_account = fetch_account(rpc, mint, "Token mint not found on chain").await?;
+    if !program::is_token_program(&mint_account.owner) {
+        return Err(AppError::BadRequest("Unsupported token program for mint".into()));
+    }
+    Ok(program::SplToken {
+        mint: *mint,
+        token_program: mint_account.owner,
+        decimals: program::decode_mint_decimals(&mint_account.data)?,
+    })
+}
+
+/// Withdraw-fees instruction paying `admin` (its ATA for SPL); `spl` is
+/// None for the SOL treasury.
+///
+/// SOL: admin, treasury (2)
+/// SPL: admin, admin_ata, treasury, treasury_vault, token_program (5)
+pub fn build_withdraw_fees_instruction(
+    admin: &Pubkey,
+    amount: u64,
+    spl: Option<&program::SplToken>,
+) -> Instruction {
+    let (accounts, token_type) = match spl {
+        None => (
+            vec![
+                AccountMeta::new(*admin, true),
+                AccountMeta::new(program::find_treasury_pda_sol().0, false),
+            ],
+            TOKEN_TYPE_SOL,
+        ),
+        Some(token) => (
+            vec![
+                AccountMeta::new(*admin, true),
+                AccountMeta::new(
+                    program::find_associated_token_address(admin, &token.mint, &token.token_program),
+                    false,
+                ),
+                AccountMeta::new(program::find_treasury_pda(&token.mint).0, false),
+                AccountMeta::new(program::find_treasury_vault_pda(&token.mint).0, false),
+                AccountMeta::new_readonly(token.token_program, false),
+            ],
+            TOKEN_TYPE_SPL,
+        ),
+    };
+
+    Instruction {
+        program_id: *PROGRAM_ID,
+        accounts,
+        data: program::build_withdraw_fees_data(token_type, amount),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sol_withdraw_uses_admin_and_treasury() {
+        let ix = build_withdraw_fees_instruction(&ADMIN, 1_500, None);
+
+        let mut data = vec![4, TOKEN_TYPE_SOL];
+        data.extend_from_slice(&1_500u64.to_le_bytes());
+        assert_eq!(ix.data, data);
+        let metas: Vec<(Pubkey, bool, bool)> = ix
+            .accounts
+            .iter()
+            .map(|m| (m.pubkey, m.is_signer, m.is_writable))
+            .collect();
+        assert_eq!(
+            metas,
+            vec![(*ADMIN, true, true), (program::find_treasury_pda_sol().0, false, true)]
+        );
+    }
+
+    #[test]
+    fn spl_withdraw_pays_the_admin_ata() {
+        let token = program::SplToken {
+            mint: Pubkey::new_unique(),
+            token_program: *TOKEN_2022_PROGRAM_ID,
+            decimals: 6,
+        };
+        let ix = build_withdraw_fees_instruction(&ADMIN, 0, Some(&token));
+
+        assert_eq!(ix.data, vec![4, TOKEN_TYPE_SPL, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let metas: Vec<(Pubkey, bool, bool)> = ix
+            .accounts
+            .iter()
+            .map(|m| (m.pubkey, m.is_signer, m.is_writable))
+            .collect();
+        assert_eq!(
+            metas,
+            vec![
+                (*ADMIN, true, true),
+                (
+                    program::find_associated_token_address(&ADMIN, &token.mint, &token.token_program),
+                    false,
+                    true,
+                ),
+                (program::find_treasury_pda(&token.mint).0, false, true),
+                (program::find_treasury_vault_pda(&token.mint).0, false, true),
+                (*TOKEN_2022_PROGRAM_ID, false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn base_amount_inverts_display_amount() {
+        let token = program::SplToken {
+            mint: Pubkey::new_unique(),
+            token_program: *TOKEN_PROGRAM_ID,
+            decimals: 6,
+        };
+        assert_eq!(program::base_amount(1.5, Some(&token)), 1_500_000);
+        assert_eq!(program::base_amount(0.25, None), 250_000_000);
+    }
+
+    #[test]
+    fn display_amount_scales_by_mint_decimals() {
+        let token = |decimals| program::SplToken {
+            mint: Pubkey::new_unique(),
+            token_program: *TOKEN_PROGRAM_ID,
+            decimals,
+        };
+        // 2.5 USDC at 6 decimals, not 0.0025 as if it were lamports
+        assert_eq!(program::display_amount(2_500_000, Some(&token(6))), (2.5, "tokens"));
+        assert_eq!(program::display_amount(2_500_000_000, Some(&token(9))), (2.5, "tokens"));
+        assert_eq!(program::display_amount(2_500_000_000, None), (2.5, "SOL"));
+        assert_eq!(program::display_amount(1, Some(&token(6))), (0.000001, "tokens"));
+    }
+}
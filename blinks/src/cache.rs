@@ -0,0 +1,181 @@
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::actions::fetch_account;
+use crate::error::AppError;
+use crate::program::{self, RedPacketAccount, SplToken};
+
+/// Past this many cached packets, expired entries are dropped on insert
+const MAX_CACHED_PACKETS: usize = 10_000;
+
+/// Past this many cached mints, the mint cache starts over
+const MAX_CACHED_MINTS: usize = 1_000;
+
+/// Parse RED_PACKET_CACHE_TTL_MS. Unset uses `default`; 0 turns caching off.
+pub fn parse_cache_ttl(raw: Option<&str>, default: Duration) -> Result<Duration, String> {
+    match raw.map(str::trim) {
+        None | Some("") => Ok(default),
+        Some(raw) => raw
+            .parse()
+            .map(Duration::from_millis)
+            .map_err(|_| format!("Invalid RED_PACKET_CACHE_TTL_MS: {raw:?}")),
+    }
+}
+
+/// Decoded red packets keyed by (creator, id), so a blink opened by many
+/// wallets costs one RPC read per TTL instead of one per view. SPL mints'
+/// decimals and token program are kept too, without a TTL: neither changes
+/// once the mint exists.
+pub struct RedPacketCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<(Pubkey, u64), (Instant, RedPacketAccount)>>,
+    mints: Mutex<HashMap<Pubkey, SplToken>>,
+}
+
+impl RedPacketCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+            mints: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, creator: &Pubkey, id: u64, now: Instant) -> Option<RedPacketAccount> {
+        let entries = self.entries.lock().unwrap();
+        let (fetched_at, rp) = entries.get(&(*creator, id))?;
+        (now.saturating_duration_since(*fetched_at) < self.ttl).then(|| rp.clone())
+    }
+
+    pub fn insert(&self, creator: &Pubkey, id: u64, rp: &RedPacketAccount, now: Instant) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_CACHED_PACKETS {
+            entries.retain(|_, (fetched_at, _)| now.saturating_duration_since(*fetched_at) < self.ttl);
+        }
+        entries.insert((*creator, id), (now, rp.clone()));
+    }
+
+    /// Fetch and decode a red packet, from the cache when possible. `fresh`
+    /// always reads the chain (and refreshes the entry): use it whenever the
+    /// result picks a slot or gates a transaction, since a stale
+    /// num_claimed builds claims that fail on-chain.
+    pub async fn fetch(
+        &self,
+        rpc: &RpcClient,
+        creator: &Pubkey,
+        id: u64,
+        fresh: bool,
+    ) -> Result<RedPacketAccount, AppError> {
+        if !fresh {
+            if let Some(rp) = self.get(creator, id, Instant::now()) {
+                return Ok(rp);
+            }
+        }
+
+        let (red_packet_addr, _) = program::find_red_packet_pda(creator, id);
+        let account = fetch_account(rpc, &red_packet_addr, "Red packet not found on chain").await?;
+        let rp = program::decode_red_packet(&account.data)?;
+
+        self.insert(creator, id, &rp, Instant::now());
+        Ok(rp)
+    }
+
+    pub fn get_mint(&self, mint: &Pubkey) -> Option<SplToken> {
+        self.mints.lock().unwrap().get(mint).cloned()
+    }
+
+    pub fn insert_mint(&self, token: &SplToken) {
+        let mut mints = self.mints.lock().unwrap();
+        if mints.len() >= MAX_CACHED_MINTS {
+            mints.clear();
+        }
+        mints.insert(token.mint, token.clone());
+    }
+
+    /// Decimals and token program of `mint`, read from the chain once
+    pub async fn fetch_mint(&self, rpc: &RpcClient, mint: &Pubkey) -> Result<SplToken, AppError> {
+        if let Some(token) = self.get_mint(mint) {
+            return Ok(token);
+        }
+
+        let account = fetch_account(rpc, mint, "Token mint not found on chain").await?;
+        if !program::is_token_program(&account.owner) {
+            return Err(AppError::BadRequest("Unsupported token program for mint".into()));
+        }
+        let token = SplToken {
+            mint: *mint,
+            token_program: account.owner,
+            decimals: program::decode_mint_decimals(&account.data)?,
+        };
+
+        self.insert_mint(&token);
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(num_claimed: u8) -> RedPacketAccount {
+        RedPacketAccount {
+            num_recipients: 3,
+            num_claimed,
+            ..RedPacketAccount::mock()
+        }
+    }
+
+    #[test]
+    fn parses_ttl_with_default() {
+        let default = Duration::from_secs(2);
+        assert_eq!(parse_cache_ttl(None, default), Ok(default));
+        assert_eq!(parse_cache_ttl(Some("500"), default), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_cache_ttl(Some("0"), default), Ok(Duration::ZERO));
+        assert!(parse_cache_ttl(Some("2s"), default).is_err());
+    }
+
+    #[test]
+    fn entries_expire_after_ttl_and_refresh_on_insert() {
+        let cache = RedPacketCache::new(Duration::from_secs(2));
+        let creator = Pubkey::new_unique();
+        let start = Instant::now();
+
+        cache.insert(&creator, 1, &packet(0), start);
+        assert_eq!(cache.get(&creator, 1, start + Duration::from_secs(1)).unwrap().num_claimed, 0);
+        assert!(cache.get(&creator, 2, start).is_none());
+        assert!(cache.get(&Pubkey::new_unique(), 1, start).is_none());
+        assert!(cache.get(&creator, 1, start + Duration::from_secs(2)).is_none());
+
+        // A fresh fetch overwrites the entry and restarts its TTL
+        cache.insert(&creator, 1, &packet(1), start + Duration::from_secs(3));
+        assert_eq!(cache.get(&creator, 1, start + Duration::from_secs(4)).unwrap().num_claimed, 1);
+    }
+
+    #[test]
+    fn zero_ttl_caches_nothing() {
+        let cache = RedPacketCache::new(Duration::ZERO);
+        let creator = Pubkey::new_unique();
+        let now = Instant::now();
+        cache.insert(&creator, 1, &packet(0), now);
+        assert!(cache.get(&creator, 1, now).is_none());
+    }
+
+    #[test]
+    fn mints_stay_cached_even_with_caching_off() {
+        let cache = RedPacketCache::new(Duration::ZERO);
+        let token = SplToken {
+            mint: Pubkey::new_unique(),
+            token_program: *crate::consts::TOKEN_PROGRAM_ID,
+            decimals: 6,
+        };
+        assert!(cache.get_mint(&token.mint).is_none());
+        cache.insert_mint(&token);
+        assert_eq!(cache.get_mint(&token.mint), Some(token));
+    }
+}
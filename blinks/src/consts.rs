@@ -1,41 +1,238 @@
 use solana_sdk::pubkey::Pubkey;
 use std::sync::LazyLock;
 
-pub const DEFAULT_RPC_URL: &str = "https://api.devnet.solana.com";
+/// The cluster the server targets, from CLUSTER: `devnet` (the default)
+/// or `mainnet`, so one binary serves either
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cluster {
+    Devnet,
+    Mainnet,
+}
+
+impl Cluster {
+    /// Parse CLUSTER. Unset is devnet.
+    pub fn parse(raw: Option<&str>) -> Result<Self, String> {
+        match raw.map(str::trim) {
+            None | Some("") | Some("devnet") => Ok(Cluster::Devnet),
+            Some("mainnet") | Some("mainnet-beta") => Ok(Cluster::Mainnet),
+            Some(raw) => Err(format!("Invalid CLUSTER: {raw:?}")),
+        }
+    }
+
+    /// RPC endpoint used when RPC_URL is unset
+    pub fn default_rpc_url(self) -> &'static str {
+        match self {
+            Cluster::Devnet => "https://api.devnet.solana.com",
+            Cluster::Mainnet => "https://api.mainnet-beta.solana.com",
+        }
+    }
+
+    /// CAIP-2 id of the chain, sent in X-Blockchain-Ids
+    pub fn blockchain_id(self) -> &'static str {
+        match self {
+            Cluster::Devnet => "solana:EtWTRABZaYq6iMfeYKouRu166VU2xqa1",
+            Cluster::Mainnet => "solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp",
+        }
+    }
+
+    /// Suffix of blink descriptions, so devnet packets aren't mistaken for
+    /// real funds
+    pub fn label(self) -> &'static str {
+        match self {
+            Cluster::Devnet => " (devnet)",
+            Cluster::Mainnet => "",
+        }
+    }
+
+    /// An address from its env var (`raw`), else the devnet deployment's
+    /// `devnet`. Mainnet has no default: the program's mainnet build reads
+    /// its addresses from the build environment too.
+    pub fn address(self, var: &str, raw: Option<&str>, devnet: &str) -> Result<Pubkey, String> {
+        match (raw.map(str::trim), self) {
+            (None | Some(""), Cluster::Devnet) => Ok(devnet.parse().expect("hardcoded devnet address is valid")),
+            (None | Some(""), Cluster::Mainnet) => Err(format!("{var} must be set when CLUSTER=mainnet")),
+            (Some(raw), _) => raw.parse().map_err(|_| format!("Invalid {var}: {raw:?}")),
+        }
+    }
+}
+
+pub static CLUSTER: LazyLock<Cluster> = LazyLock::new(|| {
+    Cluster::parse(std::env::var("CLUSTER").ok().as_deref()).unwrap_or_else(|err| panic!("{err}"))
+});
 pub const DEFAULT_HOST: &str = "0.0.0.0";
 pub const DEFAULT_PORT: &str = "3001";
+/// Requests per minute per client IP on the RPC-backed routes
+pub const DEFAULT_RATE_LIMIT_PER_MINUTE: u32 = 60;
+/// How long a decoded red packet is served from cache (milliseconds)
+pub const DEFAULT_RED_PACKET_CACHE_TTL_MS: u64 = 2_000;
+/// Compute unit price blink transactions offer (micro-lamports per unit)
+pub const DEFAULT_PRIORITY_FEE_MICRO_LAMPORTS: u64 = 1_000;
+
+/// Compute unit limits blink transactions request, with headroom over what
+/// each program instruction uses. ATA_COMPUTE_UNITS is added for each ATA
+/// create or token account close riding along.
+pub const CREATE_COMPUTE_UNITS: u32 = 60_000;
+pub const CLAIM_COMPUTE_UNITS: u32 = 80_000;
+pub const CLOSE_COMPUTE_UNITS: u32 = 50_000;
+pub const ATA_COMPUTE_UNITS: u32 = 30_000;
 
 #[allow(dead_code)]
 pub const CHAIN_PARAM: &str = "_chain";
 
-/// Red packet program ID
+/// Red packet program ID: PROGRAM_ID, or the devnet deployment's
 pub static PROGRAM_ID: LazyLock<Pubkey> = LazyLock::new(|| {
-    "CeAkHjhJzgrwbg8QWQ8tx6h5UxMZVKuGBeEDYczbc6Gz"
+    CLUSTER
+        .address(
+            "PROGRAM_ID",
+            std::env::var("PROGRAM_ID").ok().as_deref(),
+            "CeAkHjhJzgrwbg8QWQ8tx6h5UxMZVKuGBeEDYczbc6Gz",
+        )
+        .unwrap_or_else(|err| panic!("{err}"))
+});
+
+/// Bootstrap treasury admin (the program's ADMIN): PROGRAM_ADMIN, or the
+/// devnet deployment's. The only wallet the withdraw-fees action builds
+/// transactions for.
+pub static ADMIN: LazyLock<Pubkey> = LazyLock::new(|| {
+    CLUSTER
+        .address(
+            "PROGRAM_ADMIN",
+            std::env::var("PROGRAM_ADMIN").ok().as_deref(),
+            "HyBxuaafzKP6k4zkEDUp4LrZctS9mJVNUEEJBmp9cp7L",
+        )
+        .unwrap_or_else(|err| panic!("{err}"))
+});
+
+/// SPL Token and Token-2022 (the red packet program accepts either) and
+/// the Associated Token Account program
+pub static TOKEN_PROGRAM_ID: LazyLock<Pubkey> = LazyLock::new(|| {
+    "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+        .parse()
+        .expect("hardcoded token program ID is valid")
+});
+pub static TOKEN_2022_PROGRAM_ID: LazyLock<Pubkey> = LazyLock::new(|| {
+    "TokenzQdBNbLqP5VEhdkAS6EPFLC8PHnSnTg9gj8zfQ"
         .parse()
-        .expect("hardcoded program ID is valid")
+        .expect("hardcoded token-2022 program ID is valid")
+});
+pub static ASSOCIATED_TOKEN_PROGRAM_ID: LazyLock<Pubkey> = LazyLock::new(|| {
+    "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"
+        .parse()
+        .expect("hardcoded associated token program ID is valid")
+});
+/// Wrapped SOL: SPL packets of this mint can pay out as native SOL
+pub static NATIVE_MINT: LazyLock<Pubkey> = LazyLock::new(|| {
+    "So11111111111111111111111111111111111111112"
+        .parse()
+        .expect("hardcoded native mint is valid")
 });
 
 /// PDA seeds
 pub const SEED_PREFIX: &[u8] = b"redpacket";
 pub const VAULT_SEED: &[u8] = b"vault";
 pub const TREASURY_SEED: &[u8] = b"treasury";
+pub const TREASURY_VAULT_SEED: &[u8] = b"treasury_vault";
+pub const RECEIPT_SEED: &[u8] = b"receipt";
 
 /// Sentinel "mint" for native SOL treasury PDA derivation
 pub const NATIVE_SOL_MINT: [u8; 32] = [0xFF; 32];
 
 /// Token types
+pub const TOKEN_TYPE_SPL: u8 = 0;
 pub const TOKEN_TYPE_SOL: u8 = 1;
 
+/// Longest claim password the program accepts (bytes)
+pub const MAX_SECRET_LEN: usize = 64;
+/// Longest creator memo the program accepts (UTF-8 bytes)
+pub const MAX_MEMO_LEN: usize = 64;
+
+/// Red packet account discriminator and fixed header size (before amounts/claimers)
+pub const REDPACKET_DISCRIMINATOR: u8 = 1;
+pub const REDPACKET_BASE_SIZE: usize = 405;
+/// amount + claimer + claimed_at per slot of a regular (not large or vesting) packet
+pub const PER_RECIPIENT_SIZE: usize = 48;
+
+/// Treasury account discriminator and the size of the oldest layout still
+/// on chain (later fields are read as their implicit defaults)
+pub const TREASURY_DISCRIMINATOR: u8 = 2;
+pub const TREASURY_LEGACY_SIZE: usize = 43;
+
+/// Red packet layout version byte (offset 79); older packets need `migrate`
+pub const REDPACKET_VERSION: u8 = 16;
+/// Program major version (constants.rs PROGRAM_VERSION) these instruction
+/// layouts are built for; the server won't start against another
+pub const PROGRAM_MAJOR_VERSION: u8 = 2;
+pub const REDPACKET_VERSION_OFFSET: usize = 79;
+
+/// RedPacket flags byte (offset 80)
+pub const FLAG_ALLOW_REPEAT: u8 = 1 << 0;
+pub const FLAG_NO_SELF_CLAIM: u8 = 1 << 1;
+/// Claims are deduped by receipt PDAs; there is no claimers array
+pub const FLAG_LARGE: u8 = 1 << 2;
+/// Set by the program on random packets whose amounts it draws at claim time
+pub const FLAG_RANDOM_ONCHAIN: u8 = 1 << 3;
+/// Set by the program once a commit-reveal packet's creator reveals its secret
+pub const FLAG_REVEALED: u8 = 1 << 4;
+/// Create only: the creator may claim their own packet, which the program
+/// otherwise marks FLAG_NO_SELF_CLAIM
+pub const FLAG_CREATOR_CAN_CLAIM: u8 = 1 << 5;
+/// Set by the program once finalize pays a lucky packet's jackpot
+pub const FLAG_LUCKY_PAID: u8 = 1 << 6;
+/// Each slot unlocks linearly from vesting_start to expires_at; claimers
+/// come back for what has unlocked since
+pub const FLAG_VESTING: u8 = 1 << 7;
+
 /// Split modes
 pub const SPLIT_EVEN: u8 = 0;
 pub const SPLIT_RANDOM: u8 = 1;
+/// Fixed unequal amounts from per-slot weights in basis points
+pub const SPLIT_WEIGHTED: u8 = 2;
+/// Weighted splits' weights must sum to this
+pub const WEIGHT_TOTAL_BPS: u32 = 10_000;
+/// Random amounts plus a jackpot for one randomly chosen slot
+pub const SPLIT_LUCKY: u8 = 3;
+/// Claims enter a draw; finalize pays the whole pot to one entrant
+pub const SPLIT_RAFFLE: u8 = 4;
 
 /// Fee: 0.1% = 10 basis points
-pub const FEE_RATE_BPS: u64 = 10;
+/// Default treasury fee; each treasury stores its own fee_bps
+pub const FEE_RATE_BPS: u16 = 10;
 pub const FEE_DENOMINATOR: u64 = 10_000;
 
 /// Max recipients per red packet
-pub const MAX_RECIPIENTS: u8 = 20;
+pub const MAX_RECIPIENTS: u8 = 100;
+/// Largest pot the program accepts (base units), as AmountTooLarge
+pub const MAX_TOTAL_AMOUNT: u64 = u64::MAX / 2;
+
+/// Furthest expiry the program accepts, from the current time (365 days).
+/// Also the default max_expiry_secs of a treasury, which may lower it.
+pub const MAX_EXPIRY_SECONDS: i64 = 365 * 24 * 60 * 60;
+/// Default min_expiry_secs of a treasury
+pub const MIN_EXPIRY_SECONDS: i64 = 60;
 
 /// Icon URL for blink cards
 pub const ICON_URL: &str = "https://redpackets.space/red-packet-icon.svg";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cluster_defaults_to_devnet_and_mainnet_needs_its_addresses() {
+        assert_eq!(Cluster::parse(None), Ok(Cluster::Devnet));
+        assert_eq!(Cluster::parse(Some("mainnet")), Ok(Cluster::Mainnet));
+        assert!(Cluster::parse(Some("testnet")).is_err());
+        assert_eq!(Cluster::Mainnet.blockchain_id(), "solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp");
+
+        let devnet_id = "CeAkHjhJzgrwbg8QWQ8tx6h5UxMZVKuGBeEDYczbc6Gz";
+        assert_eq!(Cluster::Devnet.address("PROGRAM_ID", None, devnet_id).unwrap().to_string(), devnet_id);
+        assert_eq!(*PROGRAM_ID, devnet_id.parse().unwrap());
+        assert!(Cluster::Mainnet.address("PROGRAM_ID", Some(""), devnet_id).is_err());
+        let mainnet_id = Pubkey::new_unique();
+        assert_eq!(
+            Cluster::Mainnet.address("PROGRAM_ID", Some(&mainnet_id.to_string()), devnet_id),
+            Ok(mainnet_id)
+        );
+        assert!(Cluster::Devnet.address("PROGRAM_ID", Some("not-a-key"), devnet_id).is_err());
+    }
+}
@@ -1,15 +1,102 @@
 use axum::http::header;
-use axum::http::{HeaderValue, Method};
-use tower_http::cors::CorsLayer;
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::query::UNMIGRATED_PACKETS_HEADER;
+use crate::spec::{ACTION_VERSION_HEADER, BLOCKCHAIN_IDS_HEADER};
+
+/// Parse ALLOWED_ORIGINS (comma-separated). `None` when the variable is
+/// unset, which keeps CORS open to any origin for development.
+pub fn parse_allowed_origins(raw: Option<&str>) -> Result<Option<Vec<HeaderValue>>, String> {
+    let Some(raw) = raw else {
+        return Ok(None);
+    };
+
+    raw.split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            HeaderValue::from_str(origin).map_err(|_| format!("Invalid origin in ALLOWED_ORIGINS: {origin:?}"))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+/// Blinks are embedded cross-origin by wallets, so without an allowlist any
+/// origin may call the actions. With one, other origins get no CORS headers.
+pub fn actions_cors(allowed_origins: Option<Vec<HeaderValue>>) -> CorsLayer {
+    let allow_origin = match allowed_origins {
+        Some(origins) => AllowOrigin::list(origins),
+        None => AllowOrigin::from(Any),
+    };
 
-pub fn actions_cors() -> CorsLayer {
     CorsLayer::new()
-        .allow_origin(HeaderValue::from_static("*"))
+        .allow_origin(allow_origin)
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::OPTIONS])
         .allow_headers([
             header::CONTENT_TYPE,
             header::AUTHORIZATION,
             header::CONTENT_ENCODING,
             header::ACCEPT_ENCODING,
+            HeaderName::from_static(ACTION_VERSION_HEADER),
+            HeaderName::from_static(BLOCKCHAIN_IDS_HEADER),
+        ])
+        // Wallets read the negotiated version and chain off the response,
+        // and the listing's count of packets it left out
+        .expose_headers([
+            HeaderName::from_static(ACTION_VERSION_HEADER),
+            HeaderName::from_static(BLOCKCHAIN_IDS_HEADER),
+            HeaderName::from_static(UNMIGRATED_PACKETS_HEADER),
         ])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    async fn allow_origin_header(cors: CorsLayer, origin: &str) -> Option<HeaderValue> {
+        let app = Router::new().route("/", get(|| async { "ok" })).layer(cors);
+        let res = app
+            .oneshot(Request::get("/").header(header::ORIGIN, origin).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        res.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).cloned()
+    }
+
+    #[test]
+    fn parses_comma_separated_origins() {
+        assert_eq!(parse_allowed_origins(None).unwrap(), None);
+        assert_eq!(
+            parse_allowed_origins(Some(" https://a.example, https://b.example ,")).unwrap(),
+            Some(vec![
+                HeaderValue::from_static("https://a.example"),
+                HeaderValue::from_static("https://b.example"),
+            ])
+        );
+        assert!(parse_allowed_origins(Some("https://a.example,bad\norigin")).is_err());
+    }
+
+    #[tokio::test]
+    async fn disallowed_origin_gets_no_cors_headers() {
+        let allowlist = || parse_allowed_origins(Some("https://dapp.example")).unwrap();
+
+        assert_eq!(
+            allow_origin_header(actions_cors(allowlist()), "https://dapp.example").await,
+            Some(HeaderValue::from_static("https://dapp.example"))
+        );
+        assert_eq!(allow_origin_header(actions_cors(allowlist()), "https://evil.example").await, None);
+    }
+
+    #[tokio::test]
+    async fn unset_allowlist_allows_any_origin() {
+        assert_eq!(
+            allow_origin_header(actions_cors(None), "https://anywhere.example").await,
+            Some(HeaderValue::from_static("*"))
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 
@@ -9,10 +9,19 @@ pub enum AppError {
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
     #[error("Not found: {0}")]
     NotFound(String),
 
-    #[error("RPC error: {0}")]
+    #[error("Too many requests, retry in {0}s")]
+    RateLimited(u64),
+
+    /// The RPC node failed us (a 502), as opposed to a missing account's
+    /// NotFound. A failed simulation or confirmation names the program
+    /// error, if any.
+    #[error("RPC error: {}", crate::program_error::describe_client_error(.0).unwrap_or_else(|| .0.to_string()))]
     Rpc(Box<solana_client::client_error::ClientError>),
 
     #[error("Serialization error: {0}")]
@@ -25,18 +34,55 @@ impl From<solana_client::client_error::ClientError> for AppError {
     }
 }
 
+impl AppError {
+    /// The variant, as the `kind` label of the error metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::NotFound(_) => "not_found",
+            AppError::RateLimited(_) => "rate_limited",
+            AppError::Rpc(_) => "rpc",
+            AppError::Serialization(_) => "serialization",
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let status = match &self {
             AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::FORBIDDEN,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
-            AppError::Rpc(_) | AppError::Serialization(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Rpc(_) => StatusCode::BAD_GATEWAY,
+            AppError::Serialization(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
         let body = ActionError {
             message: self.to_string(),
         };
 
-        (status, Json(body)).into_response()
+        let mut response = (status, Json(body)).into_response();
+        if let AppError::RateLimited(secs) = self {
+            response.headers_mut().insert(header::RETRY_AFTER, secs.into());
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::client_error::{ClientError, ClientErrorKind};
+
+    #[test]
+    fn rpc_failures_are_bad_gateways_and_missing_accounts_not_found() {
+        let rpc: ClientError = ClientErrorKind::Custom("connection reset".into()).into();
+        assert_eq!(AppError::from(rpc).into_response().status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(
+            AppError::NotFound("Red packet not found on chain".into()).into_response().status(),
+            StatusCode::NOT_FOUND
+        );
     }
 }
@@ -0,0 +1,216 @@
+// Nothing in the server reads transaction logs yet; these are for indexers
+// and tooling built on this crate.
+#![allow(dead_code)]
+
+use base64::Engine;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::consts::PROGRAM_ID;
+
+/// Binary events the program logs with sol_log_data under its `logging`
+/// feature (programs/solana-redpacket/src/event.rs): a tag byte, then the
+/// fields in order, integers little-endian and addresses as 32 raw bytes.
+/// Keep the layouts here in step with the program's.
+pub const CREATE_EVENT_TAG: u8 = 0;
+pub const CLAIM_EVENT_TAG: u8 = 1;
+pub const CLOSE_EVENT_TAG: u8 = 2;
+
+const CREATE_EVENT_LEN: usize = 59;
+const CLAIM_EVENT_LEN: usize = 74;
+const CLOSE_EVENT_LEN: usize = 41;
+
+/// Prefix the runtime gives each sol_log_data line
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// Under the program's `cpi-events` feature, the same bytes also arrive as
+/// the data of an emit_event inner instruction (this discriminator, then the
+/// event), which the program invokes on itself when a create, claim or close
+/// ends with the event authority and the program accounts
+pub const EMIT_EVENT_DISCRIMINATOR: u8 = 22;
+pub const EVENT_AUTHORITY_SEED: &[u8] = b"event_authority";
+
+/// The PDA emit_event requires as signer; the program hardcodes it
+pub fn find_event_authority_pda() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[EVENT_AUTHORITY_SEED], &PROGRAM_ID)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramEvent {
+    Create {
+        creator: Pubkey,
+        id: u64,
+        total: u64,
+        num_recipients: u8,
+        split_mode: u8,
+        expires_at: i64,
+    },
+    Claim {
+        red_packet: Pubkey,
+        claimer: Pubkey,
+        slot: u8,
+        amount: u64,
+    },
+    /// Also logged for cancels. `refunded` excludes rent, and is 0 when the
+    /// leftover was burned or donated instead.
+    Close { red_packet: Pubkey, refunded: u64 },
+}
+
+fn pubkey_at(data: &[u8], offset: usize) -> Pubkey {
+    Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// One event's bytes, as logged. None for an unknown tag or the wrong length.
+pub fn decode_event(data: &[u8]) -> Option<ProgramEvent> {
+    match (data.first()?, data.len()) {
+        (&CREATE_EVENT_TAG, CREATE_EVENT_LEN) => Some(ProgramEvent::Create {
+            creator: pubkey_at(data, 1),
+            id: u64_at(data, 33),
+            total: u64_at(data, 41),
+            num_recipients: data[49],
+            split_mode: data[50],
+            expires_at: u64_at(data, 51) as i64,
+        }),
+        (&CLAIM_EVENT_TAG, CLAIM_EVENT_LEN) => Some(ProgramEvent::Claim {
+            red_packet: pubkey_at(data, 1),
+            claimer: pubkey_at(data, 33),
+            slot: data[65],
+            amount: u64_at(data, 66),
+        }),
+        (&CLOSE_EVENT_TAG, CLOSE_EVENT_LEN) => Some(ProgramEvent::Close {
+            red_packet: pubkey_at(data, 1),
+            refunded: u64_at(data, 33),
+        }),
+        _ => None,
+    }
+}
+
+/// The event on a `Program data: <base64>` log line. Other programs in the
+/// transaction may log data too, so only lines from the red packet program's
+/// own invocation should be passed in.
+pub fn parse_log_line(line: &str) -> Option<ProgramEvent> {
+    let encoded = line.strip_prefix(PROGRAM_DATA_PREFIX)?;
+    // sol_log_data logs each slice as its own base64 field; events are one
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    decode_event(&data)
+}
+
+/// The event carried by an inner instruction's data. Only the program can
+/// sign for the event authority, so an emit_event that succeeded is the
+/// program's own, wherever it appears in the transaction; check the inner
+/// instruction's program id is PROGRAM_ID before passing its data in.
+pub fn parse_inner_instruction(data: &[u8]) -> Option<ProgramEvent> {
+    match data.split_first()? {
+        (&EMIT_EVENT_DISCRIMINATOR, event) => decode_event(event),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What the program's event emitters write
+    fn encode(event: &ProgramEvent) -> Vec<u8> {
+        let mut data = Vec::new();
+        match event {
+            ProgramEvent::Create { creator, id, total, num_recipients, split_mode, expires_at } => {
+                data.push(CREATE_EVENT_TAG);
+                data.extend_from_slice(creator.as_ref());
+                data.extend_from_slice(&id.to_le_bytes());
+                data.extend_from_slice(&total.to_le_bytes());
+                data.push(*num_recipients);
+                data.push(*split_mode);
+                data.extend_from_slice(&expires_at.to_le_bytes());
+            }
+            ProgramEvent::Claim { red_packet, claimer, slot, amount } => {
+                data.push(CLAIM_EVENT_TAG);
+                data.extend_from_slice(red_packet.as_ref());
+                data.extend_from_slice(claimer.as_ref());
+                data.push(*slot);
+                data.extend_from_slice(&amount.to_le_bytes());
+            }
+            ProgramEvent::Close { red_packet, refunded } => {
+                data.push(CLOSE_EVENT_TAG);
+                data.extend_from_slice(red_packet.as_ref());
+                data.extend_from_slice(&refunded.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    fn events() -> [ProgramEvent; 3] {
+        [
+            ProgramEvent::Create {
+                creator: Pubkey::new_unique(),
+                id: u64::MAX - 1,
+                total: 1_000_000_000,
+                num_recipients: 3,
+                split_mode: 1,
+                expires_at: -1,
+            },
+            ProgramEvent::Claim {
+                red_packet: Pubkey::new_unique(),
+                claimer: Pubkey::new_unique(),
+                slot: 254,
+                amount: 333_333_334,
+            },
+            ProgramEvent::Close { red_packet: Pubkey::new_unique(), refunded: 42 },
+        ]
+    }
+
+    #[test]
+    fn events_round_trip_at_the_program_lengths() {
+        for (event, len) in events().iter().zip([CREATE_EVENT_LEN, CLAIM_EVENT_LEN, CLOSE_EVENT_LEN]) {
+            let data = encode(event);
+            assert_eq!(data.len(), len);
+            assert_eq!(decode_event(&data).as_ref(), Some(event));
+        }
+    }
+
+    #[test]
+    fn log_lines_decode_and_strangers_are_skipped() {
+        for event in events() {
+            let line = format!(
+                "Program data: {}",
+                base64::engine::general_purpose::STANDARD.encode(encode(&event))
+            );
+            assert_eq!(parse_log_line(&line), Some(event));
+        }
+
+        let claim = encode(&events()[1]);
+        assert_eq!(decode_event(&claim[..CLAIM_EVENT_LEN - 1]), None);
+        assert_eq!(decode_event(&[9; CLOSE_EVENT_LEN]), None);
+        assert_eq!(decode_event(&[]), None);
+        assert_eq!(parse_log_line("Program log: RP_CLAIM id=1"), None);
+        assert_eq!(parse_log_line("Program data: not base64!"), None);
+    }
+
+    #[test]
+    fn inner_instructions_decode_after_the_emit_event_discriminator() {
+        for event in events() {
+            let mut data = vec![EMIT_EVENT_DISCRIMINATOR];
+            data.extend(encode(&event));
+            assert_eq!(parse_inner_instruction(&data), Some(event));
+        }
+
+        // A claim instruction (discriminator 1) isn't an event
+        let mut claim = vec![1];
+        claim.extend(encode(&events()[1]));
+        assert_eq!(parse_inner_instruction(&claim), None);
+        assert_eq!(parse_inner_instruction(&[EMIT_EVENT_DISCRIMINATOR]), None);
+        assert_eq!(parse_inner_instruction(&[]), None);
+    }
+
+    #[test]
+    fn event_authority_matches_the_program_constant() {
+        let (authority, bump) = find_event_authority_pda();
+        assert_eq!(authority.to_string(), "BvGRmgiZL6kgHF6znJSnFSRU3bqU1mmCgeAWxicMAzLq");
+        assert_eq!(bump, 254);
+    }
+}
@@ -1,15 +1,25 @@
 mod actions;
+mod cache;
 mod consts;
 mod cors;
 mod error;
+mod events;
+mod metrics;
 mod program;
+mod program_error;
+mod query;
+mod rate_limit;
 mod router;
 mod spec;
 mod state;
 
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::message::Message;
+use solana_sdk::signature::Signer;
+use solana_sdk::transaction::Transaction;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::signal;
 use tracing_subscriber::EnvFilter;
@@ -22,32 +32,117 @@ async fn main() {
         .with_env_filter(EnvFilter::from_default_env().add_directive("info".parse().unwrap()))
         .init();
 
-    let rpc_url = std::env::var("RPC_URL").unwrap_or_else(|_| consts::DEFAULT_RPC_URL.into());
+    let cluster = *consts::CLUSTER;
+    let rpc_url = std::env::var("RPC_URL").unwrap_or_else(|_| cluster.default_rpc_url().into());
     let host = std::env::var("HOST").unwrap_or_else(|_| consts::DEFAULT_HOST.into());
     let port = std::env::var("PORT").unwrap_or_else(|_| consts::DEFAULT_PORT.into());
     let base_url = std::env::var("BASE_URL").unwrap_or_else(|_| format!("http://{host}:{port}"));
     let bind_addr = format!("{host}:{port}");
+    let allowed_origins = cors::parse_allowed_origins(std::env::var("ALLOWED_ORIGINS").ok().as_deref())
+        .expect("Invalid ALLOWED_ORIGINS");
+    let rate_limit = rate_limit::parse_rate_limit(
+        std::env::var("RATE_LIMIT_PER_MINUTE").ok().as_deref(),
+        consts::DEFAULT_RATE_LIMIT_PER_MINUTE,
+    )
+    .expect("Invalid RATE_LIMIT_PER_MINUTE");
+    let cache_ttl = cache::parse_cache_ttl(
+        std::env::var("RED_PACKET_CACHE_TTL_MS").ok().as_deref(),
+        Duration::from_millis(consts::DEFAULT_RED_PACKET_CACHE_TTL_MS),
+    )
+    .expect("Invalid RED_PACKET_CACHE_TTL_MS");
+    let simulate = actions::parse_simulate(std::env::var("SIMULATE_TRANSACTIONS").ok().as_deref())
+        .expect("Invalid SIMULATE_TRANSACTIONS");
+    let priority_fee = actions::parse_priority_fee(
+        std::env::var("PRIORITY_FEE_MICRO_LAMPORTS").ok().as_deref(),
+        consts::DEFAULT_PRIORITY_FEE_MICRO_LAMPORTS,
+    )
+    .expect("Invalid PRIORITY_FEE_MICRO_LAMPORTS");
+    let relayer = actions::parse_relayer_keypair(std::env::var("RELAYER_KEYPAIR").ok().as_deref())
+        .expect("Invalid RELAYER_KEYPAIR")
+        .map(Arc::new);
 
+    tracing::info!("Cluster: {cluster:?}, program {}, admin {}", *consts::PROGRAM_ID, *consts::ADMIN);
     tracing::info!("RPC endpoint: {rpc_url}");
     tracing::info!("Base URL: {base_url}");
     tracing::info!("Listening on {bind_addr}");
+    match &allowed_origins {
+        Some(origins) => tracing::info!("CORS allowlist: {} origin(s)", origins.len()),
+        None => tracing::info!("CORS: any origin (ALLOWED_ORIGINS unset)"),
+    }
+    match rate_limit {
+        0 => tracing::info!("Rate limit: off"),
+        n => tracing::info!("Rate limit: {n} requests/minute per IP"),
+    }
+    tracing::info!("Red packet cache TTL: {}ms", cache_ttl.as_millis());
+    tracing::info!("Transaction simulation: {}", if simulate { "on" } else { "off" });
+    match priority_fee {
+        0 => tracing::info!("Priority fee: off"),
+        n => tracing::info!("Priority fee: {n} micro-lamports/CU"),
+    }
+    match &relayer {
+        Some(relayer) => tracing::info!("Relayer: {} sponsors claims sent with relay=1", relayer.pubkey()),
+        None => tracing::info!("Relayer: off (RELAYER_KEYPAIR unset)"),
+    }
 
-    let rpc = Arc::new(RpcClient::new_with_commitment(
-        rpc_url,
-        CommitmentConfig::confirmed(),
-    ));
-    let app = router::build_router(rpc, base_url);
+    let metrics = Arc::new(metrics::Metrics::new());
+    let rpc = Arc::new(metrics::metered_rpc_client(rpc_url, metrics.clone()));
+    match fetch_program_version(&rpc).await {
+        Ok(version) => {
+            if let Err(err) = program::check_program_version(&version) {
+                panic!("{err}");
+            }
+            tracing::info!("Program version: {version}");
+            if version.layout != consts::REDPACKET_VERSION {
+                tracing::warn!(
+                    "The program writes layout v{}, but this server decodes v{}",
+                    version.layout,
+                    consts::REDPACKET_VERSION
+                );
+            }
+        }
+        // An RPC outage, or a deployment from before get_version
+        Err(err) => tracing::warn!("Couldn't read the program version: {err}"),
+    }
+
+    let app = router::build_router(router::RouterConfig {
+        rpc,
+        base_url,
+        allowed_origins,
+        rate_limiter: rate_limit::RateLimiter::new(rate_limit),
+        red_packet_cache: Arc::new(cache::RedPacketCache::new(cache_ttl)),
+        simulate,
+        priority_fee,
+        relayer,
+        metrics,
+    });
 
     let listener = TcpListener::bind(&bind_addr)
         .await
         .expect("Failed to bind address");
 
-    axum::serve(listener, app)
+    // Connect info gives the rate limiter each client's IP
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .with_graceful_shutdown(shutdown_signal())
         .await
         .expect("Server error");
 }
 
+/// Simulate get_version against the configured RPC. The admin wallet is
+/// the fee payer, as a funded account every cluster running the program
+/// should have; a simulation charges it nothing.
+async fn fetch_program_version(rpc: &RpcClient) -> Result<program::ProgramVersion, error::AppError> {
+    let blockhash = rpc.get_latest_blockhash().await?;
+    let msg = Message::new_with_blockhash(
+        &[program::build_get_version_instruction()],
+        Some(&consts::ADMIN),
+        &blockhash,
+    );
+    let return_data = actions::simulate_tx(rpc, &Transaction::new_unsigned(msg))
+        .await?
+        .ok_or_else(|| error::AppError::BadRequest("get_version returned no data".into()))?;
+    program::decode_program_version(&return_data)
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -0,0 +1,282 @@
+use async_trait::async_trait;
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use solana_client::client_error;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::RpcClientConfig;
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use solana_rpc_client::http_sender::HttpSender;
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::router::AppState;
+
+/// Upper bounds (seconds) of the latency histogram buckets. Serving a
+/// request is nearly all RPC round-trips, so they span a fast read to a slow
+/// simulation.
+const LATENCY_BUCKETS: [f64; 8] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Action path and "get" (metadata) or "post" (execute)
+type Key = (&'static str, &'static str);
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations at or under each of LATENCY_BUCKETS
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    /// The `_bucket`, `_sum` and `_count` series of `name` for `labels`
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        for (n, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {n}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    requests: BTreeMap<Key, u64>,
+    errors: BTreeMap<(&'static str, &'static str, &'static str), u64>,
+    latency: BTreeMap<Key, Histogram>,
+    /// Route pattern, HTTP method and status
+    route_requests: BTreeMap<(String, String, u16), u64>,
+    route_latency: BTreeMap<(String, String), Histogram>,
+    /// RPC method name, e.g. "getAccountInfo"
+    rpc_errors: BTreeMap<String, u64>,
+    rpc_latency: BTreeMap<String, Histogram>,
+}
+
+/// Per-action request counts, error counts by AppError variant and latency,
+/// the same per route for every rate-limited route, and the latency of each
+/// RPC call, served at /metrics in the Prometheus text format. Actions are
+/// counted only when registered and routes by their pattern, so labels stay
+/// bounded whatever paths clients ask for.
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<Counters>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one served `method` request to `action`, taking `elapsed`
+    pub fn observe<T>(
+        &self,
+        action: &'static str,
+        method: &'static str,
+        elapsed: Duration,
+        result: &Result<T, AppError>,
+    ) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.requests.entry((action, method)).or_default() += 1;
+        if let Err(err) = result {
+            *counters.errors.entry((action, method, err.kind())).or_default() += 1;
+        }
+        counters.latency.entry((action, method)).or_default().observe(elapsed);
+    }
+
+    /// Count one `method` request to the `route` pattern, answered with
+    /// `status` after `elapsed`
+    pub fn observe_route(&self, route: &str, method: &str, status: u16, elapsed: Duration) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.route_requests.entry((route.into(), method.into(), status)).or_default() += 1;
+        counters.route_latency.entry((route.into(), method.into())).or_default().observe(elapsed);
+    }
+
+    /// Count one call of the RPC `method`, taking `elapsed`
+    pub fn observe_rpc(&self, method: &str, elapsed: Duration, failed: bool) {
+        let mut counters = self.counters.lock().unwrap();
+        if failed {
+            *counters.rpc_errors.entry(method.into()).or_default() += 1;
+        }
+        counters.rpc_latency.entry(method.into()).or_default().observe(elapsed);
+    }
+
+    /// Every counter, in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let counters = self.counters.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# HELP blinks_action_requests_total Action requests served.\n");
+        out.push_str("# TYPE blinks_action_requests_total counter\n");
+        for ((action, method), n) in &counters.requests {
+            let _ = writeln!(out, "blinks_action_requests_total{{action=\"{action}\",method=\"{method}\"}} {n}");
+        }
+
+        out.push_str("# HELP blinks_action_errors_total Action requests that failed, by error kind.\n");
+        out.push_str("# TYPE blinks_action_errors_total counter\n");
+        for ((action, method, kind), n) in &counters.errors {
+            let _ = writeln!(
+                out,
+                "blinks_action_errors_total{{action=\"{action}\",method=\"{method}\",kind=\"{kind}\"}} {n}"
+            );
+        }
+
+        out.push_str("# HELP blinks_action_duration_seconds Time to serve an action, RPC calls included.\n");
+        out.push_str("# TYPE blinks_action_duration_seconds histogram\n");
+        for ((action, method), histogram) in &counters.latency {
+            let labels = format!("action=\"{action}\",method=\"{method}\"");
+            histogram.render(&mut out, "blinks_action_duration_seconds", &labels);
+        }
+
+        out.push_str("# HELP blinks_http_requests_total Requests to rate-limited routes, by status.\n");
+        out.push_str("# TYPE blinks_http_requests_total counter\n");
+        for ((route, method, status), n) in &counters.route_requests {
+            let _ = writeln!(
+                out,
+                "blinks_http_requests_total{{route=\"{route}\",method=\"{method}\",status=\"{status}\"}} {n}"
+            );
+        }
+
+        out.push_str("# HELP blinks_http_duration_seconds Time to answer a rate-limited route.\n");
+        out.push_str("# TYPE blinks_http_duration_seconds histogram\n");
+        for ((route, method), histogram) in &counters.route_latency {
+            let labels = format!("route=\"{route}\",method=\"{method}\"");
+            histogram.render(&mut out, "blinks_http_duration_seconds", &labels);
+        }
+
+        out.push_str("# HELP blinks_rpc_errors_total RPC calls that failed.\n");
+        out.push_str("# TYPE blinks_rpc_errors_total counter\n");
+        for (method, n) in &counters.rpc_errors {
+            let _ = writeln!(out, "blinks_rpc_errors_total{{method=\"{method}\"}} {n}");
+        }
+
+        out.push_str("# HELP blinks_rpc_duration_seconds Time an RPC call took, failures included.\n");
+        out.push_str("# TYPE blinks_rpc_duration_seconds histogram\n");
+        for (method, histogram) in &counters.rpc_latency {
+            histogram.render(&mut out, "blinks_rpc_duration_seconds", &format!("method=\"{method}\""));
+        }
+        out
+    }
+}
+
+/// Middleware for the rate-limited routes, outside the limiter so its 429s
+/// are counted too. Routes are labelled by their pattern (the
+/// `/api/actions/{*path}` catch-all as one), never the requested path.
+pub async fn meter_routes(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let method = req.method().to_string();
+    let started = Instant::now();
+    let response = next.run(req).await;
+    state
+        .metrics
+        .observe_route(&route, &method, response.status().as_u16(), started.elapsed());
+    response
+}
+
+/// The RPC transport, timing every call into `metrics`
+struct MeteredSender {
+    inner: HttpSender,
+    metrics: Arc<Metrics>,
+}
+
+#[async_trait]
+impl RpcSender for MeteredSender {
+    async fn send(&self, request: RpcRequest, params: serde_json::Value) -> client_error::Result<serde_json::Value> {
+        let started = Instant::now();
+        let result = self.inner.send(request, params).await;
+        self.metrics
+            .observe_rpc(&request.to_string(), started.elapsed(), result.is_err());
+        result
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.inner.get_transport_stats()
+    }
+
+    fn url(&self) -> String {
+        self.inner.url()
+    }
+}
+
+/// A confirmed-commitment client on `url` whose calls are timed into `metrics`
+pub fn metered_rpc_client(url: String, metrics: Arc<Metrics>) -> RpcClient {
+    RpcClient::new_sender(
+        MeteredSender {
+            inner: HttpSender::new(url),
+            metrics,
+        },
+        RpcClientConfig::with_commitment(CommitmentConfig::confirmed()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_requests_errors_and_latency_buckets() {
+        let metrics = Metrics::new();
+        let ok: Result<(), AppError> = Ok(());
+        let bad: Result<(), AppError> = Err(AppError::BadRequest("Red packet has expired".into()));
+        metrics.observe("claim", "post", Duration::from_millis(80), &ok);
+        metrics.observe("claim", "post", Duration::from_millis(700), &bad);
+        metrics.observe("create", "get", Duration::from_secs(30), &ok);
+
+        let text = metrics.render();
+        assert!(text.contains("blinks_action_requests_total{action=\"claim\",method=\"post\"} 2\n"));
+        assert!(text.contains("blinks_action_requests_total{action=\"create\",method=\"get\"} 1\n"));
+        assert!(text.contains("blinks_action_errors_total{action=\"claim\",method=\"post\",kind=\"bad_request\"} 1\n"));
+        assert!(!text.contains("blinks_action_errors_total{action=\"create\""));
+
+        // Buckets are cumulative; over the last bound only +Inf counts it
+        let claim = "action=\"claim\",method=\"post\"";
+        assert!(text.contains(&format!("blinks_action_duration_seconds_bucket{{{claim},le=\"0.05\"}} 0\n")));
+        assert!(text.contains(&format!("blinks_action_duration_seconds_bucket{{{claim},le=\"0.1\"}} 1\n")));
+        assert!(text.contains(&format!("blinks_action_duration_seconds_bucket{{{claim},le=\"1\"}} 2\n")));
+        assert!(text.contains(&format!("blinks_action_duration_seconds_count{{{claim}}} 2\n")));
+        let create = "action=\"create\",method=\"get\"";
+        assert!(text.contains(&format!("blinks_action_duration_seconds_bucket{{{create},le=\"10\"}} 0\n")));
+        assert!(text.contains(&format!("blinks_action_duration_seconds_bucket{{{create},le=\"+Inf\"}} 1\n")));
+    }
+
+    #[test]
+    fn counts_routes_by_status_and_rpc_calls_by_method() {
+        let metrics = Metrics::new();
+        metrics.observe_route("/api/redpacket/peek", "GET", 200, Duration::from_millis(40));
+        metrics.observe_route("/api/redpacket/peek", "GET", 429, Duration::from_millis(1));
+        metrics.observe_route("/api/redpacket/peek", "GET", 200, Duration::from_millis(300));
+        metrics.observe_rpc("getAccountInfo", Duration::from_millis(90), false);
+        metrics.observe_rpc("getAccountInfo", Duration::from_secs(3), true);
+
+        let text = metrics.render();
+        let peek = "route=\"/api/redpacket/peek\",method=\"GET\"";
+        assert!(text.contains(&format!("blinks_http_requests_total{{{peek},status=\"200\"}} 2\n")));
+        assert!(text.contains(&format!("blinks_http_requests_total{{{peek},status=\"429\"}} 1\n")));
+        assert!(text.contains(&format!("blinks_http_duration_seconds_bucket{{{peek},le=\"0.05\"}} 2\n")));
+        assert!(text.contains(&format!("blinks_http_duration_seconds_count{{{peek}}} 3\n")));
+
+        assert!(text.contains("blinks_rpc_errors_total{method=\"getAccountInfo\"} 1\n"));
+        let rpc = "method=\"getAccountInfo\"";
+        assert!(text.contains(&format!("blinks_rpc_duration_seconds_bucket{{{rpc},le=\"0.1\"}} 1\n")));
+        assert!(text.contains(&format!("blinks_rpc_duration_seconds_bucket{{{rpc},le=\"5\"}} 2\n")));
+    }
+}
@@ -1,3 +1,6 @@
+use serde::Serialize;
+use solana_compute_budget_interface::ComputeBudgetInstruction;
+use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::native_token::LAMPORTS_PER_SOL;
 use solana_sdk::pubkey::Pubkey;
 
@@ -26,13 +29,107 @@ pub fn find_treasury_pda_sol() -> (Pubkey, u8) {
     Pubkey::find_program_address(&[TREASURY_SEED, &NATIVE_SOL_MINT], &PROGRAM_ID)
 }
 
+pub fn find_treasury_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TREASURY_SEED, mint.as_ref()], &PROGRAM_ID)
+}
+
+pub fn find_treasury_vault_pda(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TREASURY_VAULT_SEED, mint.as_ref()], &PROGRAM_ID)
+}
+
+/// A large packet's claim receipt for `claimer`; its existence means claimed
+pub fn find_receipt_pda(red_packet: &Pubkey, claimer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECEIPT_SEED, red_packet.as_ref(), claimer.as_ref()], &PROGRAM_ID)
+}
+
+pub fn is_token_program(program: &Pubkey) -> bool {
+    *program == *TOKEN_PROGRAM_ID || *program == *TOKEN_2022_PROGRAM_ID
+}
+
+pub fn find_associated_token_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+/// CreateIdempotent for `owner`'s ATA, paid by `payer`; a no-op when the
+/// account already exists.
+pub fn create_ata_idempotent_instruction(
+    payer: &Pubkey,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *ASSOCIATED_TOKEN_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(find_associated_token_address(owner, mint, token_program), false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(solana_sdk::system_program::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+        ],
+        data: vec![1],
+    }
+}
+
+/// Token CloseAccount: `account`'s lamports (for wSOL, the wrapped SOL too)
+/// go to `destination`; `owner` signs
+pub fn close_account_instruction(
+    account: &Pubkey,
+    destination: &Pubkey,
+    owner: &Pubkey,
+    token_program: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+        data: vec![9],
+    }
+}
+
+/// `instructions` behind a compute unit limit and, unless `micro_lamports`
+/// is 0, a compute unit price (the priority fee). The limit replaces the
+/// 200k-per-instruction default, so the fee is paid on what the
+/// transaction can actually use.
+pub fn with_compute_budget(instructions: Vec<Instruction>, unit_limit: u32, micro_lamports: u64) -> Vec<Instruction> {
+    let mut budgeted = Vec::with_capacity(instructions.len() + 2);
+    budgeted.push(ComputeBudgetInstruction::set_compute_unit_limit(unit_limit));
+    if micro_lamports > 0 {
+        budgeted.push(ComputeBudgetInstruction::set_compute_unit_price(micro_lamports));
+    }
+    budgeted.extend(instructions);
+    budgeted
+}
+
 // ============================================================
 // Instruction data builders
 // ============================================================
 
 /// Build create instruction data.
 /// Layout: [disc=0][token_type][id:u64][total_amount:u64][num_recipients:u8]
-///         [split_mode:u8][expires_at:i64][rp_bump:u8][vault_bump:u8][amounts?:u64*N]
+///         [split_mode:u8][expires_at:i64][rp_bump:u8][vault_bump:u8]
+///         [flags?:u8][options?:u8][secret_hash?:32][voucher_authority?:32]
+///         [gate_mint?:32][gate_min_amount?:u64][recipients?:32*N]
+///         [reveal_commitment?:32][reveal_deadline?:i64]
+///         [memo_len?:u8][memo?:64][vesting_start?:i64][weights?:u16*N]
+///
+/// Random splits carry no amounts: the program draws each one at claim time.
+/// Weighted splits end with one weight per slot, whatever precedes them.
+/// The flags byte (FLAG_*) is only appended when nonzero or when `recipients`
+/// or a `memo` follow it, and then the create options byte, which Blinks
+/// leaves 0 (leftovers are refunded); the program treats missing trailing
+/// fields as off.
+/// Blinks never sets a password, voucher, gate, commitment or vesting start,
+/// so assigned packets zero the first three and the rest are left out. The memo (at most MAX_MEMO_LEN bytes) is zero-padded to 64.
+#[allow(clippy::too_many_arguments)]
 pub fn build_create_data(
     id: u64,
     total_amount: u64,
@@ -41,15 +138,12 @@ pub fn build_create_data(
     expires_at: i64,
     rp_bump: u8,
     vault_bump: u8,
-    amounts: Option<&[u64]>,
+    flags: u8,
+    recipients: Option<&[Pubkey]>,
+    memo: Option<&str>,
+    weights: Option<&[u16]>,
 ) -> Vec<u8> {
-    let base_len = 30; // 1+1+8+8+1+1+8+1+1
-    let amounts_len = if split_mode == SPLIT_RANDOM {
-        8 * num_recipients as usize
-    } else {
-        0
-    };
-    let mut data = vec![0u8; base_len + amounts_len];
+    let mut data = vec![0u8; 30]; // 1+1+8+8+1+1+8+1+1
 
     data[0] = 0; // discriminator
     data[1] = TOKEN_TYPE_SOL;
@@ -61,34 +155,93 @@ pub fn build_create_data(
     data[28] = rp_bump;
     data[29] = vault_bump;
 
-    if split_mode == SPLIT_RANDOM {
-        if let Some(amounts) = amounts {
-            for (i, &amt) in amounts.iter().enumerate() {
-                let offset = 30 + i * 8;
-                data[offset..offset + 8].copy_from_slice(&amt.to_le_bytes());
-            }
+    if flags != 0 || recipients.is_some() || memo.is_some() {
+        data.push(flags);
+    }
+    if recipients.is_some() || memo.is_some() {
+        data.push(0); // create options
+    }
+    if let Some(recipients) = recipients {
+        data.extend_from_slice(&[0u8; 104]); // secret_hash, voucher_authority, gate
+        for recipient in recipients {
+            data.extend_from_slice(recipient.as_ref());
         }
     }
+    if let Some(memo) = memo {
+        let mut block = [0u8; 1 + MAX_MEMO_LEN];
+        block[0] = memo.len() as u8;
+        block[1..1 + memo.len()].copy_from_slice(memo.as_bytes());
+        data.extend_from_slice(&block);
+    }
+    for weight in weights.unwrap_or_default() {
+        data.extend_from_slice(&weight.to_le_bytes());
+    }
 
     data
 }
 
-/// Build claim instruction data: [disc=1][token_type][slot_index]
-pub fn build_claim_data(slot_index: u8) -> Vec<u8> {
-    vec![1, TOKEN_TYPE_SOL, slot_index]
+/// Build claim instruction data:
+/// [disc=1][token_type][slot_index][options?:u8][secret?]
+///
+/// `has_destination` sets the options byte's CLAIM_TO_DESTINATION bit (1): a
+/// destination account follows the claim's other accounts. `secret` is the
+/// password preimage for password-protected packets. The options byte is
+/// only written when one of them is used. Blinks never sets CLAIM_EXACT_SLOT,
+/// so the program ignores `slot_index` and picks the slot itself.
+pub fn build_claim_data(token_type: u8, slot_index: u8, has_destination: bool, secret: Option<&[u8]>) -> Vec<u8> {
+    let mut data = vec![1, token_type, slot_index];
+    if has_destination || secret.is_some() {
+        data.push(has_destination as u8);
+    }
+    if let Some(secret) = secret {
+        data.extend_from_slice(secret);
+    }
+    data
 }
 
 /// Build close instruction data: [disc=2][token_type]
-pub fn build_close_data() -> Vec<u8> {
-    vec![2, TOKEN_TYPE_SOL]
+pub fn build_close_data(token_type: u8) -> Vec<u8> {
+    vec![2, token_type]
+}
+
+/// Build cancel instruction data: [disc=7][token_type] (same accounts as close)
+pub fn build_cancel_data(token_type: u8) -> Vec<u8> {
+    vec![7, token_type]
+}
+
+/// Build withdraw-fees instruction data: [disc=4][token_type][amount:u64]
+/// (`amount` 0 withdraws everything collected)
+pub fn build_withdraw_fees_data(token_type: u8, amount: u64) -> Vec<u8> {
+    let mut data = vec![4, token_type];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data
+}
+
+/// Build peek instruction data: [disc=23] (accounts: red_packet)
+pub fn build_peek_instruction(red_packet: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *PROGRAM_ID,
+        accounts: vec![AccountMeta::new_readonly(*red_packet, false)],
+        data: vec![23],
+    }
+}
+
+/// Build get_version instruction data: [disc=25] (no accounts)
+pub fn build_get_version_instruction() -> Instruction {
+    Instruction {
+        program_id: *PROGRAM_ID,
+        accounts: vec![],
+        data: vec![25],
+    }
 }
 
 // ============================================================
 // Account deserialization
 // ============================================================
 
-/// Red packet account layout (71 + 40*N bytes, discriminator=1)
-#[derive(Debug)]
+/// Red packet account layout (405 + 48*N bytes, 405 + 56*N for vesting
+/// packets, or 405 + 8*N for large ones; discriminator=1, version=16)
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct RedPacketAccount {
     pub creator: Pubkey,
@@ -102,19 +255,122 @@ pub struct RedPacketAccount {
     pub vault_bump: u8,
     pub token_type: u8,
     pub expires_at: i64,
+    /// Fee actually charged (create + any top-ups), as recorded on-chain
+    pub fee_paid: u64,
+    /// One wallet may claim several slots
+    pub allow_repeat: bool,
+    /// The creator may not claim their own packet
+    pub no_self_claim: bool,
+    /// Claims are receipted (see `find_receipt_pda`); `claimers` stays empty
+    pub large: bool,
+    /// Random amounts are drawn at claim time; unclaimed slots read 0
+    pub random_onchain: bool,
+    /// Finalize has paid a lucky packet's jackpot
+    pub lucky_paid: bool,
+    /// sha256 of the claim password, for password-protected packets
+    pub secret_hash: Option<[u8; 32]>,
+    /// Key whose ed25519 vouchers gate claims (issued off-chain)
+    pub voucher_authority: Option<Pubkey>,
+    /// Mint claimers must hold, at least `gate_min_amount` base units of it
+    pub gate_mint: Option<Pubkey>,
+    pub gate_min_amount: u64,
+    /// Recipients were fixed at create: `claimers` holds every slot's wallet
+    pub assigned: bool,
+    /// Unix time of the create; 0 for packets migrated from before it was stored
+    pub created_at: i64,
+    /// Commit-reveal packet still waiting on its creator's reveal: claims fail
+    /// until then, and past `reveal_deadline` it can only be closed
+    pub reveal_pending: bool,
+    pub reveal_deadline: i64,
+    /// Raffle entrant drawn by finalize; the pot is paid once remaining_amount is 0
+    pub winner: Option<Pubkey>,
+    /// Slots unlock linearly from `vesting_start` to `expires_at`; `taken`
+    /// holds what each claimed slot has paid out so far
+    pub vesting: bool,
+    pub vesting_start: i64,
+    /// Whether each slot has been claimed. Claims take the lowest unclaimed
+    /// slot unless they name one, so claimed slots may have gaps.
+    pub claimed: Vec<bool>,
+    /// The creator's greeting, if they left one
+    pub memo: Option<String>,
+    /// SPL packets whose unclaimed tokens are burned at close, not refunded
+    pub burn_unclaimed: bool,
+    /// Packets whose unclaimed balance goes to the treasury at close
+    pub donate_unclaimed: bool,
+    /// The wallet the packet and vault addresses derive from. `creator`, who
+    /// closes the packet, differs from it once transfer_creator has run.
+    pub original_creator: Pubkey,
     pub amounts: Vec<u64>,
     pub claimers: Vec<Pubkey>,
+    /// Unix time each slot was first claimed: 0 while unclaimed or when it
+    /// was claimed before migration; empty on large packets
+    pub claimed_at: Vec<i64>,
+    pub taken: Vec<u64>,
+}
+
+#[cfg(test)]
+impl RedPacketAccount {
+    /// An open, even SOL packet of 1000 lamports over 2 slots that never
+    /// expires, for tests to override field by field
+    pub fn mock() -> Self {
+        let creator = Pubkey::new_unique();
+        RedPacketAccount {
+            creator,
+            id: 1,
+            total_amount: 1_000,
+            remaining_amount: 1_000,
+            num_recipients: 2,
+            num_claimed: 0,
+            split_mode: SPLIT_EVEN,
+            bump: 0,
+            vault_bump: 0,
+            token_type: TOKEN_TYPE_SOL,
+            expires_at: i64::MAX,
+            fee_paid: 0,
+            allow_repeat: false,
+            no_self_claim: false,
+            large: false,
+            random_onchain: false,
+            lucky_paid: false,
+            secret_hash: None,
+            voucher_authority: None,
+            gate_mint: None,
+            gate_min_amount: 0,
+            assigned: false,
+            created_at: 0,
+            reveal_pending: false,
+            reveal_deadline: 0,
+            winner: None,
+            vesting: false,
+            vesting_start: 0,
+            claimed: vec![],
+            memo: None,
+            burn_unclaimed: false,
+            donate_unclaimed: false,
+            original_creator: creator,
+            amounts: vec![],
+            claimers: vec![],
+            claimed_at: vec![],
+            taken: vec![],
+        }
+    }
 }
 
 pub fn decode_red_packet(data: &[u8]) -> Result<RedPacketAccount, AppError> {
-    if data.len() < 71 {
+    if data.len() < REDPACKET_BASE_SIZE {
         return Err(AppError::BadRequest("Red packet data too short".into()));
     }
 
-    if data[0] != 1 {
+    if data[0] != REDPACKET_DISCRIMINATOR {
         return Err(AppError::BadRequest("Invalid red packet discriminator".into()));
     }
 
+    if data[REDPACKET_VERSION_OFFSET] != REDPACKET_VERSION {
+        return Err(AppError::BadRequest(
+            "Red packet uses an old account layout; it must be migrated first".into(),
+        ));
+    }
+
     let creator = Pubkey::try_from(&data[1..33])
         .map_err(|_| AppError::BadRequest("Invalid creator pubkey".into()))?;
     let id = u64::from_le_bytes(data[33..41].try_into().unwrap());
@@ -127,19 +383,50 @@ pub fn decode_red_packet(data: &[u8]) -> Result<RedPacketAccount, AppError> {
     let vault_bump = data[61];
     let token_type = data[62];
     let expires_at = i64::from_le_bytes(data[63..71].try_into().unwrap());
+    let fee_paid = u64::from_le_bytes(data[71..79].try_into().unwrap());
+    let allow_repeat = data[80] & FLAG_ALLOW_REPEAT != 0;
+    let no_self_claim = data[80] & FLAG_NO_SELF_CLAIM != 0;
+    let large = data[80] & FLAG_LARGE != 0;
+    let random_onchain = data[80] & FLAG_RANDOM_ONCHAIN != 0;
+    let lucky_paid = data[80] & FLAG_LUCKY_PAID != 0;
+    let secret_hash: [u8; 32] = data[81..113].try_into().unwrap();
+    let secret_hash = (secret_hash != [0u8; 32]).then_some(secret_hash);
+    let voucher_authority: [u8; 32] = data[113..145].try_into().unwrap();
+    let voucher_authority = (voucher_authority != [0u8; 32]).then(|| Pubkey::new_from_array(voucher_authority));
+    let gate_mint: [u8; 32] = data[145..177].try_into().unwrap();
+    let gate_mint = (gate_mint != [0u8; 32]).then(|| Pubkey::new_from_array(gate_mint));
+    let gate_min_amount = u64::from_le_bytes(data[177..185].try_into().unwrap());
+    let assigned = data[185] != 0;
+    let created_at = i64::from_le_bytes(data[186..194].try_into().unwrap());
+    let reveal_pending = data[80] & FLAG_REVEALED == 0 && data[194..226] != [0u8; 32];
+    let reveal_deadline = i64::from_le_bytes(data[226..234].try_into().unwrap());
+    let winner: [u8; 32] = data[234..266].try_into().unwrap();
+    let winner = (winner != [0u8; 32]).then(|| Pubkey::new_from_array(winner));
+    let vesting = data[80] & FLAG_VESTING != 0;
+    let vesting_start = i64::from_le_bytes(data[266..274].try_into().unwrap());
+    let claimed = (0..num_recipients as usize)
+        .map(|i| data[274 + i / 8] & (1 << (i % 8)) != 0)
+        .collect();
+    let memo_len = (data[306] as usize).min(MAX_MEMO_LEN);
+    let memo = (memo_len > 0).then(|| String::from_utf8_lossy(&data[307..307 + memo_len]).into_owned());
+    let burn_unclaimed = data[371] != 0;
+    let donate_unclaimed = data[372] != 0;
+    let original_creator = Pubkey::new_from_array(data[373..405].try_into().unwrap());
 
     let mut amounts = Vec::with_capacity(num_recipients as usize);
     for i in 0..num_recipients as usize {
-        let offset = 71 + i * 8;
+        let offset = REDPACKET_BASE_SIZE + i * 8;
         if offset + 8 > data.len() {
             break;
         }
         amounts.push(u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()));
     }
 
-    let claimers_offset = 71 + num_recipients as usize * 8;
-    let mut claimers = Vec::with_capacity(num_recipients as usize);
-    for i in 0..num_recipients as usize {
+    let claimers_offset = REDPACKET_BASE_SIZE + num_recipients as usize * 8;
+    // Large packets end after the amounts
+    let num_claimers = if large { 0 } else { num_recipients as usize };
+    let mut claimers = Vec::with_capacity(num_claimers);
+    for i in 0..num_claimers {
         let offset = claimers_offset + i * 32;
         if offset + 32 > data.len() {
             break;
@@ -149,6 +436,28 @@ pub fn decode_red_packet(data: &[u8]) -> Result<RedPacketAccount, AppError> {
         }
     }
 
+    let claimed_at_offset = claimers_offset + num_claimers * 32;
+    let mut claimed_at = Vec::with_capacity(num_claimers);
+    for i in 0..num_claimers {
+        let offset = claimed_at_offset + i * 8;
+        if offset + 8 > data.len() {
+            break;
+        }
+        claimed_at.push(i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()));
+    }
+
+    // Vesting packets end with a taken amount per slot
+    let taken_offset = claimed_at_offset + num_claimers * 8;
+    let num_taken = if vesting { num_recipients as usize } else { 0 };
+    let mut taken = Vec::with_capacity(num_taken);
+    for i in 0..num_taken {
+        let offset = taken_offset + i * 8;
+        if offset + 8 > data.len() {
+            break;
+        }
+        taken.push(u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()));
+    }
+
     Ok(RedPacketAccount {
         creator,
         id,
@@ -161,64 +470,328 @@ pub fn decode_red_packet(data: &[u8]) -> Result<RedPacketAccount, AppError> {
         vault_bump,
         token_type,
         expires_at,
+        fee_paid,
+        allow_repeat,
+        no_self_claim,
+        large,
+        random_onchain,
+        lucky_paid,
+        secret_hash,
+        voucher_authority,
+        gate_mint,
+        gate_min_amount,
+        assigned,
+        created_at,
+        reveal_pending,
+        reveal_deadline,
+        winner,
+        vesting,
+        vesting_start,
+        claimed,
+        memo,
+        burn_unclaimed,
+        donate_unclaimed,
+        original_creator,
         amounts,
         claimers,
+        claimed_at,
+        taken,
+    })
+}
+
+/// (slot, amount, claimer) for each claimed slot, in slot order. Claims
+/// record their claimer under the slot they took, so claimers[i] is slot
+/// i's even when claims landed out of order. Large packets keep no
+/// claimers and have none.
+pub fn slot_claims(rp: &RedPacketAccount) -> Vec<(usize, u64, Pubkey)> {
+    rp.claimers
+        .iter()
+        .enumerate()
+        .filter(|&(slot, _)| rp.claimed.get(slot).copied().unwrap_or(false))
+        .map(|(slot, claimer)| (slot, rp.amounts.get(slot).copied().unwrap_or(0), *claimer))
+        .collect()
+}
+
+/// The slot a claim that doesn't name one takes: the lowest unclaimed
+pub fn next_slot(rp: &RedPacketAccount) -> Option<usize> {
+    rp.claimed.iter().position(|&claimed| !claimed)
+}
+
+/// The lowest unclaimed slot reserved for `wallet` on an assigned packet,
+/// which the program pays when that wallet claims
+pub fn assigned_slot(rp: &RedPacketAccount, wallet: &Pubkey) -> Option<usize> {
+    if !rp.assigned {
+        return None;
+    }
+    (0..rp.claimers.len()).find(|&i| !rp.claimed[i] && rp.claimers[i] == *wallet)
+}
+
+/// The claimed slot `wallet` holds on a vesting packet, which it may claim
+/// again as more of it unlocks
+pub fn vesting_slot(rp: &RedPacketAccount, wallet: &Pubkey) -> Option<usize> {
+    if !rp.vesting {
+        return None;
+    }
+    (0..rp.claimers.len()).find(|&i| rp.claimed[i] && rp.claimers[i] == *wallet)
+}
+
+/// How much of `amount` has unlocked at `now`, linearly from `start` to `end`
+/// (rounded down, as the program does)
+pub fn vested_amount(amount: u64, start: i64, end: i64, now: i64) -> u64 {
+    if now >= end {
+        return amount;
+    }
+    if now <= start {
+        return 0;
+    }
+    (amount as u128 * (now - start) as u128 / (end - start) as u128) as u64
+}
+
+/// What a claim on `slot` pays now: the slot amount, or for vesting packets
+/// whatever has unlocked and not yet been taken
+pub fn claimable(rp: &RedPacketAccount, slot: usize, now: i64) -> u64 {
+    let amount = rp.amounts.get(slot).copied().unwrap_or(0);
+    if !rp.vesting {
+        return amount;
+    }
+    let taken = rp.taken.get(slot).copied().unwrap_or(0);
+    vested_amount(amount, rp.vesting_start, rp.expires_at, now).saturating_sub(taken)
+}
+
+/// Return data of the program's peek instruction
+/// (programs/solana-redpacket/src/instructions/peek.rs): a packet's status,
+/// from a simulation rather than the account layout. Keep in step with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PacketPeek {
+    /// As `get_status`: "active", "fully_claimed" or "expired"
+    pub status: &'static str,
+    pub token_type: u8,
+    pub split_mode: u8,
+    pub num_recipients: u8,
+    pub num_claimed: u8,
+    pub total_amount: u64,
+    pub remaining_amount: u64,
+    /// What a claim of the lowest unclaimed slot pays now; 0 when the packet
+    /// isn't active or draws its amounts at claim time
+    pub next_amount: u64,
+    pub expires_at: i64,
+    /// The cluster clock the status was computed at
+    pub now: i64,
+}
+
+const PEEK_LEN: usize = 45;
+const PEEK_STATUSES: [&str; 3] = ["active", "fully_claimed", "expired"];
+
+pub fn decode_peek(data: &[u8]) -> Result<PacketPeek, AppError> {
+    let bad = || AppError::BadRequest("Invalid peek return data".into());
+    if data.len() != PEEK_LEN {
+        return Err(bad());
+    }
+    let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    Ok(PacketPeek {
+        status: PEEK_STATUSES.get(data[0] as usize).copied().ok_or_else(bad)?,
+        token_type: data[1],
+        split_mode: data[2],
+        num_recipients: data[3],
+        num_claimed: data[4],
+        total_amount: u64_at(5),
+        remaining_amount: u64_at(13),
+        next_amount: u64_at(21),
+        expires_at: u64_at(29) as i64,
+        now: u64_at(37) as i64,
+    })
+}
+
+/// Return data of the program's get_version instruction: its
+/// PROGRAM_VERSION, then the red packet layout version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+    pub layout: u8,
+}
+
+impl std::fmt::Display for ProgramVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{} (layout v{})", self.major, self.minor, self.patch, self.layout)
+    }
+}
+
+pub fn decode_program_version(data: &[u8]) -> Result<ProgramVersion, AppError> {
+    match *data {
+        [major, minor, patch, layout] => Ok(ProgramVersion { major, minor, patch, layout }),
+        _ => Err(AppError::BadRequest("Invalid get_version return data".into())),
+    }
+}
+
+/// Whether this server can talk to a deployment reporting `version`: the
+/// major version must match. A different layout only means old packets
+/// await `migrate` (or the server is behind), so it is merely worth a warning.
+pub fn check_program_version(version: &ProgramVersion) -> Result<(), String> {
+    if version.major != PROGRAM_MAJOR_VERSION {
+        return Err(format!(
+            "The deployed program is {version}, but this server was built for major version {PROGRAM_MAJOR_VERSION}"
+        ));
+    }
+    Ok(())
+}
+
+/// The token behind an SPL packet, read from its vault and mint accounts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplToken {
+    pub mint: Pubkey,
+    /// Owner of the vault account
+    pub token_program: Pubkey,
+    pub decimals: u8,
+}
+
+/// Mint of an SPL token account (first 32 bytes)
+pub fn decode_token_account_mint(data: &[u8]) -> Result<Pubkey, AppError> {
+    data.get(0..32)
+        .and_then(|bytes| Pubkey::try_from(bytes).ok())
+        .ok_or_else(|| AppError::BadRequest("Invalid token account data".into()))
+}
+
+/// Balance of an SPL token account (u64 at offset 64)
+pub fn decode_token_account_amount(data: &[u8]) -> Result<u64, AppError> {
+    data.get(64..72)
+        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| AppError::BadRequest("Invalid token account data".into()))
+}
+
+/// Decimals of an SPL mint (u8 at offset 44)
+pub fn decode_mint_decimals(data: &[u8]) -> Result<u8, AppError> {
+    if data.len() < 82 {
+        return Err(AppError::BadRequest("Invalid mint account data".into()));
+    }
+    Ok(data[44])
+}
+
+/// Treasury fee_bps (u16 at offset 43). Legacy 43-byte treasuries predate
+/// the field and charge the default rate.
+pub fn decode_treasury_fee_bps(data: &[u8]) -> u16 {
+    match data.get(43..45) {
+        Some(bytes) => u16::from_le_bytes([bytes[0], bytes[1]]),
+        None => FEE_RATE_BPS,
+    }
+}
+
+/// Treasury account (127 bytes, discriminator=2). Older treasuries are
+/// shorter; the fields they lack decode as the values the program assumes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreasuryAccount {
+    pub bump: u8,
+    pub vault_bump: u8,
+    /// NATIVE_SOL_MINT for the SOL treasury
+    pub mint: Pubkey,
+    /// Lamports collected by the SOL treasury (SPL fees sit in the treasury vault)
+    pub sol_fees_collected: u64,
+    pub fee_bps: u16,
+    /// Creates and claims against this mint are paused
+    pub paused: bool,
+    /// How soon and how far out (seconds from now) a create may set expires_at
+    pub min_expiry_secs: i64,
+    pub max_expiry_secs: i64,
+}
+
+pub fn decode_treasury(data: &[u8]) -> Result<TreasuryAccount, AppError> {
+    if data.len() < TREASURY_LEGACY_SIZE {
+        return Err(AppError::BadRequest("Treasury data too short".into()));
+    }
+    if data[0] != TREASURY_DISCRIMINATOR {
+        return Err(AppError::BadRequest("Invalid treasury discriminator".into()));
+    }
+
+    Ok(TreasuryAccount {
+        bump: data[1],
+        vault_bump: data[2],
+        mint: Pubkey::new_from_array(data[3..35].try_into().unwrap()),
+        sol_fees_collected: u64::from_le_bytes(data[35..43].try_into().unwrap()),
+        fee_bps: decode_treasury_fee_bps(data),
+        paused: data.get(109).is_some_and(|&paused| paused != 0),
+        min_expiry_secs: data
+            .get(111..119)
+            .map_or(MIN_EXPIRY_SECONDS, |bytes| i64::from_le_bytes(bytes.try_into().unwrap())),
+        max_expiry_secs: data
+            .get(119..127)
+            .map_or(MAX_EXPIRY_SECONDS, |bytes| i64::from_le_bytes(bytes.try_into().unwrap())),
     })
 }
 
+/// Treasury account data of the given length (43 for the oldest layout,
+/// 127 for the current one), with every field the length covers set
+#[cfg(test)]
+pub fn mock_treasury_data(mint: [u8; 32], len: usize) -> Vec<u8> {
+    let mut data = vec![0u8; len];
+    data[0] = TREASURY_DISCRIMINATOR;
+    data[1] = 254;
+    data[2] = 253;
+    data[3..35].copy_from_slice(&mint);
+    data[35..43].copy_from_slice(&12_345u64.to_le_bytes());
+    if len > 43 {
+        data[43..45].copy_from_slice(&25u16.to_le_bytes());
+    }
+    if len > 109 {
+        data[109] = 1;
+    }
+    if len > 111 {
+        data[111..119].copy_from_slice(&3_600i64.to_le_bytes());
+        data[119..127].copy_from_slice(&86_400i64.to_le_bytes());
+    }
+    data
+}
+
 // ============================================================
 // Helpers
 // ============================================================
 
-pub fn compute_fee(total_amount: u64) -> u64 {
-    let fee = total_amount * FEE_RATE_BPS / FEE_DENOMINATOR;
-    if fee > 0 { fee } else { 1 }
+/// The program's compute_fee (create.rs): at least 1 unit unless fee-free,
+/// through u128 so only a fee too big for a u64 (None) overflows
+pub fn compute_fee(total_amount: u64, fee_bps: u16) -> Option<u64> {
+    if fee_bps == 0 {
+        return Some(0);
+    }
+    let fee = total_amount as u128 * fee_bps as u128 / FEE_DENOMINATOR as u128;
+    u64::try_from(fee).ok().map(|fee| fee.max(1))
 }
 
 pub fn sol_to_lamports(sol: f64) -> u64 {
     (sol * LAMPORTS_PER_SOL as f64).round() as u64
 }
 
-pub fn lamports_to_sol(lamports: u64) -> f64 {
-    lamports as f64 / LAMPORTS_PER_SOL as f64
+/// Rent-exempt minimum for `data_len` bytes, as the program computes it
+pub fn rent_exempt(data_len: usize) -> u64 {
+    (data_len as u64 + 128) * 2 * 3480
 }
 
-pub fn generate_random_split(total_amount: u64, num_recipients: usize) -> Vec<u64> {
-    if num_recipients == 0 {
-        return vec![];
-    }
-    if num_recipients == 1 {
-        return vec![total_amount];
-    }
-
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
+/// What a SOL create takes from the creator on top of the pot and fee: the
+/// packet account's rent plus the vault's rent-exempt minimum, which the
+/// program keeps in the vault until close refunds both
+pub fn sol_create_rent(num_recipients: u8) -> u64 {
+    rent_exempt(REDPACKET_BASE_SIZE + num_recipients as usize * PER_RECIPIENT_SIZE) + rent_exempt(0)
+}
 
-    let mut cuts: Vec<f64> = (0..num_recipients - 1).map(|_| rng.gen::<f64>()).collect();
-    cuts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+pub fn lamports_to_sol(lamports: u64) -> f64 {
+    lamports as f64 / LAMPORTS_PER_SOL as f64
+}
 
-    let total = total_amount as f64;
-    let mut amounts = Vec::with_capacity(num_recipients);
-    let mut prev = 0.0;
-    for &cut in &cuts {
-        let raw = (cut * total).floor() as u64 - (prev * total).floor() as u64;
-        amounts.push(if raw < 1 { 1 } else { raw });
-        prev = cut;
+/// A packet amount in display units with its unit name: lamports as SOL,
+/// SPL base units scaled by the mint's decimals.
+pub fn display_amount(amount: u64, spl: Option<&SplToken>) -> (f64, &'static str) {
+    match spl {
+        Some(token) => (amount as f64 / 10f64.powi(token.decimals as i32), "tokens"),
+        None => (lamports_to_sol(amount), "SOL"),
     }
-    amounts.push(total_amount.saturating_sub((prev * total).floor() as u64));
+}
 
-    // Adjust last slot to ensure exact sum
-    let sum: u64 = amounts.iter().sum();
-    if sum != total_amount {
-        let last = amounts.last_mut().unwrap();
-        if sum < total_amount {
-            *last += total_amount - sum;
-        } else {
-            *last = last.saturating_sub(sum - total_amount);
-        }
+/// Inverse of `display_amount`: a display amount in lamports or SPL base units
+pub fn base_amount(amount: f64, spl: Option<&SplToken>) -> u64 {
+    match spl {
+        Some(token) => (amount * 10f64.powi(token.decimals as i32)).round() as u64,
+        None => sol_to_lamports(amount),
     }
-
-    amounts
 }
 
 /// Get status string from red packet state
@@ -231,3 +804,109 @@ pub fn get_status(rp: &RedPacketAccount, now_unix: i64) -> &'static str {
         "active"
     }
 }
+
+/// " — created 3 hours ago" suffix for a packet's description, or "" when
+/// the create time wasn't recorded
+pub fn packet_age(rp: &RedPacketAccount, now_unix: i64) -> String {
+    if rp.created_at == 0 {
+        return String::new();
+    }
+    format!(" — created {}", time_ago(rp.created_at, now_unix))
+}
+
+/// " — last claimed 5 minutes ago" suffix for a packet's description, or ""
+/// until a claim with a recorded time
+pub fn last_claim_age(rp: &RedPacketAccount, now_unix: i64) -> String {
+    match rp.claimed_at.iter().copied().max() {
+        Some(last) if last > 0 => format!(" — last claimed {}", time_ago(last, now_unix)),
+        _ => String::new(),
+    }
+}
+
+/// "3 hours ago", rounded down, or "just now" under a minute
+fn time_ago(then: i64, now_unix: i64) -> String {
+    let age = now_unix.saturating_sub(then).max(0);
+    let (count, unit) = match age {
+        0..60 => return "just now".into(),
+        60..3_600 => (age / 60, "minute"),
+        3_600..86_400 => (age / 3_600, "hour"),
+        _ => (age / 86_400, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peek_return_data_decodes_at_the_program_offsets() {
+        let mut data = vec![2, TOKEN_TYPE_SOL, SPLIT_EVEN, 3, 1];
+        for value in [300u64, 200, 0, 900, 1_000] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        assert_eq!(
+            decode_peek(&data).unwrap(),
+            PacketPeek {
+                status: "expired",
+                token_type: TOKEN_TYPE_SOL,
+                split_mode: SPLIT_EVEN,
+                num_recipients: 3,
+                num_claimed: 1,
+                total_amount: 300,
+                remaining_amount: 200,
+                next_amount: 0,
+                expires_at: 900,
+                now: 1_000,
+            }
+        );
+
+        assert!(decode_peek(&data[..44]).is_err());
+        data[0] = 3;
+        assert!(decode_peek(&data).is_err());
+    }
+
+    #[test]
+    fn program_versions_must_match_the_major_the_server_was_built_for() {
+        let built_for = decode_program_version(&[PROGRAM_MAJOR_VERSION, 4, 2, REDPACKET_VERSION]).unwrap();
+        assert_eq!(built_for.to_string(), format!("{PROGRAM_MAJOR_VERSION}.4.2 (layout v{REDPACKET_VERSION})"));
+        assert!(check_program_version(&built_for).is_ok());
+        // A newer layout alone doesn't stop the server
+        let newer_layout = ProgramVersion { layout: REDPACKET_VERSION + 1, ..built_for };
+        assert!(check_program_version(&newer_layout).is_ok());
+
+        let next_major = ProgramVersion { major: PROGRAM_MAJOR_VERSION + 1, ..built_for };
+        assert!(check_program_version(&next_major).is_err());
+        assert!(decode_program_version(&[PROGRAM_MAJOR_VERSION, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn treasuries_decode_every_layout_and_reject_short_or_closed_ones() {
+        let mint = Pubkey::new_unique();
+        let current = decode_treasury(&mock_treasury_data(mint.to_bytes(), 127)).unwrap();
+        assert_eq!(
+            current,
+            TreasuryAccount {
+                bump: 254,
+                vault_bump: 253,
+                mint,
+                sol_fees_collected: 12_345,
+                fee_bps: 25,
+                paused: true,
+                min_expiry_secs: 3_600,
+                max_expiry_secs: 86_400,
+            }
+        );
+        // Fields a shorter layout lacks read as the program's defaults
+        let legacy = decode_treasury(&mock_treasury_data(mint.to_bytes(), 43)).unwrap();
+        assert_eq!((legacy.fee_bps, legacy.paused), (FEE_RATE_BPS, false));
+        assert_eq!((legacy.min_expiry_secs, legacy.max_expiry_secs), (MIN_EXPIRY_SECONDS, MAX_EXPIRY_SECONDS));
+
+        assert!(decode_treasury(&mock_treasury_data(NATIVE_SOL_MINT, 127)[..42]).is_err());
+        let mut closed = mock_treasury_data(NATIVE_SOL_MINT, 127);
+        closed[0] = 0xFF;
+        assert!(decode_treasury(&closed).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use solana_client::client_error::ClientError;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::transaction::TransactionError;
+
+/// Declares `describe` from the program's own error table, so every code
+/// the program can return maps to its name and `RedPacketError::message`.
+macro_rules! red_packet_errors {
+    ($($variant:ident = $code:literal => $message:literal,)*) => {
+        /// Name and message of a red packet program error code
+        pub fn describe(code: u32) -> Option<(&'static str, &'static str)> {
+            match code {
+                $($code => Some((stringify!($variant), $message)),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+include!("../../programs/solana-redpacket/src/error_table.rs");
+
+/// "Name: message" for the custom program error behind a failed simulation
+/// or confirmation, if there is one. Other programs in the same transaction
+/// (the ATA program) have their own codes, so it's labelled as ours only by
+/// code.
+pub fn describe_client_error(err: &ClientError) -> Option<String> {
+    describe_transaction_error(&err.get_transaction_error()?)
+}
+
+/// Like `describe_client_error`, for the error a simulation reports
+pub fn describe_transaction_error(err: &TransactionError) -> Option<String> {
+    match err {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => {
+            let (name, message) = describe(*code)?;
+            Some(format!("{message} ({name}, error {code})"))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::client_error::ClientErrorKind;
+
+    #[test]
+    fn maps_every_program_error() {
+        assert_eq!(describe(0), Some(("InvalidAmount", "The amount is invalid")));
+        assert_eq!(describe(37).unwrap().0, "ExpiryTooFar");
+        assert_eq!(describe(53).unwrap().0, "InsufficientForRent");
+        assert_eq!(describe(61).unwrap().0, "InvalidEventAuthority");
+        assert_eq!(describe(62).unwrap().0, "CreatorMismatch");
+        assert_eq!(describe(63), None);
+    }
+
+    #[test]
+    fn describes_custom_instruction_errors_only() {
+        let custom: ClientError = ClientErrorKind::TransactionError(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(30),
+        ))
+        .into();
+        assert_eq!(
+            describe_client_error(&custom).as_deref(),
+            Some("Creates, top-ups and claims are paused for this mint (ProgramPaused, error 30)")
+        );
+
+        let other: ClientError =
+            ClientErrorKind::TransactionError(TransactionError::BlockhashNotFound).into();
+        assert_eq!(describe_client_error(&other), None);
+        let unknown: ClientError = ClientErrorKind::TransactionError(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(999),
+        ))
+        .into();
+        assert_eq!(describe_client_error(&unknown), None);
+    }
+}
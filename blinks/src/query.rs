@@ -0,0 +1,695 @@
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use serde::Serialize;
+use solana_account_decoder_client_types::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::Transaction;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::actions::create::claim_url;
+use crate::actions::{account_exists, fetch_account, get_id_param, get_optional_id_param, get_param, simulate_tx};
+use crate::consts::*;
+use crate::error::AppError;
+use crate::program::{self, RedPacketAccount, TreasuryAccount};
+use crate::router::AppState;
+
+// ============================================================
+// RPC helpers
+// ============================================================
+
+/// A creator's packets, split by whether this server can decode them
+pub struct CreatorPackets {
+    pub packets: Vec<(Pubkey, RedPacketAccount)>,
+    /// Packets on an older layout (or a newer one than REDPACKET_VERSION),
+    /// left out until they are migrated
+    pub unmigrated: Vec<Pubkey>,
+}
+
+/// Fetch every red packet created by `creator` via getProgramAccounts.
+///
+/// Filters on the discriminator (offset 0) and creator (offset 1). With
+/// `header_only` the RPC returns just the fixed header slice, which is all
+/// the aggregate queries need — amounts and claimers come back empty.
+pub async fn fetch_creator_packets(
+    rpc: &RpcClient,
+    creator: &Pubkey,
+    header_only: bool,
+) -> Result<CreatorPackets, AppError> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(0, vec![REDPACKET_DISCRIMINATOR])),
+            RpcFilterType::Memcmp(Memcmp::new_raw_bytes(1, creator.to_bytes().to_vec())),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: header_only.then_some(UiDataSliceConfig {
+                offset: 0,
+                length: REDPACKET_BASE_SIZE,
+            }),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let accounts = rpc
+        .get_program_accounts_with_config(&PROGRAM_ID, config)
+        .await?;
+
+    split_creator_packets(accounts.into_iter().map(|(address, account)| (address, account.data)))
+}
+
+/// Decode the packets on the current layout and set the others aside by
+/// their version byte (v0 packets, too short to have one, included)
+pub fn split_creator_packets(
+    accounts: impl IntoIterator<Item = (Pubkey, Vec<u8>)>,
+) -> Result<CreatorPackets, AppError> {
+    let mut split = CreatorPackets {
+        packets: vec![],
+        unmigrated: vec![],
+    };
+    for (address, data) in accounts {
+        if data.get(REDPACKET_VERSION_OFFSET) == Some(&REDPACKET_VERSION) {
+            split.packets.push((address, program::decode_red_packet(&data)?));
+        } else {
+            split.unmigrated.push(address);
+        }
+    }
+    Ok(split)
+}
+
+fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+fn parse_creator(creator: &str) -> Result<Pubkey, AppError> {
+    creator
+        .parse()
+        .map_err(|_| AppError::BadRequest("Invalid creator pubkey".into()))
+}
+
+// ============================================================
+// GET /api/redpacket/by-creator/{creator}/stats
+// ============================================================
+
+/// Aggregate rollup over a creator's packets.
+///
+/// Counts cover every packet; amount totals only cover SOL packets (in
+/// lamports), since SPL packets of different mints can't be summed.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatorStats {
+    pub creator: String,
+    pub total_packets: u64,
+    pub active: u64,
+    pub expired: u64,
+    pub fully_claimed: u64,
+    pub total_claims: u64,
+    pub spl_packets: u64,
+    pub sol_funded_lamports: u64,
+    pub sol_distributed_lamports: u64,
+    pub sol_remaining_lamports: u64,
+    pub sol_fees_paid_lamports: u64,
+    /// Packets on another layout version, left out of every other field
+    /// until migrated
+    pub unmigrated_packets: u64,
+}
+
+pub fn aggregate_stats(creator: &Pubkey, packets: &[RedPacketAccount], now_unix: i64) -> CreatorStats {
+    let mut stats = CreatorStats {
+        creator: creator.to_string(),
+        ..Default::default()
+    };
+
+    for rp in packets {
+        stats.total_packets += 1;
+        stats.total_claims += rp.num_claimed as u64;
+
+        match program::get_status(rp, now_unix) {
+            "fully_claimed" => stats.fully_claimed += 1,
+            "expired" => stats.expired += 1,
+            _ => stats.active += 1,
+        }
+
+        if rp.token_type == TOKEN_TYPE_SOL {
+            stats.sol_funded_lamports = stats.sol_funded_lamports.saturating_add(rp.total_amount);
+            stats.sol_remaining_lamports =
+                stats.sol_remaining_lamports.saturating_add(rp.remaining_amount);
+            stats.sol_distributed_lamports = stats
+                .sol_distributed_lamports
+                .saturating_add(rp.total_amount.saturating_sub(rp.remaining_amount));
+            stats.sol_fees_paid_lamports = stats.sol_fees_paid_lamports.saturating_add(rp.fee_paid);
+        } else {
+            stats.spl_packets += 1;
+        }
+    }
+
+    stats
+}
+
+pub async fn creator_stats(
+    Path(creator): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<CreatorStats>, AppError> {
+    let creator = parse_creator(&creator)?;
+    let CreatorPackets { packets, unmigrated } = fetch_creator_packets(&state.rpc, &creator, true).await?;
+    let packets: Vec<RedPacketAccount> = packets.into_iter().map(|(_, rp)| rp).collect();
+
+    Ok(Json(CreatorStats {
+        unmigrated_packets: unmigrated.len() as u64,
+        ..aggregate_stats(&creator, &packets, now_unix())
+    }))
+}
+
+// ============================================================
+// GET /api/redpackets?creator=&limit=&before=
+// ============================================================
+
+pub const DEFAULT_LIST_LIMIT: usize = 20;
+pub const MAX_LIST_LIMIT: usize = 100;
+/// How many of the creator's packets the listing leaves out for being on
+/// another layout version (until migrated)
+pub const UNMIGRATED_PACKETS_HEADER: &str = "x-unmigrated-packets";
+
+/// One row of a creator's packet listing: the header fields plus status.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedPacketSummary {
+    pub address: String,
+    /// Decimal string, like the `id` query param, so JS clients keep precision
+    pub id: String,
+    pub token_type: u8,
+    pub split_mode: u8,
+    pub total_amount: u64,
+    pub remaining_amount: u64,
+    pub fee_paid: u64,
+    pub num_recipients: u8,
+    pub num_claimed: u8,
+    pub allow_repeat: bool,
+    pub no_self_claim: bool,
+    /// Up to 255 slots, claims deduped by receipt PDAs
+    pub large: bool,
+    pub password_protected: bool,
+    pub voucher_required: bool,
+    /// Base58 mint claimers must hold, with the least balance (base units)
+    pub gate_mint: Option<String>,
+    pub gate_min_amount: u64,
+    /// Only the wallets chosen at create may claim
+    pub assigned: bool,
+    /// Unix time of the create; 0 for packets migrated from before it was stored
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub status: &'static str,
+}
+
+/// Newest-first page of packets: ids below `before` (when given), sorted by
+/// id descending, at most `limit` of them. Pass the last row's id as the next
+/// `before` to continue.
+pub fn list_page(
+    mut packets: Vec<(Pubkey, RedPacketAccount)>,
+    before: Option<u64>,
+    limit: usize,
+    now_unix: i64,
+) -> Vec<RedPacketSummary> {
+    if let Some(before) = before {
+        packets.retain(|(_, rp)| rp.id < before);
+    }
+    packets.sort_unstable_by(|(_, a), (_, b)| b.id.cmp(&a.id));
+
+    packets
+        .into_iter()
+        .take(limit)
+        .map(|(address, rp)| RedPacketSummary {
+            address: address.to_string(),
+            id: rp.id.to_string(),
+            token_type: rp.token_type,
+            split_mode: rp.split_mode,
+            total_amount: rp.total_amount,
+            remaining_amount: rp.remaining_amount,
+            fee_paid: rp.fee_paid,
+            num_recipients: rp.num_recipients,
+            num_claimed: rp.num_claimed,
+            allow_repeat: rp.allow_repeat,
+            no_self_claim: rp.no_self_claim,
+            large: rp.large,
+            password_protected: rp.secret_hash.is_some(),
+            voucher_required: rp.voucher_authority.is_some(),
+            gate_mint: rp.gate_mint.map(|mint| mint.to_string()),
+            gate_min_amount: rp.gate_min_amount,
+            assigned: rp.assigned,
+            created_at: rp.created_at,
+            expires_at: rp.expires_at,
+            status: program::get_status(&rp, now_unix),
+        })
+        .collect()
+}
+
+/// `limit` defaults to DEFAULT_LIST_LIMIT and is capped at MAX_LIST_LIMIT.
+fn parse_limit(params: &HashMap<String, String>) -> Result<usize, AppError> {
+    if !params.contains_key("limit") {
+        return Ok(DEFAULT_LIST_LIMIT);
+    }
+    let limit: usize = get_param(params, "limit")?;
+    if limit == 0 {
+        return Err(AppError::BadRequest("'limit' must be at least 1".into()));
+    }
+    Ok(limit.min(MAX_LIST_LIMIT))
+}
+
+/// The RPC has to return every packet of the creator before paging: there's
+/// no server-side sort, and packet sizes vary with the recipient count, so a
+/// dataSize filter can't stand in for the discriminator filter that keeps
+/// treasuries out. Unmigrated packets are counted in UNMIGRATED_PACKETS_HEADER.
+pub async fn list_red_packets(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<([(&'static str, String); 1], Json<Vec<RedPacketSummary>>), AppError> {
+    let creator = parse_creator(
+        params
+            .get("creator")
+            .ok_or_else(|| AppError::BadRequest("Missing 'creator' parameter".into()))?,
+    )?;
+    let limit = parse_limit(&params)?;
+    let before = get_optional_id_param(&params, "before")?;
+
+    let CreatorPackets { packets, unmigrated } = fetch_creator_packets(&state.rpc, &creator, true).await?;
+    Ok((
+        [(UNMIGRATED_PACKETS_HEADER, unmigrated.len().to_string())],
+        Json(list_page(packets, before, limit, now_unix())),
+    ))
+}
+
+// ============================================================
+// GET /api/redpacket/claims?creator=&id=
+// ============================================================
+
+/// Who claimed one slot of a packet and what it paid.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotClaimSummary {
+    pub slot: usize,
+    /// The slot's amount in base units; on vesting packets, what it vests in
+    /// full rather than what has been taken so far
+    pub amount: u64,
+    pub claimer: String,
+    /// Unix time of the claim; 0 when it predates migration
+    pub claimed_at: i64,
+}
+
+pub fn claims_summary(rp: &RedPacketAccount) -> Vec<SlotClaimSummary> {
+    program::slot_claims(rp)
+        .into_iter()
+        .map(|(slot, amount, claimer)| SlotClaimSummary {
+            slot,
+            amount,
+            claimer: claimer.to_string(),
+            claimed_at: rp.claimed_at.get(slot).copied().unwrap_or(0),
+        })
+        .collect()
+}
+
+/// The claimed slots of one packet, in slot order. Large packets record
+/// claims in receipts only, so they list none.
+pub async fn red_packet_claims(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<SlotClaimSummary>>, AppError> {
+    let creator: Pubkey = get_param(&params, "creator")?;
+    let id = get_id_param(&params)?;
+
+    let (address, _) = program::find_red_packet_pda(&creator, id);
+    let account = fetch_account(&state.rpc, &address, "Red packet not found on chain").await?;
+    let rp = program::decode_red_packet(&account.data)?;
+    Ok(Json(claims_summary(&rp)))
+}
+
+// ============================================================
+// GET /api/redpacket/peek?creator=&id=
+// ============================================================
+
+/// A packet's status from simulating the program's peek instruction, so it
+/// doesn't depend on this server's copy of the account layout. The creator
+/// is the simulation's fee payer, so it needs enough SOL for one fee.
+pub async fn red_packet_peek(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<program::PacketPeek>, AppError> {
+    let creator: Pubkey = get_param(&params, "creator")?;
+    let id = get_id_param(&params)?;
+
+    let (address, _) = program::find_red_packet_pda(&creator, id);
+    let blockhash = state.rpc.get_latest_blockhash().await?;
+    let msg = Message::new_with_blockhash(&[program::build_peek_instruction(&address)], Some(&creator), &blockhash);
+    let return_data = simulate_tx(&state.rpc, &Transaction::new_unsigned(msg))
+        .await?
+        .ok_or_else(|| AppError::BadRequest("Peek returned no data".into()))?;
+    Ok(Json(program::decode_peek(&return_data)?))
+}
+
+// ============================================================
+// GET /api/treasury?mint=
+// ============================================================
+
+/// A treasury's fee settings and what it has collected so far.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreasurySummary {
+    pub address: String,
+    /// Base58 mint, or None for the SOL treasury
+    pub mint: Option<String>,
+    pub bump: u8,
+    pub vault_bump: u8,
+    pub fee_bps: u16,
+    pub paused: bool,
+    pub min_expiry_secs: i64,
+    pub max_expiry_secs: i64,
+    pub sol_fees_collected: u64,
+    /// The SPL treasury's fee vault and its balance (base units)
+    pub vault: Option<String>,
+    pub vault_balance: Option<u64>,
+}
+
+pub fn treasury_summary(
+    address: &Pubkey,
+    treasury: &TreasuryAccount,
+    vault: Option<(Pubkey, u64)>,
+) -> TreasurySummary {
+    TreasurySummary {
+        address: address.to_string(),
+        mint: (treasury.mint.to_bytes() != NATIVE_SOL_MINT).then(|| treasury.mint.to_string()),
+        bump: treasury.bump,
+        vault_bump: treasury.vault_bump,
+        fee_bps: treasury.fee_bps,
+        paused: treasury.paused,
+        min_expiry_secs: treasury.min_expiry_secs,
+        max_expiry_secs: treasury.max_expiry_secs,
+        sol_fees_collected: treasury.sol_fees_collected,
+        vault: vault.map(|(vault, _)| vault.to_string()),
+        vault_balance: vault.map(|(_, balance)| balance),
+    }
+}
+
+/// `mint` picks an SPL treasury; without it the SOL treasury is read.
+pub async fn treasury(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<TreasurySummary>, AppError> {
+    let mint: Option<Pubkey> = params
+        .get("mint")
+        .map(|mint| {
+            mint.parse()
+                .map_err(|_| AppError::BadRequest("Invalid mint pubkey".into()))
+        })
+        .transpose()?;
+
+    let (address, _) = match &mint {
+        Some(mint) => program::find_treasury_pda(mint),
+        None => program::find_treasury_pda_sol(),
+    };
+    let account = fetch_account(&state.rpc, &address, "Treasury not found on chain").await?;
+    let treasury = program::decode_treasury(&account.data)?;
+
+    let vault = match &mint {
+        Some(mint) => {
+            let (vault, _) = program::find_treasury_vault_pda(mint);
+            let account = fetch_account(&state.rpc, &vault, "Treasury vault not found on chain").await?;
+            Some((vault, program::decode_token_account_amount(&account.data)?))
+        }
+        None => None,
+    };
+
+    Ok(Json(treasury_summary(&address, &treasury, vault)))
+}
+
+// ============================================================
+// GET /api/qr?creator=&id=
+// ============================================================
+
+/// What a frontend needs to render a packet's claim QR code.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimQr {
+    pub red_packet: String,
+    /// The claim blink, the same link the create action returns
+    pub claim_url: String,
+    /// `solana-action:` URI wrapping claim_url, for wallets that scan it
+    pub action_uri: String,
+}
+
+pub fn claim_qr(base_url: &str, creator: &Pubkey, id: u64) -> ClaimQr {
+    let (red_packet, _) = program::find_red_packet_pda(creator, id);
+    let claim_url = claim_url(base_url, creator, id);
+    ClaimQr {
+        red_packet: red_packet.to_string(),
+        action_uri: format!("solana-action:{}", encode_uri_component(&claim_url)),
+        claim_url,
+    }
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters, as the
+/// Solana Actions spec requires of a link carrying its own query string.
+fn encode_uri_component(raw: &str) -> String {
+    let mut encoded = String::with_capacity(raw.len());
+    for b in raw.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{b:02X}"));
+        }
+    }
+    encoded
+}
+
+/// 404s for a packet that doesn't exist, so a QR never points at nothing.
+pub async fn qr(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ClaimQr>, AppError> {
+    let creator: Pubkey = get_param(&params, "creator")?;
+    let id = get_id_param(&params)?;
+
+    let (red_packet, _) = program::find_red_packet_pda(&creator, id);
+    if !account_exists(&state.rpc, &red_packet).await? {
+        return Err(AppError::NotFound("Red packet not found on chain".into()));
+    }
+    Ok(Json(claim_qr(&state.base_url, &creator, id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_packet(
+        token_type: u8,
+        total_amount: u64,
+        remaining_amount: u64,
+        num_recipients: u8,
+        num_claimed: u8,
+        expires_at: i64,
+    ) -> RedPacketAccount {
+        RedPacketAccount {
+            id: 0,
+            total_amount,
+            remaining_amount,
+            num_recipients,
+            num_claimed,
+            token_type,
+            expires_at,
+            fee_paid: program::compute_fee(total_amount, FEE_RATE_BPS).unwrap(),
+            ..RedPacketAccount::mock()
+        }
+    }
+
+    #[test]
+    fn aggregates_counts_and_sol_totals() {
+        let now = 1_000;
+        let creator = Pubkey::new_unique();
+        let packets = vec![
+            // active, 1 of 3 claimed
+            mock_packet(TOKEN_TYPE_SOL, 300, 200, 3, 1, now + 60),
+            // expired with leftovers
+            mock_packet(TOKEN_TYPE_SOL, 1_000, 400, 4, 2, now - 60),
+            // fully claimed (takes precedence over expiry)
+            mock_packet(TOKEN_TYPE_SOL, 50, 0, 2, 2, now - 60),
+            // SPL packet: counted, amounts excluded
+            mock_packet(0, 9_999, 9_999, 5, 0, now + 60),
+        ];
+
+        let stats = aggregate_stats(&creator, &packets, now);
+
+        assert_eq!(
+            stats,
+            CreatorStats {
+                creator: creator.to_string(),
+                total_packets: 4,
+                active: 2,
+                expired: 1,
+                fully_claimed: 1,
+                total_claims: 5,
+                spl_packets: 1,
+                sol_funded_lamports: 1_350,
+                sol_distributed_lamports: 750,
+                sol_remaining_lamports: 600,
+                sol_fees_paid_lamports: 3,
+                unmigrated_packets: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn old_layout_packets_are_set_aside_instead_of_failing_the_query() {
+        let creator = Pubkey::new_unique();
+        let header = |len: usize, version: u8| {
+            let mut data = vec![0u8; len];
+            data[0] = REDPACKET_DISCRIMINATOR;
+            data[1..33].copy_from_slice(creator.as_ref());
+            if len > REDPACKET_VERSION_OFFSET {
+                data[REDPACKET_VERSION_OFFSET] = version;
+            }
+            data
+        };
+        let (current, previous, v0) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        let split = split_creator_packets([
+            (previous, header(REDPACKET_BASE_SIZE - 32, REDPACKET_VERSION - 1)),
+            (current, header(REDPACKET_BASE_SIZE, REDPACKET_VERSION)),
+            // v0 ended before the version byte
+            (v0, header(71, 0)),
+        ])
+        .unwrap();
+        assert_eq!(split.packets.len(), 1);
+        assert_eq!(split.packets[0].0, current);
+        assert_eq!(split.packets[0].1.creator, creator);
+        assert_eq!(split.unmigrated, [previous, v0]);
+
+        // A current-layout packet that doesn't decode is still an error
+        assert!(split_creator_packets([(current, header(REDPACKET_BASE_SIZE - 1, REDPACKET_VERSION))]).is_err());
+    }
+
+    fn with_id(id: u64, expires_at: i64) -> (Pubkey, RedPacketAccount) {
+        let mut rp = mock_packet(TOKEN_TYPE_SOL, 100, 100, 2, 0, expires_at);
+        rp.id = id;
+        (Pubkey::new_unique(), rp)
+    }
+
+    #[test]
+    fn list_page_sorts_newest_first_and_pages_with_before() {
+        let now = 1_000;
+        let packets = vec![with_id(5, now + 60), with_id(9, now - 60), with_id(7, now + 60)];
+
+        let first = list_page(packets, None, 2, now);
+        let ids: Vec<&str> = first.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["9", "7"]);
+        assert_eq!(first[0].status, "expired");
+        assert_eq!(first[1].status, "active");
+
+        let packets = vec![with_id(5, now + 60), with_id(9, now - 60), with_id(7, now + 60)];
+        let next = list_page(packets, Some(7), 2, now);
+        let ids: Vec<&str> = next.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["5"]);
+    }
+
+    #[test]
+    fn limit_defaults_and_is_capped() {
+        let mut params = HashMap::new();
+        assert_eq!(parse_limit(&params).unwrap(), DEFAULT_LIST_LIMIT);
+        params.insert("limit".to_string(), "1000".to_string());
+        assert_eq!(parse_limit(&params).unwrap(), MAX_LIST_LIMIT);
+        params.insert("limit".to_string(), "0".to_string());
+        assert!(parse_limit(&params).is_err());
+    }
+
+    #[test]
+    fn empty_creator_has_zero_stats() {
+        let creator = Pubkey::new_unique();
+        let stats = aggregate_stats(&creator, &[], 0);
+        assert_eq!(stats.total_packets, 0);
+        assert_eq!(stats.sol_funded_lamports, 0);
+        assert_eq!(stats.creator, creator.to_string());
+    }
+
+    #[test]
+    fn sol_treasury_summary_has_no_mint_or_vault() {
+        let address = Pubkey::new_unique();
+        let treasury = program::decode_treasury(&program::mock_treasury_data(NATIVE_SOL_MINT, 127)).unwrap();
+        assert_eq!(
+            treasury_summary(&address, &treasury, None),
+            TreasurySummary {
+                address: address.to_string(),
+                mint: None,
+                bump: 254,
+                vault_bump: 253,
+                fee_bps: 25,
+                paused: true,
+                min_expiry_secs: 3_600,
+                max_expiry_secs: 86_400,
+                sol_fees_collected: 12_345,
+                vault: None,
+                vault_balance: None,
+            }
+        );
+    }
+
+    #[test]
+    fn legacy_spl_treasury_uses_default_fee_and_reports_vault() {
+        let mint = Pubkey::new_unique();
+        let vault = Pubkey::new_unique();
+        let treasury = program::decode_treasury(&program::mock_treasury_data(mint.to_bytes(), 43)).unwrap();
+        let summary = treasury_summary(&Pubkey::new_unique(), &treasury, Some((vault, 70)));
+
+        assert_eq!(summary.mint, Some(mint.to_string()));
+        assert_eq!(summary.fee_bps, FEE_RATE_BPS);
+        assert!(!summary.paused);
+        assert_eq!(summary.min_expiry_secs, MIN_EXPIRY_SECONDS);
+        assert_eq!(summary.max_expiry_secs, MAX_EXPIRY_SECONDS);
+        assert_eq!(summary.vault, Some(vault.to_string()));
+        assert_eq!(summary.vault_balance, Some(70));
+    }
+
+    #[test]
+    fn qr_wraps_the_claim_url_in_an_action_uri() {
+        let creator = Pubkey::new_unique();
+        let qr = claim_qr("https://blinks.example", &creator, 42);
+        assert_eq!(
+            qr.claim_url,
+            format!("https://blinks.example/api/actions/claim?creator={creator}&id=42")
+        );
+        assert_eq!(
+            qr.action_uri,
+            format!("solana-action:https%3A%2F%2Fblinks.example%2Fapi%2Factions%2Fclaim%3Fcreator%3D{creator}%26id%3D42")
+        );
+        assert_eq!(qr.red_packet, program::find_red_packet_pda(&creator, 42).0.to_string());
+    }
+
+    #[test]
+    fn claims_pair_each_claimed_slot_with_its_amount_and_claimer() {
+        let alice = Pubkey::new_unique();
+        let bob = Pubkey::new_unique();
+        let mut rp = mock_packet(TOKEN_TYPE_SOL, 600, 200, 3, 2, i64::MAX);
+        rp.amounts = vec![300, 200, 100];
+        rp.claimers = vec![alice, Pubkey::default(), bob];
+        rp.claimed = vec![true, false, true];
+        rp.claimed_at = vec![10, 0, 20];
+
+        assert_eq!(
+            claims_summary(&rp),
+            vec![
+                SlotClaimSummary { slot: 0, amount: 300, claimer: alice.to_string(), claimed_at: 10 },
+                SlotClaimSummary { slot: 2, amount: 100, claimer: bob.to_string(), claimed_at: 20 },
+            ]
+        );
+
+        rp.large = true;
+        rp.claimers.clear();
+        rp.claimed_at.clear();
+        assert!(claims_summary(&rp).is_empty());
+    }
+}
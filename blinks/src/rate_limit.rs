@@ -0,0 +1,164 @@
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::router::AppState;
+
+/// Past this many tracked IPs, idle (fully refilled) buckets are dropped
+const MAX_TRACKED_IPS: usize = 10_000;
+
+/// Parse RATE_LIMIT_PER_MINUTE. Unset uses `default`; 0 turns limiting off.
+pub fn parse_rate_limit(raw: Option<&str>, default: u32) -> Result<u32, String> {
+    match raw.map(str::trim) {
+        None | Some("") => Ok(default),
+        Some(raw) => raw
+            .parse()
+            .map_err(|_| format!("Invalid RATE_LIMIT_PER_MINUTE: {raw:?}")),
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-IP token bucket: each IP may burst up to `per_minute` requests and
+/// regains one every `60 / per_minute` seconds.
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for `ip`, or return how long until the next one frees up
+    pub fn check(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = self.per_minute as f64;
+        let per_sec = capacity / 60.0;
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            (bucket.tokens + elapsed * per_sec).min(capacity)
+        };
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_IPS && !buckets.contains_key(&ip) {
+            buckets.retain(|_, bucket| refill(bucket) < capacity);
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens = refill(bucket);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_sec))
+        }
+    }
+}
+
+/// Middleware for the RPC-backed routes. The client is the TCP peer, so
+/// serve with `into_make_service_with_connect_info`; requests without
+/// connect info are not limited.
+pub async fn rate_limit(State(state): State<Arc<AppState>>, req: Request, next: Next) -> Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+
+    if let Some(ip) = peer {
+        if let Err(wait) = state.rate_limiter.check(ip, Instant::now()) {
+            return AppError::RateLimited(wait.as_secs_f64().ceil() as u64).into_response();
+        }
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, StatusCode};
+    use std::net::Ipv4Addr;
+    use tower::ServiceExt;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+    }
+
+    #[test]
+    fn parses_limit_with_default() {
+        assert_eq!(parse_rate_limit(None, 60), Ok(60));
+        assert_eq!(parse_rate_limit(Some(""), 60), Ok(60));
+        assert_eq!(parse_rate_limit(Some(" 120 "), 60), Ok(120));
+        assert_eq!(parse_rate_limit(Some("0"), 60), Ok(0));
+        assert!(parse_rate_limit(Some("-1"), 60).is_err());
+        assert!(parse_rate_limit(Some("fast"), 60).is_err());
+    }
+
+    #[test]
+    fn bucket_bursts_then_refills_per_ip() {
+        let limiter = RateLimiter::new(3);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check(ip(1), start).is_ok());
+        }
+        // 3/min = one token every 20s
+        let wait = limiter.check(ip(1), start).unwrap_err();
+        assert_eq!(wait.as_secs_f64().round(), 20.0);
+        assert!(limiter.check(ip(2), start).is_ok());
+
+        assert!(limiter.check(ip(1), start + Duration::from_secs(10)).is_err());
+        assert!(limiter.check(ip(1), start + Duration::from_secs(21)).is_ok());
+        assert!(limiter.check(ip(1), start + Duration::from_secs(21)).is_err());
+    }
+
+    #[test]
+    fn zero_disables_limiting() {
+        let limiter = RateLimiter::new(0);
+        let now = Instant::now();
+        for _ in 0..1_000 {
+            assert!(limiter.check(ip(1), now).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn router_returns_429_past_the_limit_but_not_for_actions_json() {
+        let app = crate::router::build_router(crate::router::RouterConfig::mock());
+        let get = |uri: &str| {
+            let mut req = Request::get(uri).body(Body::empty()).unwrap();
+            req.extensions_mut()
+                .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))));
+            req
+        };
+
+        // Unknown action: 404 without touching the RPC, but still counted
+        let res = app.clone().oneshot(get("/api/actions/nope")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = app.clone().oneshot(get("/api/actions/nope")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[header::RETRY_AFTER], "60");
+
+        let res = app.oneshot(get("/actions.json")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
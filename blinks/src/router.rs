@@ -1,44 +1,122 @@
 use axum::extract::{Path, Query, State};
+use axum::middleware;
+use axum::response::{IntoResponse, Response};
 use axum::routing::get;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::{Json, Router};
+use serde::Serialize;
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::signature::Keypair;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 use tower_http::trace::TraceLayer;
 
 use crate::actions::claim::ClaimAction;
-use crate::actions::close::CloseAction;
-use crate::actions::create::CreateAction;
+use crate::actions::close::{self, CloseAction};
+use crate::actions::create::{self, CreateAction};
+use crate::actions::withdraw_fees::WithdrawFeesAction;
 use crate::actions::ActionRegistry;
+use crate::cache::RedPacketCache;
+use crate::consts::CLUSTER;
 use crate::cors::actions_cors;
 use crate::error::AppError;
+use crate::metrics::{self, Metrics};
+use crate::query;
+use crate::rate_limit::{self, RateLimiter};
 use crate::register_actions;
-use crate::spec::{ActionGetResponse, ActionPostRequest, ActionPostResponse, ActionsJson};
+use crate::spec::{
+    versioned_body, ActionPostRequest, ActionVersion, ActionsJson, ACTION_VERSION_HEADER, BLOCKCHAIN_IDS_HEADER,
+};
 
 pub struct AppState {
     pub rpc: Arc<RpcClient>,
     pub registry: ActionRegistry,
     pub actions_json: ActionsJson,
     pub base_url: String,
+    pub rate_limiter: RateLimiter,
+    pub red_packet_cache: Arc<RedPacketCache>,
+    pub metrics: Arc<Metrics>,
 }
 
-pub fn build_router(rpc: Arc<RpcClient>, base_url: String) -> Router {
-    let registry = register_actions![CreateAction, ClaimAction, CloseAction];
+/// Everything the server is configured with, read from the environment in
+/// main.rs.
+pub struct RouterConfig {
+    /// Built with `metrics::metered_rpc_client` on `metrics`, so RPC calls
+    /// are timed
+    pub rpc: Arc<RpcClient>,
+    pub base_url: String,
+    /// The parsed ALLOWED_ORIGINS list; `None` allows any origin
+    pub allowed_origins: Option<Vec<HeaderValue>>,
+    /// Applies to every route except `/actions.json`, `/metrics` and the
+    /// health checks, since each of them calls the RPC
+    pub rate_limiter: RateLimiter,
+    /// Shared by Claim and Close
+    pub red_packet_cache: Arc<RedPacketCache>,
+    /// Create, Claim and Close simulate each transaction before returning it
+    pub simulate: bool,
+    /// Compute unit price Create, Claim and Close offer
+    pub priority_fee: u64,
+    /// Pays the fee of Claim transactions sent with `relay=1`
+    pub relayer: Option<Arc<Keypair>>,
+    pub metrics: Arc<Metrics>,
+}
+
+/// Actions served and every rate-limited route are counted and timed for
+/// `/metrics`.
+pub fn build_router(config: RouterConfig) -> Router {
+    let RouterConfig {
+        rpc,
+        base_url,
+        allowed_origins,
+        rate_limiter,
+        red_packet_cache,
+        simulate,
+        priority_fee,
+        relayer,
+        metrics,
+    } = config;
+    let registry = register_actions![
+        CreateAction::new(simulate, priority_fee),
+        ClaimAction::new(red_packet_cache.clone(), simulate, priority_fee, relayer),
+        CloseAction::new(red_packet_cache.clone(), simulate, priority_fee),
+        WithdrawFeesAction,
+    ];
     let actions_json = registry.build_actions_json();
     let state = Arc::new(AppState {
         rpc,
         registry,
         actions_json,
         base_url,
+        rate_limiter,
+        red_packet_cache,
+        metrics,
     });
 
     Router::new()
-        .route("/actions.json", get(get_actions_json))
+        // Static route wins over the {*path} catch-all below
+        .route("/api/actions/create/instruction", get(create::create_instruction))
+        .route("/api/actions/close/expired", get(close::close_expired))
         .route(
             "/api/actions/{*path}",
             get(handle_action_get).post(handle_action_post),
         )
-        .layer(actions_cors())
+        .route(
+            "/api/redpacket/by-creator/{creator}/stats",
+            get(query::creator_stats),
+        )
+        .route("/api/redpacket/claims", get(query::red_packet_claims))
+        .route("/api/redpacket/peek", get(query::red_packet_peek))
+        .route("/api/redpackets", get(query::list_red_packets))
+        .route("/api/treasury", get(query::treasury))
+        .route("/api/qr", get(query::qr))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit::rate_limit))
+        .route_layer(middleware::from_fn_with_state(state.clone(), metrics::meter_routes))
+        .route("/actions.json", get(get_actions_json))
+        .route("/metrics", get(get_metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .layer(actions_cors(allowed_origins))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|req: &axum::http::Request<_>| {
@@ -67,40 +145,206 @@ async fn get_actions_json(State(state): State<Arc<AppState>>) -> Json<ActionsJso
     Json(state.actions_json.clone())
 }
 
+/// Prometheus scrape endpoint. Like the health checks, never rate limited.
+async fn get_metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
+
+#[derive(Debug, Serialize)]
+struct Health {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    slot: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Liveness: the process is up. Never touches the RPC.
+async fn healthz() -> Json<Health> {
+    Json(Health {
+        status: "ok",
+        slot: None,
+        error: None,
+    })
+}
+
+/// Readiness: the configured RPC answers a get_slot, so a load balancer can
+/// stop routing to an instance whose actions would only fail with 500s
+async fn readyz(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Health>) {
+    match state.rpc.get_slot().await {
+        Ok(slot) => (
+            StatusCode::OK,
+            Json(Health {
+                status: "ok",
+                slot: Some(slot),
+                error: None,
+            }),
+        ),
+        Err(err) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(Health {
+                status: "unavailable",
+                slot: None,
+                error: Some(format!("RPC unreachable: {err}")),
+            }),
+        ),
+    }
+}
+
 async fn handle_action_get(
     Path(path): Path<String>,
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Query(params): Query<HashMap<String, String>>,
-) -> Result<Json<ActionGetResponse>, AppError> {
+) -> Result<Response, AppError> {
+    let version = requested_version(&headers);
     let action = state
         .registry
         .get(&path)
         .ok_or_else(|| AppError::NotFound(format!("Action not found: {path}")))?;
 
-    action
-        .metadata(&state.rpc, &state.base_url, params)
-        .await
-        .map(Json)
+    let started = Instant::now();
+    let result = action.metadata(&state.rpc, &state.base_url, params).await;
+    state.metrics.observe(action.path(), "get", started.elapsed(), &result);
+    Ok(versioned_response(&result?, "action", version))
 }
 
 async fn handle_action_post(
     Path(path): Path<String>,
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Query(params): Query<HashMap<String, String>>,
     Json(body): Json<ActionPostRequest>,
-) -> Result<Json<ActionPostResponse>, AppError> {
+) -> Result<Response, AppError> {
+    let version = requested_version(&headers);
     let action = state
         .registry
         .get(&path)
         .ok_or_else(|| AppError::NotFound(format!("Action not found: {path}")))?;
 
-    let account = body
-        .account
-        .parse()
-        .map_err(|_| AppError::BadRequest("Invalid account pubkey".into()))?;
+    let started = Instant::now();
+    let result = match body.account.parse() {
+        Ok(account) => action.execute(&state.rpc, &state.base_url, account, params).await,
+        Err(_) => Err(AppError::BadRequest("Invalid account pubkey".into())),
+    };
+    state.metrics.observe(action.path(), "post", started.elapsed(), &result);
+    Ok(versioned_response(&result?, "transaction", version))
+}
+
+/// The spec version negotiated from the wallet's X-Action-Version header;
+/// `None` when it sent none
+fn requested_version(headers: &HeaderMap) -> Option<ActionVersion> {
+    ActionVersion::negotiate(headers.get(ACTION_VERSION_HEADER).and_then(|v| v.to_str().ok()))
+}
+
+/// JSON body shaped for `version`, tagged with the version and chain headers
+/// wallets check (legacy requests are answered as the latest version)
+fn versioned_response<T: Serialize>(body: &T, response_type: &str, version: Option<ActionVersion>) -> Response {
+    let mut response = Json(versioned_body(body, response_type, version)).into_response();
+    let negotiated = version.unwrap_or(ActionVersion::LATEST).to_string();
+    let headers = response.headers_mut();
+    headers.insert(ACTION_VERSION_HEADER, HeaderValue::from_str(&negotiated).unwrap());
+    headers.insert(BLOCKCHAIN_IDS_HEADER, HeaderValue::from_static(CLUSTER.blockchain_id()));
+    response
+}
+
+/// A server on an unreachable (metered) RPC, limited to one request per
+/// minute, with no cache, simulation, priority fee or relayer. Tests change
+/// fields with struct update syntax.
+#[cfg(test)]
+impl RouterConfig {
+    pub fn mock() -> Self {
+        let metrics = Arc::new(Metrics::new());
+        RouterConfig {
+            rpc: Arc::new(metrics::metered_rpc_client("http://127.0.0.1:1".into(), metrics.clone())),
+            base_url: "http://localhost".into(),
+            allowed_origins: None,
+            rate_limiter: RateLimiter::new(1),
+            red_packet_cache: Arc::new(RedPacketCache::new(std::time::Duration::ZERO)),
+            simulate: false,
+            priority_fee: 0,
+            relayer: None,
+            metrics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn healthz_is_up_and_readyz_reports_an_unreachable_rpc() {
+        let app = build_router(RouterConfig::mock());
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        // Not rate limited, and no connect info needed
+        for _ in 0..2 {
+            let res = app.clone().oneshot(get("/healthz")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(&body[..], br#"{"status":"ok"}"#);
+        }
+
+        let res = app.oneshot(get("/readyz")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(res.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(body["status"], "unavailable");
+        assert!(body["error"].as_str().unwrap().starts_with("RPC unreachable"));
+    }
+
+    #[tokio::test]
+    async fn metrics_count_served_actions_by_outcome() {
+        let app = build_router(RouterConfig::mock());
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+        let creator = solana_sdk::pubkey::Pubkey::new_unique();
+
+        let res = app.clone().oneshot(get(&format!("/api/actions/claim?creator={creator}&id=1"))).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+        let res = app.clone().oneshot(get("/api/actions/claim?creator=nope&id=1")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        // Unknown actions aren't counted
+        let res = app.clone().oneshot(get("/api/actions/nope")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let res = app.oneshot(get("/metrics")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let text = std::str::from_utf8(&body).unwrap();
+        assert!(text.contains("blinks_action_requests_total{action=\"claim\",method=\"get\"} 2\n"));
+        assert!(text.contains("blinks_action_errors_total{action=\"claim\",method=\"get\",kind=\"rpc\"} 1\n"));
+        assert!(text.contains("blinks_action_errors_total{action=\"claim\",method=\"get\",kind=\"bad_request\"} 1\n"));
+        assert!(!text.contains("action=\"nope\""));
+
+        // Every rate-limited route by its pattern, and the RPC calls behind them
+        let actions = "route=\"/api/actions/{*path}\",method=\"GET\"";
+        assert!(text.contains(&format!("blinks_http_requests_total{{{actions},status=\"502\"}} 1\n")));
+        assert!(text.contains(&format!("blinks_http_requests_total{{{actions},status=\"404\"}} 1\n")));
+        assert!(text.contains(&format!("blinks_http_duration_seconds_count{{{actions}}} 3\n")));
+        assert!(text.contains("blinks_rpc_errors_total{method=\"getAccountInfo\"} 1\n"));
+        assert!(!text.contains("route=\"/metrics\""));
+    }
+
+    #[tokio::test]
+    async fn metrics_count_query_routes_and_their_rpc_calls() {
+        let app = build_router(RouterConfig::mock());
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let res = app.clone().oneshot(get("/api/treasury")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
 
-    action
-        .execute(&state.rpc, &state.base_url, account, params)
-        .await
-        .map(Json)
+        let res = app.oneshot(get("/metrics")).await.unwrap();
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let text = std::str::from_utf8(&body).unwrap();
+        let treasury = "route=\"/api/treasury\",method=\"GET\"";
+        assert!(text.contains(&format!("blinks_http_requests_total{{{treasury},status=\"502\"}} 1\n")));
+        assert!(text.contains("blinks_rpc_duration_seconds_count{method=\""));
+    }
 }
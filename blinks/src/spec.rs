@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ActionsJson {
@@ -195,26 +196,166 @@ pub struct ActionPostResponse {
     pub message: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub links: Option<NextActionLinks>,
+    /// Not part of the Actions spec: set by the create action so integrators
+    /// can track the new packet without parsing `message`
+    #[serde(rename = "redPacket", skip_serializing_if = "Option::is_none")]
+    pub red_packet: Option<CreatedRedPacket>,
+}
+
+/// Identifies the packet a create transaction will open. All fields are
+/// strings: `id` is decimal (u64, like the `id` query param), the addresses
+/// are base58.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedRedPacket {
+    pub id: String,
+    pub red_packet: String,
+    pub vault: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NextActionLinks {
-    pub next: NextAction,
+    pub next: NextActionLink,
 }
 
+/// What the wallet shows once the transaction confirms: `action` right away
+/// (inline), or whatever `href` answers when POSTed the signature
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
-pub enum NextAction {
+pub enum NextActionLink {
     Inline {
-        #[serde(flatten)]
-        action: ActionGetResponse,
+        action: NextAction,
     },
     Post {
         href: String,
     },
 }
 
+/// A chained action; wallets only render those tagged `"type": "action"`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum NextAction {
+    Action(ActionGetResponse),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActionError {
     pub message: String,
 }
+
+/// Request header a wallet names its Actions spec version in; responses echo
+/// the negotiated one
+pub const ACTION_VERSION_HEADER: &str = "x-action-version";
+/// Response header listing the CAIP-2 chains the actions transact on
+pub const BLOCKCHAIN_IDS_HEADER: &str = "x-blockchain-ids";
+
+/// Actions spec version, as carried by X-Action-Version ("major.minor")
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ActionVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl ActionVersion {
+    /// The newest spec version these actions implement
+    pub const LATEST: Self = Self { major: 2, minor: 4 };
+    /// First version with typed responses and `links.next` chaining
+    pub const TYPED_SINCE: Self = Self { major: 2, minor: 1 };
+
+    /// The version to answer in: the lower of the wallet's and ours. `None`
+    /// (no header, or one that doesn't parse) means the legacy shape.
+    pub fn negotiate(header: Option<&str>) -> Option<Self> {
+        let mut parts = header?.trim().split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().unwrap_or("0").parse().ok()?;
+        Some(Self { major, minor }.min(Self::LATEST))
+    }
+}
+
+impl fmt::Display for ActionVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// A GET (`"action"`) or POST (`"transaction"`) body shaped for `version`:
+/// typed versions get the spec's `type` field, while legacy and pre-chaining
+/// wallets get neither it nor `links.next`.
+pub fn versioned_body<T: Serialize>(
+    body: &T,
+    response_type: &str,
+    version: Option<ActionVersion>,
+) -> serde_json::Value {
+    let mut json = serde_json::to_value(body).expect("action responses are always serializable");
+    let Some(fields) = json.as_object_mut() else {
+        return json;
+    };
+    if version.is_some_and(|v| v >= ActionVersion::TYPED_SINCE) {
+        fields.insert("type".into(), response_type.into());
+    } else if let Some(links) = fields.get_mut("links").and_then(|links| links.as_object_mut()) {
+        links.remove("next");
+        if links.is_empty() {
+            fields.remove("links");
+        }
+    }
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(major: u16, minor: u16) -> Option<ActionVersion> {
+        Some(ActionVersion { major, minor })
+    }
+
+    #[test]
+    fn negotiates_down_to_the_lower_version() {
+        assert_eq!(ActionVersion::negotiate(None), None);
+        assert_eq!(ActionVersion::negotiate(Some("2.2")), version(2, 2));
+        assert_eq!(ActionVersion::negotiate(Some(" 2 ")), version(2, 0));
+        assert_eq!(ActionVersion::negotiate(Some("3.0.1")), Some(ActionVersion::LATEST));
+        assert_eq!(ActionVersion::negotiate(Some("latest")), None);
+        assert_eq!(ActionVersion::LATEST.to_string(), "2.4");
+    }
+
+    #[test]
+    fn typed_versions_tag_responses_and_keep_chaining() {
+        let post = ActionPostResponse {
+            transaction: "tx".into(),
+            message: None,
+            links: Some(NextActionLinks {
+                next: NextActionLink::Post { href: "/api/actions/next".into() },
+            }),
+            red_packet: None,
+        };
+
+        let typed = versioned_body(&post, "transaction", Some(ActionVersion::LATEST));
+        assert_eq!(typed["type"], "transaction");
+        assert_eq!(typed["links"]["next"]["href"], "/api/actions/next");
+
+        for legacy in [None, version(2, 0)] {
+            let json = versioned_body(&post, "transaction", legacy);
+            assert!(json.get("type").is_none());
+            assert!(json.get("links").is_none());
+        }
+
+        // Inline next actions carry their own type, whatever the version
+        let inline = NextActionLinks {
+            next: NextActionLink::Inline {
+                action: NextAction::Action(ActionGetResponse::new("icon", "Next", "Description", "Go")),
+            },
+        };
+        let json = serde_json::to_value(&inline).unwrap();
+        assert_eq!(json["next"]["type"], "inline");
+        assert_eq!(json["next"]["action"]["type"], "action");
+        assert_eq!(json["next"]["action"]["title"], "Next");
+
+        let get = ActionGetResponse::new("icon", "Title", "Description", "Go")
+            .with_links(vec![LinkedAction { href: "/go".into(), label: "Go".into(), parameters: None }]);
+        assert_eq!(versioned_body(&get, "action", version(2, 1))["type"], "action");
+        let legacy = versioned_body(&get, "action", None);
+        assert!(legacy.get("type").is_none());
+        assert_eq!(legacy["links"]["actions"][0]["href"], "/go");
+    }
+}
@@ -0,0 +1,25 @@
+//! Account role checks. Every account an instruction writes (lamports or
+//! data) must be passed writable, and accounts in different roles must be
+//! different accounts: a vault passed as the claimer would otherwise be
+//! debited and credited in the same lamport math.
+
+use pinocchio::{AccountView, ProgramResult};
+use crate::error::RedPacketError;
+
+/// Fails with AccountNotWritable unless every one of `accounts` is writable
+pub fn check_writable(accounts: &[&AccountView]) -> ProgramResult {
+    if accounts.iter().any(|account| !account.is_writable()) {
+        return Err(RedPacketError::AccountNotWritable.into());
+    }
+    Ok(())
+}
+
+/// Fails with DuplicateAccount if any two of `accounts` are the same account
+pub fn check_distinct(accounts: &[&AccountView]) -> ProgramResult {
+    for (i, account) in accounts.iter().enumerate() {
+        if accounts[i + 1..].iter().any(|other| other.address() == account.address()) {
+            return Err(RedPacketError::DuplicateAccount.into());
+        }
+    }
+    Ok(())
+}
@@ -1,58 +1,254 @@
-use pinocchio::Address;
+use pinocchio::{error::ProgramError, Address};
+#[cfg(not(feature = "fixed-rent"))]
+use pinocchio::sysvars::{rent::Rent, Sysvar};
 
-pub const ID: Address = Address::new_from_array(five8_const::decode_32_const(
-    "CeAkHjhJzgrwbg8QWQ8tx6h5UxMZVKuGBeEDYczbc6Gz",
-));
+#[cfg(all(feature = "devnet", feature = "mainnet"))]
+compile_error!("Enable only one cluster feature: `devnet` or `mainnet`");
+#[cfg(not(any(feature = "devnet", feature = "mainnet")))]
+compile_error!("Enable a cluster feature: `devnet` (the default) or `mainnet`");
+
+/// Devnet deployment: the default
+#[cfg(feature = "devnet")]
+mod cluster {
+    pub const PROGRAM_ID: &str = "CeAkHjhJzgrwbg8QWQ8tx6h5UxMZVKuGBeEDYczbc6Gz";
+    pub const ADMIN: &str = "HyBxuaafzKP6k4zkEDUp4LrZctS9mJVNUEEJBmp9cp7L";
+    pub const EVENT_AUTHORITY: &str = "BvGRmgiZL6kgHF6znJSnFSRU3bqU1mmCgeAWxicMAzLq";
+    pub const EVENT_AUTHORITY_BUMP: u8 = 254;
+    pub const FEE_RATE_BPS: u16 = 10;
+}
+
+/// Mainnet deployment (`--no-default-features --features mainnet`). Its
+/// addresses come from the build environment, so deploying with another
+/// keypair needs no source edit: REDPACKET_PROGRAM_ID, REDPACKET_ADMIN,
+/// REDPACKET_EVENT_AUTHORITY (the program's [EVENT_AUTHORITY_SEED] PDA)
+/// and REDPACKET_EVENT_AUTHORITY_BUMP.
+#[cfg(all(feature = "mainnet", not(feature = "devnet")))]
+mod cluster {
+    pub const PROGRAM_ID: &str = env!("REDPACKET_PROGRAM_ID");
+    pub const ADMIN: &str = env!("REDPACKET_ADMIN");
+    pub const EVENT_AUTHORITY: &str = env!("REDPACKET_EVENT_AUTHORITY");
+    pub const EVENT_AUTHORITY_BUMP: u8 = super::parse_u8(env!("REDPACKET_EVENT_AUTHORITY_BUMP"));
+    pub const FEE_RATE_BPS: u16 = 10;
+}
+
+/// A decimal u8 from the build environment, checked at compile time
+#[cfg(all(feature = "mainnet", not(feature = "devnet")))]
+const fn parse_u8(raw: &str) -> u8 {
+    let bytes = raw.as_bytes();
+    assert!(!bytes.is_empty(), "expected a decimal u8");
+    let mut value: u16 = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(bytes[i].is_ascii_digit(), "expected a decimal u8");
+        value = value * 10 + (bytes[i] - b'0') as u16;
+        assert!(value <= u8::MAX as u16, "expected a decimal u8");
+        i += 1;
+    }
+    value as u8
+}
+
+pub const ID: Address = Address::new_from_array(five8_const::decode_32_const(cluster::PROGRAM_ID));
 
 /// Seeds
 pub const SEED_PREFIX: &[u8] = b"redpacket";
 pub const VAULT_SEED: &[u8] = b"vault";
 pub const TREASURY_SEED: &[u8] = b"treasury";
 pub const TREASURY_VAULT_SEED: &[u8] = b"treasury_vault";
+pub const RECEIPT_SEED: &[u8] = b"receipt";
+/// Signs the self-CPI that carries events under `cpi-events`
+pub const EVENT_AUTHORITY_SEED: &[u8] = b"event_authority";
 
 /// Token types
 pub const TOKEN_TYPE_SPL: u8 = 0;
 pub const TOKEN_TYPE_SOL: u8 = 1;
 
 /// Limits
-pub const MAX_RECIPIENTS: u8 = 20;
+pub const MAX_RECIPIENTS: u8 = 100;
+/// Largest pot a packet may hold, across top-ups: half the u64 range, so
+/// the pot plus its fee and every running total stay far from overflow
+pub const MAX_TOTAL_AMOUNT: u64 = u64::MAX / 2;
+/// Large packets keep no claimers array, so only num_recipients' u8 caps them
+pub const MAX_RECIPIENTS_LARGE: u8 = u8::MAX;
+/// Furthest expiry create or extend_expiry accept, from the current time
+/// (365 days), so a typo can't lock funds away for centuries. Also the
+/// default max_expiry_secs and the most set_expiry_bounds allows.
+pub const MAX_EXPIRY_SECONDS: i64 = 365 * 24 * 60 * 60;
+/// Default min_expiry_secs: create refuses packets expiring sooner than this
+pub const MIN_EXPIRY_SECONDS: i64 = 60;
+
+/// Longest password preimage a claim may carry
+pub const MAX_SECRET_LEN: usize = 64;
+/// Longest creator memo (UTF-8 bytes) a packet may carry
+pub const MAX_MEMO_LEN: usize = 64;
 
 /// Discriminators
 pub const REDPACKET_DISCRIMINATOR: u8 = 1;
 pub const TREASURY_DISCRIMINATOR: u8 = 2;
+pub const RECEIPT_DISCRIMINATOR: u8 = 3;
+/// Written by close/cancel before the account is garbage-collected
+pub const CLOSED_DISCRIMINATOR: u8 = 0xFF;
+
+/// RedPacket layout version (offset 79). v0 packets predate fee_paid and the
+/// version byte itself; `migrate` upgrades older packets in place.
+/// v1: + fee_paid, version. v2: + allow_repeat. v3: + secret_hash.
+/// v4: + voucher_authority. v5: + gate_mint, gate_min_amount. v6: + assigned.
+/// v7: + created_at. v8: + reveal_commitment, reveal_deadline. v9: + winner.
+/// v10: + vesting_start. v11: + claimed_at per slot, after claimers.
+/// v12: + claimed_slots. v13: + memo_len, memo. v14: + burn_unclaimed.
+/// v15: + donate_unclaimed. v16: + original_creator.
+pub const REDPACKET_VERSION: u8 = 16;
+/// Program revision (major, minor, patch) get_version reports, next to
+/// REDPACKET_VERSION. Bump major whenever an instruction's data or accounts
+/// change incompatibly: clients built for another major refuse to run.
+pub const PROGRAM_VERSION: [u8; 3] = [2, 0, 0];
+/// First layout version with large packets (FLAG_LARGE)
+pub const REDPACKET_LARGE_SINCE: u8 = 6;
+/// First layout version with vesting packets (FLAG_VESTING)
+pub const REDPACKET_VESTING_SINCE: u8 = 10;
+/// First layout version recording when each slot was claimed
+pub const REDPACKET_CLAIMED_AT_SINCE: u8 = 11;
+
+/// Treasury layout version (offset 110). Treasuries without the byte (43 to
+/// 110 bytes) read as v1 and stay usable; `migrate_treasury` grows them to
+/// the current layout, as does any admin instruction that writes a newer field.
+pub const TREASURY_VERSION: u8 = 3;
+
+/// RedPacket flags (offset 80, the create data's flags byte). v2-v6 packets
+/// stored allow_repeat there as 0/1, which reads as FLAG_ALLOW_REPEAT.
+pub const FLAG_ALLOW_REPEAT: u8 = 1 << 0;
+/// The creator may not claim their own packet. Create sets it on every
+/// split mode unless the data carries FLAG_CREATOR_CAN_CLAIM.
+pub const FLAG_NO_SELF_CLAIM: u8 = 1 << 1;
+/// Large mode: claims are deduped by receipt PDAs instead of the claimers
+/// array, allowing up to MAX_RECIPIENTS_LARGE slots. Set at create only.
+pub const FLAG_LARGE: u8 = 1 << 2;
+/// Create data only, never stored: the creator may claim their own packet,
+/// of any split mode
+pub const FLAG_CREATOR_CAN_CLAIM: u8 = 1 << 5;
+/// Vesting: each slot unlocks linearly from vesting_start to expires_at, and
+/// its claimer claims again to collect what has unlocked since. Even and
+/// weighted splits only. Set at create only.
+pub const FLAG_VESTING: u8 = 1 << 7;
+pub const KNOWN_FLAGS: u8 = FLAG_ALLOW_REPEAT
+    | FLAG_NO_SELF_CLAIM
+    | FLAG_LARGE
+    | FLAG_CREATOR_CAN_CLAIM
+    | FLAG_VESTING;
+/// Random packets whose claims draw their amounts on-chain. Create sets it on
+/// every random packet and clients can't; random packets from before it pay
+/// the amounts their creator stored.
+pub const FLAG_RANDOM_ONCHAIN: u8 = 1 << 3;
+/// Set by reveal on commit-reveal packets: reveal_commitment now holds the
+/// creator's secret, and claims are open. Clients can't set it.
+pub const FLAG_REVEALED: u8 = 1 << 4;
+/// Set by the finalize that pays a lucky packet's jackpot. Clients can't set it.
+pub const FLAG_LUCKY_PAID: u8 = 1 << 6;
 
 /// Split modes
 pub const SPLIT_EVEN: u8 = 0;
 pub const SPLIT_RANDOM: u8 = 1;
+/// Fixed unequal amounts: create carries one u16 weight per slot
+pub const SPLIT_WEIGHTED: u8 = 2;
+/// Weighted splits' weights are basis points and must sum to this
+pub const WEIGHT_TOTAL_BPS: u32 = 10_000;
+/// Random amounts, except that one randomly placed slot also takes a
+/// LUCKY_SHARE_BPS jackpot of total_amount
+pub const SPLIT_LUCKY: u8 = 3;
+pub const LUCKY_SHARE_BPS: u16 = 6_000;
+/// On-chain random draws stay between these shares of the even split (basis
+/// points of what the draws share over num_recipients), so no slot is dust
+/// and none takes most of the pot
+pub const RANDOM_FLOOR_BPS: u16 = 1_000;
+pub const RANDOM_CEILING_BPS: u16 = 20_000;
+/// Claims only enter the draw; finalize pays the whole pot to one entrant
+pub const SPLIT_RAFFLE: u8 = 4;
+
+/// Create options (the create data byte after flags). Their own byte, since
+/// every bit of the flags byte means something once stored.
+/// close and partial_withdraw burn what is left in the vault instead of
+/// refunding it (stored as burn_unclaimed). SPL packets only.
+pub const CREATE_BURN_UNCLAIMED: u8 = 1 << 0;
+/// close and partial_withdraw pay what is left in the vault to the treasury
+/// as fees instead of refunding it (stored as donate_unclaimed). Not with
+/// CREATE_BURN_UNCLAIMED.
+pub const CREATE_DONATE_UNCLAIMED: u8 = 1 << 1;
+
+/// Claim options (claim data byte 2)
+/// Pay the destination account instead of the claimer
+pub const CLAIM_TO_DESTINATION: u8 = 1 << 0;
+/// Take exactly the slot the slot byte names (on assigned packets it must be
+/// one assigned to the claimer)
+pub const CLAIM_EXACT_SLOT: u8 = 1 << 1;
 
-/// Fee: 0.1% = 10 basis points
-pub const FEE_RATE_BPS: u64 = 10;
+/// Fee: 0.1% = 10 basis points on both clusters. Each treasury stores its
+/// own fee_bps; this is the default for init_treasury and for treasuries
+/// created before fee_bps was stored.
+pub const FEE_RATE_BPS: u16 = cluster::FEE_RATE_BPS;
+pub const MAX_FEE_BPS: u16 = 500; // 5% cap for init_treasury and update_fee
 pub const FEE_DENOMINATOR: u64 = 10_000;
 
 /// Account sizes
-pub const REDPACKET_BASE_SIZE: usize = 71;
-pub const PER_RECIPIENT_SIZE: usize = 40;
-pub const TREASURY_SIZE: usize = 43; // discriminator(1) + bump(1) + vault_bump(1) + mint(32) + sol_fees(8)
-pub const TOKEN_ACCOUNT_SIZE: usize = 165;
+/// Header size of every layout version, indexed by version
+pub const REDPACKET_BASE_SIZES: [usize; 17] =
+    [71, 80, 81, 113, 145, 185, 186, 194, 234, 266, 274, 274, 306, 371, 372, 373, 405];
+pub const REDPACKET_BASE_SIZE: usize = REDPACKET_BASE_SIZES[REDPACKET_VERSION as usize];
+/// Amount, claimer and claimed_at (before v11 only the first two: 40 bytes)
+pub const PER_RECIPIENT_SIZE: usize = 48;
+/// Large packets store only the amounts
+pub const PER_RECIPIENT_SIZE_LARGE: usize = 8;
+/// Vesting packets also store what each claimer has collected so far
+pub const PER_RECIPIENT_SIZE_VESTING: usize = 56;
+pub const TREASURY_SIZE: usize = 127; // discriminator(1) + bump(1) + vault_bump(1) + mint(32) + sol_fees(8) + fee_bps(2) + admin(32) + pending_admin(32) + paused(1) + version(1) + min_expiry(8) + max_expiry(8)
+pub const TREASURY_LEGACY_SIZE: usize = 43; // pre-fee_bps treasuries (45 bytes: pre-admin)
+pub const RECEIPT_SIZE: usize = 2; // discriminator(1) + bump(1)
+pub const TOKEN_ACCOUNT_SIZE: usize = 165; // both token programs, for mints `token::check_mint` accepts
 
-pub const fn redpacket_size(num_recipients: u8) -> usize {
-    REDPACKET_BASE_SIZE + PER_RECIPIENT_SIZE * num_recipients as usize
+pub const fn redpacket_size(num_recipients: u8, large: bool, vesting: bool) -> usize {
+    redpacket_layout_size(REDPACKET_VERSION, num_recipients, large, vesting)
 }
 
-/// Admin authority for fee withdrawal
-pub const ADMIN: Address = Address::new_from_array(five8_const::decode_32_const(
-    "HyBxuaafzKP6k4zkEDUp4LrZctS9mJVNUEEJBmp9cp7L",
-));
+/// Account size of a packet in layout `version`
+pub const fn redpacket_layout_size(version: u8, num_recipients: u8, large: bool, vesting: bool) -> usize {
+    let per_recipient = if large {
+        PER_RECIPIENT_SIZE_LARGE
+    } else {
+        let per_recipient = if vesting { PER_RECIPIENT_SIZE_VESTING } else { PER_RECIPIENT_SIZE };
+        if version < REDPACKET_CLAIMED_AT_SINCE {
+            per_recipient - 8
+        } else {
+            per_recipient
+        }
+    };
+    REDPACKET_BASE_SIZES[version as usize] + per_recipient * num_recipients as usize
+}
+
+/// Bootstrap admin: written into every new treasury, and the admin of
+/// treasuries created before the admin was stored (until handed over with
+/// propose_admin / accept_admin). Also gates non-default fees at init.
+pub const ADMIN: Address = Address::new_from_array(five8_const::decode_32_const(cluster::ADMIN));
+
+/// The PDA of [EVENT_AUTHORITY_SEED] and its bump: the only signer
+/// emit_event accepts
+pub const EVENT_AUTHORITY: Address = Address::new_from_array(five8_const::decode_32_const(cluster::EVENT_AUTHORITY));
+pub const EVENT_AUTHORITY_BUMP: u8 = cluster::EVENT_AUTHORITY_BUMP;
 
 /// Sentinel "mint" for native SOL treasury PDA derivation (not a real mint)
 pub const NATIVE_SOL_MINT: [u8; 32] = [0xFF; 32];
 
-/// Rent-exempt minimum: (data_len + 128) * 3480 * 2
-/// Based on DEFAULT_LAMPORTS_PER_BYTE_YEAR = 3480, exemption_threshold = 2.0
-/// These values have been stable since Solana genesis.
+/// Rent-exempt minimum for `data_len` bytes, read from the Rent sysvar so
+/// accounts are funded at the cluster's actual rate
+#[cfg(not(feature = "fixed-rent"))]
+#[inline(always)]
+pub fn rent_exempt(data_len: usize) -> Result<u64, ProgramError> {
+    Rent::get()?.try_minimum_balance(data_len)
+}
+
+/// With `fixed-rent`, for environments without the Rent sysvar: the genesis
+/// rate, (data_len + 128) * 3480 * 2
+#[cfg(feature = "fixed-rent")]
 #[inline(always)]
-pub const fn rent_exempt(data_len: usize) -> u64 {
-    ((data_len as u64) + 128) * 2 * 3480
+pub fn rent_exempt(data_len: usize) -> Result<u64, ProgramError> {
+    Ok(((data_len as u64) + 128) * 2 * 3480)
 }
 
 /// Well-known program IDs
@@ -60,3 +256,33 @@ pub const SYSTEM_PROGRAM_ID: Address = Address::new_from_array([0; 32]);
 pub const TOKEN_PROGRAM_ID: Address = Address::new_from_array(five8_const::decode_32_const(
     "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
 ));
+pub const TOKEN_2022_PROGRAM_ID: Address = Address::new_from_array(five8_const::decode_32_const(
+    "TokenzQdBNbLqP5VEhdkAS6EPFLC8PHnSnTg9gj8zfQ",
+));
+pub const ED25519_PROGRAM_ID: Address = Address::new_from_array(five8_const::decode_32_const(
+    "Ed25519SigVerify111111111111111111111111111",
+));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "devnet")]
+    #[test]
+    fn devnet_addresses_decode_to_the_deployed_keys() {
+        assert_eq!(ID.as_ref()[..8], [172, 246, 16, 253, 53, 197, 118, 170]);
+        assert_eq!(ADMIN.as_ref()[..8], [252, 31, 233, 190, 36, 167, 111, 157]);
+        assert_eq!(EVENT_AUTHORITY.as_ref()[..8], [162, 58, 24, 120, 239, 66, 62, 19]);
+        assert_eq!((EVENT_AUTHORITY_BUMP, FEE_RATE_BPS), (254, 10));
+    }
+
+    #[cfg(all(feature = "mainnet", not(feature = "devnet")))]
+    #[test]
+    fn mainnet_addresses_are_its_own() {
+        let devnet = five8_const::decode_32_const("CeAkHjhJzgrwbg8QWQ8tx6h5UxMZVKuGBeEDYczbc6Gz");
+        assert_ne!(ID.as_ref(), &devnet);
+        assert_ne!(ID, ADMIN);
+        assert_ne!(ID, EVENT_AUTHORITY);
+        assert!(FEE_RATE_BPS <= MAX_FEE_BPS);
+    }
+}
@@ -1,33 +1,75 @@
 use pinocchio::error::ProgramError;
 
-#[repr(u32)]
-pub enum RedPacketError {
-    InvalidAmount = 0,
-    InvalidRecipientCount = 1,
-    InvalidSplitMode = 2,
-    AlreadyClaimed = 3,
-    RedPacketFull = 4,
-    Expired = 5,
-    NotExpiredOrFull = 6,
-    Unauthorized = 7,
-    InvalidPDA = 8,
-    InvalidAccountOwner = 9,
-    InvalidDiscriminator = 10,
-    AmountMismatch = 11,
-    NotEnoughAccounts = 12,
-    UnauthorizedAdmin = 13,
-    TreasuryNotInitialized = 14,
-    InsufficientTreasuryBalance = 15,
-    TreasuryAlreadyInitialized = 16,
-    InvalidMint = 17,
-    InvalidTokenAccount = 18,
-    InvalidTokenProgram = 19,
-    InvalidSystemProgram = 20,
-    InvalidTokenType = 21,
+/// Declares `RedPacketError` with a message per variant, so a variant can't
+/// be added without one.
+macro_rules! red_packet_errors {
+    ($($variant:ident = $code:literal => $message:literal,)*) => {
+        /// Custom error codes, declared in error_table.rs
+        #[repr(u32)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum RedPacketError {
+            $($variant = $code,)*
+        }
+
+        impl RedPacketError {
+            /// What the error means for the user
+            pub const fn message(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $message,)*
+                }
+            }
+        }
+
+        /// The variant behind a `ProgramError::Custom` code; unknown codes
+        /// come back as the error.
+        impl TryFrom<u32> for RedPacketError {
+            type Error = u32;
+
+            fn try_from(code: u32) -> Result<Self, u32> {
+                match code {
+                    $($code => Ok(Self::$variant),)*
+                    _ => Err(code),
+                }
+            }
+        }
+    };
 }
 
+include!("error_table.rs");
+
 impl From<RedPacketError> for ProgramError {
     fn from(e: RedPacketError) -> Self {
         ProgramError::Custom(e as u32)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_code_round_trips_with_a_message() {
+        let mut count = 0;
+        while let Ok(error) = RedPacketError::try_from(count) {
+            assert_eq!(error as u32, count);
+            assert!(!error.message().is_empty());
+            count += 1;
+        }
+        assert_eq!(count, RedPacketError::CreatorMismatch as u32 + 1);
+        // Codes are contiguous: nothing past the first gap
+        assert!((count..1_000).all(|code| RedPacketError::try_from(code) == Err(code)));
+    }
+
+    #[test]
+    fn messages_are_the_ones_clients_show() {
+        assert_eq!(
+            RedPacketError::try_from(3).map(RedPacketError::message),
+            Ok("This wallet has already claimed this packet")
+        );
+        assert_eq!(ProgramError::from(RedPacketError::ProgramPaused), ProgramError::Custom(30));
+        assert_eq!(
+            RedPacketError::ProgramPaused.message(),
+            "Creates, top-ups and claims are paused for this mint"
+        );
+    }
+}
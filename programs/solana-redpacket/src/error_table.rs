@@ -0,0 +1,69 @@
+// The program's custom errors in code order, each with what it means for
+// the user. Not a module: error.rs includes it to declare RedPacketError, and
+// blinks/src/program_error.rs to describe failed transactions, each with its
+// own `red_packet_errors!`. New variants go at the end.
+red_packet_errors! {
+    InvalidAmount = 0 => "The amount is invalid",
+    InvalidRecipientCount = 1 => "The number of recipients is out of range",
+    InvalidSplitMode = 2 => "The split mode is invalid for this packet",
+    AlreadyClaimed = 3 => "This wallet has already claimed this packet",
+    RedPacketFull = 4 => "Every slot of this packet has been claimed",
+    Expired = 5 => "The packet (or its expiry) is in the past",
+    NotExpiredOrFull = 6 => "The packet is still open: it hasn't expired or been fully claimed",
+    Unauthorized = 7 => "Only the packet's creator can do this",
+    InvalidPDA = 8 => "An account isn't at the address the program derives",
+    InvalidAccountOwner = 9 => "An account is owned by the wrong program",
+    InvalidDiscriminator = 10 => "An account isn't a red packet",
+    AmountMismatch = 11 => "The amounts don't add up to the packet total",
+    NotEnoughAccounts = 12 => "The instruction is missing accounts",
+    UnauthorizedAdmin = 13 => "Only the treasury admin can do this",
+    TreasuryNotInitialized = 14 => "The treasury for this mint isn't initialized",
+    InsufficientTreasuryBalance = 15 => "The treasury doesn't hold that much in fees",
+    TreasuryAlreadyInitialized = 16 => "The treasury for this mint already exists",
+    InvalidMint = 17 => "The token mint doesn't match",
+    InvalidTokenAccount = 18 => "A token account has the wrong mint or owner",
+    InvalidTokenProgram = 19 => "The token program isn't Token or Token-2022",
+    InvalidSystemProgram = 20 => "The system program account is wrong",
+    InvalidTokenType = 21 => "The token type must be SOL or SPL and match the packet",
+    InvalidExpiry = 22 => "The new expiry is invalid",
+    InvalidFeeRate = 23 => "The fee rate is above the cap",
+    AccountClosed = 24 => "The packet has been closed",
+    AlreadyHasClaims = 25 => "The packet can't be cancelled once someone has claimed",
+    AlreadyWithdrawn = 26 => "There is nothing left to withdraw",
+    UnsupportedVersion = 27 => "The account layout is too new or too old; it may need migrating",
+    AlreadyMigrated = 28 => "The account is already on the current layout",
+    InvalidSlot = 29 => "That slot can't be claimed",
+    ProgramPaused = 30 => "Creates, top-ups and claims are paused for this mint",
+    TreasuryNotEmpty = 31 => "The treasury still holds fees",
+    InvalidDestination = 32 => "The payout destination is invalid",
+    InvalidSecret = 33 => "The password is wrong",
+    InvalidVoucher = 34 => "The claim voucher is missing or invalid",
+    GateNotSatisfied = 35 => "The claimer doesn't hold enough of the gate token",
+    NotAssignedRecipient = 36 => "This wallet isn't one of the packet's recipients",
+    ExpiryTooFar = 37 => "The expiry is further out than allowed",
+    UnsupportedMintExtension = 38 => "The mint uses an unsupported Token-2022 extension",
+    SelfClaimForbidden = 39 => "The creator can't claim this packet",
+    LargeModeUnsupported = 40 => "Large packets don't support this",
+    RedPacketStillOpen = 41 => "The packet is still open",
+    NotRevealed = 42 => "The creator hasn't revealed the packet's secret yet",
+    AlreadyRevealed = 43 => "The packet has no pending reveal",
+    InvalidWeights = 44 => "The weights must be non-zero and sum to 100%",
+    RaffleNotFinalized = 45 => "The raffle or lucky jackpot must be finalized first",
+    NoRaffleEntrants = 46 => "Nobody entered the raffle",
+    InvalidVesting = 47 => "The vesting schedule is invalid",
+    NothingVested = 48 => "Nothing has vested yet",
+    VestingOutstanding = 49 => "Claimers still have vested amounts to collect",
+    InvalidMemo = 50 => "The memo is too long or not UTF-8",
+    BurnUnsupported = 51 => "Only SPL packets can burn leftovers",
+    ExpiryTooSoon = 52 => "The expiry is sooner than allowed",
+    InsufficientForRent = 53 => "The wallet can't cover the packet, its rent and the fee",
+    DuplicateAccount = 54 => "The same account was passed in two roles",
+    AccountNotWritable = 55 => "An account the program writes was passed read-only",
+    VaultFrozen = 56 => "The packet's token vault is frozen",
+    AmountTooLarge = 57 => "The amount is above the largest pot a packet may hold",
+    InsufficientCreatorBalance = 58 => "The creator's token account can't cover the packet and the fee",
+    RedPacketAlreadyExists = 59 => "This creator already has a packet with that id",
+    InsufficientVaultBalance = 60 => "The packet's vault holds less than this claim pays",
+    InvalidEventAuthority = 61 => "Only the program's event authority can emit events",
+    CreatorMismatch = 62 => "Only the packet's current creator can hand it over",
+}
@@ -0,0 +1,195 @@
+//! Events for off-chain indexers (only built with `logging` or `cpi-events`).
+//!
+//! With `logging`, structured log lines, one event per line:
+//! `RP_<KIND> key=value key=value ...`. Amounts are in base units (lamports
+//! or token units), addresses in base58, so indexers can rebuild packet
+//! state from transaction logs without fetching accounts.
+//!
+//! Create, claim and close also log a fixed-layout binary event through
+//! sol_log_data (a `Program data: <base64>` line) for parsers that would
+//! rather not tokenize text: a tag byte, then the fields in order,
+//! integers little-endian and addresses as 32 raw bytes. The blinks server's
+//! `events` module decodes them; keep the two layouts in step.
+//!
+//! Logs are truncated once a transaction logs too much, so with `cpi-events`
+//! the binary events can also travel as instruction data: when the event
+//! authority and then the program itself are the last two accounts of a
+//! create, claim or close, the program invokes its own emit_event
+//! instruction with the event's bytes, signed by the event authority, and
+//! indexers read them from the transaction's inner instructions. Without
+//! those accounts nothing is invoked, so existing account layouts keep
+//! working. claim_batch's trailing accounts are its recipients, so batch
+//! claims are only logged.
+
+#[cfg(feature = "logging")]
+use pinocchio::error::ProgramError;
+#[cfg(feature = "cpi-events")]
+use pinocchio::{
+    cpi::{invoke_signed, Seed, Signer},
+    instruction::{InstructionAccount, InstructionView},
+};
+use pinocchio::{AccountView, ProgramResult};
+
+#[cfg(feature = "cpi-events")]
+use crate::constants::{EVENT_AUTHORITY, EVENT_AUTHORITY_BUMP, EVENT_AUTHORITY_SEED, ID};
+#[cfg(feature = "logging")]
+use crate::state::FixedBuf;
+
+/// Longest event (SPL RP_CREATE) is ~290 bytes
+#[cfg(feature = "logging")]
+const EVENT_CAPACITY: usize = 320;
+
+#[cfg(feature = "logging")]
+pub struct Event {
+    buf: FixedBuf<EVENT_CAPACITY>,
+}
+
+#[cfg(feature = "logging")]
+impl Event {
+    pub fn new(kind: &str) -> Result<Self, ProgramError> {
+        let mut buf = FixedBuf::new();
+        buf.extend_from_slice(kind.as_bytes())?;
+        Ok(Self { buf })
+    }
+
+    fn key(&mut self, key: &str) -> Result<(), ProgramError> {
+        self.buf.push(b' ')?;
+        self.buf.extend_from_slice(key.as_bytes())?;
+        self.buf.push(b'=')
+    }
+
+    pub fn u64(&mut self, key: &str, value: u64) -> Result<&mut Self, ProgramError> {
+        self.key(key)?;
+        self.buf.push_u64(value)?;
+        Ok(self)
+    }
+
+    pub fn i64(&mut self, key: &str, value: i64) -> Result<&mut Self, ProgramError> {
+        self.key(key)?;
+        self.buf.push_i64(value)?;
+        Ok(self)
+    }
+
+    pub fn address(&mut self, key: &str, address: &[u8]) -> Result<&mut Self, ProgramError> {
+        self.key(key)?;
+        self.buf.push_base58(address)?;
+        Ok(self)
+    }
+
+    pub fn emit(&self) {
+        crate::log(self.buf.as_str());
+    }
+}
+
+/// Discriminator of emit_event, the instruction the self-CPI calls
+pub const EMIT_EVENT_DISCRIMINATOR: u8 = 22;
+
+/// CreateEvent: tag, creator, id, total, num_recipients, split_mode, expires_at
+pub const CREATE_EVENT_LEN: usize = 1 + 32 + 8 + 8 + 1 + 1 + 8;
+pub const CREATE_EVENT_TAG: u8 = 0;
+/// ClaimEvent: tag, red_packet, claimer, slot, amount
+pub const CLAIM_EVENT_LEN: usize = 1 + 32 + 32 + 1 + 8;
+pub const CLAIM_EVENT_TAG: u8 = 1;
+/// CloseEvent: tag, red_packet, refunded
+pub const CLOSE_EVENT_LEN: usize = 1 + 32 + 8;
+pub const CLOSE_EVENT_TAG: u8 = 2;
+
+pub struct CreateEvent<'a> {
+    pub creator: &'a [u8],
+    pub id: u64,
+    pub total: u64,
+    pub num_recipients: u8,
+    pub split_mode: u8,
+    pub expires_at: i64,
+}
+
+impl CreateEvent<'_> {
+    pub fn to_bytes(&self) -> [u8; CREATE_EVENT_LEN] {
+        let mut data = [0u8; CREATE_EVENT_LEN];
+        data[0] = CREATE_EVENT_TAG;
+        data[1..33].copy_from_slice(self.creator);
+        data[33..41].copy_from_slice(&self.id.to_le_bytes());
+        data[41..49].copy_from_slice(&self.total.to_le_bytes());
+        data[49] = self.num_recipients;
+        data[50] = self.split_mode;
+        data[51..59].copy_from_slice(&self.expires_at.to_le_bytes());
+        data
+    }
+
+    pub fn emit(&self, accounts: &[AccountView]) -> ProgramResult {
+        publish(accounts, &self.to_bytes())
+    }
+}
+
+pub struct ClaimEvent<'a> {
+    pub red_packet: &'a [u8],
+    pub claimer: &'a [u8],
+    pub slot: u8,
+    pub amount: u64,
+}
+
+impl ClaimEvent<'_> {
+    pub fn to_bytes(&self) -> [u8; CLAIM_EVENT_LEN] {
+        let mut data = [0u8; CLAIM_EVENT_LEN];
+        data[0] = CLAIM_EVENT_TAG;
+        data[1..33].copy_from_slice(self.red_packet);
+        data[33..65].copy_from_slice(self.claimer);
+        data[65] = self.slot;
+        data[66..74].copy_from_slice(&self.amount.to_le_bytes());
+        data
+    }
+
+    pub fn emit(&self, accounts: &[AccountView]) -> ProgramResult {
+        publish(accounts, &self.to_bytes())
+    }
+}
+
+/// Also logged for cancels. `refunded` is what went back to the creator,
+/// excluding rent: 0 when the leftover was burned or donated instead.
+pub struct CloseEvent<'a> {
+    pub red_packet: &'a [u8],
+    pub refunded: u64,
+}
+
+impl CloseEvent<'_> {
+    pub fn to_bytes(&self) -> [u8; CLOSE_EVENT_LEN] {
+        let mut data = [0u8; CLOSE_EVENT_LEN];
+        data[0] = CLOSE_EVENT_TAG;
+        data[1..33].copy_from_slice(self.red_packet);
+        data[33..41].copy_from_slice(&self.refunded.to_le_bytes());
+        data
+    }
+
+    pub fn emit(&self, accounts: &[AccountView]) -> ProgramResult {
+        publish(accounts, &self.to_bytes())
+    }
+}
+
+/// Log `event`, and with `cpi-events` invoke emit_event with it if
+/// `accounts` end with the event authority and the program
+fn publish(_accounts: &[AccountView], event: &[u8]) -> ProgramResult {
+    crate::log_data(event);
+    #[cfg(feature = "cpi-events")]
+    {
+        let [.., authority, program] = _accounts else {
+            return Ok(());
+        };
+        if authority.address() != &EVENT_AUTHORITY || program.address() != &ID {
+            return Ok(());
+        }
+
+        let mut data = [0u8; 1 + CLAIM_EVENT_LEN];
+        data[0] = EMIT_EVENT_DISCRIMINATOR;
+        data[1..=event.len()].copy_from_slice(event);
+        let accounts = [InstructionAccount::readonly_signer(authority.address())];
+        let instruction = InstructionView {
+            program_id: &ID,
+            accounts: &accounts,
+            data: &data[..=event.len()],
+        };
+        let bump = [EVENT_AUTHORITY_BUMP];
+        let seeds = [Seed::from(EVENT_AUTHORITY_SEED), Seed::from(bump.as_ref())];
+        invoke_signed(&instruction, &[authority], &[Signer::from(&seeds)])?;
+    }
+    Ok(())
+}
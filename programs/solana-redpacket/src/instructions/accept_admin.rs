@@ -0,0 +1,37 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+use crate::log;
+use crate::constants::ID;
+use crate::accounts::check_writable;
+use crate::error::RedPacketError;
+use crate::state;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+///
+/// Accounts: pending_admin (signer), treasury (writable)
+///
+/// Second half of an admin handover: the nominee from propose_admin signs
+/// to become the treasury's admin, and the nomination is cleared.
+pub fn process_accept_admin(accounts: &[AccountView], _data: &[u8]) -> ProgramResult {
+    if accounts.len() < 2 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let pending_admin = &accounts[0];
+    let treasury = &accounts[1];
+
+    if !pending_admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut tr = state::load_treasury_mut(treasury, &ID)?;
+    check_writable(&[treasury])?;
+
+    if tr.pending_admin() != Some(pending_admin.address().as_ref()) {
+        return Err(RedPacketError::UnauthorizedAdmin.into());
+    }
+    tr.set_admin(pending_admin.address().as_ref());
+    tr.set_pending_admin(&[0u8; 32]);
+
+    log("Admin accepted");
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use pinocchio::{AccountView, ProgramResult};
+use crate::instructions::close::close_red_packet;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+/// [0] token_type: u8 (0=SPL, 1=SOL)
+///
+/// Accounts are the same as close. Succeeds before expiry as long as
+/// num_claimed == 0, returning the full vault balance and rent. Logs
+/// RP_CANCEL instead of RP_CLOSE.
+pub fn process_cancel(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    close_red_packet(accounts, data, true)
+}
@@ -1,30 +1,69 @@
 use pinocchio::{
-    cpi::{Seed, Signer},
+    cpi::{set_return_data, Seed, Signer},
     error::ProgramError,
-    sysvars::{clock::Clock, Sysvar},
+    sysvars::{clock::Clock, instructions::Instructions, Sysvar},
     AccountView, Address, ProgramResult,
 };
-use pinocchio_token::instructions::Transfer;
+use pinocchio_system::instructions::{Allocate, Assign, CreateAccount};
 use crate::log;
-use crate::constants::{ID, SEED_PREFIX, TOKEN_PROGRAM_ID, TOKEN_TYPE_SOL, VAULT_SEED};
+use crate::constants::{
+    rent_exempt, ED25519_PROGRAM_ID, ID,
+    NATIVE_SOL_MINT, RECEIPT_SEED, RECEIPT_SIZE, SEED_PREFIX, SYSTEM_PROGRAM_ID, TOKEN_TYPE_SOL, VAULT_SEED,
+};
+use crate::accounts::{check_distinct, check_writable};
 use crate::error::RedPacketError;
-use crate::state;
+use crate::ix_data::ClaimArgs;
+use crate::state::{self, RedPacketView};
+use crate::token::{self, Transfer};
 
 /// Instruction data layout:
 /// [0] discriminator (already consumed)
 /// [0] token_type: u8 (0=SPL, 1=SOL)
+/// [1] slot: u8 (optional, ignored unless CLAIM_EXACT_SLOT; claims take the
+///     lowest unclaimed slot, or the claimer's lowest unclaimed one on
+///     assigned packets)
+/// [2] options: u8 (optional, default 0; CLAIM_TO_DESTINATION = pay the
+///     destination account instead of the claimer, CLAIM_EXACT_SLOT = take
+///     exactly `slot`, which must be unclaimed, and on assigned packets
+///     assigned to the claimer; required on voucher packets, whose voucher
+///     signs the slot)
+/// [3..] secret: password preimage, up to MAX_SECRET_LEN bytes (only for
+///       password-protected packets)
+///
+/// Accounts:
+/// SOL: claimer, red_packet, vault, treasury (4)
+/// SPL: claimer, claimer_token_account, red_packet, vault, token_program, treasury (6)
+/// Then, only when needed and in this order: the instructions sysvar (voucher
+/// packets), the claimer's gate-mint token account (gated packets), the
+/// destination (has_destination; a system account for SOL, a token account
+/// of the packet's mint for SPL, which then replaces claimer_token_account).
+/// Large packets end with the claimer's receipt PDA and the system program;
+/// the claimer (writable) pays the receipt's rent.
+///
+/// The signer is still the one recorded in claimers (or receipted), so a
+/// destination doesn't get around one-claim-per-wallet. On-chain random
+/// packets draw the amount here (see `random_amount`) and record it in the slot;
+/// a lucky packet's jackpot is left out of the draws for finalize to hand to
+/// one claimed slot (see `RedPacketView::lucky_jackpot`). Raffle slots hold 0,
+/// so a claim only enters the claimer for finalize's draw. Commit-reveal
+/// packets fail with NotRevealed until the creator reveals.
+///
+/// On vesting packets a wallet's first claim takes a slot like any other and
+/// pays what has unlocked so far (possibly nothing). Claiming again with the
+/// same signer pays what unlocked since, or fails with NothingVested; such a
+/// claim works even after expiry or once the packet is full, and skips the
+/// password, voucher and gate checks its first claim passed, so it carries
+/// no extra accounts but an optional destination.
+///
+/// Sets return data to the amount paid (u64 LE) and the slot (u8).
 pub fn process_claim(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
-    // Parse token type
-    if data.is_empty() {
-        return Err(ProgramError::InvalidInstructionData);
-    }
-    let token_type = data[0];
+    let ClaimArgs { token_type, exact_slot, has_destination, secret } = ClaimArgs::try_from(data)?;
     state::validate_token_type(token_type)?;
 
     let is_sol = token_type == TOKEN_TYPE_SOL;
 
     // Parse accounts based on token type
-    let min_accounts = if is_sol { 3 } else { 5 };
+    let min_accounts = if is_sol { 4 } else { 6 };
     if accounts.len() < min_accounts {
         return Err(RedPacketError::NotEnoughAccounts.into());
     }
@@ -32,51 +71,53 @@ pub fn process_claim(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
     let claimer;
     let red_packet;
     let vault;
+    let treasury;
 
     if is_sol {
         claimer = &accounts[0];
         red_packet = &accounts[1];
         vault = &accounts[2];
+        treasury = &accounts[3];
     } else {
         claimer = &accounts[0];
         // accounts[1] = claimer_token_account (used later)
         red_packet = &accounts[2];
         vault = &accounts[3];
         // accounts[4] = token_program (used later)
+        treasury = &accounts[5];
 
         // Validate token program
-        if accounts[4].address() != &TOKEN_PROGRAM_ID {
-            return Err(RedPacketError::InvalidTokenProgram.into());
-        }
+        token::validate_token_program(&accounts[4])?;
     }
 
     // Validate claimer is signer
     if !claimer.is_signer() {
         return Err(ProgramError::MissingRequiredSignature);
     }
-
-    // Validate red packet account
-    state::validate_redpacket(red_packet, &ID)?;
+    check_writable(&[red_packet, vault])?;
+    // Only SPL claims pass a claimer_token_account (accounts[1])
+    let roles = [claimer, red_packet, vault, treasury, &accounts[1]];
+    check_distinct(if is_sol { &roles[..4] } else { &roles })?;
 
     // Read state, perform checks, and verify vault PDA
-    let (amount, num_recipients, num_claimed, bump, creator_bytes, id_bytes) = {
-        let rp_data = red_packet.try_borrow()?;
+    let (amount, slot, held, last_claim, bump, creator_bytes, id_bytes, recipient, receipt, now) = {
+        let mut rp = state::load_redpacket_mut(red_packet, &ID)?;
 
         // Verify token_type matches stored state
-        if state::get_token_type(&rp_data) != token_type {
+        if rp.token_type() != token_type {
             return Err(RedPacketError::InvalidTokenType.into());
         }
 
-        let num_recipients = state::get_num_recipients(&rp_data);
-        let num_claimed = state::get_num_claimed(&rp_data);
-        let expires_at = state::get_expires_at(&rp_data);
-        let bump = state::get_bump(&rp_data);
-        let vault_bump = state::get_vault_bump(&rp_data);
+        let num_recipients = rp.num_recipients();
+        let num_claimed = rp.num_claimed();
+        let expires_at = rp.expires_at();
+        let bump = rp.bump();
+        let vault_bump = rp.vault_bump();
 
-        // Get creator and id for PDA verification
+        // Get the seed creator and id for PDA verification
         let mut creator_bytes = [0u8; 32];
-        creator_bytes.copy_from_slice(state::get_creator(&rp_data));
-        let id = state::get_id(&rp_data);
+        creator_bytes.copy_from_slice(rp.original_creator());
+        let id = rp.id();
         let id_bytes = id.to_le_bytes();
 
         // Verify vault PDA
@@ -90,47 +131,211 @@ pub fn process_claim(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
             return Err(RedPacketError::InvalidPDA.into());
         }
 
-        // Check not expired
+        // A vesting claimer coming back for its own slot
+        let vesting = rp.vesting();
+        let held = if vesting {
+            rp.find_claimed_slot(claimer.address().as_ref())
+        } else {
+            None
+        };
+
         let clock = Clock::get()?;
-        if clock.unix_timestamp >= expires_at {
-            return Err(RedPacketError::Expired.into());
-        }
+        let large = rp.large();
+        let mut extra_accounts = accounts[min_accounts..].iter();
+        // Checked once the slot is known
+        let mut voucher = None;
+
+        if held.is_none() {
+            // Check not expired
+            if clock.unix_timestamp >= expires_at {
+                return Err(RedPacketError::Expired.into());
+            }
+
+            // Check not full
+            if num_claimed >= num_recipients {
+                return Err(RedPacketError::RedPacketFull.into());
+            }
 
-        // Check not full
-        if num_claimed >= num_recipients {
-            return Err(RedPacketError::RedPacketFull.into());
+            // Commit-reveal packets stay shut until the creator reveals
+            if rp.pending_commitment().is_some() {
+                return Err(RedPacketError::NotRevealed.into());
+            }
+
+            if rp.no_self_claim() && claimer.address().as_ref() == rp.creator() {
+                return Err(RedPacketError::SelfClaimForbidden.into());
+            }
+
+            // Check not already claimed (unless the creator allowed repeat
+            // claims, in which case the wallet simply takes another slot).
+            // Large packets check the receipt instead, below.
+            if !large
+                && !rp.allow_repeat()
+                && rp.has_claimed(claimer.address().as_ref())
+            {
+                return Err(RedPacketError::AlreadyClaimed.into());
+            }
+
+            // Password-protected packets need the preimage of the stored hash
+            if let Some(secret_hash) = rp.secret_hash() {
+                if secret.is_empty() {
+                    return Err(RedPacketError::InvalidSecret.into());
+                }
+                if crate::sha256(secret) != secret_hash {
+                    return Err(RedPacketError::InvalidSecret.into());
+                }
+            }
+
+            // Voucher packets need the authority's signature for this exact
+            // claim, so the claim must name the slot the voucher approves
+            if let Some(authority) = rp.voucher_authority() {
+                let sysvar = extra_accounts
+                    .next()
+                    .ok_or(RedPacketError::NotEnoughAccounts)?;
+                if exact_slot.is_none() {
+                    return Err(RedPacketError::InvalidVoucher.into());
+                }
+                let mut authority_bytes = [0u8; 32];
+                authority_bytes.copy_from_slice(authority);
+                voucher = Some((sysvar, authority_bytes));
+            }
+
+            // Gated packets need the claimer to hold enough of the gate mint
+            if let Some((gate_mint, min_amount)) = rp.gate() {
+                let gate_account = extra_accounts
+                    .next()
+                    .ok_or(RedPacketError::NotEnoughAccounts)?;
+                check_gate(gate_account, gate_mint, min_amount, claimer.address())?;
+            }
         }
 
-        // Check not already claimed
-        if state::has_claimed(&rp_data, num_recipients, num_claimed, claimer.address().as_ref()) {
-            return Err(RedPacketError::AlreadyClaimed.into());
+        // Where the payout goes: the claimer (or its token account) by default
+        let recipient = if has_destination {
+            let destination = extra_accounts
+                .next()
+                .ok_or(RedPacketError::NotEnoughAccounts)?;
+            // An SPL vault paying itself would pass the token program but
+            // still book the payout, leaving the vault unable to close
+            check_distinct(&[destination, red_packet, vault, treasury])?;
+            check_destination(destination, vault, is_sol)?;
+            destination
+        } else if is_sol {
+            claimer
+        } else {
+            &accounts[1]
+        };
+
+        check_writable(&[recipient])?;
+
+        let receipt = if large {
+            let receipt = extra_accounts
+                .next()
+                .ok_or(RedPacketError::NotEnoughAccounts)?;
+            let system_program = extra_accounts
+                .next()
+                .ok_or(RedPacketError::NotEnoughAccounts)?;
+            if system_program.address() != &SYSTEM_PROGRAM_ID {
+                return Err(RedPacketError::InvalidSystemProgram.into());
+            }
+            // The claimer pays the receipt's rent
+            check_writable(&[claimer, receipt])?;
+            Some(receipt)
+        } else {
+            None
+        };
+
+        // The slot named, or else the lowest unclaimed one; assigned packets
+        // only pay the claimer's own slots
+        let slot = match (held, exact_slot) {
+            (Some(slot), _) => slot,
+            (None, Some(exact)) => {
+                if exact >= num_recipients {
+                    return Err(ProgramError::InvalidInstructionData);
+                }
+                if rp.is_claimed(exact) {
+                    return Err(RedPacketError::AlreadyClaimed.into());
+                }
+                if rp.assigned()
+                    && rp.claimer_at(exact) != claimer.address().as_ref()
+                {
+                    return Err(RedPacketError::NotAssignedRecipient.into());
+                }
+                exact
+            }
+            (None, None) if rp.assigned() => {
+                rp.find_assigned_slot(claimer.address().as_ref())
+                    .ok_or(RedPacketError::NotAssignedRecipient)?
+            }
+            (None, None) => rp.unclaimed_slots()
+                .next()
+                .ok_or(RedPacketError::RedPacketFull)?,
+        };
+
+        if let Some((sysvar, authority)) = voucher {
+            check_voucher(sysvar, &authority, red_packet.address(), claimer.address(), slot)?;
         }
 
-        let amount = state::get_amount_at(&rp_data, num_claimed);
+        let amount = if vesting {
+            let unlocked = state::vested_amount(
+                rp.amount_at(slot),
+                rp.vesting_start(),
+                expires_at,
+                clock.unix_timestamp,
+            );
+            // An extended expiry can leave unlocked below taken for a while
+            let taken = rp.taken_at(slot);
+            let amount = unlocked.saturating_sub(taken);
+            if held.is_some() && amount == 0 {
+                return Err(RedPacketError::NothingVested.into());
+            }
+            rp.set_taken_at(slot, taken + amount);
+            amount
+        } else if rp.random_onchain() {
+            let draw = match rp.revealed_secret() {
+                Some(secret) => reveal_draw(secret, num_claimed),
+                None => slot_draw(red_packet.address(), num_claimed, clock.slot),
+            };
+            // A lucky packet's jackpot stays in the vault for finalize
+            let amount = random_amount(
+                rp.remaining_amount()
+                    .checked_sub(rp.lucky_jackpot())
+                    .ok_or(ProgramError::ArithmeticOverflow)?,
+                num_recipients - num_claimed,
+                rp.random_bounds(),
+                u64::from_le_bytes(draw[..8].try_into().unwrap()),
+            );
+            rp.set_amount_at(slot, amount);
+            amount
+        } else {
+            rp.amount_at(slot)
+        };
+
+        // The claim that fills a (non-vesting) packet pays out the rest
+        let last_claim = held.is_none() && !vesting && num_claimed + 1 == num_recipients;
+
+        (amount, slot, held, last_claim, bump, creator_bytes, id_bytes, recipient, receipt, clock.unix_timestamp)
+    }; // drop mutable borrow
 
-        (amount, num_recipients, num_claimed, bump, creator_bytes, id_bytes)
-    }; // drop immutable borrow
+    check_claims_open(treasury, vault, is_sol)?;
+
+    if let Some(receipt) = receipt {
+        create_receipt(receipt, claimer, red_packet)?;
+    }
 
     // Transfer based on token type
-    if is_sol {
+    let paid = if is_sol {
         // Verify vault is owned by our program (defense-in-depth)
         if !vault.owned_by(&ID) {
             return Err(RedPacketError::InvalidAccountOwner.into());
         }
 
-        // Direct lamport transfer: vault -> claimer
-        vault.set_lamports(
-            vault.lamports()
-                .checked_sub(amount)
-                .ok_or(ProgramError::ArithmeticOverflow)?,
-        );
-        claimer.set_lamports(
-            claimer.lamports()
-                .checked_add(amount)
-                .ok_or(ProgramError::ArithmeticOverflow)?,
-        );
+        // Direct lamport transfer: vault -> claimer (or destination)
+        let payout = sol_payout(vault, amount, last_claim)?;
+        pay_from_sol_vault(vault, recipient, payout)?;
+        payout
     } else {
-        // SPL Transfer: vault -> claimer_token_account (red_packet PDA signs)
+        // SPL Transfer: vault -> claimer_token_account or destination
+        // (red_packet PDA signs). check_claims_open matched the vault's mint.
+        token::check_vault(vault, red_packet, None)?;
         let bump_bytes = [bump];
         let rp_seeds = [
             Seed::from(SEED_PREFIX),
@@ -142,34 +347,415 @@ pub fn process_claim(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
 
         Transfer {
             from: vault,
-            to: &accounts[1], // claimer_token_account
+            to: recipient,
             authority: red_packet,
             amount,
+            token_program: accounts[4].address(),
         }
         .invoke_signed(&rp_signer)?;
-    }
+        amount
+    };
 
     // Update state
     {
-        let mut rp_data = red_packet.try_borrow_mut()?;
-
-        state::set_claimer_at(
-            &mut rp_data,
-            num_recipients,
-            num_claimed,
-            claimer.address().as_ref(),
-        );
-
-        state::set_num_claimed(&mut rp_data, num_claimed + 1);
-        let remaining = state::get_remaining_amount(&rp_data);
-        state::set_remaining_amount(
-            &mut rp_data,
-            remaining
-                .checked_sub(amount)
-                .ok_or(ProgramError::ArithmeticOverflow)?,
-        );
-    }
-
-    log("Claimed");
+        let mut rp = RedPacketView::try_from(red_packet.try_borrow_mut()?)?;
+
+        if held.is_none() {
+            if receipt.is_none() {
+                rp.set_claimer_at(slot, claimer.address().as_ref());
+                rp.set_claimed_at(slot, now);
+            }
+            rp.set_claimed(slot);
+        }
+        // A clamped last claim leaves what the vault lacked on the books;
+        // close sweeps the vault either way
+        let remaining = rp.remaining_amount();
+        rp.set_remaining_amount(remaining.checked_sub(paid).ok_or(ProgramError::ArithmeticOverflow)?);
+    }
+
+    #[cfg(feature = "logging")]
+    {
+        crate::event::Event::new("RP_CLAIM")?
+            .u64("id", u64::from_le_bytes(id_bytes))?
+            .address("creator", &creator_bytes)?
+            .u64("slot", slot as u64)?
+            .u64("amount", paid)?
+            .address("claimer", claimer.address().as_ref())?
+            .emit();
+    }
+    #[cfg(any(feature = "logging", feature = "cpi-events"))]
+    crate::event::ClaimEvent {
+        red_packet: red_packet.address().as_ref(),
+        claimer: claimer.address().as_ref(),
+        slot,
+        amount: paid,
+    }
+    .emit(accounts)?;
+
+    // Return data: what this claim paid (u64 LE) then its slot, so clients
+    // simulating or confirming it know a random amount without diffing
+    // balances. Set last, after the event self-CPI, so nothing replaces it.
+    let mut paid_data = [0u8; 9];
+    paid_data[..8].copy_from_slice(&paid.to_le_bytes());
+    paid_data[8] = slot;
+    set_return_data(&paid_data);
+
+    Ok(())
+}
+
+/// An on-chain random packet's payout given a slot's `draw`: within the
+/// packet's (floor, ceiling) `bounds` and at most twice the average of
+/// what's left, always leaving each later slot enough to stay in bounds too,
+/// and the exact remainder for the last one. Bounds a top-up (or a draw
+/// from before they existed) has put out of reach give way, down to 1 unit
+/// per later slot.
+fn random_amount(remaining: u64, slots_left: u8, bounds: (u64, u64), draw: u64) -> u64 {
+    let slots_left = slots_left as u64;
+    if slots_left <= 1 {
+        return remaining;
+    }
+
+    let floor = bounds.0.min(remaining / slots_left).max(1);
+    let ceiling = bounds.1.max(remaining.div_ceil(slots_left));
+    let low = floor.max(remaining.saturating_sub(ceiling.saturating_mul(slots_left - 1)));
+    let high = ceiling
+        .min(2 * (remaining / slots_left))
+        .min(remaining.saturating_sub(floor * (slots_left - 1)))
+        .max(low);
+    low + draw % (high - low + 1)
+}
+
+/// Draw bytes for claim `num_claimed` (counting from 0, whichever slot it
+/// takes): sha256(clock slot || red_packet || num_claimed), the first 8 of
+/// which size the amount. Nothing about it is fixed at create, so the creator
+/// can't line the big slot up for themselves, but a claimer sees the draw as
+/// the claim runs: one that follows the claim with a balance check in the
+/// same transaction (or claims through a CPI wrapper) can abort and retry
+/// until it draws near the 2x cap, at the cost of failed fees, and a leader
+/// can nudge the slot. Hence nothing bigger rides on it: lucky jackpots are
+/// drawn once by finalize after claims close, and packets whose split must
+/// not be steerable use commit-reveal (`reveal_draw`).
+fn slot_draw(red_packet: &Address, num_claimed: u8, slot: u64) -> [u8; 32] {
+    let mut seed = [0u8; 41];
+    seed[..8].copy_from_slice(&slot.to_le_bytes());
+    seed[8..40].copy_from_slice(red_packet.as_ref());
+    seed[40] = num_claimed;
+    crate::sha256(&seed)
+}
+
+/// Draw bytes for claim `num_claimed` of a commit-reveal packet, from
+/// sha256(secret || num_claimed): fixed by the commitment, so neither
+/// claimers nor leaders can steer it
+fn reveal_draw(secret: &[u8], num_claimed: u8) -> [u8; 32] {
+    let mut seed = [0u8; 33];
+    seed[..32].copy_from_slice(secret);
+    seed[32] = num_claimed;
+    crate::sha256(&seed)
+}
+
+/// Create the claimer's receipt for a large packet, failing with
+/// AlreadyClaimed if it exists. The address must be the canonical-bump PDA,
+/// or another bump would give the same wallet a second receipt. Lamports
+/// already sent to the address (which makes CreateAccount fail) count toward
+/// the rent instead of blocking the claim.
+fn create_receipt(receipt: &AccountView, claimer: &AccountView, red_packet: &AccountView) -> ProgramResult {
+    let (expected_receipt, bump) = Address::find_program_address(
+        &[RECEIPT_SEED, red_packet.address().as_ref(), claimer.address().as_ref()],
+        &ID,
+    );
+    if receipt.address() != &expected_receipt {
+        return Err(RedPacketError::InvalidPDA.into());
+    }
+    if state::is_receipt(receipt, &ID)? {
+        return Err(RedPacketError::AlreadyClaimed.into());
+    }
+
+    let bump_bytes = [bump];
+    let receipt_seeds = [
+        Seed::from(RECEIPT_SEED),
+        Seed::from(red_packet.address().as_ref()),
+        Seed::from(claimer.address().as_ref()),
+        Seed::from(bump_bytes.as_ref()),
+    ];
+    let receipt_signer = [Signer::from(&receipt_seeds)];
+
+    let rent = rent_exempt(RECEIPT_SIZE)?;
+    if receipt.lamports() == 0 {
+        CreateAccount {
+            from: claimer,
+            to: receipt,
+            lamports: rent,
+            space: RECEIPT_SIZE as u64,
+            owner: &ID,
+        }
+        .invoke_signed(&receipt_signer)?;
+    } else {
+        let shortfall = rent.saturating_sub(receipt.lamports());
+        if shortfall > 0 {
+            pinocchio_system::instructions::Transfer {
+                from: claimer,
+                to: receipt,
+                lamports: shortfall,
+            }
+            .invoke()?;
+        }
+        Allocate {
+            account: receipt,
+            space: RECEIPT_SIZE as u64,
+        }
+        .invoke_signed(&receipt_signer)?;
+        Assign {
+            account: receipt,
+            owner: &ID,
+        }
+        .invoke_signed(&receipt_signer)?;
+    }
+
+    state::init_receipt(&mut receipt.try_borrow_mut()?, bump);
     Ok(())
 }
+
+/// A voucher is an ed25519 program instruction right before the claim in which
+/// `authority` signs red_packet (32) || claimer (32) || slot (1), the slot the
+/// claim takes (voucher claims name it with CLAIM_EXACT_SLOT), so on weighted
+/// packets the voucher approves an amount too. The ed25519
+/// program already failed the transaction if the signature is bad, so only
+/// the key and message it checked are compared here. Its offsets must all
+/// point into its own data (instruction index u16::MAX), as web3.js builds it.
+fn check_voucher(
+    sysvar: &AccountView,
+    authority: &[u8],
+    red_packet: &Address,
+    claimer: &Address,
+    slot: u8,
+) -> ProgramResult {
+    let instructions = Instructions::try_from(sysvar)?;
+    let ix = instructions
+        .get_instruction_relative(-1)
+        .map_err(|_| ProgramError::from(RedPacketError::InvalidVoucher))?;
+    if ix.get_program_id() != &ED25519_PROGRAM_ID {
+        return Err(RedPacketError::InvalidVoucher.into());
+    }
+
+    // [0] num_signatures, [1] padding, then per signature: signature_offset,
+    // signature_ix, public_key_offset, public_key_ix, message_offset,
+    // message_size, message_ix (u16 each)
+    let data = ix.get_instruction_data();
+    if data.len() < 16 || data[0] != 1 {
+        return Err(RedPacketError::InvalidVoucher.into());
+    }
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    if read_u16(4) != u16::MAX || read_u16(8) != u16::MAX || read_u16(14) != u16::MAX {
+        return Err(RedPacketError::InvalidVoucher.into());
+    }
+    let pubkey_offset = read_u16(6) as usize;
+    let message_offset = read_u16(10) as usize;
+    let message_len = read_u16(12) as usize;
+
+    let mut expected = [0u8; 65];
+    expected[..32].copy_from_slice(red_packet.as_ref());
+    expected[32..64].copy_from_slice(claimer.as_ref());
+    expected[64] = slot;
+
+    let pubkey = data.get(pubkey_offset..pubkey_offset + 32);
+    let message = data.get(message_offset..message_offset + message_len);
+    if pubkey != Some(authority) || message != Some(&expected[..]) {
+        return Err(RedPacketError::InvalidVoucher.into());
+    }
+    Ok(())
+}
+
+/// `gate_account` must be a token account (either token program) for `gate_mint`, owned by the
+/// claimer and holding at least `min_amount`.
+fn check_gate(
+    gate_account: &AccountView,
+    gate_mint: &[u8],
+    min_amount: u64,
+    claimer: &Address,
+) -> ProgramResult {
+    if !token::owned_by_token_program(gate_account) {
+        return Err(RedPacketError::InvalidAccountOwner.into());
+    }
+
+    // Token account: mint 0..32, owner 32..64, amount 64..72
+    let tdata = gate_account.try_borrow()?;
+    if tdata.len() < 72 {
+        return Err(RedPacketError::InvalidTokenAccount.into());
+    }
+    let amount = u64::from_le_bytes(tdata[64..72].try_into().unwrap());
+    if &tdata[0..32] != gate_mint || &tdata[32..64] != claimer.as_ref() || amount < min_amount {
+        return Err(RedPacketError::GateNotSatisfied.into());
+    }
+    Ok(())
+}
+
+/// A claim destination must be a plain wallet for SOL (the vault, the packet
+/// and other program accounts are refused) or an SPL token account of the
+/// vault's mint other than the vault itself (process_claim checks that).
+/// `vault` must already be verified.
+fn check_destination(destination: &AccountView, vault: &AccountView, is_sol: bool) -> ProgramResult {
+    if is_sol {
+        if !destination.owned_by(&SYSTEM_PROGRAM_ID) {
+            return Err(RedPacketError::InvalidDestination.into());
+        }
+        return Ok(());
+    }
+
+    if !token::owned_by_token_program(destination) {
+        return Err(RedPacketError::InvalidDestination.into());
+    }
+    let ddata = destination.try_borrow()?;
+    if ddata.len() < 72 {
+        return Err(RedPacketError::InvalidTokenAccount.into());
+    }
+    let vdata = vault.try_borrow()?;
+    if vdata.len() < 32 || ddata[0..32] != vdata[0..32] {
+        return Err(RedPacketError::InvalidMint.into());
+    }
+    Ok(())
+}
+
+/// Move `amount` lamports out of a SOL vault. The vault keeps its
+/// rent-exempt minimum until close drains it, so a payout that would dip
+/// into it fails with InsufficientForRent instead of leaving the vault to be
+/// garbage-collected with the packet still open.
+pub(crate) fn pay_from_sol_vault(vault: &AccountView, to: &AccountView, amount: u64) -> ProgramResult {
+    check_writable(&[vault, to])?;
+    let floor = rent_exempt(0)?;
+    let left = vault
+        .lamports()
+        .checked_sub(amount)
+        .filter(|&left| left >= floor)
+        .ok_or(RedPacketError::InsufficientForRent)?;
+    vault.set_lamports(left);
+    to.set_lamports(
+        to.lamports()
+            .checked_add(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?,
+    );
+    Ok(())
+}
+
+/// What a SOL claim of `amount` pays from `vault`. A vault holding less
+/// above its rent floor than the packet records (it never should) fails
+/// with InsufficientVaultBalance, except on the `last_claim`, which takes
+/// whatever is there so the packet can still fill and close. Either way
+/// both values are logged for operators.
+fn sol_payout(vault: &AccountView, amount: u64, last_claim: bool) -> Result<u64, ProgramError> {
+    let available = vault.lamports().saturating_sub(rent_exempt(0)?);
+    if available >= amount {
+        return Ok(amount);
+    }
+
+    log("Vault holds less than the packet records");
+    #[cfg(feature = "logging")]
+    {
+        crate::event::Event::new("RP_VAULT_SHORT")?
+            .u64("available", available)?
+            .u64("recorded", amount)?
+            .emit();
+    }
+    if !last_claim {
+        return Err(RedPacketError::InsufficientVaultBalance.into());
+    }
+    Ok(available)
+}
+
+/// Claims stop while the packet's treasury is paused. Only init_treasury
+/// creates treasuries (one PDA per mint), so a valid treasury storing the
+/// packet's mint is the right one. `vault` must already be verified.
+pub(crate) fn check_claims_open(
+    treasury: &AccountView,
+    vault: &AccountView,
+    is_sol: bool,
+) -> ProgramResult {
+    let tr = state::load_treasury(treasury, &ID)?;
+
+    let treasury_mint = tr.mint();
+    let mint_matches = if is_sol {
+        treasury_mint == NATIVE_SOL_MINT.as_ref()
+    } else {
+        // SPL vaults are token accounts: mint is bytes 0..32
+        let vdata = vault.try_borrow()?;
+        vdata.len() >= 32 && &vdata[0..32] == treasury_mint
+    };
+    if !mint_matches {
+        return Err(RedPacketError::InvalidMint.into());
+    }
+
+    if tr.paused() {
+        return Err(RedPacketError::ProgramPaused.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{RANDOM_CEILING_BPS, RANDOM_FLOOR_BPS};
+    use crate::state::weighted_amount;
+
+    /// The (floor, ceiling) a fresh packet of `total` over `n` slots draws within
+    fn fresh_bounds(total: u64, n: u8) -> (u64, u64) {
+        let share = total / n as u64;
+        (weighted_amount(share, RANDOM_FLOOR_BPS), weighted_amount(share, RANDOM_CEILING_BPS))
+    }
+
+    /// Every slot's payout as claims fill the packet, claim `i` drawing `draw(i)`
+    fn split(total: u64, n: u8, bounds: (u64, u64), draw: impl Fn(u8) -> u64) -> Vec<u64> {
+        let mut remaining = total;
+        (0..n)
+            .map(|claimed| {
+                let amount = random_amount(remaining, n - claimed, bounds, draw(claimed));
+                remaining -= amount;
+                amount
+            })
+            .collect()
+    }
+
+    #[test]
+    fn random_split_is_reproducible_and_sums_to_the_total() {
+        for seed in 0..64u8 {
+            let red_packet = Address::new_from_array([seed; 32]);
+            let draw = |claimed| u64::from_le_bytes(slot_draw(&red_packet, claimed, 7)[..8].try_into().unwrap());
+            let (total, n) = (1_000_000 + seed as u64 * 7_919, 1 + seed % 20);
+            let bounds = fresh_bounds(total, n);
+
+            let amounts = split(total, n, bounds, draw);
+            assert_eq!(amounts, split(total, n, bounds, draw));
+            assert_eq!(amounts.len(), n as usize);
+            assert_eq!(amounts.iter().sum::<u64>(), total);
+            let (floor, ceiling) = (bounds.0.max(1), bounds.1.max(total / n as u64));
+            assert!(amounts.iter().all(|&amount| (floor..=ceiling).contains(&amount)));
+        }
+    }
+
+    #[test]
+    fn every_slot_gets_a_unit_and_the_last_the_exact_remainder() {
+        let draws: [fn(u8) -> u64; 3] = [
+            |_| 0,
+            |_| u64::MAX,
+            |claimed| (claimed as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15),
+        ];
+        for n in [1u8, 2, 3, 7, 20, 21, 100, 255] {
+            let n64 = n as u64;
+            for total in [n64, n64 + 1, 2 * n64 - 1, 1_000, 123_457, 999_999_999, 1_000_000_000] {
+                if total < n64 {
+                    continue;
+                }
+                // Fresh bounds, bounds a tenfold top-up left behind, and a
+                // draw from before bounds were stored
+                for bounds in [fresh_bounds(total, n), fresh_bounds(total / 10, n), (0, 0)] {
+                    for draw in draws {
+                        let amounts = split(total, n, bounds, draw);
+                        assert_eq!(amounts.iter().sum::<u64>(), total, "n={n} total={total}");
+                        assert!(amounts.iter().all(|&amount| amount >= 1), "n={n} total={total}");
+                        let (last, before) = amounts.split_last().unwrap();
+                        let remainder = total - before.iter().sum::<u64>();
+                        assert_eq!((random_amount(remainder, 1, bounds, draw(n - 1)), *last), (remainder, remainder));
+                    }
+                }
+            }
+        }
+    }
+}
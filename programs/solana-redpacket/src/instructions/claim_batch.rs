@@ -0,0 +1,236 @@
+use pinocchio::{
+    cpi::{Seed, Signer},
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, Address, ProgramResult,
+};
+use crate::constants::{
+    ID, MAX_RECIPIENTS, SEED_PREFIX, SPLIT_EVEN, TOKEN_TYPE_SOL, VAULT_SEED,
+};
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::instructions::claim::{check_claims_open, pay_from_sol_vault};
+use crate::state;
+use crate::token::{self, Transfer};
+
+/// Instruction data layout:
+/// [0]     discriminator (already consumed)
+/// [0]     token_type: u8 (0=SPL, 1=SOL)
+/// [1..]   slot: u8 per recipient, in account order
+///
+/// Accounts:
+/// SOL: creator (signer), red_packet, vault, treasury, recipient...
+/// SPL: creator (signer), red_packet, vault, token_program, treasury, recipient_token_account...
+///
+/// The creator hands out several even-split slots in one transaction, each
+/// named by its slot byte, which must be an unclaimed slot (and not repeat
+/// within the batch): a batch built against stale state fails with
+/// InvalidSlot instead of landing on other slots. SPL
+/// recipients are recorded as the token account's owner, matching claim.
+/// On assigned packets each recipient must be the one assigned to its slot,
+/// and with FLAG_NO_SELF_CLAIM none may be the creator. Large packets can't be
+/// batched: each recipient would need its own receipt. Neither can vesting
+/// packets, whose slots pay out over time rather than in full.
+/// Password, voucher and token-gate checks are skipped here: they gate
+/// strangers, and only the creator can batch.
+pub fn process_claim_batch(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.len() < 2 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let token_type = data[0];
+    state::validate_token_type(token_type)?;
+    let slots = &data[1..];
+    if slots.len() > MAX_RECIPIENTS as usize {
+        return Err(RedPacketError::RedPacketFull.into());
+    }
+    let count = slots.len() as u8;
+
+    let is_sol = token_type == TOKEN_TYPE_SOL;
+
+    let fixed_accounts = if is_sol { 4 } else { 5 };
+    if accounts.len() < fixed_accounts + slots.len() {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+
+    let creator = &accounts[0];
+    let red_packet = &accounts[1];
+    let vault = &accounts[2];
+    if !is_sol {
+        token::validate_token_program(&accounts[3])?;
+    }
+    let treasury = &accounts[fixed_accounts - 1];
+    let recipients = &accounts[fixed_accounts..fixed_accounts + slots.len()];
+
+    if !creator.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_writable(&[red_packet, vault])?;
+    check_distinct(&[creator, red_packet, vault, treasury])?;
+    // Recipients may repeat (repeat-claim packets) or be the creator, but
+    // never the packet's own accounts
+    for recipient in recipients {
+        check_writable(&[recipient])?;
+        check_distinct(&[recipient, red_packet, vault, treasury])?;
+    }
+
+    // Check everything and record every claimer under one borrow
+    let mut amounts = [0u64; MAX_RECIPIENTS as usize];
+    let (bump, creator_bytes, id_bytes) = {
+        let mut rp = state::load_redpacket_mut(red_packet, &ID)?;
+
+        if rp.token_type() != token_type {
+            return Err(RedPacketError::InvalidTokenType.into());
+        }
+        if rp.creator() != creator.address().as_ref() {
+            return Err(RedPacketError::Unauthorized.into());
+        }
+        if rp.split_mode() != SPLIT_EVEN {
+            return Err(RedPacketError::InvalidSplitMode.into());
+        }
+        if rp.large() {
+            return Err(RedPacketError::LargeModeUnsupported.into());
+        }
+        if rp.vesting() {
+            return Err(RedPacketError::InvalidVesting.into());
+        }
+
+        let num_recipients = rp.num_recipients();
+        let num_claimed = rp.num_claimed();
+        let allow_repeat = rp.allow_repeat();
+        let no_self_claim = rp.no_self_claim();
+        let assigned = rp.assigned();
+        let id_bytes = rp.id().to_le_bytes();
+        let mut creator_bytes = [0u8; 32];
+        creator_bytes.copy_from_slice(rp.original_creator());
+
+        let vault_bump_bytes = [rp.vault_bump()];
+        let expected_vault = Address::create_program_address(
+            &[VAULT_SEED, &creator_bytes, &id_bytes, &vault_bump_bytes],
+            &ID,
+        )
+        .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+        if vault.address() != &expected_vault {
+            return Err(RedPacketError::InvalidPDA.into());
+        }
+        check_claims_open(treasury, vault, is_sol)?;
+
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= rp.expires_at() {
+            return Err(RedPacketError::Expired.into());
+        }
+        if count > num_recipients - num_claimed {
+            return Err(RedPacketError::RedPacketFull.into());
+        }
+
+        let mut total = 0u64;
+        for (i, recipient) in recipients.iter().enumerate() {
+            let slot = slots[i];
+            if slot >= num_recipients || rp.is_claimed(slot) {
+                return Err(RedPacketError::InvalidSlot.into());
+            }
+
+            let claimer = recipient_wallet(recipient, is_sol)?;
+            if no_self_claim && claimer == creator.address().as_ref() {
+                return Err(RedPacketError::SelfClaimForbidden.into());
+            }
+            if assigned && rp.claimer_at(slot) != claimer.as_ref() {
+                return Err(RedPacketError::NotAssignedRecipient.into());
+            }
+
+            // Earlier slots of this batch are always off-limits; earlier
+            // claims only when the creator didn't allow repeats
+            for &j in &slots[..i] {
+                if rp.claimer_at(j) == claimer.as_ref() {
+                    return Err(RedPacketError::AlreadyClaimed.into());
+                }
+            }
+            if !allow_repeat && rp.has_claimed(&claimer) {
+                return Err(RedPacketError::AlreadyClaimed.into());
+            }
+
+            rp.set_claimer_at(slot, &claimer);
+            rp.set_claimed_at(slot, clock.unix_timestamp);
+            rp.set_claimed(slot);
+            amounts[i] = rp.amount_at(slot);
+            total = total
+                .checked_add(amounts[i])
+                .ok_or(ProgramError::ArithmeticOverflow)?;
+        }
+
+        let remaining = rp.remaining_amount();
+        rp.set_remaining_amount(remaining.checked_sub(total).ok_or(ProgramError::ArithmeticOverflow)?);
+
+        (rp.bump(), creator_bytes, id_bytes)
+    }; // drop mutable borrow (SPL transfers are signed by red_packet)
+
+    if is_sol {
+        if !vault.owned_by(&ID) {
+            return Err(RedPacketError::InvalidAccountOwner.into());
+        }
+
+        for (recipient, &amount) in recipients.iter().zip(amounts.iter()) {
+            pay_from_sol_vault(vault, recipient, amount)?;
+        }
+    } else {
+        let bump_bytes = [bump];
+        let rp_seeds = [
+            Seed::from(SEED_PREFIX),
+            Seed::from(creator_bytes.as_ref()),
+            Seed::from(id_bytes.as_ref()),
+            Seed::from(bump_bytes.as_ref()),
+        ];
+        let rp_signer = [Signer::from(&rp_seeds)];
+
+        token::check_vault(vault, red_packet, None)?;
+        for (recipient, &amount) in recipients.iter().zip(amounts.iter()) {
+            Transfer {
+                from: vault,
+                to: recipient,
+                authority: red_packet,
+                amount,
+                token_program: accounts[3].address(),
+            }
+            .invoke_signed(&rp_signer)?;
+        }
+    }
+
+    // One RP_CLAIM per slot, same as individual claims
+    #[cfg(feature = "logging")]
+    {
+        for (i, recipient) in recipients.iter().enumerate() {
+            let claimer = recipient_wallet(recipient, is_sol)?;
+            crate::event::Event::new("RP_CLAIM")?
+                .u64("id", u64::from_le_bytes(id_bytes))?
+                .address("creator", creator.address().as_ref())?
+                .u64("slot", slots[i] as u64)?
+                .u64("amount", amounts[i])?
+                .address("claimer", &claimer)?
+                .emit();
+            crate::event::ClaimEvent {
+                red_packet: red_packet.address().as_ref(),
+                claimer: &claimer,
+                slot: slots[i],
+                amount: amounts[i],
+            }
+            .emit(&[])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The wallet a batch recipient is recorded as: the account itself for SOL,
+/// the token account's owner (bytes 32..64) for SPL
+fn recipient_wallet(recipient: &AccountView, is_sol: bool) -> Result<[u8; 32], ProgramError> {
+    let mut wallet = [0u8; 32];
+    if is_sol {
+        wallet.copy_from_slice(recipient.address().as_ref());
+    } else {
+        let tdata = recipient.try_borrow()?;
+        if tdata.len() < 72 {
+            return Err(RedPacketError::InvalidTokenAccount.into());
+        }
+        wallet.copy_from_slice(&tdata[32..64]);
+    }
+    Ok(wallet)
+}
@@ -4,21 +4,48 @@ use pinocchio::{
     sysvars::{clock::Clock, Sysvar},
     AccountView, Address, ProgramResult,
 };
-use pinocchio_token::instructions::{CloseAccount, Transfer};
-use crate::log;
-use crate::constants::{ID, SEED_PREFIX, TOKEN_PROGRAM_ID, TOKEN_TYPE_SOL, VAULT_SEED};
+use crate::constants::{
+    rent_exempt, ID, NATIVE_SOL_MINT, SEED_PREFIX, TOKEN_TYPE_SOL, TREASURY_SEED, TREASURY_VAULT_SEED, VAULT_SEED,
+};
+use crate::accounts::{check_distinct, check_writable};
 use crate::error::RedPacketError;
-use crate::state;
+use crate::instructions::claim::pay_from_sol_vault;
+use crate::ix_data::CloseArgs;
+use crate::state::{self, RedPacketView, TreasuryView};
+use crate::token::{self, Burn, CloseAccount, Transfer};
 
 /// Instruction data layout:
 /// [0] discriminator (already consumed)
 /// [0] token_type: u8 (0=SPL, 1=SOL)
 pub fn process_close(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
-    // Parse token type
-    if data.is_empty() {
-        return Err(ProgramError::InvalidInstructionData);
-    }
-    let token_type = data[0];
+    close_red_packet(accounts, data, false)
+}
+
+/// Shared by close and cancel: same accounts and data, and both refund the
+/// vault + rent to the creator and tombstone the red packet. Close requires
+/// the packet to be expired or fully claimed (or its reveal deadline missed);
+/// cancel works at any time but only before the first claim. Raffles with
+/// entrants only close once finalize has paid the pot out, and vesting
+/// packets once their claimers have collected every claimed slot in full
+/// (partial_withdraw takes back the unclaimed rest meanwhile).
+///
+/// SOL: creator, red_packet, vault, then the SOL treasury (writable) on
+///      donate_unclaimed packets (3-4)
+/// SPL: creator, creator_token_account, red_packet, vault, token_program,
+///      then the vault's mint (writable) on burn_unclaimed packets, or the
+///      mint's treasury and treasury_vault (writable) on donate_unclaimed
+///      ones (5-7)
+///
+/// burn_unclaimed packets burn the remaining tokens instead of refunding
+/// them, and donate_unclaimed packets pay them to the treasury as fees,
+/// unless cancelled before expiry. The extra accounts are only needed while
+/// something remains; the vault and packet rent still come back.
+pub(crate) fn close_red_packet(
+    accounts: &[AccountView],
+    data: &[u8],
+    cancel: bool,
+) -> ProgramResult {
+    let CloseArgs { token_type } = CloseArgs::try_from(data)?;
     state::validate_token_type(token_type)?;
 
     let is_sol = token_type == TOKEN_TYPE_SOL;
@@ -44,43 +71,43 @@ pub fn process_close(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
         vault = &accounts[3];
         // accounts[4] = token_program (used later)
 
-        if accounts[4].address() != &TOKEN_PROGRAM_ID {
-            return Err(RedPacketError::InvalidTokenProgram.into());
-        }
+        token::validate_token_program(&accounts[4])?;
     }
 
     // Validate creator is signer
     if !creator.is_signer() {
         return Err(ProgramError::MissingRequiredSignature);
     }
-
-    // Validate red packet account
-    state::validate_redpacket(red_packet, &ID)?;
+    // The creator takes the vault and packet lamports
+    check_writable(&[creator, red_packet, vault])?;
+    // Only SPL closes pass a creator_token_account (accounts[1])
+    let roles = [creator, red_packet, vault, &accounts[1]];
+    check_distinct(if is_sol { &roles[..3] } else { &roles })?;
 
     // Read state and check authorization
-    let (bump, creator_bytes, id_bytes, remaining_amount) = {
-        let rp_data = red_packet.try_borrow()?;
+    let (bump, creator_bytes, id_bytes, remaining_amount, expected_vault, leftover) = {
+        let rp = state::load_redpacket(red_packet, &ID)?;
 
         // Verify token_type matches stored state
-        if state::get_token_type(&rp_data) != token_type {
+        if rp.token_type() != token_type {
             return Err(RedPacketError::InvalidTokenType.into());
         }
 
         // Verify creator matches
-        if state::get_creator(&rp_data) != creator.address().as_ref() {
+        if rp.creator() != creator.address().as_ref() {
             return Err(RedPacketError::Unauthorized.into());
         }
 
-        let num_recipients = state::get_num_recipients(&rp_data);
-        let num_claimed = state::get_num_claimed(&rp_data);
-        let expires_at = state::get_expires_at(&rp_data);
-        let bump = state::get_bump(&rp_data);
-        let vault_bump = state::get_vault_bump(&rp_data);
-        let remaining_amount = state::get_remaining_amount(&rp_data);
+        let num_recipients = rp.num_recipients();
+        let num_claimed = rp.num_claimed();
+        let expires_at = rp.expires_at();
+        let bump = rp.bump();
+        let vault_bump = rp.vault_bump();
+        let remaining_amount = rp.remaining_amount();
 
         let mut creator_bytes = [0u8; 32];
-        creator_bytes.copy_from_slice(state::get_creator(&rp_data));
-        let id = state::get_id(&rp_data);
+        creator_bytes.copy_from_slice(rp.original_creator());
+        let id = rp.id();
         let id_bytes = id.to_le_bytes();
 
         // Verify vault PDA
@@ -94,16 +121,41 @@ pub fn process_close(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
             return Err(RedPacketError::InvalidPDA.into());
         }
 
-        // Must be either fully claimed or expired
-        let all_claimed = num_claimed >= num_recipients;
-        let clock = Clock::get()?;
-        let is_expired = clock.unix_timestamp >= expires_at;
+        if cancel {
+            // Cancel: nothing claimed yet, so the full pot goes back
+            if num_claimed > 0 {
+                return Err(RedPacketError::AlreadyHasClaims.into());
+            }
+        } else {
+            // Must be fully claimed, expired, or a commit-reveal packet
+            // its creator let the reveal deadline pass on (nothing claimed)
+            let all_claimed = num_claimed >= num_recipients;
+            let clock = Clock::get()?;
+            let is_expired = clock.unix_timestamp >= expires_at;
+            let reveal_lapsed = rp.pending_commitment().is_some()
+                && clock.unix_timestamp >= rp.reveal_deadline();
 
-        if !all_claimed && !is_expired {
-            return Err(RedPacketError::NotExpiredOrFull.into());
+            if !all_claimed && !is_expired && !reveal_lapsed {
+                return Err(RedPacketError::NotExpiredOrFull.into());
+            }
+            // Entrants are owed the draw (the raffle pot or lucky jackpot),
+            // so it can't come back first
+            if rp.draw_unpaid() {
+                return Err(RedPacketError::RaffleNotFinalized.into());
+            }
+            if rp.vesting_owed() > 0 {
+                return Err(RedPacketError::VestingOutstanding.into());
+            }
         }
 
-        (bump, creator_bytes, id_bytes, remaining_amount)
+        // A cancel before expiry just undoes the create, so it refunds
+        let leftover = if cancel && Clock::get()?.unix_timestamp < expires_at {
+            Leftover::Refund
+        } else {
+            leftover(rp.as_view())
+        };
+
+        (bump, creator_bytes, id_bytes, remaining_amount, expected_vault, leftover)
     }; // drop immutable borrow
 
     if is_sol {
@@ -111,8 +163,22 @@ pub fn process_close(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
         if !vault.owned_by(&ID) {
             return Err(RedPacketError::InvalidAccountOwner.into());
         }
+        // Checked again where lamports move: no token program stands in
+        // between, so a program-owned account with other seeds (another
+        // packet's vault, say) must never be drained into this creator
+        if vault.address() != &expected_vault {
+            return Err(RedPacketError::InvalidPDA.into());
+        }
 
-        // Transfer ALL vault lamports to creator (remaining_amount + rent)
+        // A donation takes remaining_amount to the treasury first, or what
+        // the vault holds above rent when a clamped last claim left it short
+        let donation = remaining_amount.min(vault.lamports().saturating_sub(rent_exempt(0)?));
+        if leftover == Leftover::Donate && donation > 0 {
+            let treasury = accounts.get(3).ok_or(RedPacketError::NotEnoughAccounts)?;
+            donate_lamports(vault, treasury, donation)?;
+        }
+
+        // Transfer ALL other vault lamports to creator (remaining_amount + rent)
         let vault_lamports = vault.lamports();
         if vault_lamports > 0 {
             creator.set_lamports(
@@ -123,6 +189,8 @@ pub fn process_close(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
             vault.set_lamports(0);
         }
     } else {
+        token::check_vault(vault, red_packet, None)?;
+
         // Build red_packet PDA signer for SPL operations
         let bump_bytes = [bump];
         let rp_seeds = [
@@ -133,15 +201,9 @@ pub fn process_close(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
         ];
         let rp_signer = [Signer::from(&rp_seeds)];
 
-        // Transfer remaining USDC from vault to creator's token account
+        // Return (or burn or donate) the remaining tokens
         if remaining_amount > 0 {
-            Transfer {
-                from: vault,
-                to: &accounts[1], // creator_token_account
-                authority: red_packet,
-                amount: remaining_amount,
-            }
-            .invoke_signed(&rp_signer)?;
+            refund_tokens(accounts, vault, red_packet, remaining_amount, leftover, &rp_signer)?;
         }
 
         // Close vault token account (SOL rent goes to creator)
@@ -149,6 +211,7 @@ pub fn process_close(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
             account: vault,
             destination: creator,
             authority: red_packet,
+            token_program: accounts[4].address(),
         }
         .invoke_signed(&rp_signer)?;
     }
@@ -163,14 +226,176 @@ pub fn process_close(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
     );
     red_packet.set_lamports(0);
 
-    // Zero out account data
+    // Zero out account data and tombstone it, so a claim later in the
+    // same transaction fails with AccountClosed
     {
-        let mut rp_data = red_packet.try_borrow_mut()?;
-        for byte in rp_data.iter_mut() {
-            *byte = 0;
+        let mut rp = RedPacketView::try_from(red_packet.try_borrow_mut()?)?;
+
+        // Last chance to read the final state; `refunded` excludes rent
+        #[cfg(feature = "logging")]
+        {
+            crate::event::Event::new(if cancel { "RP_CANCEL" } else { "RP_CLOSE" })?
+                .u64("id", rp.id())?
+                .address("creator", &creator_bytes)?
+                .u64(
+                    match leftover {
+                        Leftover::Refund => "refunded",
+                        Leftover::Burn => "burned",
+                        Leftover::Donate => "donated",
+                    },
+                    remaining_amount,
+                )?
+                .u64("claimed", rp.num_claimed() as u64)?
+                .emit();
         }
+
+        rp.data_mut().fill(0);
+        rp.tombstone();
+    }
+
+    #[cfg(any(feature = "logging", feature = "cpi-events"))]
+    crate::event::CloseEvent {
+        red_packet: red_packet.address().as_ref(),
+        refunded: match leftover {
+            Leftover::Refund => remaining_amount,
+            Leftover::Burn | Leftover::Donate => 0,
+        },
     }
+    .emit(accounts)?;
+
+    Ok(())
+}
+
+/// Where the unclaimed balance that close or partial_withdraw takes out of
+/// the vault goes
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Leftover {
+    Refund,
+    Burn,
+    Donate,
+}
+
+/// What the packet chose at create (burn_unclaimed, donate_unclaimed)
+pub(crate) fn leftover(rp: RedPacketView<&[u8]>) -> Leftover {
+    if rp.burn_unclaimed() {
+        Leftover::Burn
+    } else if rp.donate_unclaimed() {
+        Leftover::Donate
+    } else {
+        Leftover::Refund
+    }
+}
 
-    log("Closed");
+/// Move `amount` from an SPL vault to the creator's token account
+/// (accounts[1]), burn it (the vault's mint follows token_program, at
+/// accounts[5]) or pay it to the mint's treasury_vault (treasury and
+/// treasury_vault at accounts[5..7]). Shared with partial_withdraw.
+pub(crate) fn refund_tokens(
+    accounts: &[AccountView],
+    vault: &AccountView,
+    red_packet: &AccountView,
+    amount: u64,
+    leftover: Leftover,
+    signer: &[Signer],
+) -> ProgramResult {
+    let token_program = accounts[4].address();
+    match leftover {
+        Leftover::Refund => {
+            check_writable(&[&accounts[1]])?;
+            Transfer {
+                from: vault,
+                to: &accounts[1], // creator_token_account
+                authority: red_packet,
+                amount,
+                token_program,
+            }
+            .invoke_signed(signer)
+        }
+        Leftover::Burn => {
+            let mint = accounts.get(5).ok_or(RedPacketError::NotEnoughAccounts)?;
+            check_writable(&[mint])?;
+            if vault.try_borrow()?.get(..32) != Some(mint.address().as_ref()) {
+                return Err(RedPacketError::InvalidMint.into());
+            }
+            Burn {
+                account: vault,
+                mint,
+                authority: red_packet,
+                amount,
+                token_program,
+            }
+            .invoke_signed(signer)
+        }
+        Leftover::Donate => {
+            let (treasury, treasury_vault) = match accounts.get(5..7) {
+                Some([treasury, treasury_vault]) => (treasury, treasury_vault),
+                _ => return Err(RedPacketError::NotEnoughAccounts.into()),
+            };
+            // The packet doesn't store its mint; the vault token account does (offset 0)
+            let mut mint = [0u8; 32];
+            mint.copy_from_slice(
+                vault.try_borrow()?.get(..32).ok_or(RedPacketError::InvalidTokenAccount)?,
+            );
+            validate_donation_treasury(treasury, Some(treasury_vault), &mint)?;
+            check_writable(&[treasury_vault])?;
+            Transfer {
+                from: vault,
+                to: treasury_vault,
+                authority: red_packet,
+                amount,
+                token_program,
+            }
+            .invoke_signed(signer)
+        }
+    }
+}
+
+/// Pay `amount` lamports from a SOL vault into the SOL treasury, counted as
+/// collected fees. Shared with partial_withdraw.
+pub(crate) fn donate_lamports(vault: &AccountView, treasury: &AccountView, amount: u64) -> ProgramResult {
+    validate_donation_treasury(treasury, None, &NATIVE_SOL_MINT)?;
+
+    pay_from_sol_vault(vault, treasury, amount)?;
+
+    let mut tr = TreasuryView::try_from(treasury.try_borrow_mut()?)?;
+    let current = tr.sol_fees_collected();
+    tr.set_sol_fees_collected(current.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?);
+    Ok(())
+}
+
+/// Check a donation's treasury (and, for SPL, its treasury_vault) the way
+/// create does: the treasury PDA of `mint`, holding that mint. Pausing the
+/// treasury doesn't stop donations, just as it doesn't stop close.
+fn validate_donation_treasury(
+    treasury: &AccountView,
+    treasury_vault: Option<&AccountView>,
+    mint: &[u8],
+) -> ProgramResult {
+    let tr = state::load_treasury(treasury, &ID)?;
+
+    let t_bump_bytes = [tr.bump()];
+    let expected_treasury = Address::create_program_address(
+        &[TREASURY_SEED, mint, &t_bump_bytes],
+        &ID,
+    )
+    .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+    if treasury.address() != &expected_treasury {
+        return Err(RedPacketError::InvalidPDA.into());
+    }
+
+    if let Some(treasury_vault) = treasury_vault {
+        if tr.mint() != mint {
+            return Err(RedPacketError::InvalidMint.into());
+        }
+        let tv_bump_bytes = [tr.vault_bump()];
+        let expected_tv = Address::create_program_address(
+            &[TREASURY_VAULT_SEED, mint, &tv_bump_bytes],
+            &ID,
+        )
+        .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+        if treasury_vault.address() != &expected_tv {
+            return Err(RedPacketError::InvalidPDA.into());
+        }
+    }
     Ok(())
 }
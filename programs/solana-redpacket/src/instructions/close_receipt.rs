@@ -0,0 +1,70 @@
+use pinocchio::{error::ProgramError, AccountView, Address, ProgramResult};
+use crate::log;
+use crate::constants::{CLOSED_DISCRIMINATOR, ID, RECEIPT_SEED};
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::state;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+///
+/// Accounts: claimer (signer, writable), receipt (writable), red_packet
+///
+/// Returns a large packet's claim receipt rent to the claimer. Only once the
+/// packet is closed or fully claimed: while a slot is left, the receipt is
+/// what stops the claimer from taking it too. Top-ups and extensions refuse
+/// fully claimed packets, so those never reopen.
+pub fn process_close_receipt(accounts: &[AccountView], _data: &[u8]) -> ProgramResult {
+    if accounts.len() < 3 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let claimer = &accounts[0];
+    let receipt = &accounts[1];
+    let red_packet = &accounts[2];
+
+    if !claimer.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_writable(&[claimer, receipt])?;
+    check_distinct(&[claimer, receipt, red_packet])?;
+    if !state::is_receipt(receipt, &ID)? {
+        return Err(RedPacketError::InvalidDiscriminator.into());
+    }
+
+    // The seeds tie the receipt to this claimer and this packet address
+    {
+        let receipt_data = receipt.try_borrow()?;
+        let bump_bytes = [state::get_receipt_bump(&receipt_data)];
+        let expected_receipt = Address::create_program_address(
+            &[RECEIPT_SEED, red_packet.address().as_ref(), claimer.address().as_ref(), &bump_bytes],
+            &ID,
+        )
+        .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+        if receipt.address() != &expected_receipt {
+            return Err(RedPacketError::InvalidPDA.into());
+        }
+    }
+
+    // Closed packets are tombstoned, then garbage-collected to the system
+    // program
+    if red_packet.lamports() > 0 && red_packet.owned_by(&ID) {
+        let rp_data = red_packet.try_borrow()?;
+        if rp_data.first() != Some(&CLOSED_DISCRIMINATOR)
+            && state::get_num_claimed(&rp_data) < state::get_num_recipients(&rp_data)
+        {
+            return Err(RedPacketError::RedPacketStillOpen.into());
+        }
+    }
+
+    claimer.set_lamports(
+        claimer
+            .lamports()
+            .checked_add(receipt.lamports())
+            .ok_or(ProgramError::ArithmeticOverflow)?,
+    );
+    receipt.set_lamports(0);
+    receipt.try_borrow_mut()?.fill(0);
+
+    log("Receipt closed");
+    Ok(())
+}
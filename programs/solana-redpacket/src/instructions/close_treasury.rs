@@ -0,0 +1,153 @@
+use pinocchio::{
+    cpi::{Seed, Signer},
+    error::ProgramError,
+    AccountView, Address, ProgramResult,
+};
+use crate::log;
+use crate::constants::{
+    ID, NATIVE_SOL_MINT, TOKEN_TYPE_SOL, TREASURY_SEED, TREASURY_VAULT_SEED,
+    rent_exempt,
+};
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::state;
+use crate::token::{self, CloseAccount};
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+/// [0] token_type: u8 (0=SPL, 1=SOL)
+///
+/// Accounts:
+/// SOL: admin (signer, writable), treasury (writable)
+/// SPL: admin (signer, writable), treasury (writable), treasury_vault (writable), token_program
+///
+/// Retires a mint's treasury once its fees are withdrawn: closes the vault
+/// (SPL), sends all rent to the admin and tombstones the treasury, so new
+/// packets against it fail with TreasuryNotInitialized. init_treasury can
+/// recreate it later.
+pub fn process_close_treasury(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let token_type = data[0];
+    state::validate_token_type(token_type)?;
+
+    let is_sol = token_type == TOKEN_TYPE_SOL;
+
+    let min_accounts = if is_sol { 2 } else { 4 };
+    if accounts.len() < min_accounts {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+
+    let admin = &accounts[0];
+    let treasury = &accounts[1];
+
+    if !admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if is_sol {
+        check_writable(&[admin, treasury])?;
+        check_distinct(&[admin, treasury])?;
+    } else {
+        token::validate_token_program(&accounts[3])?;
+        check_writable(&[admin, treasury, &accounts[2]])?;
+        check_distinct(&[admin, treasury, &accounts[2]])?;
+    }
+
+    // Verify admin, mint/token_type agreement and treasury PDA; treasury is
+    // empty once fees are withdrawn
+    let (treasury_bump, mint) = {
+        let tr = state::load_treasury(treasury, &ID)?;
+        if tr.admin() != admin.address().as_ref() {
+            return Err(RedPacketError::UnauthorizedAdmin.into());
+        }
+
+        let mut mint = [0u8; 32];
+        mint.copy_from_slice(tr.mint());
+        if (mint == NATIVE_SOL_MINT) != is_sol {
+            return Err(RedPacketError::InvalidMint.into());
+        }
+
+        let bump = tr.bump();
+        let bump_bytes = [bump];
+        let expected_treasury = Address::create_program_address(
+            &[TREASURY_SEED, &mint, &bump_bytes],
+            &ID,
+        )
+        .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+        if treasury.address() != &expected_treasury {
+            return Err(RedPacketError::InvalidPDA.into());
+        }
+
+        if is_sol {
+            let lamports_above_rent =
+                treasury.lamports().saturating_sub(rent_exempt(treasury.data_len())?);
+            if tr.sol_fees_collected() != 0 || lamports_above_rent != 0 {
+                return Err(RedPacketError::TreasuryNotEmpty.into());
+            }
+        } else {
+            let treasury_vault = &accounts[2];
+            let tv_bump_bytes = [tr.vault_bump()];
+            let expected_tv = Address::create_program_address(
+                &[TREASURY_VAULT_SEED, &mint, &tv_bump_bytes],
+                &ID,
+            )
+            .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+            if treasury_vault.address() != &expected_tv {
+                return Err(RedPacketError::InvalidPDA.into());
+            }
+
+            // Token account amount is at offset 64
+            let vdata = treasury_vault.try_borrow()?;
+            if vdata.len() < 72 {
+                return Err(RedPacketError::InvalidTokenAccount.into());
+            }
+            if vdata[64..72] != [0u8; 8] {
+                return Err(RedPacketError::TreasuryNotEmpty.into());
+            }
+        }
+
+        (bump, mint)
+    };
+
+    if !is_sol {
+        // Close treasury_vault (rent to admin); the treasury PDA is its owner
+        let bump_bytes = [treasury_bump];
+        let seeds = [
+            Seed::from(TREASURY_SEED),
+            Seed::from(mint.as_ref()),
+            Seed::from(bump_bytes.as_ref()),
+        ];
+        let signer = [Signer::from(&seeds)];
+
+        CloseAccount {
+            account: &accounts[2],
+            destination: admin,
+            authority: treasury,
+            token_program: accounts[3].address(),
+        }
+        .invoke_signed(&signer)?;
+    }
+
+    // Drain treasury PDA lamports to admin
+    admin.set_lamports(
+        admin
+            .lamports()
+            .checked_add(treasury.lamports())
+            .ok_or(ProgramError::ArithmeticOverflow)?,
+    );
+    treasury.set_lamports(0);
+
+    // Zero out and tombstone, so later instructions in the same transaction
+    // see an uninitialized treasury
+    {
+        let mut tdata = treasury.try_borrow_mut()?;
+        for byte in tdata.iter_mut() {
+            *byte = 0;
+        }
+        state::tombstone_treasury(&mut tdata);
+    }
+
+    log("Treasury closed");
+    Ok(())
+}
@@ -5,16 +5,19 @@ use pinocchio::{
     AccountView, Address, ProgramResult,
 };
 use pinocchio_system::instructions::CreateAccount;
-use pinocchio_token::instructions::{InitializeAccount3, Transfer};
-use crate::log;
 use crate::constants::{
-    FEE_DENOMINATOR, FEE_RATE_BPS, ID, MAX_RECIPIENTS, NATIVE_SOL_MINT, SEED_PREFIX,
-    SPLIT_EVEN, SPLIT_RANDOM, SYSTEM_PROGRAM_ID, TOKEN_ACCOUNT_SIZE, TOKEN_PROGRAM_ID,
-    TOKEN_TYPE_SOL, TOKEN_TYPE_SPL, TREASURY_SEED, TREASURY_VAULT_SEED, VAULT_SEED,
+    CREATE_BURN_UNCLAIMED, CREATE_DONATE_UNCLAIMED, FEE_DENOMINATOR, FLAG_ALLOW_REPEAT, FLAG_CREATOR_CAN_CLAIM, FLAG_LARGE,
+    FLAG_NO_SELF_CLAIM,
+    FLAG_RANDOM_ONCHAIN, FLAG_VESTING, ID, LUCKY_SHARE_BPS,
+    MAX_MEMO_LEN, MAX_RECIPIENTS, MAX_RECIPIENTS_LARGE, MAX_TOTAL_AMOUNT, NATIVE_SOL_MINT, SEED_PREFIX, SPLIT_EVEN, SPLIT_LUCKY, SPLIT_RAFFLE, SPLIT_RANDOM, SPLIT_WEIGHTED, SYSTEM_PROGRAM_ID, TOKEN_ACCOUNT_SIZE,
+    TOKEN_TYPE_SPL, TREASURY_SEED, TREASURY_VAULT_SEED, VAULT_SEED, WEIGHT_TOTAL_BPS,
     redpacket_size, rent_exempt,
 };
+use crate::accounts::{check_distinct, check_writable};
 use crate::error::RedPacketError;
-use crate::state;
+use crate::ix_data::CreateArgs;
+use crate::state::{self, TreasuryView};
+use crate::token::{self, InitializeAccount3, Transfer};
 
 /// Instruction data layout:
 /// [0]       discriminator (already consumed)
@@ -22,118 +25,224 @@ use crate::state;
 /// [1..9]    id: u64
 /// [9..17]   total_amount: u64
 /// [17]      num_recipients: u8
-/// [18]      split_mode: u8
-/// [19..27]  expires_at: i64
+/// [18]      split_mode: u8 (random packets draw each claim's amount on-chain,
+///           so total_amount must cover at least 1 unit per slot; weighted
+///           packets pay slot i total_amount * weights[i] / 10_000; lucky
+///           packets draw like random ones around a LUCKY_SHARE_BPS jackpot
+///           finalize hands one claimed slot, leaving the draws at least 1
+///           unit per slot; raffle slots pay
+///           nothing and finalize hands the whole pot to one entrant)
+/// [19..27]  expires_at: i64 (min_expiry_secs to max_expiry_secs from now, per the treasury)
 /// [27]      rp_bump: u8
 /// [28]      vault_bump: u8
-/// [29..]    amounts: [u64; N] (only for random mode)
+/// [29]      flags: u8 (optional, default 0; FLAG_ALLOW_REPEAT = a wallet may
+///           claim more than one slot, FLAG_NO_SELF_CLAIM = the creator may
+///           not claim (the default), FLAG_CREATOR_CAN_CLAIM = the creator
+///           may claim, FLAG_LARGE = up to
+///           MAX_RECIPIENTS_LARGE slots, deduped by receipts; without repeats,
+///           recipients, raffles or lucky splits, FLAG_VESTING = slots unlock linearly until
+///           expires_at; even or weighted, without repeats or large)
+/// [30]      options: u8 (optional, needs flags before it, default 0;
+///           CREATE_BURN_UNCLAIMED = close and partial_withdraw burn what
+///           would go back to the creator; SPL only, CREATE_DONATE_UNCLAIMED
+///           = they pay it to the treasury instead; not with burning)
+/// [next..+32] secret_hash: [u8; 32] (optional, needs options before it;
+///           sha256 of the claim password, zeros = no password)
+/// [next..+32] voucher_authority: [u8; 32] (optional, needs secret_hash before
+///           it; key that must sign each claim's voucher, zeros = none)
+/// [next..+32] gate_mint: [u8; 32] (optional, needs voucher_authority before
+///           it; claimers must hold this mint, zeros = no gate)
+/// [next..+8]  gate_min_amount: u64 (with gate_mint; least balance required)
+/// [next..]    recipients: [[u8; 32]; N] (optional, needs gate_min_amount
+///           before it; slot i may only be claimed by recipients[i])
+/// [next..+32] reveal_commitment: [u8; 32] (optional, random or lucky, needs
+///           gate_min_amount before it; sha256 of a secret the creator must
+///           reveal before claims open)
+/// [next..+8]  reveal_deadline: i64 (with reveal_commitment; after now, at
+///           most expires_at. Unrevealed by then, close refunds everything)
+/// [next..+65] memo: memo_len u8, then [u8; MAX_MEMO_LEN] (optional, needs
+///           options before it; the creator's UTF-8 greeting, zero-padded)
+/// [last 8]    vesting_start: i64 (vesting only, needs options before it;
+///           the final bytes before any weights; before expires_at, and may
+///           be in the past)
+/// [last 2N]   weights: [u16; N] (weighted only, always the final bytes;
+///           nonzero basis points summing to 10_000, the last slot also
+///           takes the rounding remainder)
 pub fn process_create(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
-    // Parse token type first to determine account layout
-    if data.is_empty() {
-        return Err(ProgramError::InvalidInstructionData);
-    }
-    let token_type = data[0];
-    state::validate_token_type(token_type)?;
-
-    // Parse common instruction data (shifted +1 for token_type)
-    if data.len() < 29 {
-        return Err(ProgramError::InvalidInstructionData);
-    }
-
-    let id = u64::from_le_bytes(data[1..9].try_into().unwrap());
-    let total_amount = u64::from_le_bytes(data[9..17].try_into().unwrap());
-    let num_recipients = data[17];
-    let split_mode = data[18];
-    let expires_at = i64::from_le_bytes(data[19..27].try_into().unwrap());
-    let rp_bump = data[27];
-    let vault_bump = data[28];
+    let args = CreateArgs::try_from(data)?;
+    state::validate_token_type(args.token_type)?;
 
     // Validate inputs
-    if total_amount == 0 {
+    if args.total_amount == 0 {
         return Err(RedPacketError::InvalidAmount.into());
     }
-    if num_recipients == 0 || num_recipients > MAX_RECIPIENTS {
-        return Err(RedPacketError::InvalidRecipientCount.into());
+    if args.total_amount > MAX_TOTAL_AMOUNT {
+        return Err(RedPacketError::AmountTooLarge.into());
     }
-    if split_mode != SPLIT_EVEN && split_mode != SPLIT_RANDOM {
+    if args.split_mode > SPLIT_RAFFLE {
         return Err(RedPacketError::InvalidSplitMode.into());
     }
 
-    // Validate expiry
+    let vesting = args.flags & FLAG_VESTING != 0;
+
+    // Large packets have no claimers array to hold assigned recipients or
+    // tell repeat claims apart
+    let large = args.flags & FLAG_LARGE != 0;
+    let max_recipients = if large { MAX_RECIPIENTS_LARGE } else { MAX_RECIPIENTS };
+    if args.num_recipients == 0 || args.num_recipients > max_recipients {
+        return Err(RedPacketError::InvalidRecipientCount.into());
+    }
+    // Raffle and lucky finalizes draw from the claimers array, and vesting
+    // claimers come back to the slot it records
+    let draws_claimers = args.split_mode == SPLIT_RAFFLE || args.split_mode == SPLIT_LUCKY;
+    if large && (args.flags & FLAG_ALLOW_REPEAT != 0 || draws_claimers || vesting) {
+        return Err(RedPacketError::LargeModeUnsupported.into());
+    }
+    if vesting {
+        if args.split_mode != SPLIT_EVEN && args.split_mode != SPLIT_WEIGHTED {
+            return Err(RedPacketError::InvalidSplitMode.into());
+        }
+        if args.flags & FLAG_ALLOW_REPEAT != 0 || args.vesting_start >= args.expires_at {
+            return Err(RedPacketError::InvalidVesting.into());
+        }
+    }
+
+    // A packet is for handing out, so the creator stays out of every split
+    // mode unless they opt back in
+    if args.flags & FLAG_CREATOR_CAN_CLAIM != 0 && args.flags & FLAG_NO_SELF_CLAIM != 0 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let flags = if args.flags & FLAG_CREATOR_CAN_CLAIM == 0 {
+        args.flags | FLAG_NO_SELF_CLAIM
+    } else {
+        args.flags & !FLAG_CREATOR_CAN_CLAIM
+    };
+
+    // Lamports can't be burned, so only SPL packets may opt in
+    let burn_unclaimed = args.options & CREATE_BURN_UNCLAIMED != 0;
+    if burn_unclaimed && args.token_type != TOKEN_TYPE_SPL {
+        return Err(RedPacketError::BurnUnsupported.into());
+    }
+
+    // Leftovers can go one way only
+    let donate_unclaimed = args.options & CREATE_DONATE_UNCLAIMED != 0;
+    if donate_unclaimed && burn_unclaimed {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+
+    // Validate expiry (the treasury's bounds are checked once it is loaded)
     let clock = Clock::get()?;
-    if expires_at <= clock.unix_timestamp {
+    if args.expires_at <= clock.unix_timestamp {
         return Err(RedPacketError::Expired.into());
     }
 
-    // Compute amounts
-    let n = num_recipients as usize;
-    let mut amounts = [0u64; 20];
+    if args.split_mode == SPLIT_WEIGHTED {
+        let mut sum = 0u32;
+        let mut min_weight = u16::MAX;
+        for weight in args.weights.chunks_exact(2) {
+            let weight = u16::from_le_bytes([weight[0], weight[1]]);
+            if weight == 0 {
+                return Err(RedPacketError::InvalidWeights.into());
+            }
+            sum += weight as u32;
+            min_weight = min_weight.min(weight);
+        }
+        if sum != WEIGHT_TOTAL_BPS {
+            return Err(RedPacketError::InvalidWeights.into());
+        }
+        // Every slot must pay out something
+        if state::weighted_amount(args.total_amount, min_weight) == 0 {
+            return Err(RedPacketError::InvalidAmount.into());
+        }
+    }
+    // Even slots each get total / n, which rounds to nothing below one
+    // base unit per slot
+    if args.split_mode == SPLIT_EVEN && args.total_amount < args.num_recipients as u64 {
+        return Err(RedPacketError::InvalidAmount.into());
+    }
 
-    if split_mode == SPLIT_EVEN {
-        let per_person = total_amount / num_recipients as u64;
-        let remainder = total_amount % num_recipients as u64;
-        for i in 0..n {
-            amounts[i] = per_person;
+    // Random amounts are drawn claim by claim, so nobody (the creator
+    // included) knows in advance which slot pays the most, or which one
+    // lands a lucky packet's jackpot
+    let flags = if args.split_mode == SPLIT_RANDOM || args.split_mode == SPLIT_LUCKY {
+        let jackpot = if args.split_mode == SPLIT_LUCKY {
+            state::weighted_amount(args.total_amount, LUCKY_SHARE_BPS)
+        } else {
+            0
+        };
+        if args.total_amount - jackpot < args.num_recipients as u64
+            || (args.split_mode == SPLIT_LUCKY && jackpot == 0)
+        {
+            return Err(RedPacketError::InvalidAmount.into());
         }
-        amounts[n - 1] = per_person
-            .checked_add(remainder)
-            .ok_or(ProgramError::ArithmeticOverflow)?;
+        flags | FLAG_RANDOM_ONCHAIN
     } else {
-        let amounts_data = &data[29..];
-        if amounts_data.len() < 8 * n {
-            return Err(ProgramError::InvalidInstructionData);
+        flags
+    };
+
+    if let Some(memo) = args.memo {
+        let (len, memo) = (memo[0] as usize, &memo[1..]);
+        if len > MAX_MEMO_LEN
+            || memo[len..].iter().any(|&b| b != 0)
+            || core::str::from_utf8(&memo[..len]).is_err()
+        {
+            return Err(RedPacketError::InvalidMemo.into());
         }
-        let mut sum = 0u64;
-        for i in 0..n {
-            let offset = i * 8;
-            let amount = u64::from_le_bytes(
-                amounts_data[offset..offset + 8].try_into().unwrap(),
-            );
-            if amount == 0 {
-                return Err(RedPacketError::InvalidAmount.into());
-            }
-            amounts[i] = amount;
-            sum = sum.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+    }
+
+    if args.reveal_commitment != [0u8; 32] {
+        if flags & FLAG_RANDOM_ONCHAIN == 0 {
+            return Err(RedPacketError::InvalidSplitMode.into());
         }
-        if sum != total_amount {
-            return Err(RedPacketError::AmountMismatch.into());
+        if args.reveal_deadline <= clock.unix_timestamp || args.reveal_deadline > args.expires_at {
+            return Err(RedPacketError::InvalidExpiry.into());
         }
     }
 
-    // Compute fee
-    let fee = core::cmp::max(
-        1,
-        total_amount
-            .checked_mul(FEE_RATE_BPS)
-            .ok_or(ProgramError::ArithmeticOverflow)?
-            / FEE_DENOMINATOR,
-    );
+    if large && !args.recipients.is_empty() {
+        return Err(RedPacketError::LargeModeUnsupported.into());
+    }
 
-    if token_type == TOKEN_TYPE_SPL {
-        process_create_spl(
-            accounts, id, total_amount, num_recipients, split_mode,
-            expires_at, rp_bump, vault_bump, fee, &amounts[..n],
-        )
+    if args.token_type == TOKEN_TYPE_SPL {
+        process_create_spl(accounts, &args, clock.unix_timestamp, flags)
     } else {
-        process_create_sol(
-            accounts, id, total_amount, num_recipients, split_mode,
-            expires_at, rp_bump, vault_bump, fee, &amounts[..n],
-        )
+        process_create_sol(accounts, &args, clock.unix_timestamp, flags)
     }
 }
 
-/// SPL token path: 9 accounts
+/// Protocol fee on `amount` at the treasury's `fee_bps`, minimum 1 unit
+/// unless the treasury is configured fee-free (0 bps). The product goes
+/// through u128, so only a fee that can't itself fit a u64 overflows. The
+/// blinks server's compute_fee is the same algorithm; keep them in step.
+pub(crate) fn compute_fee(amount: u64, fee_bps: u16) -> Result<u64, ProgramError> {
+    if fee_bps == 0 {
+        return Ok(0);
+    }
+    let fee = amount as u128 * fee_bps as u128 / FEE_DENOMINATOR as u128;
+    let fee = u64::try_from(fee).map_err(|_| ProgramError::ArithmeticOverflow)?;
+    Ok(core::cmp::max(1, fee))
+}
+
+/// Expiry must be between the treasury's min_expiry_secs and
+/// max_expiry_secs from `now`
+fn check_expiry_bounds(tr: TreasuryView<&[u8]>, expires_at: i64, now: i64) -> ProgramResult {
+    let lifetime = expires_at - now;
+    if lifetime < tr.min_expiry_secs() {
+        return Err(RedPacketError::ExpiryTooSoon.into());
+    }
+    if lifetime > tr.max_expiry_secs() {
+        return Err(RedPacketError::ExpiryTooFar.into());
+    }
+    Ok(())
+}
+
+/// SPL token path: 9 accounts. token_program is Token or Token-2022, matching
+/// the mint; the vault is created under it.
 fn process_create_spl(
     accounts: &[AccountView],
-    id: u64,
-    total_amount: u64,
-    num_recipients: u8,
-    split_mode: u8,
-    expires_at: i64,
-    rp_bump: u8,
-    vault_bump: u8,
-    fee: u64,
-    amounts: &[u64],
+    args: &CreateArgs,
+    created_at: i64,
+    flags: u8,
 ) -> ProgramResult {
     if accounts.len() < 9 {
         return Err(RedPacketError::NotEnoughAccounts.into());
@@ -151,16 +260,16 @@ fn process_create_spl(
     if !creator.is_signer() {
         return Err(ProgramError::MissingRequiredSignature);
     }
-    if token_program.address() != &TOKEN_PROGRAM_ID {
-        return Err(RedPacketError::InvalidTokenProgram.into());
-    }
+    check_writable(&[creator, creator_token_account, red_packet, vault, treasury_vault])?;
+    check_distinct(&[creator, creator_token_account, red_packet, vault, treasury, treasury_vault])?;
+    let token_program = token::validate_token_program(token_program)?;
     if system_program.address() != &SYSTEM_PROGRAM_ID {
         return Err(RedPacketError::InvalidSystemProgram.into());
     }
 
     // Verify red_packet PDA
-    let id_bytes = id.to_le_bytes();
-    let rp_bump_bytes = [rp_bump];
+    let id_bytes = args.id.to_le_bytes();
+    let rp_bump_bytes = [args.rp_bump];
     let expected_rp = Address::create_program_address(
         &[SEED_PREFIX, creator.address().as_ref(), &id_bytes, &rp_bump_bytes],
         &ID,
@@ -169,9 +278,13 @@ fn process_create_spl(
     if red_packet.address() != &expected_rp {
         return Err(RedPacketError::InvalidPDA.into());
     }
+    // A reused id: refuse it here rather than inside CreateAccount
+    if red_packet.lamports() > 0 || red_packet.data_len() > 0 {
+        return Err(RedPacketError::RedPacketAlreadyExists.into());
+    }
 
     // Verify vault PDA
-    let vault_bump_bytes = [vault_bump];
+    let vault_bump_bytes = [args.vault_bump];
     let expected_vault = Address::create_program_address(
         &[VAULT_SEED, creator.address().as_ref(), &id_bytes, &vault_bump_bytes],
         &ID,
@@ -181,18 +294,15 @@ fn process_create_spl(
         return Err(RedPacketError::InvalidPDA.into());
     }
 
-    // Validate treasury
-    state::validate_treasury(treasury, &ID)?;
-
     // Verify mint matches treasury, verify treasury PDA, and verify treasury_vault PDA
-    {
-        let tdata = treasury.try_borrow()?;
-        if mint.address().as_ref() != state::get_treasury_mint(&tdata) {
+    let fee = {
+        let tr = state::load_treasury(treasury, &ID)?;
+        if mint.address().as_ref() != tr.mint() {
             return Err(RedPacketError::InvalidMint.into());
         }
 
         // Verify treasury PDA (includes mint in seeds)
-        let t_bump = state::get_treasury_bump(&tdata);
+        let t_bump = tr.bump();
         let t_bump_bytes = [t_bump];
         let expected_treasury = Address::create_program_address(
             &[TREASURY_SEED, mint.address().as_ref(), &t_bump_bytes],
@@ -204,7 +314,7 @@ fn process_create_spl(
         }
 
         // Verify treasury_vault PDA (includes mint in seeds)
-        let tv_bump = state::get_treasury_vault_bump(&tdata);
+        let tv_bump = tr.vault_bump();
         let tv_bump_bytes = [tv_bump];
         let expected_tv = Address::create_program_address(
             &[TREASURY_VAULT_SEED, mint.address().as_ref(), &tv_bump_bytes],
@@ -214,11 +324,43 @@ fn process_create_spl(
         if treasury_vault.address() != &expected_tv {
             return Err(RedPacketError::InvalidPDA.into());
         }
+
+        if tr.paused() {
+            return Err(RedPacketError::ProgramPaused.into());
+        }
+        check_expiry_bounds(tr.as_view(), args.expires_at, created_at)?;
+
+        compute_fee(args.total_amount, tr.fee_bps())?
+    };
+
+    // The vault is a plain TOKEN_ACCOUNT_SIZE account, so the mint may not
+    // need account extensions
+    token::check_mint(mint)?;
+
+    // Refuse up front when the creator's token account holds another mint or
+    // can't cover the pot and the fee, rather than failing inside the token
+    // program's transfer after the packet and vault were created.
+    {
+        if !token::owned_by_token_program(creator_token_account) {
+            return Err(RedPacketError::InvalidAccountOwner.into());
+        }
+        let cdata = creator_token_account.try_borrow()?;
+        if cdata.len() < 72 {
+            return Err(RedPacketError::InvalidTokenAccount.into());
+        }
+        if &cdata[0..32] != mint.address().as_ref() {
+            return Err(RedPacketError::InvalidMint.into());
+        }
+        let balance = u64::from_le_bytes(cdata[64..72].try_into().unwrap());
+        let needed = args.total_amount.checked_add(fee).ok_or(ProgramError::ArithmeticOverflow)?;
+        if balance < needed {
+            return Err(RedPacketError::InsufficientCreatorBalance.into());
+        }
     }
 
     // Create red_packet PDA
-    let account_size = redpacket_size(num_recipients);
-    let rp_rent = rent_exempt(account_size);
+    let account_size = redpacket_size(args.num_recipients, flags & FLAG_LARGE != 0, flags & FLAG_VESTING != 0);
+    let rp_rent = rent_exempt(account_size)?;
 
     let rp_seeds = [
         Seed::from(SEED_PREFIX),
@@ -238,7 +380,7 @@ fn process_create_spl(
     .invoke_signed(&rp_signer)?;
 
     // Create vault token account
-    let vault_rent = rent_exempt(TOKEN_ACCOUNT_SIZE);
+    let vault_rent = rent_exempt(TOKEN_ACCOUNT_SIZE)?;
 
     let vault_seeds = [
         Seed::from(VAULT_SEED),
@@ -253,7 +395,7 @@ fn process_create_spl(
         to: vault,
         lamports: vault_rent,
         space: TOKEN_ACCOUNT_SIZE as u64,
-        owner: &TOKEN_PROGRAM_ID,
+        owner: token_program,
     }
     .invoke_signed(&vault_signer)?;
 
@@ -261,6 +403,7 @@ fn process_create_spl(
         account: vault,
         mint,
         owner: red_packet.address(),
+        token_program,
     }
     .invoke()?;
 
@@ -269,7 +412,8 @@ fn process_create_spl(
         from: creator_token_account,
         to: vault,
         authority: creator,
-        amount: total_amount,
+        amount: args.total_amount,
+        token_program,
     }
     .invoke()?;
 
@@ -279,43 +423,53 @@ fn process_create_spl(
         to: treasury_vault,
         authority: creator,
         amount: fee,
+        token_program,
     }
     .invoke()?;
 
     // Initialize red_packet PDA data
     {
         let mut pda_data = red_packet.try_borrow_mut()?;
-        state::init_redpacket(
-            &mut pda_data,
-            creator.address().as_ref(),
-            id,
-            total_amount,
-            num_recipients,
-            split_mode,
-            rp_bump,
-            vault_bump,
-            TOKEN_TYPE_SPL,
-            expires_at,
-            amounts,
-        );
-    }
-
-    log("Red packet created");
+        state::init_redpacket(&mut pda_data, creator.address().as_ref(), args, created_at, fee, flags);
+    }
+
+    #[cfg(feature = "logging")]
+    {
+        crate::event::Event::new("RP_CREATE")?
+            .u64("id", args.id)?
+            .address("creator", creator.address().as_ref())?
+            .u64("token_type", args.token_type as u64)?
+            .address("mint", mint.address().as_ref())?
+            .u64("total", args.total_amount)?
+            .u64("recipients", args.num_recipients as u64)?
+            .u64("split", args.split_mode as u64)?
+            .u64("allow_repeat", (flags & FLAG_ALLOW_REPEAT != 0) as u64)?
+            .u64("no_self_claim", (flags & FLAG_NO_SELF_CLAIM != 0) as u64)?
+            .u64("large", (flags & FLAG_LARGE != 0) as u64)?
+            .i64("expires_at", args.expires_at)?
+            .u64("fee", fee)?
+            .emit();
+    }
+    #[cfg(any(feature = "logging", feature = "cpi-events"))]
+    crate::event::CreateEvent {
+        creator: creator.address().as_ref(),
+        id: args.id,
+        total: args.total_amount,
+        num_recipients: args.num_recipients,
+        split_mode: args.split_mode,
+        expires_at: args.expires_at,
+    }
+    .emit(accounts)?;
+
     Ok(())
 }
 
 /// Native SOL path: 5 accounts
 fn process_create_sol(
     accounts: &[AccountView],
-    id: u64,
-    total_amount: u64,
-    num_recipients: u8,
-    split_mode: u8,
-    expires_at: i64,
-    rp_bump: u8,
-    vault_bump: u8,
-    fee: u64,
-    amounts: &[u64],
+    args: &CreateArgs,
+    created_at: i64,
+    flags: u8,
 ) -> ProgramResult {
     if accounts.len() < 5 {
         return Err(RedPacketError::NotEnoughAccounts.into());
@@ -329,13 +483,15 @@ fn process_create_sol(
     if !creator.is_signer() {
         return Err(ProgramError::MissingRequiredSignature);
     }
+    check_writable(&[creator, red_packet, vault, treasury])?;
+    check_distinct(&[creator, red_packet, vault, treasury])?;
     if system_program.address() != &SYSTEM_PROGRAM_ID {
         return Err(RedPacketError::InvalidSystemProgram.into());
     }
 
     // Verify red_packet PDA
-    let id_bytes = id.to_le_bytes();
-    let rp_bump_bytes = [rp_bump];
+    let id_bytes = args.id.to_le_bytes();
+    let rp_bump_bytes = [args.rp_bump];
     let expected_rp = Address::create_program_address(
         &[SEED_PREFIX, creator.address().as_ref(), &id_bytes, &rp_bump_bytes],
         &ID,
@@ -344,9 +500,13 @@ fn process_create_sol(
     if red_packet.address() != &expected_rp {
         return Err(RedPacketError::InvalidPDA.into());
     }
+    // A reused id: refuse it here rather than inside CreateAccount
+    if red_packet.lamports() > 0 || red_packet.data_len() > 0 {
+        return Err(RedPacketError::RedPacketAlreadyExists.into());
+    }
 
     // Verify vault PDA
-    let vault_bump_bytes = [vault_bump];
+    let vault_bump_bytes = [args.vault_bump];
     let expected_vault = Address::create_program_address(
         &[VAULT_SEED, creator.address().as_ref(), &id_bytes, &vault_bump_bytes],
         &ID,
@@ -357,10 +517,9 @@ fn process_create_sol(
     }
 
     // Validate treasury and verify treasury PDA (includes NATIVE_SOL_MINT in seeds)
-    state::validate_treasury(treasury, &ID)?;
-    {
-        let tdata = treasury.try_borrow()?;
-        let t_bump = state::get_treasury_bump(&tdata);
+    let fee = {
+        let tr = state::load_treasury(treasury, &ID)?;
+        let t_bump = tr.bump();
         let t_bump_bytes = [t_bump];
         let expected_treasury = Address::create_program_address(
             &[TREASURY_SEED, &NATIVE_SOL_MINT, &t_bump_bytes],
@@ -370,11 +529,33 @@ fn process_create_sol(
         if treasury.address() != &expected_treasury {
             return Err(RedPacketError::InvalidPDA.into());
         }
-    }
+
+        if tr.paused() {
+            return Err(RedPacketError::ProgramPaused.into());
+        }
+        check_expiry_bounds(tr.as_view(), args.expires_at, created_at)?;
+
+        compute_fee(args.total_amount, tr.fee_bps())?
+    };
 
     // Create red_packet PDA
-    let account_size = redpacket_size(num_recipients);
-    let rp_rent = rent_exempt(account_size);
+    let account_size = redpacket_size(args.num_recipients, flags & FLAG_LARGE != 0, flags & FLAG_VESTING != 0);
+    let rp_rent = rent_exempt(account_size)?;
+
+    // The vault holds its rent-exempt minimum on top of the pot until close,
+    // so claims can never drain it below that. Refuse up front when the
+    // creator can't cover both rents, the pot and the fee.
+    let vault_rent = rent_exempt(0)?;
+    let vault_lamports = vault_rent
+        .checked_add(args.total_amount)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    let needed = vault_lamports
+        .checked_add(rp_rent)
+        .and_then(|needed| needed.checked_add(fee))
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    if creator.lamports() < needed {
+        return Err(RedPacketError::InsufficientForRent.into());
+    }
 
     let rp_seeds = [
         Seed::from(SEED_PREFIX),
@@ -394,11 +575,6 @@ fn process_create_sol(
     .invoke_signed(&rp_signer)?;
 
     // Create vault PDA (0-byte account, holds SOL as lamports)
-    let vault_rent = rent_exempt(0);
-    let vault_lamports = vault_rent
-        .checked_add(total_amount)
-        .ok_or(ProgramError::ArithmeticOverflow)?;
-
     let vault_seeds = [
         Seed::from(VAULT_SEED),
         Seed::from(creator.address().as_ref()),
@@ -426,32 +602,43 @@ fn process_create_sol(
 
     // Update treasury sol_fees_collected
     {
-        let mut tdata = treasury.try_borrow_mut()?;
-        let current = state::get_sol_fees_collected(&tdata);
-        state::set_sol_fees_collected(
-            &mut tdata,
-            current.checked_add(fee).ok_or(ProgramError::ArithmeticOverflow)?,
-        );
+        let mut tr = TreasuryView::try_from(treasury.try_borrow_mut()?)?;
+        let current = tr.sol_fees_collected();
+        tr.set_sol_fees_collected(current.checked_add(fee).ok_or(ProgramError::ArithmeticOverflow)?);
     }
 
     // Initialize red_packet PDA data
     {
         let mut pda_data = red_packet.try_borrow_mut()?;
-        state::init_redpacket(
-            &mut pda_data,
-            creator.address().as_ref(),
-            id,
-            total_amount,
-            num_recipients,
-            split_mode,
-            rp_bump,
-            vault_bump,
-            TOKEN_TYPE_SOL,
-            expires_at,
-            amounts,
-        );
-    }
-
-    log("SOL red packet created");
+        state::init_redpacket(&mut pda_data, creator.address().as_ref(), args, created_at, fee, flags);
+    }
+
+    #[cfg(feature = "logging")]
+    {
+        crate::event::Event::new("RP_CREATE")?
+            .u64("id", args.id)?
+            .address("creator", creator.address().as_ref())?
+            .u64("token_type", args.token_type as u64)?
+            .u64("total", args.total_amount)?
+            .u64("recipients", args.num_recipients as u64)?
+            .u64("split", args.split_mode as u64)?
+            .u64("allow_repeat", (flags & FLAG_ALLOW_REPEAT != 0) as u64)?
+            .u64("no_self_claim", (flags & FLAG_NO_SELF_CLAIM != 0) as u64)?
+            .u64("large", (flags & FLAG_LARGE != 0) as u64)?
+            .i64("expires_at", args.expires_at)?
+            .u64("fee", fee)?
+            .emit();
+    }
+    #[cfg(any(feature = "logging", feature = "cpi-events"))]
+    crate::event::CreateEvent {
+        creator: creator.address().as_ref(),
+        id: args.id,
+        total: args.total_amount,
+        num_recipients: args.num_recipients,
+        split_mode: args.split_mode,
+        expires_at: args.expires_at,
+    }
+    .emit(accounts)?;
+
     Ok(())
 }
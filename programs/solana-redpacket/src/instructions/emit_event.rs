@@ -0,0 +1,22 @@
+use pinocchio::{AccountView, ProgramResult};
+use crate::constants::EVENT_AUTHORITY;
+use crate::error::RedPacketError;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+/// [0..] one binary event (see event.rs)
+///
+/// Accounts: event_authority (signer)
+///
+/// Only built with `cpi-events`. The program invokes this itself to put
+/// create, claim and close events in the transaction's inner instructions,
+/// where log truncation can't drop them. Only the program can sign for the
+/// event authority, so rejecting any other signer keeps the events
+/// trustworthy; otherwise it does nothing.
+pub fn process_emit_event(accounts: &[AccountView], _data: &[u8]) -> ProgramResult {
+    let event_authority = accounts.first().ok_or(RedPacketError::NotEnoughAccounts)?;
+    if event_authority.address() != &EVENT_AUTHORITY || !event_authority.is_signer() {
+        return Err(RedPacketError::InvalidEventAuthority.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use pinocchio::{
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, ProgramResult,
+};
+use crate::constants::{ID, MAX_EXPIRY_SECONDS};
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::state;
+
+/// Instruction data layout:
+/// [0]      discriminator (already consumed)
+/// [0..8]   new_expires_at: i64
+///
+/// Accounts: creator (signer), red_packet (writable)
+///
+/// Expiry can only move forward, at most MAX_EXPIRY_SECONDS past now, and
+/// only while the packet still has unclaimed slots. An expired (but not yet
+/// closed or withdrawn) packet can be reopened. The treasury isn't passed,
+/// so a shorter horizon set with set_expiry_bounds binds create only.
+pub fn process_extend_expiry(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.len() < 8 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let new_expires_at = i64::from_le_bytes(data[0..8].try_into().unwrap());
+
+    if accounts.len() < 2 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let creator = &accounts[0];
+    let red_packet = &accounts[1];
+
+    if !creator.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_writable(&[red_packet])?;
+    check_distinct(&[creator, red_packet])?;
+
+    // Closed packets are zeroed and reassigned, so this also rejects them
+    let mut rp = state::load_redpacket_mut(red_packet, &ID)?;
+
+    if rp.creator() != creator.address().as_ref() {
+        return Err(RedPacketError::Unauthorized.into());
+    }
+    if rp.num_claimed() >= rp.num_recipients() {
+        return Err(RedPacketError::RedPacketFull.into());
+    }
+    // The leftovers were already withdrawn; the vault can't pay out new claims
+    if rp.remaining_amount() == 0 {
+        return Err(RedPacketError::AlreadyWithdrawn.into());
+    }
+
+    let old_expires_at = rp.expires_at();
+    if new_expires_at <= old_expires_at {
+        return Err(RedPacketError::InvalidExpiry.into());
+    }
+    let clock = Clock::get()?;
+    if new_expires_at <= clock.unix_timestamp {
+        return Err(RedPacketError::Expired.into());
+    }
+    if new_expires_at - clock.unix_timestamp > MAX_EXPIRY_SECONDS {
+        return Err(RedPacketError::ExpiryTooFar.into());
+    }
+
+    rp.set_expires_at(new_expires_at);
+
+    #[cfg(feature = "logging")]
+    {
+        let mut msg = state::FixedBuf::<64>::new();
+        msg.extend_from_slice(b"Expiry extended: ")?;
+        msg.push_i64(old_expires_at)?;
+        msg.extend_from_slice(b" -> ")?;
+        msg.push_i64(new_expires_at)?;
+        crate::log(msg.as_str());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use pinocchio::{
+    cpi::{Seed, Signer},
+    error::ProgramError,
+    sysvars::{clock::Clock, slot_hashes::SlotHashes, Sysvar},
+    AccountView, Address, ProgramResult,
+};
+use crate::log;
+use crate::constants::{ID, SEED_PREFIX, SPLIT_LUCKY, SPLIT_RAFFLE, TOKEN_TYPE_SOL, VAULT_SEED};
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::instructions::claim::pay_from_sol_vault;
+use crate::state::{self, RedPacketView};
+use crate::token::{self, Transfer};
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+/// [0] token_type: u8 (0=SPL, 1=SOL)
+///
+/// Accounts:
+/// SOL: red_packet (writable), vault (writable), winner (writable), slot_hashes sysvar
+/// SPL: red_packet (writable), vault (writable), winner_token_account (writable),
+///      token_program, slot_hashes sysvar
+///
+/// Anyone may call it. The first call on an expired or full raffle or lucky
+/// packet draws the winner among the claimed slots from sha256(sha256(every
+/// slot's claimer) || newest slot hash) and records it,
+/// whatever `winner` was passed, so a caller can't retry until someone they
+/// like wins. The prize goes to `winner` as soon as it is the drawn entrant
+/// (or, for SPL, a token account it owns of the vault's mint): hence a drawn
+/// but unpaid packet just needs finalize again with the right account. A
+/// raffle's prize is its whole pot (remaining_amount), a lucky packet's its
+/// jackpot, which sets FLAG_LUCKY_PAID; what the unclaimed slots of a lucky
+/// packet would have drawn stays for close. The vault and packet rent stay
+/// for the creator's close.
+pub fn process_finalize(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let token_type = data[0];
+    state::validate_token_type(token_type)?;
+
+    let is_sol = token_type == TOKEN_TYPE_SOL;
+    let min_accounts = if is_sol { 4 } else { 5 };
+    if accounts.len() < min_accounts {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let red_packet = &accounts[0];
+    let vault = &accounts[1];
+    let winner = &accounts[2];
+    let slot_hashes = &accounts[min_accounts - 1];
+    check_writable(&[red_packet, vault, winner])?;
+    check_distinct(&[red_packet, vault, winner])?;
+    if !is_sol {
+        token::validate_token_program(&accounts[3])?;
+    }
+
+    let (bump, creator_bytes, id_bytes, remaining_amount, prize, winner_bytes) = {
+        let mut rp = state::load_redpacket_mut(red_packet, &ID)?;
+
+        if rp.token_type() != token_type {
+            return Err(RedPacketError::InvalidTokenType.into());
+        }
+        let split_mode = rp.split_mode();
+        if split_mode != SPLIT_RAFFLE && split_mode != SPLIT_LUCKY {
+            return Err(RedPacketError::InvalidSplitMode.into());
+        }
+
+        let mut creator_bytes = [0u8; 32];
+        creator_bytes.copy_from_slice(rp.original_creator());
+        let id_bytes = rp.id().to_le_bytes();
+
+        let vault_bump_bytes = [rp.vault_bump()];
+        let expected_vault = Address::create_program_address(
+            &[VAULT_SEED, &creator_bytes, &id_bytes, &vault_bump_bytes],
+            &ID,
+        )
+        .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+        if vault.address() != &expected_vault {
+            return Err(RedPacketError::InvalidPDA.into());
+        }
+
+        let remaining_amount = rp.remaining_amount();
+        let prize = if split_mode == SPLIT_LUCKY {
+            rp.lucky_jackpot()
+        } else {
+            remaining_amount
+        };
+        if prize == 0 {
+            return Err(RedPacketError::AlreadyClaimed.into());
+        }
+
+        if rp.winner().is_none() {
+            let num_recipients = rp.num_recipients();
+            let num_claimed = rp.num_claimed();
+            if num_claimed < num_recipients
+                && Clock::get()?.unix_timestamp < rp.expires_at()
+            {
+                return Err(RedPacketError::NotExpiredOrFull.into());
+            }
+            if num_claimed == 0 {
+                return Err(RedPacketError::NoRaffleEntrants.into());
+            }
+
+            let slot_hash = SlotHashes::from_account_view(slot_hashes)?
+                .get_entry(0)
+                .ok_or(ProgramError::UnsupportedSysvar)?
+                .hash;
+            let claimers = rp.claimers();
+            let mut seed = [0u8; 64];
+            seed[..32].copy_from_slice(&crate::sha256(claimers));
+            seed[32..].copy_from_slice(&slot_hash);
+            let draw = crate::sha256(&seed);
+            let index = (u64::from_le_bytes(draw[..8].try_into().unwrap()) % num_claimed as u64) as usize;
+            let slot = rp.claimed_slots().nth(index).unwrap();
+
+            let mut drawn = [0u8; 32];
+            drawn.copy_from_slice(rp.claimer_at(slot));
+            rp.set_winner(&drawn);
+            log("Winner drawn");
+        }
+
+        let mut winner_bytes = [0u8; 32];
+        winner_bytes.copy_from_slice(rp.winner().unwrap());
+        (rp.bump(), creator_bytes, id_bytes, remaining_amount, prize, winner_bytes)
+    };
+
+    if !pays_winner(winner, vault, &winner_bytes, is_sol)? {
+        return Ok(());
+    }
+
+    if is_sol {
+        if !vault.owned_by(&ID) {
+            return Err(RedPacketError::InvalidAccountOwner.into());
+        }
+        pay_from_sol_vault(vault, winner, prize)?;
+    } else {
+        let bump_bytes = [bump];
+        let rp_seeds = [
+            Seed::from(SEED_PREFIX),
+            Seed::from(creator_bytes.as_ref()),
+            Seed::from(id_bytes.as_ref()),
+            Seed::from(bump_bytes.as_ref()),
+        ];
+        let rp_signer = [Signer::from(&rp_seeds)];
+
+        token::check_vault(vault, red_packet, None)?;
+        Transfer {
+            from: vault,
+            to: winner,
+            authority: red_packet,
+            amount: prize,
+            token_program: accounts[3].address(),
+        }
+        .invoke_signed(&rp_signer)?;
+    }
+
+    {
+        let mut rp = RedPacketView::try_from(red_packet.try_borrow_mut()?)?;
+        rp.set_remaining_amount(remaining_amount - prize);
+        if rp.split_mode() == SPLIT_LUCKY {
+            rp.set_lucky_paid();
+        }
+    }
+
+    #[cfg(feature = "logging")]
+    {
+        crate::event::Event::new("RP_RAFFLE")?
+            .u64("id", u64::from_le_bytes(id_bytes))?
+            .address("creator", &creator_bytes)?
+            .address("winner", &winner_bytes)?
+            .u64("amount", prize)?
+            .emit();
+    }
+
+    log("Winner paid out");
+    Ok(())
+}
+
+/// Whether `account` is where the drawn winner gets paid: the wallet itself
+/// for SOL, a token account it owns of the vault's mint for SPL
+fn pays_winner(
+    account: &AccountView,
+    vault: &AccountView,
+    winner: &[u8],
+    is_sol: bool,
+) -> Result<bool, ProgramError> {
+    if is_sol {
+        return Ok(account.address().as_ref() == winner);
+    }
+    if !token::owned_by_token_program(account) {
+        return Ok(false);
+    }
+    let adata = account.try_borrow()?;
+    let vdata = vault.try_borrow()?;
+    Ok(adata.len() >= 64 && vdata.len() >= 32 && adata[0..32] == vdata[0..32] && &adata[32..64] == winner)
+}
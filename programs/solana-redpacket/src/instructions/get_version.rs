@@ -0,0 +1,16 @@
+use pinocchio::{cpi::set_return_data, AccountView, ProgramResult};
+use crate::constants::{PROGRAM_VERSION, REDPACKET_VERSION};
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+///
+/// Accounts: none
+///
+/// Sets return data to PROGRAM_VERSION's major, minor and patch bytes, then
+/// REDPACKET_VERSION, so clients can tell which revision is deployed before
+/// building anything for it. Ignores any accounts or data it is given.
+pub fn process_get_version(_accounts: &[AccountView], _data: &[u8]) -> ProgramResult {
+    let [major, minor, patch] = PROGRAM_VERSION;
+    set_return_data(&[major, minor, patch, REDPACKET_VERSION]);
+    Ok(())
+}
@@ -4,28 +4,39 @@ use pinocchio::{
     AccountView, Address, ProgramResult,
 };
 use pinocchio_system::instructions::CreateAccount;
-use pinocchio_token::instructions::InitializeAccount3;
 use crate::log;
 use crate::constants::{
-    ID, NATIVE_SOL_MINT, SYSTEM_PROGRAM_ID, TOKEN_ACCOUNT_SIZE, TOKEN_PROGRAM_ID,
-    TOKEN_TYPE_SOL, TREASURY_SEED, TREASURY_SIZE, TREASURY_VAULT_SEED, rent_exempt,
+    ADMIN, FEE_RATE_BPS, ID, MAX_FEE_BPS, NATIVE_SOL_MINT, SYSTEM_PROGRAM_ID,
+    TOKEN_ACCOUNT_SIZE, TOKEN_TYPE_SOL, TREASURY_SEED, TREASURY_SIZE,
+    TREASURY_VAULT_SEED, rent_exempt,
 };
+use crate::accounts::{check_distinct, check_writable};
 use crate::error::RedPacketError;
+use crate::ix_data::InitTreasuryArgs;
 use crate::state;
+use crate::token::{self, InitializeAccount3};
 
 /// Instruction data layout:
 /// [0]     discriminator (already consumed)
 /// [0]     token_type: u8 (0=SPL, 1=SOL)
 /// [1]     treasury_bump: u8
 /// [2]     vault_bump: u8 (ignored for SOL)
+/// [3..5]  fee_bps: u16 (optional, defaults to FEE_RATE_BPS)
+/// [5]     if_missing: u8 (optional, needs fee_bps; 1 = succeed without
+///         changes when this mint's treasury already exists)
+///
+/// Init is permissionless at the default rate; only ADMIN may pick a
+/// different fee_bps, so nobody can front-run a mint's treasury with a
+/// punitive fee. The new treasury's admin is always ADMIN, whoever pays.
+/// An existing treasury fails with TreasuryAlreadyInitialized unless
+/// if_missing is set, in which case its fee is left as it is.
 pub fn process_init_treasury(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
-    if data.len() < 3 {
-        return Err(ProgramError::InvalidInstructionData);
-    }
-    let token_type = data[0];
+    let InitTreasuryArgs { token_type, treasury_bump, vault_bump, fee_bps, if_missing } =
+        InitTreasuryArgs::try_from(data)?;
     state::validate_token_type(token_type)?;
-    let treasury_bump = data[1];
-    let vault_bump = data[2];
+    if fee_bps > MAX_FEE_BPS {
+        return Err(RedPacketError::InvalidFeeRate.into());
+    }
 
     let is_sol = token_type == TOKEN_TYPE_SOL;
 
@@ -41,6 +52,9 @@ pub fn process_init_treasury(accounts: &[AccountView], data: &[u8]) -> ProgramRe
     if !payer.is_signer() {
         return Err(ProgramError::MissingRequiredSignature);
     }
+    if fee_bps != FEE_RATE_BPS && payer.address() != &ADMIN {
+        return Err(RedPacketError::UnauthorizedAdmin.into());
+    }
 
     // Determine mint bytes for PDA derivation
     let mint_bytes: &[u8] = if is_sol {
@@ -51,11 +65,8 @@ pub fn process_init_treasury(accounts: &[AccountView], data: &[u8]) -> ProgramRe
         }
         &NATIVE_SOL_MINT
     } else {
-        let token_program = &accounts[4];
         let system_program = &accounts[5];
-        if token_program.address() != &TOKEN_PROGRAM_ID {
-            return Err(RedPacketError::InvalidTokenProgram.into());
-        }
+        token::validate_token_program(&accounts[4])?;
         if system_program.address() != &SYSTEM_PROGRAM_ID {
             return Err(RedPacketError::InvalidSystemProgram.into());
         }
@@ -75,11 +86,25 @@ pub fn process_init_treasury(accounts: &[AccountView], data: &[u8]) -> ProgramRe
 
     // Check treasury doesn't already exist (lamports == 0 means uninitialized)
     if treasury.lamports() > 0 {
+        if if_missing
+            && state::load_treasury(treasury, &ID).is_ok_and(|tr| tr.mint() == mint_bytes)
+        {
+            log("Treasury already initialized");
+            return Ok(());
+        }
         return Err(RedPacketError::TreasuryAlreadyInitialized.into());
     }
 
+    if is_sol {
+        check_writable(&[payer, treasury])?;
+        check_distinct(&[payer, treasury])?;
+    } else {
+        check_writable(&[payer, treasury, &accounts[2]])?;
+        check_distinct(&[payer, treasury, &accounts[2], &accounts[3]])?;
+    }
+
     // Create treasury PDA
-    let treasury_rent = rent_exempt(TREASURY_SIZE);
+    let treasury_rent = rent_exempt(TREASURY_SIZE)?;
     let treasury_seeds = [
         Seed::from(TREASURY_SEED),
         Seed::from(mint_bytes),
@@ -100,12 +125,21 @@ pub fn process_init_treasury(accounts: &[AccountView], data: &[u8]) -> ProgramRe
     let effective_vault_bump = if is_sol { 0 } else { vault_bump };
     {
         let mut tdata = treasury.try_borrow_mut()?;
-        state::init_treasury(&mut tdata, treasury_bump, effective_vault_bump, mint_bytes);
+        state::init_treasury(
+            &mut tdata,
+            treasury_bump,
+            effective_vault_bump,
+            mint_bytes,
+            fee_bps,
+            ADMIN.as_ref(),
+        );
     }
 
     if !is_sol {
         let treasury_vault = &accounts[2];
         let mint = &accounts[3];
+        let token_program = accounts[4].address();
+        token::check_mint(mint)?;
 
         // Verify treasury_vault PDA address (includes mint in seeds)
         let vault_bump_bytes = [vault_bump];
@@ -119,7 +153,7 @@ pub fn process_init_treasury(accounts: &[AccountView], data: &[u8]) -> ProgramRe
         }
 
         // Create treasury vault token account
-        let vault_rent = rent_exempt(TOKEN_ACCOUNT_SIZE);
+        let vault_rent = rent_exempt(TOKEN_ACCOUNT_SIZE)?;
         let vault_seeds = [
             Seed::from(TREASURY_VAULT_SEED),
             Seed::from(mint.address().as_ref()),
@@ -132,7 +166,7 @@ pub fn process_init_treasury(accounts: &[AccountView], data: &[u8]) -> ProgramRe
             to: treasury_vault,
             lamports: vault_rent,
             space: TOKEN_ACCOUNT_SIZE as u64,
-            owner: &TOKEN_PROGRAM_ID,
+            owner: token_program,
         }
         .invoke_signed(&vault_signer)?;
 
@@ -141,6 +175,7 @@ pub fn process_init_treasury(accounts: &[AccountView], data: &[u8]) -> ProgramRe
             account: treasury_vault,
             mint,
             owner: treasury.address(),
+            token_program,
         }
         .invoke()?;
     }
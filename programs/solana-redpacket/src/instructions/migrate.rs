@@ -0,0 +1,107 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+use crate::log;
+use crate::constants::{
+    redpacket_size, rent_exempt, CLOSED_DISCRIMINATOR, ID, REDPACKET_BASE_SIZES,
+    REDPACKET_DISCRIMINATOR, REDPACKET_LARGE_SINCE, REDPACKET_VERSION, REDPACKET_VESTING_SINCE,
+    SYSTEM_PROGRAM_ID,
+};
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::state;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+///
+/// Accounts: authority (signer, writable; creator or admin, pays the extra
+/// rent), red_packet (writable), system_program, treasury (only when the
+/// authority is the admin: any treasury they administer)
+///
+/// Upgrades an older packet (any version below REDPACKET_VERSION) in place:
+/// grows the account, shifts amounts and claimers past the new header fields
+/// and fills those with defaults. v0 already stored token_type, so every
+/// existing field carries over as-is. Slots claimed before v11 get claimed_at
+/// 0 (unknown), and a vesting packet's taken amounts move past it. Claims
+/// before v12 always took slots in order, so claimed_slots marks
+/// 0..num_claimed. Packets from before v13 have no memo, and those from
+/// before v14 (v15) refund rather than burn (donate) their leftovers, and
+/// before v16 original_creator is the creator they still have. Large packets
+/// keep their amounts-only tail.
+pub fn process_migrate(accounts: &[AccountView], _data: &[u8]) -> ProgramResult {
+    if accounts.len() < 3 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let authority = &accounts[0];
+    let red_packet = &accounts[1];
+
+    if !authority.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // The authority pays for the account growing
+    check_writable(&[authority, red_packet])?;
+    check_distinct(&[authority, red_packet])?;
+    if accounts[2].address() != &SYSTEM_PROGRAM_ID {
+        return Err(RedPacketError::InvalidSystemProgram.into());
+    }
+
+    // validate_redpacket rejects old layouts, so check the basics by hand
+    if red_packet.lamports() == 0 {
+        return Err(RedPacketError::AccountClosed.into());
+    }
+    if !red_packet.owned_by(&ID) {
+        return Err(RedPacketError::InvalidAccountOwner.into());
+    }
+
+    let (from_version, num_recipients, large, vesting) = {
+        let rp_data = red_packet.try_borrow()?;
+        if rp_data.first() == Some(&CLOSED_DISCRIMINATOR) {
+            return Err(RedPacketError::AccountClosed.into());
+        }
+        if rp_data.len() < REDPACKET_BASE_SIZES[0] {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if rp_data[0] != REDPACKET_DISCRIMINATOR {
+            return Err(RedPacketError::InvalidDiscriminator.into());
+        }
+
+        // Creator and admin may both migrate (the header prefix is shared)
+        let is_creator = state::get_creator(&rp_data) == authority.address().as_ref();
+        if !is_creator {
+            let treasury = accounts.get(3).ok_or(RedPacketError::Unauthorized)?;
+            let tr = state::load_treasury(treasury, &ID)?;
+            if tr.admin() != authority.address().as_ref() {
+                return Err(RedPacketError::Unauthorized.into());
+            }
+        }
+
+        let from_version = match state::get_layout_version(&rp_data) {
+            Some(REDPACKET_VERSION) => return Err(RedPacketError::AlreadyMigrated.into()),
+            Some(version) => version,
+            None => return Err(RedPacketError::UnsupportedVersion.into()),
+        };
+        // Before large packets existed, byte 80 wasn't always the flags
+        let large = from_version >= REDPACKET_LARGE_SINCE && state::get_large(&rp_data);
+        let vesting = from_version >= REDPACKET_VESTING_SINCE && state::get_vesting(&rp_data);
+        (from_version, state::get_num_recipients(&rp_data), large, vesting)
+    };
+
+    // Grow to the current size, topping up rent from the authority first
+    let new_size = redpacket_size(num_recipients, large, vesting);
+    let shortfall = rent_exempt(new_size)?.saturating_sub(red_packet.lamports());
+    if shortfall > 0 {
+        pinocchio_system::instructions::Transfer {
+            from: authority,
+            to: red_packet,
+            lamports: shortfall,
+        }
+        .invoke()?;
+    }
+    red_packet.resize(new_size)?;
+
+    {
+        let mut rp_data = red_packet.try_borrow_mut()?;
+        state::upgrade_redpacket(&mut rp_data, from_version, num_recipients, large, vesting);
+    }
+
+    log("Migrated");
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+use crate::log;
+use crate::constants::{ID, TREASURY_VERSION};
+use crate::error::RedPacketError;
+use crate::instructions::update_fee::{check_treasury_admin, grow_legacy_treasury};
+use crate::state;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+///
+/// Accounts: admin (signer, writable; pays the extra rent), treasury
+/// (writable), system_program
+///
+/// Upgrades an older treasury to the current layout in one step: grows the
+/// account to TREASURY_SIZE, fills the new fields with the values it was
+/// implicitly using and stamps TREASURY_VERSION.
+pub fn process_migrate_treasury(accounts: &[AccountView], _data: &[u8]) -> ProgramResult {
+    if accounts.len() < 3 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let admin = &accounts[0];
+    let treasury = &accounts[1];
+
+    if !admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    check_treasury_admin(admin, treasury)?;
+
+    if state::load_treasury(treasury, &ID)?.version() == TREASURY_VERSION {
+        return Err(RedPacketError::AlreadyMigrated.into());
+    }
+
+    grow_legacy_treasury(accounts, admin, treasury)?;
+
+    log("Treasury migrated");
+    Ok(())
+}
@@ -3,9 +3,53 @@ pub mod claim;
 pub mod close;
 pub mod init_treasury;
 pub mod withdraw_fees;
+pub mod top_up;
+pub mod extend_expiry;
+pub mod cancel;
+pub mod update_fee;
+pub mod partial_withdraw;
+pub mod migrate;
+pub mod propose_admin;
+pub mod accept_admin;
+pub mod claim_batch;
+pub mod pause;
+pub mod unpause;
+pub mod close_treasury;
+pub mod migrate_treasury;
+pub mod close_receipt;
+pub mod reveal;
+pub mod finalize;
+pub mod set_expiry_bounds;
+pub mod peek;
+pub mod transfer_creator;
+pub mod get_version;
+#[cfg(feature = "cpi-events")]
+pub mod emit_event;
 
 pub use create::process_create;
 pub use claim::process_claim;
 pub use close::process_close;
 pub use init_treasury::process_init_treasury;
 pub use withdraw_fees::process_withdraw_fees;
+pub use top_up::process_top_up;
+pub use extend_expiry::process_extend_expiry;
+pub use cancel::process_cancel;
+pub use update_fee::process_update_fee;
+pub use partial_withdraw::process_partial_withdraw;
+pub use migrate::process_migrate;
+pub use propose_admin::process_propose_admin;
+pub use accept_admin::process_accept_admin;
+pub use claim_batch::process_claim_batch;
+pub use pause::process_pause;
+pub use unpause::process_unpause;
+pub use close_treasury::process_close_treasury;
+pub use migrate_treasury::process_migrate_treasury;
+pub use close_receipt::process_close_receipt;
+pub use reveal::process_reveal;
+pub use finalize::process_finalize;
+pub use set_expiry_bounds::process_set_expiry_bounds;
+pub use peek::process_peek;
+pub use transfer_creator::process_transfer_creator;
+pub use get_version::process_get_version;
+#[cfg(feature = "cpi-events")]
+pub use emit_event::process_emit_event;
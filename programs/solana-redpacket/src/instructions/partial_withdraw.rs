@@ -0,0 +1,140 @@
+use pinocchio::{
+    cpi::{Seed, Signer},
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, Address, ProgramResult,
+};
+use crate::log;
+use crate::constants::{ID, SEED_PREFIX, TOKEN_TYPE_SOL, VAULT_SEED};
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::state::{self, RedPacketView};
+use crate::instructions::claim::pay_from_sol_vault;
+use crate::instructions::close::{self, donate_lamports, refund_tokens, Leftover};
+use crate::token;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+/// [0] token_type: u8 (0=SPL, 1=SOL)
+///
+/// Accounts are the same as close (burn_unclaimed packets burn the refund, so
+/// they also take the vault's mint, and donate_unclaimed packets pay it to
+/// the treasury, so they take that). After expiry, moves remaining_amount
+/// from the vault back to the creator and zeroes it, but leaves the red packet
+/// (and its claimer history) and the vault rent in place for a later close.
+/// A raffle's pot (a lucky packet's jackpot) belongs to its entrants, so this
+/// fails with RaffleNotFinalized once anyone entered, until finalize pays it. On vesting packets only the
+/// unclaimed slots' share comes back: remaining_amount keeps what claimers
+/// are still owed, and they go on collecting it.
+pub fn process_partial_withdraw(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let token_type = data[0];
+    state::validate_token_type(token_type)?;
+
+    let is_sol = token_type == TOKEN_TYPE_SOL;
+
+    // SOL: creator, red_packet, vault, [treasury] (3-4)
+    // SPL: creator, creator_token_account, red_packet, vault, token_program,
+    //      [mint | treasury, treasury_vault] (5-7)
+    let min_accounts = if is_sol { 3 } else { 5 };
+    if accounts.len() < min_accounts {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+
+    let creator = &accounts[0];
+    let (red_packet, vault) = if is_sol {
+        (&accounts[1], &accounts[2])
+    } else {
+        token::validate_token_program(&accounts[4])?;
+        (&accounts[2], &accounts[3])
+    };
+
+    if !creator.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_writable(&[red_packet, vault])?;
+    let roles = [creator, red_packet, vault, &accounts[1]];
+    check_distinct(if is_sol { &roles[..3] } else { &roles })?;
+
+    // Verify authorization, lifecycle and vault PDA
+    let (bump, creator_bytes, id_bytes, owed, refund, leftover) = {
+        let rp = state::load_redpacket(red_packet, &ID)?;
+
+        if rp.token_type() != token_type {
+            return Err(RedPacketError::InvalidTokenType.into());
+        }
+        if rp.creator() != creator.address().as_ref() {
+            return Err(RedPacketError::Unauthorized.into());
+        }
+
+        let expires_at = rp.expires_at();
+        let vault_bump = rp.vault_bump();
+        let remaining_amount = rp.remaining_amount();
+        let id_bytes = rp.id().to_le_bytes();
+        let mut creator_bytes = [0u8; 32];
+        creator_bytes.copy_from_slice(rp.original_creator());
+
+        let vault_bump_bytes = [vault_bump];
+        let expected_vault = Address::create_program_address(
+            &[VAULT_SEED, &creator_bytes, &id_bytes, &vault_bump_bytes],
+            &ID,
+        )
+        .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+        if vault.address() != &expected_vault {
+            return Err(RedPacketError::InvalidPDA.into());
+        }
+
+        let clock = Clock::get()?;
+        if clock.unix_timestamp < expires_at {
+            return Err(RedPacketError::NotExpiredOrFull.into());
+        }
+        let owed = rp.vesting_owed();
+        let refund = remaining_amount
+            .checked_sub(owed)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        if refund == 0 {
+            return Err(RedPacketError::AlreadyWithdrawn.into());
+        }
+        if rp.draw_unpaid() {
+            return Err(RedPacketError::RaffleNotFinalized.into());
+        }
+
+        (rp.bump(), creator_bytes, id_bytes, owed, refund, close::leftover(rp.as_view()))
+    };
+
+    if is_sol {
+        if !vault.owned_by(&ID) {
+            return Err(RedPacketError::InvalidAccountOwner.into());
+        }
+
+        // Only the pot moves; the vault keeps its rent until close
+        if leftover == Leftover::Donate {
+            let treasury = accounts.get(3).ok_or(RedPacketError::NotEnoughAccounts)?;
+            donate_lamports(vault, treasury, refund)?;
+        } else {
+            pay_from_sol_vault(vault, creator, refund)?;
+        }
+    } else {
+        let bump_bytes = [bump];
+        let rp_seeds = [
+            Seed::from(SEED_PREFIX),
+            Seed::from(creator_bytes.as_ref()),
+            Seed::from(id_bytes.as_ref()),
+            Seed::from(bump_bytes.as_ref()),
+        ];
+        let rp_signer = [Signer::from(&rp_seeds)];
+
+        token::check_vault(vault, red_packet, None)?;
+        refund_tokens(accounts, vault, red_packet, refund, leftover, &rp_signer)?;
+    }
+
+    {
+        let mut rp = RedPacketView::try_from(red_packet.try_borrow_mut()?)?;
+        rp.set_remaining_amount(owed);
+    }
+
+    log("Withdrawn");
+    Ok(())
+}
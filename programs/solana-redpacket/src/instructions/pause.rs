@@ -0,0 +1,43 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+use crate::log;
+use crate::error::RedPacketError;
+use crate::instructions::update_fee::{check_treasury_admin, grow_legacy_treasury};
+use crate::state::TreasuryView;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+///
+/// Accounts: admin (signer, writable), treasury (writable), system_program
+/// (only needed to grow a legacy treasury; admin pays the extra rent)
+///
+/// Halts creates, top-ups and claims against this treasury's mint. Close,
+/// cancel, partial_withdraw and withdraw_fees keep working so funds can
+/// always exit.
+pub fn process_pause(accounts: &[AccountView], _data: &[u8]) -> ProgramResult {
+    set_paused(accounts, true)
+}
+
+/// Shared by pause and unpause: same accounts, only the flag differs.
+pub(crate) fn set_paused(accounts: &[AccountView], paused: bool) -> ProgramResult {
+    if accounts.len() < 2 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let admin = &accounts[0];
+    let treasury = &accounts[1];
+
+    if !admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    check_treasury_admin(admin, treasury)?;
+
+    grow_legacy_treasury(accounts, admin, treasury)?;
+
+    {
+        let mut tr = TreasuryView::try_from(treasury.try_borrow_mut()?)?;
+        tr.set_paused(paused);
+    }
+
+    log(if paused { "Paused" } else { "Unpaused" });
+    Ok(())
+}
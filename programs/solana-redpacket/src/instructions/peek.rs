@@ -0,0 +1,80 @@
+use pinocchio::{
+    cpi::set_return_data,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, ProgramResult,
+};
+use crate::constants::ID;
+use crate::error::RedPacketError;
+use crate::state::{self, RedPacketView};
+
+/// Peek return data layout (PEEK_LEN bytes, integers little-endian):
+/// [0] status: PEEK_ACTIVE, PEEK_FULLY_CLAIMED or PEEK_EXPIRED (full wins)
+/// [1] token_type, [2] split_mode, [3] num_recipients, [4] num_claimed
+/// [5..13] total_amount: u64
+/// [13..21] remaining_amount: u64
+/// [21..29] next_amount: u64, what a claim of the lowest unclaimed slot pays
+///          now (a vesting slot's unlocked share); 0 when the packet isn't
+///          active or draws its amounts at claim time
+/// [29..37] expires_at: i64
+/// [37..45] now: i64, the clock the status was computed at
+///
+/// The blinks server's `program::decode_peek` decodes it; keep the two in step.
+pub const PEEK_LEN: usize = 45;
+pub const PEEK_ACTIVE: u8 = 0;
+pub const PEEK_FULLY_CLAIMED: u8 = 1;
+pub const PEEK_EXPIRED: u8 = 2;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+///
+/// Accounts: red_packet
+///
+/// Read-only view for clients that would rather simulate than decode the
+/// account layout: sets return data to the packet's peek (see PEEK_LEN) and
+/// changes nothing. Packets on an old layout fail with UnsupportedVersion
+/// until migrated, as every other instruction does.
+pub fn process_peek(accounts: &[AccountView], _data: &[u8]) -> ProgramResult {
+    let red_packet = accounts.first().ok_or(RedPacketError::NotEnoughAccounts)?;
+    let rp = state::load_redpacket(red_packet, &ID)?;
+    set_return_data(&peek(rp.as_view(), Clock::get()?.unix_timestamp));
+    Ok(())
+}
+
+/// The peek of red packet `rp` at `now`
+pub fn peek(rp: RedPacketView<&[u8]>, now: i64) -> [u8; PEEK_LEN] {
+    let num_recipients = rp.num_recipients();
+    let num_claimed = rp.num_claimed();
+    let expires_at = rp.expires_at();
+    let status = if num_claimed >= num_recipients {
+        PEEK_FULLY_CLAIMED
+    } else if now >= expires_at {
+        PEEK_EXPIRED
+    } else {
+        PEEK_ACTIVE
+    };
+
+    let next_amount = match rp.unclaimed_slots().next() {
+        Some(slot) if status == PEEK_ACTIVE && !rp.random_onchain() => {
+            let amount = rp.amount_at(slot);
+            if rp.vesting() {
+                state::vested_amount(amount, rp.vesting_start(), expires_at, now)
+            } else {
+                amount
+            }
+        }
+        _ => 0,
+    };
+
+    let mut out = [0u8; PEEK_LEN];
+    out[0] = status;
+    out[1] = rp.token_type();
+    out[2] = rp.split_mode();
+    out[3] = num_recipients;
+    out[4] = num_claimed;
+    out[5..13].copy_from_slice(&rp.total_amount().to_le_bytes());
+    out[13..21].copy_from_slice(&rp.remaining_amount().to_le_bytes());
+    out[21..29].copy_from_slice(&next_amount.to_le_bytes());
+    out[29..37].copy_from_slice(&expires_at.to_le_bytes());
+    out[37..45].copy_from_slice(&now.to_le_bytes());
+    out
+}
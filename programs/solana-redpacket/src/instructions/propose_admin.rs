@@ -0,0 +1,44 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+use crate::log;
+use crate::error::RedPacketError;
+use crate::instructions::update_fee::{check_treasury_admin, grow_legacy_treasury};
+use crate::state::TreasuryView;
+
+/// Instruction data layout:
+/// [0]      discriminator (already consumed)
+/// [0..32]  new_admin: [u8; 32]
+///
+/// Accounts: admin (signer, writable), treasury (writable), system_program
+/// (only needed to grow a legacy treasury; admin pays the extra rent)
+///
+/// First half of an admin handover: records new_admin as pending. Nothing
+/// changes until new_admin signs accept_admin, so a typo can't lock the
+/// treasury. Proposing again replaces the pending nomination.
+pub fn process_propose_admin(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.len() < 32 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let new_admin = &data[0..32];
+
+    if accounts.len() < 2 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let admin = &accounts[0];
+    let treasury = &accounts[1];
+
+    if !admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    check_treasury_admin(admin, treasury)?;
+
+    grow_legacy_treasury(accounts, admin, treasury)?;
+
+    {
+        let mut tr = TreasuryView::try_from(treasury.try_borrow_mut()?)?;
+        tr.set_pending_admin(new_admin);
+    }
+
+    log("Admin proposed");
+    Ok(())
+}
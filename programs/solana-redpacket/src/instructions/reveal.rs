@@ -0,0 +1,57 @@
+use pinocchio::{
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, ProgramResult,
+};
+use crate::log;
+use crate::constants::ID;
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::state;
+
+/// Instruction data layout:
+/// [0]      discriminator (already consumed)
+/// [0..32]  secret: [u8; 32] (sha256 must equal the packet's reveal_commitment)
+///
+/// Accounts: creator (signer), red_packet (writable)
+///
+/// Opens claims on a commit-reveal packet. The secret replaces the
+/// commitment and seeds every claim's draw. Fails with Expired once
+/// reveal_deadline has passed (the packet can then only be closed or
+/// cancelled), and with AlreadyRevealed on packets that don't wait on a reveal.
+pub fn process_reveal(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.len() < 32 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let secret = &data[..32];
+
+    if accounts.len() < 2 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let creator = &accounts[0];
+    let red_packet = &accounts[1];
+
+    if !creator.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_writable(&[red_packet])?;
+    check_distinct(&[creator, red_packet])?;
+
+    let mut rp = state::load_redpacket_mut(red_packet, &ID)?;
+
+    if rp.creator() != creator.address().as_ref() {
+        return Err(RedPacketError::Unauthorized.into());
+    }
+    let commitment = rp.pending_commitment().ok_or(RedPacketError::AlreadyRevealed)?;
+    if crate::sha256(secret) != commitment {
+        return Err(RedPacketError::InvalidSecret.into());
+    }
+    if Clock::get()?.unix_timestamp >= rp.reveal_deadline() {
+        return Err(RedPacketError::Expired.into());
+    }
+
+    rp.set_revealed(secret);
+
+    log("Commitment revealed");
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+use crate::constants::MAX_EXPIRY_SECONDS;
+use crate::error::RedPacketError;
+use crate::instructions::update_fee::{check_treasury_admin, grow_legacy_treasury};
+use crate::state::TreasuryView;
+
+/// Instruction data layout:
+/// [0]      discriminator (already consumed)
+/// [0..8]   min_expiry_secs: i64
+/// [8..16]  max_expiry_secs: i64 (min_expiry_secs..=MAX_EXPIRY_SECONDS)
+///
+/// Accounts: admin (signer, writable), treasury (writable), system_program
+/// (only needed to grow a legacy treasury; admin pays the extra rent)
+///
+/// Bounds how far out create may set expires_at against this treasury's
+/// mint. Existing packets and extend_expiry (capped at MAX_EXPIRY_SECONDS)
+/// are unaffected.
+pub fn process_set_expiry_bounds(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.len() < 16 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let min_expiry_secs = i64::from_le_bytes(data[0..8].try_into().unwrap());
+    let max_expiry_secs = i64::from_le_bytes(data[8..16].try_into().unwrap());
+    if min_expiry_secs < 0 || min_expiry_secs > max_expiry_secs || max_expiry_secs > MAX_EXPIRY_SECONDS {
+        return Err(RedPacketError::InvalidExpiry.into());
+    }
+
+    if accounts.len() < 2 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let admin = &accounts[0];
+    let treasury = &accounts[1];
+
+    if !admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    check_treasury_admin(admin, treasury)?;
+
+    grow_legacy_treasury(accounts, admin, treasury)?;
+
+    let mut tr = TreasuryView::try_from(treasury.try_borrow_mut()?)?;
+    tr.set_expiry_bounds(min_expiry_secs, max_expiry_secs);
+
+    #[cfg(feature = "logging")]
+    {
+        let mut msg = crate::state::FixedBuf::<64>::new();
+        msg.extend_from_slice(b"Expiry bounds: ")?;
+        msg.push_i64(min_expiry_secs)?;
+        msg.extend_from_slice(b"s to ")?;
+        msg.push_i64(max_expiry_secs)?;
+        msg.extend_from_slice(b"s")?;
+        crate::log(msg.as_str());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,362 @@
+use pinocchio::{
+    error::ProgramError,
+    sysvars::{clock::Clock, Sysvar},
+    AccountView, Address, ProgramResult,
+};
+use crate::log;
+use crate::constants::{
+    redpacket_size, rent_exempt, ID, LUCKY_SHARE_BPS, MAX_RECIPIENTS, MAX_RECIPIENTS_LARGE, MAX_TOTAL_AMOUNT,
+    NATIVE_SOL_MINT, SPLIT_EVEN,
+    SPLIT_RAFFLE, SYSTEM_PROGRAM_ID, TOKEN_TYPE_SOL, TREASURY_SEED, TREASURY_VAULT_SEED,
+    VAULT_SEED,
+};
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::instructions::create::compute_fee;
+use crate::ix_data::TopUpArgs;
+use crate::state::{self, RedPacketView, TreasuryView};
+use crate::token::{self, Transfer};
+
+/// Instruction data layout:
+/// [0]       discriminator (already consumed)
+/// [0]       token_type: u8 (0=SPL, 1=SOL)
+/// [1..9]    amount: u64 (added to the pot, fee charged on top)
+/// [9]       add_recipients: u8 (optional, default 0)
+/// [10..]    extra_amounts: [u64; N + add_recipients - num_claimed], one per
+///           unclaimed slot, lowest first (only for
+///           weighted packets and random packets from before
+///           FLAG_RANDOM_ONCHAIN; exactly that many, and none for
+///           other packets)
+///
+/// Accounts:
+/// SOL: creator (signer, writable), red_packet (writable), vault (writable),
+///      treasury (writable), system_program
+/// SPL: creator (signer, writable), creator_token_account (writable),
+///      red_packet (writable), vault (writable), treasury, treasury_vault
+///      (writable), token_program, system_program (only when adding recipients)
+///
+/// add_recipients appends that many empty slots after the existing ones,
+/// growing the account (the creator pays the extra rent), up to
+/// MAX_RECIPIENTS_LARGE for large packets; assigned packets have no recipient
+/// for new slots, so they can't grow. Even mode then re-splits the new
+/// remaining pot (less what vesting claimers are still owed) across the
+/// unclaimed slots. On-chain random packets just grow the pot their claims
+/// draw from, which must keep 1 unit per unclaimed slot beside any unpaid
+/// lucky jackpot. Raffles just grow the pot. Weighted and older random
+/// packets add extra_amounts[i] to the i-th unclaimed slot; the extras must
+/// sum to `amount` and be nonzero for new slots (the last add_recipients).
+/// Claimed slots are never touched. Fails while the treasury is paused.
+pub fn process_top_up(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    let TopUpArgs { token_type, amount, add_recipients, extra_amounts } = TopUpArgs::try_from(data)?;
+    state::validate_token_type(token_type)?;
+
+    if amount == 0 {
+        return Err(RedPacketError::InvalidAmount.into());
+    }
+
+    let is_sol = token_type == TOKEN_TYPE_SOL;
+
+    // SOL: creator, red_packet, vault, treasury, system_program (5)
+    // SPL: creator, creator_token_account, red_packet, vault, treasury, treasury_vault, token_program (7)
+    // SPL also needs system_program (8) to pay rent when adding recipients
+    let min_accounts = if is_sol { 5 } else if add_recipients > 0 { 8 } else { 7 };
+    if accounts.len() < min_accounts {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+
+    let creator = &accounts[0];
+    let (red_packet, vault, treasury) = if is_sol {
+        if accounts[4].address() != &SYSTEM_PROGRAM_ID {
+            return Err(RedPacketError::InvalidSystemProgram.into());
+        }
+        (&accounts[1], &accounts[2], &accounts[3])
+    } else {
+        token::validate_token_program(&accounts[6])?;
+        if add_recipients > 0 && accounts[7].address() != &SYSTEM_PROGRAM_ID {
+            return Err(RedPacketError::InvalidSystemProgram.into());
+        }
+        (&accounts[2], &accounts[3], &accounts[4])
+    };
+
+    if !creator.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if is_sol {
+        check_writable(&[creator, red_packet, vault, treasury])?;
+        check_distinct(&[creator, red_packet, vault, treasury])?;
+    } else {
+        // creator_token_account and treasury_vault
+        check_writable(&[creator, &accounts[1], red_packet, vault, &accounts[5]])?;
+        check_distinct(&[creator, &accounts[1], red_packet, vault, treasury, &accounts[5]])?;
+    }
+
+    // Verify authorization, lifecycle and vault PDA
+    let (split_mode, num_recipients, num_claimed, large, vesting, random_onchain, remaining, lucky_total) = {
+        let rp = state::load_redpacket(red_packet, &ID)?;
+
+        if rp.token_type() != token_type {
+            return Err(RedPacketError::InvalidTokenType.into());
+        }
+        if rp.creator() != creator.address().as_ref() {
+            return Err(RedPacketError::Unauthorized.into());
+        }
+
+        let num_recipients = rp.num_recipients();
+        let num_claimed = rp.num_claimed();
+        let expires_at = rp.expires_at();
+        let vault_bump = rp.vault_bump();
+        let id_bytes = rp.id().to_le_bytes();
+
+        let vault_bump_bytes = [vault_bump];
+        let expected_vault = Address::create_program_address(
+            &[VAULT_SEED, rp.original_creator(), &id_bytes, &vault_bump_bytes],
+            &ID,
+        )
+        .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+        if vault.address() != &expected_vault {
+            return Err(RedPacketError::InvalidPDA.into());
+        }
+
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= expires_at {
+            return Err(RedPacketError::Expired.into());
+        }
+        if num_claimed >= num_recipients {
+            return Err(RedPacketError::RedPacketFull.into());
+        }
+
+        if add_recipients > 0 && rp.assigned() {
+            return Err(RedPacketError::InvalidRecipientCount.into());
+        }
+
+        (
+            rp.split_mode(),
+            num_recipients,
+            num_claimed,
+            rp.large(),
+            rp.vesting(),
+            rp.random_onchain(),
+            rp.remaining_amount(),
+            // Total an unpaid lucky jackpot is a share of
+            (rp.lucky_jackpot() > 0).then(|| rp.total_amount()),
+        )
+    };
+
+    let max_recipients = if large { MAX_RECIPIENTS_LARGE } else { MAX_RECIPIENTS };
+    if add_recipients > max_recipients - num_recipients {
+        return Err(RedPacketError::InvalidRecipientCount.into());
+    }
+    let old_num_recipients = num_recipients;
+    let num_recipients = num_recipients + add_recipients;
+
+    let unclaimed = (num_recipients - num_claimed) as usize;
+    if random_onchain {
+        let pot = remaining.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?;
+        // What the draws still share leaves out a lucky packet's unpaid
+        // jackpot, which grows with the new total
+        let jackpot = match lucky_total {
+            Some(total) => state::weighted_amount(
+                total.checked_add(amount).ok_or(ProgramError::ArithmeticOverflow)?,
+                LUCKY_SHARE_BPS,
+            ),
+            None => 0,
+        };
+        if pot.saturating_sub(jackpot) < unclaimed as u64 {
+            return Err(RedPacketError::InvalidAmount.into());
+        }
+    }
+
+    // Weighted and older random packets: exactly one extra amount per
+    // unclaimed slot, summing to `amount`; new slots start empty, so theirs
+    // must be nonzero. Other packets take none.
+    let wants_extras = !random_onchain && split_mode != SPLIT_EVEN && split_mode != SPLIT_RAFFLE;
+    if extra_amounts.len() != if wants_extras { 8 * unclaimed } else { 0 } {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    if wants_extras {
+        let mut sum = 0u64;
+        for i in 0..unclaimed {
+            let offset = i * 8;
+            let extra = u64::from_le_bytes(extra_amounts[offset..offset + 8].try_into().unwrap());
+            if extra == 0 && i >= unclaimed - add_recipients as usize {
+                return Err(RedPacketError::InvalidAmount.into());
+            }
+            sum = sum.checked_add(extra).ok_or(ProgramError::ArithmeticOverflow)?;
+        }
+        if sum != amount {
+            return Err(RedPacketError::AmountMismatch.into());
+        }
+    }
+
+    let fee;
+
+    if is_sol {
+        // Verify treasury PDA (includes NATIVE_SOL_MINT in seeds)
+        {
+            let tr = state::load_treasury(treasury, &ID)?;
+            if tr.paused() {
+                return Err(RedPacketError::ProgramPaused.into());
+            }
+            let t_bump_bytes = [tr.bump()];
+            let expected_treasury = Address::create_program_address(
+                &[TREASURY_SEED, &NATIVE_SOL_MINT, &t_bump_bytes],
+                &ID,
+            )
+            .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+            if treasury.address() != &expected_treasury {
+                return Err(RedPacketError::InvalidPDA.into());
+            }
+            fee = compute_fee(amount, tr.fee_bps())?;
+        }
+
+        pinocchio_system::instructions::Transfer {
+            from: creator,
+            to: vault,
+            lamports: amount,
+        }
+        .invoke()?;
+
+        pinocchio_system::instructions::Transfer {
+            from: creator,
+            to: treasury,
+            lamports: fee,
+        }
+        .invoke()?;
+
+        let mut tr = TreasuryView::try_from(treasury.try_borrow_mut()?)?;
+        let current = tr.sol_fees_collected();
+        tr.set_sol_fees_collected(current.checked_add(fee).ok_or(ProgramError::ArithmeticOverflow)?);
+    } else {
+        let creator_token_account = &accounts[1];
+        let treasury_vault = &accounts[5];
+
+        // The packet doesn't store its mint; the vault token account does (offset 0)
+        let mut mint = [0u8; 32];
+        {
+            let vdata = vault.try_borrow()?;
+            if vdata.len() < 32 {
+                return Err(RedPacketError::InvalidTokenAccount.into());
+            }
+            mint.copy_from_slice(&vdata[0..32]);
+        }
+
+        // Verify treasury matches the vault mint, then treasury + treasury_vault PDAs
+        {
+            let tr = state::load_treasury(treasury, &ID)?;
+            if tr.paused() {
+                return Err(RedPacketError::ProgramPaused.into());
+            }
+            if tr.mint() != mint {
+                return Err(RedPacketError::InvalidMint.into());
+            }
+
+            let t_bump_bytes = [tr.bump()];
+            let expected_treasury = Address::create_program_address(
+                &[TREASURY_SEED, &mint, &t_bump_bytes],
+                &ID,
+            )
+            .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+            if treasury.address() != &expected_treasury {
+                return Err(RedPacketError::InvalidPDA.into());
+            }
+
+            let tv_bump_bytes = [tr.vault_bump()];
+            let expected_tv = Address::create_program_address(
+                &[TREASURY_VAULT_SEED, &mint, &tv_bump_bytes],
+                &ID,
+            )
+            .map_err(|_| ProgramError::from(RedPacketError::InvalidPDA))?;
+            if treasury_vault.address() != &expected_tv {
+                return Err(RedPacketError::InvalidPDA.into());
+            }
+            fee = compute_fee(amount, tr.fee_bps())?;
+        }
+
+        Transfer {
+            from: creator_token_account,
+            to: vault,
+            authority: creator,
+            amount,
+            token_program: accounts[6].address(),
+        }
+        .invoke()?;
+
+        Transfer {
+            from: creator_token_account,
+            to: treasury_vault,
+            authority: creator,
+            amount: fee,
+            token_program: accounts[6].address(),
+        }
+        .invoke()?;
+    }
+
+    // Grow for the new slots, topping up rent from the creator first
+    if add_recipients > 0 {
+        let new_size = redpacket_size(num_recipients, large, vesting);
+        let shortfall = rent_exempt(new_size)?.saturating_sub(red_packet.lamports());
+        if shortfall > 0 {
+            pinocchio_system::instructions::Transfer {
+                from: creator,
+                to: red_packet,
+                lamports: shortfall,
+            }
+            .invoke()?;
+        }
+        red_packet.resize(new_size)?;
+
+        let mut rp_data = red_packet.try_borrow_mut()?;
+        state::grow_redpacket(&mut rp_data, old_num_recipients, num_recipients);
+    }
+
+    // Update pot and unclaimed slot amounts
+    {
+        let mut rp = RedPacketView::try_from(red_packet.try_borrow_mut()?)?;
+
+        let total = rp.total_amount()
+            .checked_add(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        if total > MAX_TOTAL_AMOUNT {
+            return Err(RedPacketError::AmountTooLarge.into());
+        }
+        let remaining = rp.remaining_amount()
+            .checked_add(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        let fee_paid = rp.fee_paid()
+            .checked_add(fee)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        rp.set_total_amount(total);
+        rp.set_remaining_amount(remaining);
+        rp.set_fee_paid(fee_paid);
+
+        if split_mode == SPLIT_EVEN {
+            // A vesting packet's claimers keep what they haven't collected
+            let pot = remaining - rp.vesting_owed();
+            let (per_person, last) = state::even_split(pot, unclaimed as u64);
+            // Highest unclaimed slot first, which takes the remainder
+            let mut amount = last;
+            for slot in (0..num_recipients).rev() {
+                if rp.is_claimed(slot) {
+                    continue;
+                }
+                rp.set_amount_at(slot, amount);
+                amount = per_person;
+            }
+        } else if wants_extras {
+            let mut i = 0;
+            for slot in 0..num_recipients {
+                if rp.is_claimed(slot) {
+                    continue;
+                }
+                let offset = i * 8;
+                i += 1;
+                let extra = u64::from_le_bytes(extra_amounts[offset..offset + 8].try_into().unwrap());
+                let current = rp.amount_at(slot);
+                rp.set_amount_at(slot, current.checked_add(extra).ok_or(ProgramError::ArithmeticOverflow)?);
+            }
+        }
+    }
+
+    log("Topped up");
+    Ok(())
+}
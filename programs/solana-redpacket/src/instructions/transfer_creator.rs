@@ -0,0 +1,46 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+use crate::log;
+use crate::constants::ID;
+use crate::accounts::{check_distinct, check_writable};
+use crate::error::RedPacketError;
+use crate::state;
+
+/// Instruction data layout:
+/// [0]      discriminator (already consumed)
+/// [0..32]  new_creator: [u8; 32]
+///
+/// Accounts: creator (signer), red_packet (writable)
+///
+/// Hands the packet to new_creator, who from then on is the one that closes,
+/// cancels, tops up, extends, reveals, batch-claims and migrates it, and gets
+/// its refunds. Only the stored creator field changes: the packet and vault
+/// stay at the addresses derived from original_creator and id, so the close
+/// authority is no longer the creator the address was derived from. Takes
+/// effect at once; there is no accept step.
+pub fn process_transfer_creator(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.len() < 32 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let new_creator = &data[0..32];
+
+    if accounts.len() < 2 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let creator = &accounts[0];
+    let red_packet = &accounts[1];
+
+    if !creator.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    check_writable(&[red_packet])?;
+    check_distinct(&[creator, red_packet])?;
+
+    let mut rp = state::load_redpacket_mut(red_packet, &ID)?;
+    if rp.creator() != creator.address().as_ref() {
+        return Err(RedPacketError::CreatorMismatch.into());
+    }
+    rp.set_creator(new_creator);
+
+    log("Creator transferred");
+    Ok(())
+}
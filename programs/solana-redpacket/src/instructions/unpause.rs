@@ -0,0 +1,10 @@
+use pinocchio::{AccountView, ProgramResult};
+use crate::instructions::pause::set_paused;
+
+/// Instruction data layout:
+/// [0] discriminator (already consumed)
+///
+/// Accounts are the same as pause. Reopens creates and claims.
+pub fn process_unpause(accounts: &[AccountView], _data: &[u8]) -> ProgramResult {
+    set_paused(accounts, false)
+}
@@ -0,0 +1,103 @@
+use pinocchio::{error::ProgramError, AccountView, ProgramResult};
+use crate::constants::{ID, MAX_FEE_BPS, SYSTEM_PROGRAM_ID, TREASURY_SIZE, rent_exempt};
+use crate::accounts::check_writable;
+use crate::error::RedPacketError;
+use crate::state::{self, TreasuryView};
+
+/// Instruction data layout:
+/// [0]     discriminator (already consumed)
+/// [0..2]  fee_bps: u16 (<= MAX_FEE_BPS)
+///
+/// Accounts: admin (signer), treasury (writable), system_program
+/// (only needed to grow a legacy treasury; admin pays the extra rent)
+///
+/// Only affects packets created afterwards: existing packets keep the
+/// fee_paid they were charged.
+pub fn process_update_fee(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
+    if data.len() < 2 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let fee_bps = u16::from_le_bytes([data[0], data[1]]);
+    if fee_bps > MAX_FEE_BPS {
+        return Err(RedPacketError::InvalidFeeRate.into());
+    }
+
+    if accounts.len() < 2 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    let admin = &accounts[0];
+    let treasury = &accounts[1];
+
+    if !admin.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Program-owned + treasury discriminator (only init_treasury creates these, at PDAs)
+    check_treasury_admin(admin, treasury)?;
+
+    grow_legacy_treasury(accounts, admin, treasury)?;
+
+    let mut tr = TreasuryView::try_from(treasury.try_borrow_mut()?)?;
+    #[cfg(feature = "logging")]
+    let old_fee_bps = tr.fee_bps();
+    tr.set_fee_bps(fee_bps);
+
+    #[cfg(feature = "logging")]
+    {
+        let mut msg = state::FixedBuf::<48>::new();
+        msg.extend_from_slice(b"Fee updated: ")?;
+        msg.push_i64(old_fee_bps as i64)?;
+        msg.extend_from_slice(b" -> ")?;
+        msg.push_i64(fee_bps as i64)?;
+        msg.extend_from_slice(b" bps")?;
+        crate::log(msg.as_str());
+    }
+
+    Ok(())
+}
+
+/// The treasury must be valid, the signer must be the admin stored in it
+/// (ADMIN for legacy ones), and every admin instruction writes the treasury.
+pub(crate) fn check_treasury_admin(admin: &AccountView, treasury: &AccountView) -> ProgramResult {
+    let tr = state::load_treasury(treasury, &ID)?;
+    check_writable(&[treasury])?;
+    if tr.admin() != admin.address().as_ref() {
+        return Err(RedPacketError::UnauthorizedAdmin.into());
+    }
+    Ok(())
+}
+
+/// Grow a legacy treasury to TREASURY_SIZE, with `payer` topping up rent and
+/// the new fields set to what the treasury was implicitly using. Expects
+/// system_program at accounts[2]; a no-op for current-size treasuries.
+pub(crate) fn grow_legacy_treasury(
+    accounts: &[AccountView],
+    payer: &AccountView,
+    treasury: &AccountView,
+) -> ProgramResult {
+    let old_len = treasury.data_len();
+    if old_len >= TREASURY_SIZE {
+        return Ok(());
+    }
+    if accounts.len() < 3 {
+        return Err(RedPacketError::NotEnoughAccounts.into());
+    }
+    if accounts[2].address() != &SYSTEM_PROGRAM_ID {
+        return Err(RedPacketError::InvalidSystemProgram.into());
+    }
+    check_writable(&[payer])?;
+    let shortfall = rent_exempt(TREASURY_SIZE)?.saturating_sub(rent_exempt(old_len)?);
+    if shortfall > 0 {
+        pinocchio_system::instructions::Transfer {
+            from: payer,
+            to: treasury,
+            lamports: shortfall,
+        }
+        .invoke()?;
+    }
+    treasury.resize(TREASURY_SIZE)?;
+
+    let mut tdata = treasury.try_borrow_mut()?;
+    state::upgrade_treasury(&mut tdata, old_len);
+    Ok(())
+}
@@ -3,24 +3,27 @@ use pinocchio::{
     error::ProgramError,
     AccountView, Address, ProgramResult,
 };
-use pinocchio_token::instructions::Transfer;
 use crate::log;
-use crate::constants::{ADMIN, ID, NATIVE_SOL_MINT, TOKEN_PROGRAM_ID, TREASURY_SEED, TREASURY_SIZE, TREASURY_VAULT_SEED, TOKEN_TYPE_SOL, rent_exempt};
+use crate::constants::{ID, NATIVE_SOL_MINT, TREASURY_SEED, TREASURY_VAULT_SEED, TOKEN_TYPE_SOL, rent_exempt};
+use crate::accounts::{check_distinct, check_writable};
 use crate::error::RedPacketError;
-use crate::state;
+use crate::ix_data::WithdrawFeesArgs;
+use crate::state::{self, TreasuryView};
+use crate::token::{self, Transfer};
 
 /// Instruction data layout:
 /// [0]     discriminator (already consumed)
 /// [0]     token_type: u8 (0=SPL, 1=SOL)
 /// [1..9]  amount: u64 (0 = withdraw all)
+/// [9]     strict: u8 (optional, default 0; 1 = the destination must be the
+///         admin's own wallet / token account)
+///
+/// Accounts:
+/// SOL: admin (signer), treasury, [destination] (defaults to admin)
+/// SPL: admin (signer), destination_token_account, treasury, treasury_vault, token_program
 pub fn process_withdraw_fees(accounts: &[AccountView], data: &[u8]) -> ProgramResult {
-    // Parse token type and amount
-    if data.len() < 9 {
-        return Err(ProgramError::InvalidInstructionData);
-    }
-    let token_type = data[0];
+    let WithdrawFeesArgs { token_type, amount, strict } = WithdrawFeesArgs::try_from(data)?;
     state::validate_token_type(token_type)?;
-    let amount = u64::from_le_bytes(data[1..9].try_into().unwrap());
 
     let is_sol = token_type == TOKEN_TYPE_SOL;
 
@@ -32,24 +35,29 @@ pub fn process_withdraw_fees(accounts: &[AccountView], data: &[u8]) -> ProgramRe
 
     let admin = &accounts[0];
 
-    // Validate admin is signer and matches ADMIN constant
+    // Validate admin is signer (checked against the treasury's admin below)
     if !admin.is_signer() {
         return Err(ProgramError::MissingRequiredSignature);
     }
-    if admin.address() != &ADMIN {
-        return Err(RedPacketError::UnauthorizedAdmin.into());
-    }
 
     if is_sol {
         let treasury = &accounts[1];
+        // Optional cold wallet; the admin still has to sign
+        let destination = accounts.get(2).unwrap_or(admin);
+        if destination.address() == treasury.address()
+            || (strict && destination.address() != admin.address())
+        {
+            return Err(RedPacketError::InvalidDestination.into());
+        }
+        check_writable(&[treasury, destination])?;
 
-        // Validate treasury
-        state::validate_treasury(treasury, &ID)?;
-
-        // Verify treasury PDA (includes NATIVE_SOL_MINT in seeds)
+        // Verify admin and treasury PDA (includes NATIVE_SOL_MINT in seeds)
         {
-            let tdata = treasury.try_borrow()?;
-            let t_bump = state::get_treasury_bump(&tdata);
+            let tr = state::load_treasury(treasury, &ID)?;
+            if tr.admin() != admin.address().as_ref() {
+                return Err(RedPacketError::UnauthorizedAdmin.into());
+            }
+            let t_bump = tr.bump();
             let t_bump_bytes = [t_bump];
             let expected_treasury = Address::create_program_address(
                 &[TREASURY_SEED, &NATIVE_SOL_MINT, &t_bump_bytes],
@@ -61,12 +69,12 @@ pub fn process_withdraw_fees(accounts: &[AccountView], data: &[u8]) -> ProgramRe
             }
         }
 
-        // Read sol_fees_collected and compute withdrawal
-        let treasury_rent = rent_exempt(TREASURY_SIZE);
+        // Read sol_fees_collected and compute withdrawal (legacy treasuries are smaller)
+        let treasury_rent = rent_exempt(treasury.data_len())?;
 
         let (sol_fees, withdraw_amount) = {
-            let tdata = treasury.try_borrow()?;
-            let sol_fees = state::get_sol_fees_collected(&tdata);
+            let tr = TreasuryView::try_from(treasury.try_borrow()?)?;
+            let sol_fees = tr.sol_fees_collected();
 
             // Available = min(sol_fees_collected, lamports above rent-exempt)
             let lamports_above_rent = treasury.lamports().saturating_sub(treasury_rent);
@@ -84,51 +92,46 @@ pub fn process_withdraw_fees(accounts: &[AccountView], data: &[u8]) -> ProgramRe
             (sol_fees, withdraw_amount)
         };
 
-        // Direct lamport transfer: treasury -> admin
+        // Direct lamport transfer: treasury -> destination
         treasury.set_lamports(
             treasury.lamports()
                 .checked_sub(withdraw_amount)
                 .ok_or(ProgramError::ArithmeticOverflow)?,
         );
-        admin.set_lamports(
-            admin.lamports()
+        destination.set_lamports(
+            destination.lamports()
                 .checked_add(withdraw_amount)
                 .ok_or(ProgramError::ArithmeticOverflow)?,
         );
 
         // Update sol_fees_collected
         {
-            let mut tdata = treasury.try_borrow_mut()?;
-            state::set_sol_fees_collected(
-                &mut tdata,
-                sol_fees.checked_sub(withdraw_amount)
-                    .ok_or(ProgramError::ArithmeticOverflow)?,
-            );
+            let mut tr = TreasuryView::try_from(treasury.try_borrow_mut()?)?;
+            tr.set_sol_fees_collected(sol_fees.checked_sub(withdraw_amount).ok_or(ProgramError::ArithmeticOverflow)?);
         }
 
         log("SOL fees withdrawn");
     } else {
-        let admin_token_account = &accounts[1];
+        let destination = &accounts[1];
         let treasury = &accounts[2];
         let treasury_vault = &accounts[3];
-        let token_program = &accounts[4];
+        check_writable(&[destination, treasury_vault])?;
+        check_distinct(&[destination, treasury, treasury_vault])?;
 
         // Validate token program
-        if token_program.address() != &TOKEN_PROGRAM_ID {
-            return Err(RedPacketError::InvalidTokenProgram.into());
-        }
-
-        // Validate treasury
-        state::validate_treasury(treasury, &ID)?;
+        let token_program = token::validate_token_program(&accounts[4])?;
 
-        // Read treasury data for PDA verification and vault verification
+        // Read treasury data for admin, PDA and vault verification
         let (treasury_bump, mint_bytes) = {
-            let tdata = treasury.try_borrow()?;
-            let bump = state::get_treasury_bump(&tdata);
+            let tr = state::load_treasury(treasury, &ID)?;
+            if tr.admin() != admin.address().as_ref() {
+                return Err(RedPacketError::UnauthorizedAdmin.into());
+            }
+            let bump = tr.bump();
 
             // Copy mint bytes for PDA verification
             let mut mint = [0u8; 32];
-            mint.copy_from_slice(state::get_treasury_mint(&tdata));
+            mint.copy_from_slice(tr.mint());
 
             // Verify treasury PDA (includes mint in seeds)
             let bump_bytes = [bump];
@@ -142,7 +145,7 @@ pub fn process_withdraw_fees(accounts: &[AccountView], data: &[u8]) -> ProgramRe
             }
 
             // Verify treasury_vault PDA (includes mint in seeds)
-            let tv_bump = state::get_treasury_vault_bump(&tdata);
+            let tv_bump = tr.vault_bump();
             let tv_bump_bytes = [tv_bump];
             let expected_tv = Address::create_program_address(
                 &[TREASURY_VAULT_SEED, &mint, &tv_bump_bytes],
@@ -156,12 +159,28 @@ pub fn process_withdraw_fees(accounts: &[AccountView], data: &[u8]) -> ProgramRe
             (bump, mint)
         };
 
+        // Destination must be a token account for this treasury's mint
+        {
+            if !token::owned_by_token_program(destination) {
+                return Err(RedPacketError::InvalidAccountOwner.into());
+            }
+            let ddata = destination.try_borrow()?;
+            if ddata.len() < 72 {
+                return Err(RedPacketError::InvalidTokenAccount.into());
+            }
+            if ddata[0..32] != mint_bytes {
+                return Err(RedPacketError::InvalidMint.into());
+            }
+            if strict && &ddata[32..64] != admin.address().as_ref() {
+                return Err(RedPacketError::InvalidDestination.into());
+            }
+        }
+
+        token::check_vault(treasury_vault, treasury, Some(&mint_bytes))?;
+
         // Read vault balance from token account data (offset 64 = amount field)
         let vault_balance = {
             let vdata = treasury_vault.try_borrow()?;
-            if vdata.len() < 72 {
-                return Err(RedPacketError::InvalidTokenAccount.into());
-            }
             u64::from_le_bytes(vdata[64..72].try_into().unwrap())
         };
 
@@ -175,7 +194,7 @@ pub fn process_withdraw_fees(accounts: &[AccountView], data: &[u8]) -> ProgramRe
             return Err(RedPacketError::InsufficientTreasuryBalance.into());
         }
 
-        // Transfer from treasury_vault to admin_token_account (treasury PDA signs with mint in seeds)
+        // Transfer from treasury_vault to destination (treasury PDA signs with mint in seeds)
         let bump_bytes = [treasury_bump];
         let seeds = [
             Seed::from(TREASURY_SEED),
@@ -186,9 +205,10 @@ pub fn process_withdraw_fees(accounts: &[AccountView], data: &[u8]) -> ProgramRe
 
         Transfer {
             from: treasury_vault,
-            to: admin_token_account,
+            to: destination,
             authority: treasury,
             amount: withdraw_amount,
+            token_program,
         }
         .invoke_signed(&signer)?;
 
@@ -0,0 +1,565 @@
+// Typed instruction data. Each `try_from` takes the bytes after the
+// discriminator, checks every length before reading and fails with
+// InvalidInstructionData on anything malformed: truncated fields, trailing
+// bytes, or unknown flag and option bits. Values (amounts, expiries, the
+// token type) are left to the processors, which own those errors. Variable
+// parts are borrowed from the instruction data, never copied.
+use pinocchio::error::ProgramError;
+use crate::constants::{
+    CLAIM_EXACT_SLOT, CLAIM_TO_DESTINATION, CREATE_BURN_UNCLAIMED, CREATE_DONATE_UNCLAIMED, FEE_RATE_BPS, FLAG_VESTING,
+    KNOWN_FLAGS, MAX_MEMO_LEN, MAX_SECRET_LEN, SPLIT_WEIGHTED,
+};
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn array_at(data: &[u8], offset: usize) -> [u8; 32] {
+    data[offset..offset + 32].try_into().unwrap()
+}
+
+/// create: see `process_create` for the layout. Optional fields that weren't
+/// sent are zero (empty for the slices).
+pub struct CreateArgs<'a> {
+    pub token_type: u8,
+    pub id: u64,
+    pub total_amount: u64,
+    pub num_recipients: u8,
+    pub split_mode: u8,
+    pub expires_at: i64,
+    pub rp_bump: u8,
+    pub vault_bump: u8,
+    /// As sent, only known bits
+    pub flags: u8,
+    /// CREATE_* bits, as sent
+    pub options: u8,
+    pub secret_hash: [u8; 32],
+    pub voucher_authority: [u8; 32],
+    pub gate_mint: [u8; 32],
+    pub gate_min_amount: u64,
+    /// 32 bytes per slot
+    pub recipients: &'a [u8],
+    /// Never all zeros when sent
+    pub reveal_commitment: [u8; 32],
+    pub reveal_deadline: i64,
+    /// memo_len, then MAX_MEMO_LEN padded bytes; create checks the text
+    pub memo: Option<&'a [u8]>,
+    pub vesting_start: i64,
+    /// One u16 per slot, weighted packets only
+    pub weights: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for CreateArgs<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, ProgramError> {
+        if data.len() < 29 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let num_recipients = data[17];
+        let split_mode = data[18];
+        let n = num_recipients as usize;
+
+        // Weights sit at the very end, so the optional fields before them
+        // parse the same as on other packets. Stray bytes can't hide in
+        // between: what is left must still be an exact optional-field layout
+        let (data, weights) = if split_mode == SPLIT_WEIGHTED {
+            if data.len() < 29 + 2 * n {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            data.split_at(data.len() - 2 * n)
+        } else {
+            (data, &[][..])
+        };
+
+        // Trailing flags; older clients omit them (or send allow_repeat as 0/1)
+        let flags = match data.get(29) {
+            None => 0,
+            Some(&flags) if flags & !KNOWN_FLAGS == 0 => flags,
+            Some(_) => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        // Then the create options, which everything after them needs
+        let options = match data.get(30) {
+            None => 0,
+            Some(&options) if options & !(CREATE_BURN_UNCLAIMED | CREATE_DONATE_UNCLAIMED) == 0 => options,
+            Some(_) => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        // Vesting start comes before the weights, so it too parses from the end
+        let (data, vesting_start) = if flags & FLAG_VESTING != 0 {
+            if data.len() < 39 {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            let (data, start) = data.split_at(data.len() - 8);
+            (data, u64_at(start, 0) as i64)
+        } else {
+            (data, 0)
+        };
+
+        let mut args = CreateArgs {
+            token_type: data[0],
+            id: u64_at(data, 1),
+            total_amount: u64_at(data, 9),
+            num_recipients,
+            split_mode,
+            expires_at: u64_at(data, 19) as i64,
+            rp_bump: data[27],
+            vault_bump: data[28],
+            flags,
+            options,
+            secret_hash: [0; 32],
+            voucher_authority: [0; 32],
+            gate_mint: [0; 32],
+            gate_min_amount: 0,
+            recipients: &[],
+            reveal_commitment: [0; 32],
+            reveal_deadline: 0,
+            memo: None,
+            vesting_start,
+            weights,
+        };
+        let mut tail = data.get(31..).unwrap_or(&[]);
+
+        // The memo block is the last 65 bytes, which leaves a length 1 mod 8
+        // that the fields before it never do
+        if tail.len() % 8 == 1 {
+            if tail.len() < 1 + MAX_MEMO_LEN {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            let (rules_tail, memo) = tail.split_at(tail.len() - 1 - MAX_MEMO_LEN);
+            args.memo = Some(memo);
+            tail = rules_tail;
+        }
+
+        // The commit-reveal block is the last 40 bytes, a length recipients
+        // (32 bytes each) never leave over
+        if tail.len() >= 144 && (tail.len() - 104) % 32 == 8 {
+            let (rules_tail, commit) = tail.split_at(tail.len() - 40);
+            args.reveal_commitment = array_at(commit, 0);
+            args.reveal_deadline = u64_at(commit, 32) as i64;
+            if args.reveal_commitment == [0u8; 32] {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            tail = rules_tail;
+        }
+
+        if !matches!(tail.len(), 0 | 32 | 64 | 104) && tail.len() != 104 + 32 * n {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        if tail.len() >= 32 {
+            args.secret_hash = array_at(tail, 0);
+        }
+        if tail.len() >= 64 {
+            args.voucher_authority = array_at(tail, 32);
+        }
+        if tail.len() >= 104 {
+            args.gate_mint = array_at(tail, 64);
+            args.gate_min_amount = u64_at(tail, 96);
+            args.recipients = &tail[104..];
+        }
+        Ok(args)
+    }
+}
+
+/// claim: see `process_claim` for the layout
+pub struct ClaimArgs<'a> {
+    pub token_type: u8,
+    /// Only with CLAIM_EXACT_SLOT
+    pub exact_slot: Option<u8>,
+    pub has_destination: bool,
+    /// Password preimage, empty when none was sent
+    pub secret: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for ClaimArgs<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, ProgramError> {
+        if data.is_empty() || data.len() > 3 + MAX_SECRET_LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let options = data.get(2).copied().unwrap_or(0);
+        if options & !(CLAIM_TO_DESTINATION | CLAIM_EXACT_SLOT) != 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(ClaimArgs {
+            token_type: data[0],
+            exact_slot: (options & CLAIM_EXACT_SLOT != 0).then(|| data[1]),
+            has_destination: options & CLAIM_TO_DESTINATION != 0,
+            secret: data.get(3..).unwrap_or(&[]),
+        })
+    }
+}
+
+/// close and cancel: [0] token_type
+pub struct CloseArgs {
+    pub token_type: u8,
+}
+
+impl TryFrom<&[u8]> for CloseArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, ProgramError> {
+        match data {
+            &[token_type] => Ok(CloseArgs { token_type }),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+/// init_treasury: see `process_init_treasury` for the layout
+pub struct InitTreasuryArgs {
+    pub token_type: u8,
+    pub treasury_bump: u8,
+    pub vault_bump: u8,
+    /// FEE_RATE_BPS when not sent
+    pub fee_bps: u16,
+    pub if_missing: bool,
+}
+
+impl TryFrom<&[u8]> for InitTreasuryArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, ProgramError> {
+        let (fee_bps, if_missing) = match *data {
+            [_, _, _] => (FEE_RATE_BPS, false),
+            [_, _, _, lo, hi] => (u16::from_le_bytes([lo, hi]), false),
+            [_, _, _, lo, hi, if_missing @ (0 | 1)] => (u16::from_le_bytes([lo, hi]), if_missing == 1),
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+        Ok(InitTreasuryArgs {
+            token_type: data[0],
+            treasury_bump: data[1],
+            vault_bump: data[2],
+            fee_bps,
+            if_missing,
+        })
+    }
+}
+
+/// withdraw_fees: see `process_withdraw_fees` for the layout
+pub struct WithdrawFeesArgs {
+    pub token_type: u8,
+    /// 0 = everything collected
+    pub amount: u64,
+    pub strict: bool,
+}
+
+impl TryFrom<&[u8]> for WithdrawFeesArgs {
+    type Error = ProgramError;
+
+    fn try_from(data: &[u8]) -> Result<Self, ProgramError> {
+        let strict = match data.len() {
+            9 => false,
+            10 if data[9] <= 1 => data[9] == 1,
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+        Ok(WithdrawFeesArgs {
+            token_type: data[0],
+            amount: u64_at(data, 1),
+            strict,
+        })
+    }
+}
+
+/// top_up: see `process_top_up` for the layout
+pub struct TopUpArgs<'a> {
+    pub token_type: u8,
+    pub amount: u64,
+    /// 0 when not sent
+    pub add_recipients: u8,
+    /// 8 bytes per unclaimed slot, empty when none were sent
+    pub extra_amounts: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for TopUpArgs<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, ProgramError> {
+        if data.len() < 9 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let extra_amounts = data.get(10..).unwrap_or(&[]);
+        if extra_amounts.len() % 8 != 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(TopUpArgs {
+            token_type: data[0],
+            amount: u64_at(data, 1),
+            add_recipients: data.get(9).copied().unwrap_or(0),
+            extra_amounts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::constants::{
+        FLAG_LARGE, FLAG_LUCKY_PAID, FLAG_RANDOM_ONCHAIN, MAX_RECIPIENTS, MAX_RECIPIENTS_LARGE, SPLIT_EVEN, SPLIT_RANDOM,
+        TOKEN_TYPE_SOL, TOKEN_TYPE_SPL,
+    };
+
+    // Reference vectors: the blinks builders (blinks/src/program.rs) must
+    // produce these bytes, discriminator first
+    const CREATE_SOL_EVEN: [u8; 30] = [
+        0, 1, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0x2f, 0x68, 0x59, 0, 0, 0, 0, 3, 0, 0, 0xf1, 0x53, 0x65, 0, 0, 0, 0, 254, 253,
+    ];
+    const CLAIM_SLOT_2: [u8; 3] = [1, 1, 2];
+    const CLAIM_TO_DESTINATION_WITH_SECRET: [u8; 7] = [1, 0, 0, 1, b'p', b'w', b'd'];
+    const CLOSE_SOL: [u8; 2] = [2, 1];
+    const CANCEL_SPL: [u8; 2] = [7, 0];
+    const WITHDRAW_ALL_SOL: [u8; 10] = [4, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+    const TOP_UP_SOL: [u8; 10] = [5, 1, 0xe8, 0x03, 0, 0, 0, 0, 0, 0];
+
+    fn header(num_recipients: u8, split_mode: u8) -> Vec<u8> {
+        let mut data = CREATE_SOL_EVEN[1..].to_vec();
+        data[17] = num_recipients;
+        data[18] = split_mode;
+        data
+    }
+
+    #[test]
+    fn create_reads_the_fixed_fields_and_rejects_truncation() {
+        let args = CreateArgs::try_from(&CREATE_SOL_EVEN[1..]).unwrap();
+        assert_eq!(args.token_type, TOKEN_TYPE_SOL);
+        assert_eq!(args.id, 7);
+        assert_eq!(args.total_amount, 1_500_000_000);
+        assert_eq!(args.num_recipients, 3);
+        assert_eq!(args.split_mode, SPLIT_EVEN);
+        assert_eq!(args.expires_at, 1_700_000_000);
+        assert_eq!((args.rp_bump, args.vault_bump, args.flags), (254, 253, 0));
+        assert!(args.recipients.is_empty() && args.weights.is_empty() && args.memo.is_none());
+        assert_eq!(args.reveal_commitment, [0; 32]);
+
+        for len in 0..29 {
+            assert!(CreateArgs::try_from(&CREATE_SOL_EVEN[1..1 + len]).is_err(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn create_accepts_only_exact_optional_layouts() {
+        // n = 3: after flags and options, secret, voucher, gate, recipients,
+        // each needing the ones before it; then commit-reveal (after the
+        // gate), then the memo
+        let rules = [0, 32, 64, 104, 104 + 96];
+        let mut valid: Vec<usize> = rules.to_vec();
+        valid.extend([104 + 40, 104 + 96 + 40]);
+        valid.extend(valid.clone().iter().map(|len| len + 65));
+
+        for len in 0..400 {
+            let mut data = header(3, SPLIT_RANDOM);
+            data.extend([0, 0]);
+            data.extend(core::iter::repeat_n(1, len));
+            assert_eq!(CreateArgs::try_from(&data[..]).is_ok(), valid.contains(&len), "{len}-byte tail");
+        }
+    }
+
+    #[test]
+    fn create_splits_the_tail_into_its_fields() {
+        let mut data = header(3, SPLIT_RANDOM);
+        data.extend([0, CREATE_DONATE_UNCLAIMED]);
+        data.extend([1; 32]);
+        data.extend([2; 32]);
+        data.extend([3; 32]);
+        data.extend(9u64.to_le_bytes());
+        data.extend([4; 96]);
+        data.extend([5; 32]);
+        data.extend(1_600_000_000i64.to_le_bytes());
+        data.push(2);
+        data.extend(b"gm");
+        data.extend([0; 62]);
+
+        let args = CreateArgs::try_from(&data[..]).unwrap();
+        assert_eq!(args.options, CREATE_DONATE_UNCLAIMED);
+        assert_eq!(args.secret_hash, [1; 32]);
+        assert_eq!(args.voucher_authority, [2; 32]);
+        assert_eq!((args.gate_mint, args.gate_min_amount), ([3; 32], 9));
+        assert_eq!(args.recipients, &[4; 96][..]);
+        assert_eq!((args.reveal_commitment, args.reveal_deadline), ([5; 32], 1_600_000_000));
+        assert_eq!(args.memo.map(|memo| &memo[..3]), Some(&b"\x02gm"[..]));
+    }
+
+    #[test]
+    fn create_reads_weights_and_vesting_start_from_the_end() {
+        let mut data = header(2, SPLIT_WEIGHTED);
+        data.extend([FLAG_VESTING, 0]);
+        data.extend([0; 65]);
+        data.extend((-5i64).to_le_bytes());
+        data.extend([0x10, 0x27, 0, 0]);
+
+        let args = CreateArgs::try_from(&data[..]).unwrap();
+        assert_eq!(args.flags, FLAG_VESTING);
+        assert_eq!(args.weights, &[0x10, 0x27, 0, 0][..]);
+        assert_eq!(args.vesting_start, -5);
+        assert!(args.memo.is_some());
+
+        // A stray byte shifts every field before it out of its layout
+        assert!(CreateArgs::try_from(&[&data[..], &[0]].concat()[..]).is_err());
+        // Vesting needs the flags and options bytes and its 8 bytes
+        let mut short = header(2, SPLIT_WEIGHTED);
+        short.extend([FLAG_VESTING, 0, 0, 0, 0]);
+        assert!(CreateArgs::try_from(&short[..]).is_err());
+        let mut no_options = header(2, SPLIT_EVEN);
+        no_options.push(FLAG_VESTING);
+        no_options.extend((-5i64).to_le_bytes());
+        assert!(CreateArgs::try_from(&no_options[..]).is_err());
+    }
+
+    #[test]
+    fn create_reads_the_options_byte_after_the_flags() {
+        let mut data = header(3, SPLIT_EVEN);
+        data.push(FLAG_LARGE);
+        let args = CreateArgs::try_from(&data[..]).unwrap();
+        assert_eq!((args.flags, args.options), (FLAG_LARGE, 0));
+
+        data.push(CREATE_BURN_UNCLAIMED);
+        let args = CreateArgs::try_from(&data[..]).unwrap();
+        assert_eq!((args.flags, args.options), (FLAG_LARGE, CREATE_BURN_UNCLAIMED));
+
+        // Stored flag bits are no create options, and vice versa
+        *data.last_mut().unwrap() = 1 << 2;
+        assert!(CreateArgs::try_from(&data[..]).is_err());
+        let mut data = header(3, SPLIT_EVEN);
+        data.extend([FLAG_LUCKY_PAID, 0]);
+        assert!(CreateArgs::try_from(&data[..]).is_err());
+    }
+
+    #[test]
+    fn create_rejects_unknown_flags_and_a_zero_commitment() {
+        let mut data = header(3, SPLIT_RANDOM);
+        // FLAG_RANDOM_ONCHAIN is derived at create, never sent
+        data.push(FLAG_RANDOM_ONCHAIN);
+        assert!(CreateArgs::try_from(&data[..]).is_err());
+
+        let mut data = header(3, SPLIT_RANDOM);
+        data.extend([0, 0]);
+        data.extend([0; 104]);
+        data.extend([0; 32]);
+        data.extend(1i64.to_le_bytes());
+        assert!(CreateArgs::try_from(&data[..]).is_err());
+    }
+
+    #[test]
+    fn create_parses_the_largest_random_payloads() {
+        // Every optional field, with a recipient per slot
+        let n = MAX_RECIPIENTS as usize;
+        let mut data = header(MAX_RECIPIENTS, SPLIT_RANDOM);
+        data[0] = TOKEN_TYPE_SPL;
+        data.extend([0, 0]);
+        data.extend([1; 104]);
+        data.extend(core::iter::repeat_n(7, 32 * n));
+        data.extend([5; 40]);
+        data.extend([0; 65]);
+        assert_eq!(data.len(), 29 + 2 + 104 + 32 * n + 40 + 65);
+
+        let args = CreateArgs::try_from(&data[..]).unwrap();
+        assert_eq!(args.recipients.len(), 32 * n);
+        assert_eq!(args.reveal_commitment, [5; 32]);
+        assert!(CreateArgs::try_from(&data[..data.len() - 1]).is_err());
+        assert!(CreateArgs::try_from(&[&data[..], &[0]].concat()[..]).is_err());
+
+        // Large packets: the most slots, every rule but recipients
+        let mut data = header(MAX_RECIPIENTS_LARGE, SPLIT_RANDOM);
+        data.extend([FLAG_LARGE, 0]);
+        data.extend([1; 104]);
+        data.extend([5; 40]);
+        data.extend([0; 65]);
+        let args = CreateArgs::try_from(&data[..]).unwrap();
+        assert_eq!((args.num_recipients, args.flags), (MAX_RECIPIENTS_LARGE, FLAG_LARGE));
+        assert!(args.recipients.is_empty());
+    }
+
+    #[test]
+    fn claim_options_and_secret() {
+        let args = ClaimArgs::try_from(&CLAIM_SLOT_2[1..]).unwrap();
+        assert_eq!((args.token_type, args.exact_slot, args.has_destination), (TOKEN_TYPE_SOL, None, false));
+        assert!(args.secret.is_empty());
+        assert!(ClaimArgs::try_from(&[1][..]).is_ok());
+
+        let args = ClaimArgs::try_from(&CLAIM_TO_DESTINATION_WITH_SECRET[1..]).unwrap();
+        assert_eq!((args.token_type, args.exact_slot, args.has_destination), (TOKEN_TYPE_SPL, None, true));
+        assert_eq!(args.secret, b"pwd");
+
+        let args = ClaimArgs::try_from(&[1, 4, CLAIM_EXACT_SLOT][..]).unwrap();
+        assert_eq!(args.exact_slot, Some(4));
+
+        assert!(ClaimArgs::try_from(&[][..]).is_err());
+        assert!(ClaimArgs::try_from(&[1, 0, 1 << 2][..]).is_err());
+
+        // Everything after the options is the secret, up to MAX_SECRET_LEN
+        let mut data = [7u8; 3 + MAX_SECRET_LEN + 1];
+        data[..3].copy_from_slice(&[1, 0, 0]);
+        assert_eq!(ClaimArgs::try_from(&data[..3 + MAX_SECRET_LEN]).unwrap().secret.len(), MAX_SECRET_LEN);
+        assert!(ClaimArgs::try_from(&data[..]).is_err());
+    }
+
+    #[test]
+    fn close_takes_exactly_the_token_type() {
+        assert_eq!(CloseArgs::try_from(&CLOSE_SOL[1..]).unwrap().token_type, TOKEN_TYPE_SOL);
+        assert_eq!(CloseArgs::try_from(&CANCEL_SPL[1..]).unwrap().token_type, TOKEN_TYPE_SPL);
+        assert!(CloseArgs::try_from(&[][..]).is_err());
+        assert!(CloseArgs::try_from(&[1, 0][..]).is_err());
+    }
+
+    #[test]
+    fn init_treasury_optional_fee_and_if_missing() {
+        let args = InitTreasuryArgs::try_from(&[1, 255, 0][..]).unwrap();
+        assert_eq!((args.token_type, args.treasury_bump, args.vault_bump), (TOKEN_TYPE_SOL, 255, 0));
+        assert_eq!((args.fee_bps, args.if_missing), (FEE_RATE_BPS, false));
+
+        let args = InitTreasuryArgs::try_from(&[0, 254, 253, 0x2c, 0x01][..]).unwrap();
+        assert_eq!((args.fee_bps, args.if_missing), (300, false));
+        let args = InitTreasuryArgs::try_from(&[0, 254, 253, 10, 0, 1][..]).unwrap();
+        assert_eq!((args.fee_bps, args.if_missing), (10, true));
+
+        let data = [0, 254, 253, 10, 0, 0, 0];
+        for len in [0, 1, 2, 4, 7] {
+            assert!(InitTreasuryArgs::try_from(&data[..len]).is_err(), "{len} bytes");
+        }
+        assert!(InitTreasuryArgs::try_from(&[0, 254, 253, 10, 0, 2][..]).is_err());
+    }
+
+    #[test]
+    fn withdraw_fees_amount_and_strict() {
+        let args = WithdrawFeesArgs::try_from(&WITHDRAW_ALL_SOL[1..]).unwrap();
+        assert_eq!((args.token_type, args.amount, args.strict), (TOKEN_TYPE_SOL, 0, false));
+
+        let mut data = [0u8; 11];
+        data[1..9].copy_from_slice(&u64::MAX.to_le_bytes());
+        data[9] = 1;
+        let args = WithdrawFeesArgs::try_from(&data[..10]).unwrap();
+        assert_eq!((args.token_type, args.amount, args.strict), (TOKEN_TYPE_SPL, u64::MAX, true));
+
+        for len in [0, 1, 8, 11] {
+            assert!(WithdrawFeesArgs::try_from(&data[..len]).is_err(), "{len} bytes");
+        }
+        data[9] = 2;
+        assert!(WithdrawFeesArgs::try_from(&data[..10]).is_err());
+    }
+    #[test]
+    fn top_up_optional_recipients_and_whole_extra_amounts() {
+        let args = TopUpArgs::try_from(&TOP_UP_SOL[1..]).unwrap();
+        assert_eq!((args.token_type, args.amount, args.add_recipients), (TOKEN_TYPE_SOL, 1_000, 0));
+        assert!(args.extra_amounts.is_empty());
+
+        let mut data = TOP_UP_SOL[1..].to_vec();
+        data.push(2);
+        data.extend(5u64.to_le_bytes());
+        data.extend(7u64.to_le_bytes());
+        let args = TopUpArgs::try_from(&data[..]).unwrap();
+        assert_eq!(args.add_recipients, 2);
+        assert_eq!(args.extra_amounts.len(), 16);
+
+        // Truncated amount, or a partial extra amount
+        for len in [0, 1, 8, 11, 17, 25] {
+            let data = [&data[..], &[0; 8]].concat();
+            assert!(TopUpArgs::try_from(&data[..len]).is_err(), "{len} bytes");
+        }
+        assert!(TopUpArgs::try_from(&data[..10]).is_ok());
+    }
+}
@@ -1,18 +1,34 @@
+// On-chain the program is `no_std` with `no_allocator!()`: any `Vec`, `String`
+// or `format!` fails `cargo build-sbf` (and therefore `yarn test`) instead of
+// aborting at runtime, and `yarn check:no-alloc` fails any build, every
+// feature set included, that links an allocation in some other way. Use
+// `state::FixedBuf` for scratch space.
+#![cfg_attr(target_os = "solana", no_std)]
+
+pub mod accounts;
 pub mod constants;
 pub mod error;
+#[cfg(any(feature = "logging", feature = "cpi-events"))]
+pub mod event;
 pub mod instructions;
+pub mod ix_data;
 pub mod state;
+pub mod token;
 
 use pinocchio::{AccountView, Address, ProgramResult};
 use pinocchio::error::ProgramError;
 
 use instructions::{
-    process_claim, process_close, process_create, process_init_treasury, process_withdraw_fees,
+    process_accept_admin, process_cancel, process_claim, process_claim_batch, process_close,
+    process_close_receipt, process_close_treasury, process_create, process_extend_expiry, process_finalize,
+    process_get_version, process_init_treasury, process_migrate, process_migrate_treasury, process_partial_withdraw,
+    process_pause, process_peek, process_propose_admin, process_reveal, process_set_expiry_bounds,
+    process_top_up, process_transfer_creator, process_unpause, process_update_fee, process_withdraw_fees,
 };
 
 pinocchio::program_entrypoint!(process_instruction);
 pinocchio::no_allocator!();
-pinocchio::default_panic_handler!();
+pinocchio::nostd_panic_handler!();
 
 pub fn process_instruction(
     _program_id: &Address,
@@ -29,14 +45,37 @@ pub fn process_instruction(
         2 => process_close(accounts, data),
         3 => process_init_treasury(accounts, data),
         4 => process_withdraw_fees(accounts, data),
+        5 => process_top_up(accounts, data),
+        6 => process_extend_expiry(accounts, data),
+        7 => process_cancel(accounts, data),
+        8 => process_update_fee(accounts, data),
+        9 => process_partial_withdraw(accounts, data),
+        10 => process_migrate(accounts, data),
+        11 => process_propose_admin(accounts, data),
+        12 => process_accept_admin(accounts, data),
+        13 => process_claim_batch(accounts, data),
+        14 => process_pause(accounts, data),
+        15 => process_unpause(accounts, data),
+        16 => process_close_treasury(accounts, data),
+        17 => process_migrate_treasury(accounts, data),
+        18 => process_close_receipt(accounts, data),
+        19 => process_reveal(accounts, data),
+        20 => process_finalize(accounts, data),
+        21 => process_set_expiry_bounds(accounts, data),
+        #[cfg(feature = "cpi-events")]
+        22 => instructions::process_emit_event(accounts, data),
+        23 => process_peek(accounts, data),
+        24 => process_transfer_creator(accounts, data),
+        25 => process_get_version(accounts, data),
         _ => Err(ProgramError::InvalidInstructionData),
     }
 }
 
-// Raw sol_log syscall — replaces solana-program-log dependency
+// Raw sol_log syscalls — replace the solana-program-log dependency
 #[cfg(all(feature = "logging", target_os = "solana"))]
 extern "C" {
     fn sol_log_(message: *const u8, len: u64);
+    fn sol_log_data(data: *const u8, data_len: u64);
 }
 
 #[inline(always)]
@@ -46,3 +85,49 @@ pub fn log(_msg: &str) {
         sol_log_(_msg.as_ptr(), _msg.len() as u64);
     }
 }
+
+/// Log `_data` as one base64 `Program data:` line (binary events)
+#[inline(always)]
+pub fn log_data(_data: &[u8]) {
+    #[cfg(all(feature = "logging", target_os = "solana"))]
+    {
+        let vals = [_data];
+        unsafe {
+            sol_log_data(vals.as_ptr() as *const u8, vals.len() as u64);
+        }
+    }
+}
+
+// Raw sol_sha256 syscall — takes a list of byte slices and hashes them in order
+#[cfg(target_os = "solana")]
+extern "C" {
+    fn sol_sha256(vals: *const u8, val_len: u64, hash_result: *mut u8) -> u64;
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    #[cfg(target_os = "solana")]
+    {
+        let mut hash = [0u8; 32];
+        let vals = [data];
+        unsafe {
+            sol_sha256(vals.as_ptr() as *const u8, vals.len() as u64, hash.as_mut_ptr());
+        }
+        hash
+    }
+    #[cfg(not(target_os = "solana"))]
+    {
+        use sha2::{Digest, Sha256};
+        Sha256::digest(data).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn host_sha256_is_standard_sha256() {
+        assert_eq!(
+            super::sha256(b"abc")[..8],
+            [0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea]
+        );
+    }
+}
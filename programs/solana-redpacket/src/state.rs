@@ -1,14 +1,27 @@
+use core::ops::{Deref, DerefMut};
+
 use pinocchio::{AccountView, Address};
+use pinocchio::account::{Ref, RefMut};
 use pinocchio::error::ProgramError;
 
-use crate::constants::{REDPACKET_BASE_SIZE, REDPACKET_DISCRIMINATOR, TOKEN_TYPE_SOL, TOKEN_TYPE_SPL, TREASURY_DISCRIMINATOR, TREASURY_SIZE};
+use crate::constants::{
+    ADMIN, CLOSED_DISCRIMINATOR, CREATE_BURN_UNCLAIMED, CREATE_DONATE_UNCLAIMED, FEE_RATE_BPS, FLAG_ALLOW_REPEAT, FLAG_LARGE, FLAG_NO_SELF_CLAIM,
+    FLAG_LUCKY_PAID, FLAG_RANDOM_ONCHAIN, FLAG_REVEALED, FLAG_VESTING, LUCKY_SHARE_BPS, MAX_EXPIRY_SECONDS,
+    MAX_MEMO_LEN, MIN_EXPIRY_SECONDS,
+    PER_RECIPIENT_SIZE_LARGE,
+    RANDOM_CEILING_BPS, RANDOM_FLOOR_BPS, RECEIPT_DISCRIMINATOR,
+    REDPACKET_BASE_SIZE, REDPACKET_BASE_SIZES, REDPACKET_DISCRIMINATOR, REDPACKET_LARGE_SINCE,
+    REDPACKET_CLAIMED_AT_SINCE, REDPACKET_VERSION, REDPACKET_VESTING_SINCE, SPLIT_EVEN, SPLIT_LUCKY, SPLIT_RAFFLE, SPLIT_WEIGHTED, TOKEN_TYPE_SOL, TOKEN_TYPE_SPL, TREASURY_DISCRIMINATOR, TREASURY_LEGACY_SIZE,
+    TREASURY_VERSION, WEIGHT_TOTAL_BPS, redpacket_layout_size,
+};
 use crate::error::RedPacketError;
+use crate::ix_data::CreateArgs;
 
 // ========================
 // RedPacket account layout
 // ========================
 // 0       discriminator      u8      1
-// 1       creator            [u8;32] 32
+// 1       creator            [u8;32] 32  (signs for the packet: close, top-up, ...)
 // 33      id                 u64     8
 // 41      total_amount       u64     8
 // 49      remaining_amount   u64     8
@@ -19,8 +32,84 @@ use crate::error::RedPacketError;
 // 61      vault_bump         u8      1
 // 62      token_type         u8      1   (0=SPL, 1=SOL)
 // 63      expires_at         i64     8
-// 71      amounts            [u64;N] 8*N
-// 71+8N   claimers           [[u8;32];N] 32*N
+// 71      fee_paid           u64     8   (fees charged at create + top-ups)
+// 79      version            u8      1   (= REDPACKET_VERSION)
+// 80      flags              u8      1   (FLAG_ALLOW_REPEAT | FLAG_NO_SELF_CLAIM | FLAG_LARGE
+//                                         | FLAG_RANDOM_ONCHAIN | FLAG_REVEALED | FLAG_LUCKY_PAID
+//                                         | FLAG_VESTING)
+// 81      secret_hash        [u8;32] 32  (sha256 of the claim password; zeros = none)
+// 113     voucher_authority  [u8;32] 32  (signs claim vouchers; zeros = none)
+// 145     gate_mint          [u8;32] 32  (claimers must hold this mint; zeros = none)
+// 177     gate_min_amount    u64     8   (least gate_mint balance a claimer needs)
+// 185     assigned           u8      1   (1 = claimers were fixed at create)
+// 186     created_at         i64     8   (unix time of the create; 0 = migrated, unknown)
+// 194     reveal_commitment  [u8;32] 32  (sha256 of the creator's reveal secret, the
+//                                         secret itself once FLAG_REVEALED; zeros = none)
+// 226     reveal_deadline    i64     8   (last moment to reveal; 0 without a commitment)
+// 234     winner             [u8;32] 32  (raffle entrant finalize drew; zeros = not yet)
+// 266     vesting_start      i64     8   (when vesting slots start unlocking; 0 otherwise)
+// 274     claimed_slots      [u8;32] 32  (bit i of byte i/8 set = slot i claimed; covers
+//                                        MAX_RECIPIENTS_LARGE; num_claimed counts the bits)
+// 306     memo_len           u8      1   (0 = no memo)
+// 307     memo               [u8;64] 64  (creator's UTF-8 greeting, zero-padded)
+// 371     burn_unclaimed     u8      1   (1 = refunds are burned instead; SPL only)
+// 372     donate_unclaimed   u8      1   (1 = refunds go to the treasury as fees instead)
+// 373     original_creator   [u8;32] 32  (the creator the packet and vault PDAs derive from)
+// 405     amounts            [u64;N] 8*N
+// 405+8N  claimers           [[u8;32];N] 32*N  (absent on large packets)
+// 405+40N claimed_at         [i64;N] 8*N (unix time each slot was first claimed; 0 =
+//                                        unclaimed or claimed before v11; absent on large)
+// 405+48N taken              [u64;N] 8*N (vesting packets only: collected per slot)
+//
+// A claim takes the lowest unclaimed slot, or the unclaimed slot it names,
+// so claimed slots needn't be contiguous: claimed_slots says which they are.
+// Assigned packets store every recipient in claimers up-front, and a claim
+// takes one of the claimer's own slots where it is.
+//
+// On-chain random packets (FLAG_RANDOM_ONCHAIN) start with zero amounts; each
+// claim draws its slot's amount and records it there. Lucky packets
+// (SPLIT_LUCKY) are on-chain random too, but hold back a LUCKY_SHARE_BPS
+// jackpot of total_amount from the draws. Once expired or full, finalize
+// draws the claimed slot that wins it, as for raffles, pays its claimer and
+// sets FLAG_LUCKY_PAID; close waits for that whenever anyone claimed.
+//
+// Raffle packets (SPLIT_RAFFLE) keep every amount at zero: claims only
+// record entrants in claimers. Once expired or full, finalize draws the
+// winner and pays it remaining_amount; close waits for that payout whenever
+// anyone entered.
+//
+// Vesting packets (FLAG_VESTING) unlock each slot's amount linearly from
+// vesting_start to expires_at. A claimer keeps its slot and claims again to
+// collect amount * elapsed / duration minus taken; from expires_at on that
+// is the exact remainder, and held slots stay claimable past expiry. What
+// claimers are still owed never counts toward the creator's refunds, and
+// close waits until it has all been collected.
+//
+// Commit-reveal packets (random only) block claims until the creator reveals
+// the preimage of reveal_commitment, then draw each amount from
+// sha256(secret || slot) instead of the clock slot. reveal_deadline is at most
+// expires_at, and extend_expiry leaves it alone; once it passes unrevealed,
+// close refunds the whole pot even before expiry.
+//
+// transfer_creator overwrites creator, so whoever holds it closes the packet
+// and collects its refunds, but the packet and vault addresses stay derived
+// from original_creator and id: the PDA signer seeds always come from
+// original_creator, and close authority no longer follows from the address.
+//
+// Large packets (FLAG_LARGE) end after amounts: each claim creates a receipt
+// PDA [RECEIPT_SEED, red_packet, claimer] instead, whose existence blocks a
+// second claim by that wallet. They date from v6 (REDPACKET_LARGE_SINCE).
+//
+// Older versions share the prefix and stop earlier (see REDPACKET_BASE_SIZES):
+// v0 ends after expires_at, v1 after version, v2 after flags, v3 after
+// secret_hash, v4 after voucher_authority, v5 after gate_min_amount, v6 after
+// assigned, v7 after created_at, v8 after reveal_deadline, v9 after winner,
+// v10 and v11 after vesting_start, v12 after claimed_slots, v13 after memo,
+// v14 after burn_unclaimed, v15 after donate_unclaimed.
+// v0 has no version byte, so it is told apart by account length first.
+// Vesting packets date from v10 (REDPACKET_VESTING_SINCE) and claimed_at
+// from v11 (REDPACKET_CLAIMED_AT_SINCE). Before v12 the claimed slots were
+// always 0..num_claimed.
 
 const DISCRIMINATOR_OFFSET: usize = 0;
 const CREATOR_OFFSET: usize = 1;
@@ -34,7 +123,26 @@ const BUMP_OFFSET: usize = 60;
 const VAULT_BUMP_OFFSET: usize = 61;
 const TOKEN_TYPE_OFFSET: usize = 62;
 const EXPIRES_AT_OFFSET: usize = 63;
-const AMOUNTS_OFFSET: usize = 71;
+const FEE_PAID_OFFSET: usize = 71;
+const VERSION_OFFSET: usize = 79;
+const FLAGS_OFFSET: usize = 80;
+const SECRET_HASH_OFFSET: usize = 81;
+const VOUCHER_AUTHORITY_OFFSET: usize = 113;
+const GATE_MINT_OFFSET: usize = 145;
+const GATE_MIN_AMOUNT_OFFSET: usize = 177;
+const ASSIGNED_OFFSET: usize = 185;
+const CREATED_AT_OFFSET: usize = 186;
+const REVEAL_COMMITMENT_OFFSET: usize = 194;
+const REVEAL_DEADLINE_OFFSET: usize = 226;
+const WINNER_OFFSET: usize = 234;
+const VESTING_START_OFFSET: usize = 266;
+const CLAIMED_SLOTS_OFFSET: usize = 274;
+const MEMO_LEN_OFFSET: usize = 306;
+const MEMO_OFFSET: usize = 307;
+const BURN_UNCLAIMED_OFFSET: usize = 371;
+const DONATE_UNCLAIMED_OFFSET: usize = 372;
+const ORIGINAL_CREATOR_OFFSET: usize = 373;
+const AMOUNTS_OFFSET: usize = 405;
 
 #[inline]
 fn read_u64(data: &[u8], offset: usize) -> u64 {
@@ -63,21 +171,564 @@ fn claimers_offset(num_recipients: u8) -> usize {
     AMOUNTS_OFFSET + 8 * num_recipients as usize
 }
 
-/// Validate that an account is a valid RedPacket
-pub fn validate_redpacket(account: &AccountView, program_id: &Address) -> Result<(), ProgramError> {
-    if !account.owned_by(program_id) {
-        return Err(RedPacketError::InvalidAccountOwner.into());
+#[inline]
+fn claimed_at_offset(num_recipients: u8) -> usize {
+    claimers_offset(num_recipients) + 32 * num_recipients as usize
+}
+
+#[inline]
+fn taken_offset(num_recipients: u8) -> usize {
+    claimed_at_offset(num_recipients) + 8 * num_recipients as usize
+}
+
+// ========================
+// RedPacket views
+// ========================
+// A RedPacketView checks the data once, when it is built (length,
+// discriminator, layout version), and then reads fields without checking
+// again. It wraps `&[u8]`, `&mut [u8]` or an account borrow alike; the
+// setters only exist on writable data (RedPacketViewMut, or a view over a
+// RefMut). load_redpacket / load_redpacket_mut also check the account and
+// hold its borrow for as long as the view lives.
+//
+// Getters that hand out borrowed bytes, and the free accessors that take
+// num_recipients, keep their bodies and the view calls them; every other
+// free getter and setter is a thin wrapper over the view. The free functions
+// skip the view's checks and stay for one release: new code should go
+// through the views.
+
+/// Zero-copy view of a current-layout red packet's data
+#[derive(Clone, Copy)]
+pub struct RedPacketView<D>(D);
+
+/// RedPacketView over writable data
+pub type RedPacketViewMut<'a> = RedPacketView<&'a mut [u8]>;
+
+fn check_redpacket_data(data: &[u8]) -> Result<(), ProgramError> {
+    // Drained earlier in this transaction (tombstoned)
+    if data.first() == Some(&CLOSED_DISCRIMINATOR) {
+        return Err(RedPacketError::AccountClosed.into());
     }
-    let data = account.try_borrow()?;
     if data.len() < REDPACKET_BASE_SIZE {
         return Err(ProgramError::InvalidAccountData);
     }
     if data[DISCRIMINATOR_OFFSET] != REDPACKET_DISCRIMINATOR {
         return Err(RedPacketError::InvalidDiscriminator.into());
     }
+    // Old layouts would misparse; they must go through migrate first
+    if get_layout_version(data) != Some(REDPACKET_VERSION) {
+        return Err(RedPacketError::UnsupportedVersion.into());
+    }
+    Ok(())
+}
+
+/// `TryFrom` for a view over each kind of data it can wrap, running `$check`
+macro_rules! view_try_from {
+    ($view:ident, $check:ident) => {
+        impl<'a> TryFrom<&'a [u8]> for $view<&'a [u8]> {
+            type Error = ProgramError;
+
+            #[inline]
+            fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+                $check(data)?;
+                Ok(Self(data))
+            }
+        }
+
+        impl<'a> TryFrom<&'a mut [u8]> for $view<&'a mut [u8]> {
+            type Error = ProgramError;
+
+            #[inline]
+            fn try_from(data: &'a mut [u8]) -> Result<Self, Self::Error> {
+                $check(data)?;
+                Ok(Self(data))
+            }
+        }
+
+        impl<'a> TryFrom<Ref<'a, [u8]>> for $view<Ref<'a, [u8]>> {
+            type Error = ProgramError;
+
+            #[inline]
+            fn try_from(data: Ref<'a, [u8]>) -> Result<Self, Self::Error> {
+                $check(&data)?;
+                Ok(Self(data))
+            }
+        }
+
+        impl<'a> TryFrom<RefMut<'a, [u8]>> for $view<RefMut<'a, [u8]>> {
+            type Error = ProgramError;
+
+            #[inline]
+            fn try_from(data: RefMut<'a, [u8]>) -> Result<Self, Self::Error> {
+                $check(&data)?;
+                Ok(Self(data))
+            }
+        }
+    };
+}
+
+view_try_from!(RedPacketView, check_redpacket_data);
+
+fn check_redpacket_account(account: &AccountView, program_id: &Address) -> Result<(), ProgramError> {
+    // Closed packets: already garbage-collected (no lamports)
+    if account.lamports() == 0 {
+        return Err(RedPacketError::AccountClosed.into());
+    }
+    if !account.owned_by(program_id) {
+        return Err(RedPacketError::InvalidAccountOwner.into());
+    }
     Ok(())
 }
 
+/// Check that an account is a valid RedPacket and view its data
+#[inline]
+pub fn load_redpacket<'a>(
+    account: &'a AccountView,
+    program_id: &Address,
+) -> Result<RedPacketView<Ref<'a, [u8]>>, ProgramError> {
+    check_redpacket_account(account, program_id)?;
+    RedPacketView::try_from(account.try_borrow()?)
+}
+
+/// Like `load_redpacket`, borrowing the data mutably
+#[inline]
+pub fn load_redpacket_mut<'a>(
+    account: &'a AccountView,
+    program_id: &Address,
+) -> Result<RedPacketView<RefMut<'a, [u8]>>, ProgramError> {
+    check_redpacket_account(account, program_id)?;
+    RedPacketView::try_from(account.try_borrow_mut()?)
+}
+
+/// Validate that an account is a valid RedPacket
+pub fn validate_redpacket(account: &AccountView, program_id: &Address) -> Result<(), ProgramError> {
+    load_redpacket(account, program_id).map(|_| ())
+}
+
+impl<D: Deref<Target = [u8]>> RedPacketView<D> {
+    /// The whole account data
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Reborrow as a view over plain bytes, for helpers that only read
+    #[inline]
+    pub fn as_view(&self) -> RedPacketView<&[u8]> {
+        RedPacketView(&self.0)
+    }
+
+    #[inline]
+    pub fn creator(&self) -> &[u8] {
+        get_creator(&self.0)
+    }
+
+    /// The creator the packet and vault PDAs were derived from: the seeds to
+    /// sign with, whoever the creator is now
+    #[inline]
+    pub fn original_creator(&self) -> &[u8] {
+        get_original_creator(&self.0)
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        read_u64(&self.0, ID_OFFSET)
+    }
+
+    #[inline]
+    pub fn total_amount(&self) -> u64 {
+        read_u64(&self.0, TOTAL_AMOUNT_OFFSET)
+    }
+
+    #[inline]
+    pub fn remaining_amount(&self) -> u64 {
+        read_u64(&self.0, REMAINING_AMOUNT_OFFSET)
+    }
+
+    #[inline]
+    pub fn num_recipients(&self) -> u8 {
+        self.0[NUM_RECIPIENTS_OFFSET]
+    }
+
+    #[inline]
+    pub fn num_claimed(&self) -> u8 {
+        self.0[NUM_CLAIMED_OFFSET]
+    }
+
+    #[inline]
+    pub fn split_mode(&self) -> u8 {
+        self.0[SPLIT_MODE_OFFSET]
+    }
+
+    #[inline]
+    pub fn bump(&self) -> u8 {
+        self.0[BUMP_OFFSET]
+    }
+
+    #[inline]
+    pub fn vault_bump(&self) -> u8 {
+        self.0[VAULT_BUMP_OFFSET]
+    }
+
+    #[inline]
+    pub fn token_type(&self) -> u8 {
+        self.0[TOKEN_TYPE_OFFSET]
+    }
+
+    #[inline]
+    pub fn expires_at(&self) -> i64 {
+        read_i64(&self.0, EXPIRES_AT_OFFSET)
+    }
+
+    #[inline]
+    pub fn fee_paid(&self) -> u64 {
+        read_u64(&self.0, FEE_PAID_OFFSET)
+    }
+
+    #[inline]
+    pub fn allow_repeat(&self) -> bool {
+        self.0[FLAGS_OFFSET] & FLAG_ALLOW_REPEAT != 0
+    }
+
+    #[inline]
+    pub fn no_self_claim(&self) -> bool {
+        self.0[FLAGS_OFFSET] & FLAG_NO_SELF_CLAIM != 0
+    }
+
+    /// Claims are deduped by receipt PDAs; there is no claimers array
+    #[inline]
+    pub fn large(&self) -> bool {
+        self.0[FLAGS_OFFSET] & FLAG_LARGE != 0
+    }
+
+    /// Each claim draws its amount on-chain; unclaimed amounts are still zero
+    #[inline]
+    pub fn random_onchain(&self) -> bool {
+        self.0[FLAGS_OFFSET] & FLAG_RANDOM_ONCHAIN != 0
+    }
+
+    /// Jackpot a lucky packet's draws still hold back: LUCKY_SHARE_BPS of
+    /// total_amount until finalize pays it, and 0 on every other packet
+    #[inline]
+    pub fn lucky_jackpot(&self) -> u64 {
+        if self.split_mode() != SPLIT_LUCKY || self.0[FLAGS_OFFSET] & FLAG_LUCKY_PAID != 0 {
+            return 0;
+        }
+        weighted_amount(self.total_amount(), LUCKY_SHARE_BPS)
+    }
+
+    /// Least and most an on-chain random packet's draw pays a slot:
+    /// RANDOM_FLOOR_BPS and RANDOM_CEILING_BPS of the even share of what the
+    /// draws split (total_amount less a lucky packet's jackpot)
+    #[inline]
+    pub fn random_bounds(&self) -> (u64, u64) {
+        let total = self.total_amount();
+        let pool = if self.split_mode() == SPLIT_LUCKY {
+            total - weighted_amount(total, LUCKY_SHARE_BPS)
+        } else {
+            total
+        };
+        let share = pool / self.num_recipients() as u64;
+        (weighted_amount(share, RANDOM_FLOOR_BPS), weighted_amount(share, RANDOM_CEILING_BPS))
+    }
+
+    /// Hash a claimer's password must match, or None for open packets
+    #[inline]
+    pub fn secret_hash(&self) -> Option<&[u8]> {
+        get_secret_hash(&self.0)
+    }
+
+    /// Key whose ed25519 vouchers gate claims, or None when anyone may claim
+    #[inline]
+    pub fn voucher_authority(&self) -> Option<&[u8]> {
+        get_voucher_authority(&self.0)
+    }
+
+    /// Mint a claimer must hold and the least balance required, or None when
+    /// claims are ungated
+    #[inline]
+    pub fn gate(&self) -> Option<(&[u8], u64)> {
+        get_gate(&self.0)
+    }
+
+    #[inline]
+    pub fn assigned(&self) -> bool {
+        self.0[ASSIGNED_OFFSET] != 0
+    }
+
+    /// Unix time the packet was created, or 0 if it was migrated from a layout
+    /// that didn't record it
+    #[inline]
+    pub fn created_at(&self) -> i64 {
+        read_i64(&self.0, CREATED_AT_OFFSET)
+    }
+
+    /// Commitment claims wait on, or None if claims never needed a reveal (or it
+    /// already happened)
+    #[inline]
+    pub fn pending_commitment(&self) -> Option<&[u8]> {
+        get_pending_commitment(&self.0)
+    }
+
+    /// Raffle entrant finalize drew, or None before the draw
+    #[inline]
+    pub fn winner(&self) -> Option<&[u8]> {
+        get_winner(&self.0)
+    }
+
+    /// Slots unlock over time and claimers come back for the rest
+    #[inline]
+    pub fn vesting(&self) -> bool {
+        self.0[FLAGS_OFFSET] & FLAG_VESTING != 0
+    }
+
+    #[inline]
+    pub fn vesting_start(&self) -> i64 {
+        read_i64(&self.0, VESTING_START_OFFSET)
+    }
+
+    /// The creator's greeting (UTF-8, checked at create); empty when none
+    #[inline]
+    pub fn memo(&self) -> &[u8] {
+        get_memo(&self.0)
+    }
+
+    /// Whether close and partial_withdraw burn the tokens they would refund
+    #[inline]
+    pub fn burn_unclaimed(&self) -> bool {
+        self.0[BURN_UNCLAIMED_OFFSET] != 0
+    }
+
+    /// Whether close and partial_withdraw pay the tokens or lamports they would
+    /// refund into the treasury instead
+    #[inline]
+    pub fn donate_unclaimed(&self) -> bool {
+        self.0[DONATE_UNCLAIMED_OFFSET] != 0
+    }
+
+    /// What a vesting packet's claimers have unlocked or will unlock but not yet
+    /// collected: the part of remaining_amount that isn't the creator's. 0 on
+    /// other packets.
+    #[inline]
+    pub fn vesting_owed(&self) -> u64 {
+        if !self.vesting() {
+            return 0;
+        }
+        self.claimed_slots()
+            .map(|i| self.amount_at(i).saturating_sub(self.taken_at(i)))
+            .sum()
+    }
+
+    /// Raffle packets with entrants whose pot finalize hasn't paid out yet,
+    /// or lucky packets whose jackpot it hasn't
+    #[inline]
+    pub fn draw_unpaid(&self) -> bool {
+        self.num_claimed() > 0
+            && match self.split_mode() {
+                SPLIT_RAFFLE => self.remaining_amount() > 0,
+                SPLIT_LUCKY => self.lucky_jackpot() > 0,
+                _ => false,
+            }
+    }
+
+    /// The creator's secret, once revealed
+    #[inline]
+    pub fn revealed_secret(&self) -> Option<&[u8]> {
+        get_revealed_secret(&self.0)
+    }
+
+    #[inline]
+    pub fn reveal_deadline(&self) -> i64 {
+        read_i64(&self.0, REVEAL_DEADLINE_OFFSET)
+    }
+
+    #[inline]
+    pub fn amount_at(&self, index: u8) -> u64 {
+        read_u64(&self.0, AMOUNTS_OFFSET + 8 * index as usize)
+    }
+
+    /// Unix time slot `index` was first claimed: 0 while unclaimed, on slots
+    /// claimed before v11, and always on large packets (which don't record it)
+    #[inline]
+    pub fn claimed_at(&self, index: u8) -> i64 {
+        get_claimed_at(&self.0, self.num_recipients(), index)
+    }
+
+    /// What a vesting slot's claimer has collected so far
+    #[inline]
+    pub fn taken_at(&self, index: u8) -> u64 {
+        get_taken_at(&self.0, self.num_recipients(), index)
+    }
+
+    /// Whether slot `index` has been claimed
+    #[inline]
+    pub fn is_claimed(&self, index: u8) -> bool {
+        self.0[CLAIMED_SLOTS_OFFSET + index as usize / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// The claimed slots, lowest first
+    #[inline]
+    pub fn claimed_slots(&self) -> impl Iterator<Item = u8> + '_ {
+        claimed_slots(&self.0)
+    }
+
+    /// The unclaimed slots, lowest first; a claim that doesn't name its slot
+    /// takes the first one
+    #[inline]
+    pub fn unclaimed_slots(&self) -> impl Iterator<Item = u8> + '_ {
+        unclaimed_slots(&self.0)
+    }
+
+    /// Every slot's claimer, back to back in slot order (zeros for unclaimed
+    /// slots other than assigned ones)
+    #[inline]
+    pub fn claimers(&self) -> &[u8] {
+        get_claimers(&self.0, self.num_recipients())
+    }
+
+    #[inline]
+    pub fn claimer_at(&self, index: u8) -> &[u8] {
+        get_claimer_at(&self.0, self.num_recipients(), index)
+    }
+
+    /// The lowest unclaimed slot assigned to `claimer`, or None when it has none
+    /// left
+    #[inline]
+    pub fn find_assigned_slot(&self, claimer: &[u8]) -> Option<u8> {
+        find_assigned_slot(&self.0, self.num_recipients(), claimer)
+    }
+
+    #[inline]
+    pub fn has_claimed(&self, claimer: &[u8]) -> bool {
+        self.find_claimed_slot(claimer).is_some()
+    }
+
+    /// The lowest claimed slot recorded for `claimer`
+    #[inline]
+    pub fn find_claimed_slot(&self, claimer: &[u8]) -> Option<u8> {
+        find_claimed_slot(&self.0, self.num_recipients(), claimer)
+    }
+}
+
+impl<D: DerefMut<Target = [u8]>> RedPacketView<D> {
+    /// The whole account data, writable
+    #[inline]
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// Mark the packet closed, so a later instruction in the same
+    /// transaction fails with AccountClosed
+    #[inline]
+    pub fn tombstone(&mut self) {
+        self.0[DISCRIMINATOR_OFFSET] = CLOSED_DISCRIMINATOR;
+    }
+
+    #[inline]
+    pub fn set_total_amount(&mut self, amount: u64) {
+        write_u64(&mut self.0, TOTAL_AMOUNT_OFFSET, amount);
+    }
+
+    #[inline]
+    pub fn set_remaining_amount(&mut self, amount: u64) {
+        write_u64(&mut self.0, REMAINING_AMOUNT_OFFSET, amount);
+    }
+
+    #[inline]
+    pub fn set_fee_paid(&mut self, fee: u64) {
+        write_u64(&mut self.0, FEE_PAID_OFFSET, fee);
+    }
+
+    #[inline]
+    pub fn set_creator(&mut self, creator: &[u8]) {
+        self.0[CREATOR_OFFSET..CREATOR_OFFSET + 32].copy_from_slice(creator);
+    }
+
+    #[inline]
+    pub fn set_expires_at(&mut self, expires_at: i64) {
+        write_i64(&mut self.0, EXPIRES_AT_OFFSET, expires_at);
+    }
+
+    /// Mark unclaimed slot `index` claimed, counting it in num_claimed
+    #[inline]
+    pub fn set_claimed(&mut self, index: u8) {
+        self.0[CLAIMED_SLOTS_OFFSET + index as usize / 8] |= 1 << (index % 8);
+        self.0[NUM_CLAIMED_OFFSET] += 1;
+    }
+
+    /// Replace the commitment with the secret that opened it and open claims
+    #[inline]
+    pub fn set_revealed(&mut self, secret: &[u8]) {
+        self.0[REVEAL_COMMITMENT_OFFSET..REVEAL_COMMITMENT_OFFSET + 32].copy_from_slice(secret);
+        self.0[FLAGS_OFFSET] |= FLAG_REVEALED;
+    }
+
+    #[inline]
+    pub fn set_winner(&mut self, winner: &[u8]) {
+        self.0[WINNER_OFFSET..WINNER_OFFSET + 32].copy_from_slice(winner);
+    }
+
+    #[inline]
+    pub fn set_lucky_paid(&mut self) {
+        self.0[FLAGS_OFFSET] |= FLAG_LUCKY_PAID;
+    }
+
+    #[inline]
+    pub fn set_amount_at(&mut self, index: u8, amount: u64) {
+        write_u64(&mut self.0, AMOUNTS_OFFSET + 8 * index as usize, amount);
+    }
+
+    #[inline]
+    pub fn set_claimed_at(&mut self, index: u8, claimed_at: i64) {
+        let offset = claimed_at_offset(self.num_recipients()) + 8 * index as usize;
+        write_i64(&mut self.0, offset, claimed_at);
+    }
+
+    #[inline]
+    pub fn set_taken_at(&mut self, index: u8, amount: u64) {
+        let offset = taken_offset(self.num_recipients()) + 8 * index as usize;
+        write_u64(&mut self.0, offset, amount);
+    }
+
+    #[inline]
+    pub fn set_claimer_at(&mut self, index: u8, claimer: &[u8]) {
+        let offset = claimers_offset(self.num_recipients()) + 32 * index as usize;
+        self.0[offset..offset + 32].copy_from_slice(claimer);
+    }
+}
+
+/// Layout version of red packet data, or None if it matches no known layout
+pub fn get_layout_version(data: &[u8]) -> Option<u8> {
+    if data.len() < REDPACKET_BASE_SIZES[0] {
+        return None;
+    }
+    let num_recipients = get_num_recipients(data);
+    // v0 is the only layout without a version byte. Its 71 + 40N is 7 mod 8,
+    // while every later length is its header size (80, 81, 113, 145, 185,
+    // 186, 194, 234, 266, 274, 306, 371, 372, 373 or 405) plus a multiple of
+    // 8, so never 7 mod 8:
+    // past this check data[79] and data[80] really are version and flags
+    if data.len() == redpacket_layout_size(0, num_recipients, false, false) {
+        return Some(0);
+    }
+    if data.len() <= FLAGS_OFFSET {
+        return None;
+    }
+    let version = data[VERSION_OFFSET];
+    if version == 0 || version > REDPACKET_VERSION {
+        return None;
+    }
+    let flags = data[FLAGS_OFFSET];
+    let large = version >= REDPACKET_LARGE_SINCE && flags & FLAG_LARGE != 0;
+    let vesting = version >= REDPACKET_VESTING_SINCE && flags & FLAG_VESTING != 0;
+    (data.len() == redpacket_layout_size(version, num_recipients, large, vesting)).then_some(version)
+}
+
+#[inline]
+pub fn tombstone_redpacket(data: &mut [u8]) {
+    RedPacketView(data).tombstone();
+}
+
 // === RedPacket Readers ===
 
 #[inline]
@@ -85,163 +736,851 @@ pub fn get_creator(data: &[u8]) -> &[u8] {
     &data[CREATOR_OFFSET..CREATOR_OFFSET + 32]
 }
 
+/// The creator the packet and vault PDAs were derived from: the seeds to
+/// sign with, whoever the creator is now
+#[inline]
+pub fn get_original_creator(data: &[u8]) -> &[u8] {
+    &data[ORIGINAL_CREATOR_OFFSET..ORIGINAL_CREATOR_OFFSET + 32]
+}
+
 #[inline]
 pub fn get_id(data: &[u8]) -> u64 {
-    read_u64(data, ID_OFFSET)
+    RedPacketView(data).id()
+}
+
+#[inline]
+pub fn get_total_amount(data: &[u8]) -> u64 {
+    RedPacketView(data).total_amount()
 }
 
 #[inline]
 pub fn get_remaining_amount(data: &[u8]) -> u64 {
-    read_u64(data, REMAINING_AMOUNT_OFFSET)
+    RedPacketView(data).remaining_amount()
 }
 
 #[inline]
 pub fn get_num_recipients(data: &[u8]) -> u8 {
-    data[NUM_RECIPIENTS_OFFSET]
+    RedPacketView(data).num_recipients()
 }
 
 #[inline]
 pub fn get_num_claimed(data: &[u8]) -> u8 {
-    data[NUM_CLAIMED_OFFSET]
+    RedPacketView(data).num_claimed()
+}
+
+#[inline]
+pub fn get_split_mode(data: &[u8]) -> u8 {
+    RedPacketView(data).split_mode()
 }
 
 #[inline]
 pub fn get_bump(data: &[u8]) -> u8 {
-    data[BUMP_OFFSET]
+    RedPacketView(data).bump()
 }
 
 #[inline]
 pub fn get_vault_bump(data: &[u8]) -> u8 {
-    data[VAULT_BUMP_OFFSET]
+    RedPacketView(data).vault_bump()
 }
 
 #[inline]
 pub fn get_token_type(data: &[u8]) -> u8 {
-    data[TOKEN_TYPE_OFFSET]
+    RedPacketView(data).token_type()
 }
 
 #[inline]
 pub fn get_expires_at(data: &[u8]) -> i64 {
-    read_i64(data, EXPIRES_AT_OFFSET)
+    RedPacketView(data).expires_at()
 }
 
 #[inline]
-pub fn get_amount_at(data: &[u8], index: u8) -> u64 {
-    let offset = AMOUNTS_OFFSET + 8 * index as usize;
-    read_u64(data, offset)
+pub fn get_fee_paid(data: &[u8]) -> u64 {
+    RedPacketView(data).fee_paid()
 }
 
 #[inline]
-pub fn get_claimer_at(data: &[u8], num_recipients: u8, index: u8) -> &[u8] {
-    let base = claimers_offset(num_recipients);
-    let offset = base + 32 * index as usize;
-    &data[offset..offset + 32]
+pub fn get_allow_repeat(data: &[u8]) -> bool {
+    RedPacketView(data).allow_repeat()
 }
 
-// === RedPacket Writers ===
+#[inline]
+pub fn get_no_self_claim(data: &[u8]) -> bool {
+    RedPacketView(data).no_self_claim()
+}
 
-pub fn init_redpacket(
-    data: &mut [u8],
-    creator: &[u8],
-    id: u64,
-    total_amount: u64,
-    num_recipients: u8,
-    split_mode: u8,
-    bump: u8,
-    vault_bump: u8,
-    token_type: u8,
-    expires_at: i64,
-    amounts: &[u64],
-) {
-    data[DISCRIMINATOR_OFFSET] = REDPACKET_DISCRIMINATOR;
-    data[CREATOR_OFFSET..CREATOR_OFFSET + 32].copy_from_slice(creator);
-    write_u64(data, ID_OFFSET, id);
-    write_u64(data, TOTAL_AMOUNT_OFFSET, total_amount);
-    write_u64(data, REMAINING_AMOUNT_OFFSET, total_amount);
-    data[NUM_RECIPIENTS_OFFSET] = num_recipients;
-    data[NUM_CLAIMED_OFFSET] = 0;
-    data[SPLIT_MODE_OFFSET] = split_mode;
-    data[BUMP_OFFSET] = bump;
-    data[VAULT_BUMP_OFFSET] = vault_bump;
-    data[TOKEN_TYPE_OFFSET] = token_type;
-    write_i64(data, EXPIRES_AT_OFFSET, expires_at);
+/// Claims are deduped by receipt PDAs; there is no claimers array
+#[inline]
+pub fn get_large(data: &[u8]) -> bool {
+    RedPacketView(data).large()
+}
 
-    for (i, &amount) in amounts.iter().enumerate() {
-        let offset = AMOUNTS_OFFSET + 8 * i;
-        write_u64(data, offset, amount);
-    }
+/// Each claim draws its amount on-chain; unclaimed amounts are still zero
+#[inline]
+pub fn get_random_onchain(data: &[u8]) -> bool {
+    RedPacketView(data).random_onchain()
 }
 
+/// Jackpot a lucky packet's draws still hold back: LUCKY_SHARE_BPS of
+/// total_amount until finalize pays it, and 0 on every other packet
 #[inline]
-pub fn set_remaining_amount(data: &mut [u8], amount: u64) {
-    write_u64(data, REMAINING_AMOUNT_OFFSET, amount);
+pub fn get_lucky_jackpot(data: &[u8]) -> u64 {
+    RedPacketView(data).lucky_jackpot()
+}
+
+/// Least and most an on-chain random packet's draw pays a slot:
+/// RANDOM_FLOOR_BPS and RANDOM_CEILING_BPS of the even share of what the
+/// draws split (total_amount less a lucky packet's jackpot)
+pub fn get_random_bounds(data: &[u8]) -> (u64, u64) {
+    RedPacketView(data).random_bounds()
 }
 
+/// Hash a claimer's password must match, or None for open packets
 #[inline]
-pub fn set_num_claimed(data: &mut [u8], count: u8) {
-    data[NUM_CLAIMED_OFFSET] = count;
+pub fn get_secret_hash(data: &[u8]) -> Option<&[u8]> {
+    let hash = &data[SECRET_HASH_OFFSET..SECRET_HASH_OFFSET + 32];
+    if hash.iter().all(|&b| b == 0) {
+        return None;
+    }
+    Some(hash)
 }
 
+/// Key whose ed25519 vouchers gate claims, or None when anyone may claim
 #[inline]
-pub fn set_claimer_at(data: &mut [u8], num_recipients: u8, index: u8, claimer: &[u8]) {
-    let base = claimers_offset(num_recipients);
-    let offset = base + 32 * index as usize;
-    data[offset..offset + 32].copy_from_slice(claimer);
+pub fn get_voucher_authority(data: &[u8]) -> Option<&[u8]> {
+    let authority = &data[VOUCHER_AUTHORITY_OFFSET..VOUCHER_AUTHORITY_OFFSET + 32];
+    if authority.iter().all(|&b| b == 0) {
+        return None;
+    }
+    Some(authority)
 }
 
-pub fn has_claimed(data: &[u8], num_recipients: u8, num_claimed: u8, claimer: &[u8]) -> bool {
-    for i in 0..num_claimed {
-        if get_claimer_at(data, num_recipients, i) == claimer {
-            return true;
-        }
+/// Mint a claimer must hold and the least balance required, or None when
+/// claims are ungated
+#[inline]
+pub fn get_gate(data: &[u8]) -> Option<(&[u8], u64)> {
+    let mint = &data[GATE_MINT_OFFSET..GATE_MINT_OFFSET + 32];
+    if mint.iter().all(|&b| b == 0) {
+        return None;
     }
-    false
+    Some((mint, read_u64(data, GATE_MIN_AMOUNT_OFFSET)))
 }
 
-// ========================
-// Treasury account layout
-// ========================
-// 0    discriminator       u8      1   (= 2)
-// 1    bump                u8      1
-// 2    vault_bump          u8      1
-// 3    mint                [u8;32] 32
-// 35   sol_fees_collected  u64     8
+#[inline]
+pub fn get_assigned(data: &[u8]) -> bool {
+    RedPacketView(data).assigned()
+}
 
-const TREASURY_DISCRIMINATOR_OFFSET: usize = 0;
-const TREASURY_BUMP_OFFSET: usize = 1;
-const TREASURY_VAULT_BUMP_OFFSET: usize = 2;
-const TREASURY_MINT_OFFSET: usize = 3;
-const SOL_FEES_OFFSET: usize = 35;
+/// Unix time the packet was created, or 0 if it was migrated from a layout
+/// that didn't record it
+#[inline]
+pub fn get_created_at(data: &[u8]) -> i64 {
+    RedPacketView(data).created_at()
+}
 
-pub fn validate_treasury(account: &AccountView, program_id: &Address) -> Result<(), ProgramError> {
-    if !account.owned_by(program_id) {
-        return Err(RedPacketError::InvalidAccountOwner.into());
-    }
-    let data = account.try_borrow()?;
-    if data.len() < TREASURY_SIZE {
-        return Err(ProgramError::InvalidAccountData);
+/// Commitment claims wait on, or None if claims never needed a reveal (or it
+/// already happened)
+#[inline]
+pub fn get_pending_commitment(data: &[u8]) -> Option<&[u8]> {
+    let commitment = &data[REVEAL_COMMITMENT_OFFSET..REVEAL_COMMITMENT_OFFSET + 32];
+    if data[FLAGS_OFFSET] & FLAG_REVEALED != 0 || commitment.iter().all(|&b| b == 0) {
+        return None;
+    }
+    Some(commitment)
+}
+
+/// Raffle entrant finalize drew, or None before the draw
+#[inline]
+pub fn get_winner(data: &[u8]) -> Option<&[u8]> {
+    let winner = &data[WINNER_OFFSET..WINNER_OFFSET + 32];
+    if winner.iter().all(|&b| b == 0) {
+        return None;
+    }
+    Some(winner)
+}
+
+/// Slots unlock over time and claimers come back for the rest
+#[inline]
+pub fn get_vesting(data: &[u8]) -> bool {
+    RedPacketView(data).vesting()
+}
+
+#[inline]
+pub fn get_vesting_start(data: &[u8]) -> i64 {
+    RedPacketView(data).vesting_start()
+}
+
+/// The creator's greeting (UTF-8, checked at create); empty when none
+#[inline]
+pub fn get_memo(data: &[u8]) -> &[u8] {
+    let len = (data[MEMO_LEN_OFFSET] as usize).min(MAX_MEMO_LEN);
+    &data[MEMO_OFFSET..MEMO_OFFSET + len]
+}
+
+/// Whether close and partial_withdraw burn the tokens they would refund
+#[inline]
+pub fn get_burn_unclaimed(data: &[u8]) -> bool {
+    RedPacketView(data).burn_unclaimed()
+}
+
+/// Whether close and partial_withdraw pay the tokens or lamports they would
+/// refund into the treasury instead
+#[inline]
+pub fn get_donate_unclaimed(data: &[u8]) -> bool {
+    RedPacketView(data).donate_unclaimed()
+}
+
+/// How much of a vesting slot worth `amount` has unlocked at `now`, rounded
+/// down: nothing before `start`, all of it from `end` on
+pub fn vested_amount(amount: u64, start: i64, end: i64, now: i64) -> u64 {
+    if now >= end {
+        return amount;
+    }
+    if now <= start {
+        return 0;
+    }
+    (amount as u128 * (now - start) as u128 / (end - start) as u128) as u64
+}
+
+/// What a vesting packet's claimers have unlocked or will unlock but not yet
+/// collected: the part of remaining_amount that isn't the creator's. 0 on
+/// other packets.
+pub fn get_vesting_owed(data: &[u8]) -> u64 {
+    RedPacketView(data).vesting_owed()
+}
+
+/// Raffle packets with entrants whose pot finalize hasn't paid out yet,
+/// or lucky packets whose jackpot it hasn't
+#[inline]
+pub fn get_draw_unpaid(data: &[u8]) -> bool {
+    RedPacketView(data).draw_unpaid()
+}
+
+/// The creator's secret, once revealed
+#[inline]
+pub fn get_revealed_secret(data: &[u8]) -> Option<&[u8]> {
+    if data[FLAGS_OFFSET] & FLAG_REVEALED == 0 {
+        return None;
+    }
+    Some(&data[REVEAL_COMMITMENT_OFFSET..REVEAL_COMMITMENT_OFFSET + 32])
+}
+
+#[inline]
+pub fn get_reveal_deadline(data: &[u8]) -> i64 {
+    RedPacketView(data).reveal_deadline()
+}
+
+#[inline]
+pub fn get_amount_at(data: &[u8], index: u8) -> u64 {
+    RedPacketView(data).amount_at(index)
+}
+
+/// Unix time slot `index` was first claimed: 0 while unclaimed, on slots
+/// claimed before v11, and always on large packets (which don't record it)
+#[inline]
+pub fn get_claimed_at(data: &[u8], num_recipients: u8, index: u8) -> i64 {
+    if get_large(data) {
+        return 0;
+    }
+    read_i64(data, claimed_at_offset(num_recipients) + 8 * index as usize)
+}
+
+/// What a vesting slot's claimer has collected so far
+#[inline]
+pub fn get_taken_at(data: &[u8], num_recipients: u8, index: u8) -> u64 {
+    read_u64(data, taken_offset(num_recipients) + 8 * index as usize)
+}
+
+/// Whether slot `index` has been claimed
+#[inline]
+pub fn is_claimed(data: &[u8], index: u8) -> bool {
+    RedPacketView(data).is_claimed(index)
+}
+
+/// The claimed slots, lowest first
+pub fn claimed_slots(data: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    (0..get_num_recipients(data)).filter(move |&i| is_claimed(data, i))
+}
+
+/// The unclaimed slots, lowest first; a claim that doesn't name its slot
+/// takes the first one
+pub fn unclaimed_slots(data: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    (0..get_num_recipients(data)).filter(move |&i| !is_claimed(data, i))
+}
+
+/// Every slot's claimer, back to back in slot order (zeros for unclaimed
+/// slots other than assigned ones)
+#[inline]
+pub fn get_claimers(data: &[u8], num_recipients: u8) -> &[u8] {
+    let base = claimers_offset(num_recipients);
+    &data[base..base + 32 * num_recipients as usize]
+}
+
+#[inline]
+pub fn get_claimer_at(data: &[u8], num_recipients: u8, index: u8) -> &[u8] {
+    let base = claimers_offset(num_recipients);
+    let offset = base + 32 * index as usize;
+    &data[offset..offset + 32]
+}
+
+// === RedPacket Writers ===
+
+/// A weighted slot's share of `total`, rounded down
+pub fn weighted_amount(total: u64, weight: u16) -> u64 {
+    (total as u128 * weight as u128 / WEIGHT_TOTAL_BPS as u128) as u64
+}
+
+/// Even split of `total` over `slots` (> 0): each slot's amount, and the
+/// last slot's, which also takes the rounding remainder. Both are at most
+/// `total`, so nothing here can overflow.
+pub fn even_split(total: u64, slots: u64) -> (u64, u64) {
+    let per_slot = total / slots;
+    (per_slot, total - per_slot * (slots - 1))
+}
+
+/// Even and weighted packets split total_amount across the slots (the last
+/// one takes the remainder); random packets leave the amounts zeroed for
+/// claims to fill. `flags` are the ones create settled on, which it derives
+/// from `args.flags`, and `args` has passed create's checks (a memo's
+/// padding is zero).
+/// `data` must be freshly allocated, i.e. zeroed.
+pub fn init_redpacket(
+    data: &mut [u8],
+    creator: &[u8],
+    args: &CreateArgs,
+    created_at: i64,
+    fee_paid: u64,
+    flags: u8,
+) {
+    let (total_amount, num_recipients) = (args.total_amount, args.num_recipients);
+    data[DISCRIMINATOR_OFFSET] = REDPACKET_DISCRIMINATOR;
+    data[CREATOR_OFFSET..CREATOR_OFFSET + 32].copy_from_slice(creator);
+    data[ORIGINAL_CREATOR_OFFSET..ORIGINAL_CREATOR_OFFSET + 32].copy_from_slice(creator);
+    write_u64(data, ID_OFFSET, args.id);
+    write_u64(data, TOTAL_AMOUNT_OFFSET, total_amount);
+    write_u64(data, REMAINING_AMOUNT_OFFSET, total_amount);
+    data[NUM_RECIPIENTS_OFFSET] = num_recipients;
+    data[NUM_CLAIMED_OFFSET] = 0;
+    data[SPLIT_MODE_OFFSET] = args.split_mode;
+    data[BUMP_OFFSET] = args.rp_bump;
+    data[VAULT_BUMP_OFFSET] = args.vault_bump;
+    data[TOKEN_TYPE_OFFSET] = args.token_type;
+    write_i64(data, EXPIRES_AT_OFFSET, args.expires_at);
+    write_u64(data, FEE_PAID_OFFSET, fee_paid);
+    data[VERSION_OFFSET] = REDPACKET_VERSION;
+    data[FLAGS_OFFSET] = flags;
+    data[SECRET_HASH_OFFSET..SECRET_HASH_OFFSET + 32].copy_from_slice(&args.secret_hash);
+    data[VOUCHER_AUTHORITY_OFFSET..VOUCHER_AUTHORITY_OFFSET + 32]
+        .copy_from_slice(&args.voucher_authority);
+    data[GATE_MINT_OFFSET..GATE_MINT_OFFSET + 32].copy_from_slice(&args.gate_mint);
+    write_u64(data, GATE_MIN_AMOUNT_OFFSET, args.gate_min_amount);
+    data[ASSIGNED_OFFSET] = !args.recipients.is_empty() as u8;
+    write_i64(data, CREATED_AT_OFFSET, created_at);
+    data[REVEAL_COMMITMENT_OFFSET..REVEAL_COMMITMENT_OFFSET + 32]
+        .copy_from_slice(&args.reveal_commitment);
+    write_i64(data, REVEAL_DEADLINE_OFFSET, args.reveal_deadline);
+    data[WINNER_OFFSET..WINNER_OFFSET + 32].fill(0);
+    write_i64(data, VESTING_START_OFFSET, args.vesting_start);
+    data[CLAIMED_SLOTS_OFFSET..MEMO_LEN_OFFSET].fill(0);
+    // The memo block is memo_len then the zero-padded text, as stored
+    if let Some(memo) = args.memo {
+        data[MEMO_LEN_OFFSET..MEMO_OFFSET + MAX_MEMO_LEN].copy_from_slice(memo);
+    }
+    data[BURN_UNCLAIMED_OFFSET] = (args.options & CREATE_BURN_UNCLAIMED != 0) as u8;
+    data[DONATE_UNCLAIMED_OFFSET] = (args.options & CREATE_DONATE_UNCLAIMED != 0) as u8;
+
+    if args.split_mode == SPLIT_EVEN {
+        let (per_person, last) = even_split(total_amount, num_recipients as u64);
+        for i in 0..num_recipients {
+            set_amount_at(data, i, per_person);
+        }
+        set_amount_at(data, num_recipients - 1, last);
+    } else if args.split_mode == SPLIT_WEIGHTED {
+        let mut assigned = 0;
+        for (i, weight) in args.weights.chunks_exact(2).enumerate().take(num_recipients as usize - 1) {
+            let amount = weighted_amount(total_amount, u16::from_le_bytes([weight[0], weight[1]]));
+            set_amount_at(data, i as u8, amount);
+            assigned += amount;
+        }
+        set_amount_at(data, num_recipients - 1, total_amount - assigned);
+    }
+
+    let claimers = claimers_offset(num_recipients);
+    data[claimers..claimers + args.recipients.len()].copy_from_slice(args.recipients);
+}
+
+/// Rewrite an older packet as the current version. `data` must already be
+/// resized to `redpacket_size(num_recipients, large, vesting)`; amounts +
+/// claimers (and any claim times and taken amounts) are shifted up past the
+/// new header fields, which get defaults. Fees charged before fee_paid
+/// existed weren't recorded, so v0 packets start at 0, and neither were the
+/// create time or claim times, so created_at and every claimed_at stay 0.
+/// Older packets' claimed slots are 0..num_claimed.
+pub fn upgrade_redpacket(data: &mut [u8], from_version: u8, num_recipients: u8, large: bool, vesting: bool) {
+    let old_base_size = REDPACKET_BASE_SIZES[from_version as usize];
+    let n = num_recipients as usize;
+    if large {
+        data.copy_within(old_base_size..old_base_size + PER_RECIPIENT_SIZE_LARGE * n, AMOUNTS_OFFSET);
+    } else {
+        // amounts + claimers, then claimed_at (since v11), then any taken
+        // amounts. The tail only ever moves up, so the highest array goes
+        // first and nothing is overwritten before it moves.
+        let old_claimed_at = old_base_size + 40 * n;
+        let old_taken = redpacket_layout_size(from_version, num_recipients, false, false);
+        let taken_len = if vesting { 8 * n } else { 0 };
+        data.copy_within(old_taken..old_taken + taken_len, taken_offset(num_recipients));
+        let claimed_at = claimed_at_offset(num_recipients);
+        if from_version < REDPACKET_CLAIMED_AT_SINCE {
+            data[claimed_at..claimed_at + 8 * n].fill(0);
+        } else {
+            data.copy_within(old_claimed_at..old_taken, claimed_at);
+        }
+        data.copy_within(old_base_size..old_claimed_at, AMOUNTS_OFFSET);
+    }
+
+    if from_version < 1 {
+        write_u64(data, FEE_PAID_OFFSET, 0);
+    }
+    if from_version < 2 {
+        data[FLAGS_OFFSET] = 0;
+    }
+    if from_version < 3 {
+        data[SECRET_HASH_OFFSET..SECRET_HASH_OFFSET + 32].fill(0);
+    }
+    if from_version < 4 {
+        data[VOUCHER_AUTHORITY_OFFSET..VOUCHER_AUTHORITY_OFFSET + 32].fill(0);
+    }
+    if from_version < 5 {
+        data[GATE_MINT_OFFSET..GATE_MINT_OFFSET + 32].fill(0);
+        write_u64(data, GATE_MIN_AMOUNT_OFFSET, 0);
+    }
+    if from_version < 6 {
+        data[ASSIGNED_OFFSET] = 0;
+    }
+    if from_version < 7 {
+        write_i64(data, CREATED_AT_OFFSET, 0);
+    }
+    if from_version < 8 {
+        data[REVEAL_COMMITMENT_OFFSET..REVEAL_COMMITMENT_OFFSET + 32].fill(0);
+        write_i64(data, REVEAL_DEADLINE_OFFSET, 0);
+    }
+    if from_version < 9 {
+        data[WINNER_OFFSET..WINNER_OFFSET + 32].fill(0);
+    }
+    if from_version < 10 {
+        write_i64(data, VESTING_START_OFFSET, 0);
+    }
+    if from_version < 12 {
+        data[CLAIMED_SLOTS_OFFSET..MEMO_LEN_OFFSET].fill(0);
+        for slot in 0..get_num_claimed(data) {
+            data[CLAIMED_SLOTS_OFFSET + slot as usize / 8] |= 1 << (slot % 8);
+        }
+    }
+    if from_version < 13 {
+        data[MEMO_LEN_OFFSET..BURN_UNCLAIMED_OFFSET].fill(0);
+    }
+    if from_version < 14 {
+        data[BURN_UNCLAIMED_OFFSET] = 0;
+    }
+    if from_version < 15 {
+        data[DONATE_UNCLAIMED_OFFSET] = 0;
+    }
+    if from_version < 16 {
+        // Creators couldn't change before v16
+        data.copy_within(CREATOR_OFFSET..CREATOR_OFFSET + 32, ORIGINAL_CREATOR_OFFSET);
+    }
+    data[VERSION_OFFSET] = REDPACKET_VERSION;
+}
+
+/// Append empty slots to a packet. `data` must already be resized to
+/// `redpacket_size(new_num_recipients, large, vesting)`; claimers, claim
+/// times (and a vesting packet's taken amounts) are shifted up past the
+/// longer arrays before them, and the new entries are zeroed.
+pub fn grow_redpacket(data: &mut [u8], old_num_recipients: u8, new_num_recipients: u8) {
+    let old_claimers = claimers_offset(old_num_recipients);
+    data[NUM_RECIPIENTS_OFFSET] = new_num_recipients;
+    if get_large(data) {
+        data[old_claimers..].fill(0);
+        return;
+    }
+
+    let new_claimers = claimers_offset(new_num_recipients);
+    let claimers_len = 32 * old_num_recipients as usize;
+    let claimed_at_len = 8 * old_num_recipients as usize;
+    // Highest array first, so nothing is overwritten before it moves
+    let taken_len = if get_vesting(data) { 8 * old_num_recipients as usize } else { 0 };
+    let old_taken = taken_offset(old_num_recipients);
+    let new_taken = taken_offset(new_num_recipients);
+    let old_claimed_at = claimed_at_offset(old_num_recipients);
+    let new_claimed_at = claimed_at_offset(new_num_recipients);
+    data.copy_within(old_taken..old_taken + taken_len, new_taken);
+    data.copy_within(old_claimed_at..old_claimed_at + claimed_at_len, new_claimed_at);
+    data.copy_within(old_claimers..old_claimers + claimers_len, new_claimers);
+
+    data[old_claimers..new_claimers].fill(0);
+    data[new_claimers + claimers_len..new_claimed_at].fill(0);
+    data[new_claimed_at + claimed_at_len..new_taken].fill(0);
+    data[new_taken + taken_len..].fill(0);
+}
+
+#[inline]
+pub fn set_total_amount(data: &mut [u8], amount: u64) {
+    RedPacketView(data).set_total_amount(amount);
+}
+
+#[inline]
+pub fn set_remaining_amount(data: &mut [u8], amount: u64) {
+    RedPacketView(data).set_remaining_amount(amount);
+}
+
+#[inline]
+pub fn set_fee_paid(data: &mut [u8], fee: u64) {
+    RedPacketView(data).set_fee_paid(fee);
+}
+
+#[inline]
+pub fn set_creator(data: &mut [u8], creator: &[u8]) {
+    RedPacketView(data).set_creator(creator);
+}
+
+#[inline]
+pub fn set_expires_at(data: &mut [u8], expires_at: i64) {
+    RedPacketView(data).set_expires_at(expires_at);
+}
+
+/// Mark unclaimed slot `index` claimed, counting it in num_claimed
+#[inline]
+pub fn set_claimed(data: &mut [u8], index: u8) {
+    RedPacketView(data).set_claimed(index);
+}
+
+/// Replace the commitment with the secret that opened it and open claims
+#[inline]
+pub fn set_revealed(data: &mut [u8], secret: &[u8]) {
+    RedPacketView(data).set_revealed(secret);
+}
+
+#[inline]
+pub fn set_winner(data: &mut [u8], winner: &[u8]) {
+    RedPacketView(data).set_winner(winner);
+}
+
+#[inline]
+pub fn set_lucky_paid(data: &mut [u8]) {
+    RedPacketView(data).set_lucky_paid();
+}
+
+#[inline]
+pub fn set_amount_at(data: &mut [u8], index: u8, amount: u64) {
+    RedPacketView(data).set_amount_at(index, amount);
+}
+
+#[inline]
+pub fn set_claimed_at(data: &mut [u8], num_recipients: u8, index: u8, claimed_at: i64) {
+    write_i64(data, claimed_at_offset(num_recipients) + 8 * index as usize, claimed_at);
+}
+
+#[inline]
+pub fn set_taken_at(data: &mut [u8], num_recipients: u8, index: u8, amount: u64) {
+    write_u64(data, taken_offset(num_recipients) + 8 * index as usize, amount);
+}
+
+#[inline]
+pub fn set_claimer_at(data: &mut [u8], num_recipients: u8, index: u8, claimer: &[u8]) {
+    let base = claimers_offset(num_recipients);
+    let offset = base + 32 * index as usize;
+    data[offset..offset + 32].copy_from_slice(claimer);
+}
+
+/// The lowest unclaimed slot assigned to `claimer`, or None when it has none
+/// left
+pub fn find_assigned_slot(data: &[u8], num_recipients: u8, claimer: &[u8]) -> Option<u8> {
+    unclaimed_slots(data).find(|&i| get_claimer_at(data, num_recipients, i) == claimer)
+}
+
+pub fn has_claimed(data: &[u8], num_recipients: u8, claimer: &[u8]) -> bool {
+    find_claimed_slot(data, num_recipients, claimer).is_some()
+}
+
+/// The lowest claimed slot recorded for `claimer`
+pub fn find_claimed_slot(data: &[u8], num_recipients: u8, claimer: &[u8]) -> Option<u8> {
+    claimed_slots(data).find(|&i| get_claimer_at(data, num_recipients, i) == claimer)
+}
+
+// ========================
+// Treasury account layout
+// ========================
+// 0    discriminator       u8      1   (= 2)
+// 1    bump                u8      1
+// 2    vault_bump          u8      1
+// 3    mint                [u8;32] 32
+// 35   sol_fees_collected  u64     8
+// 43   fee_bps             u16     2   (absent on legacy 43-byte treasuries)
+// 45   admin               [u8;32] 32  (absent on 43/45-byte treasuries: ADMIN)
+// 77   pending_admin       [u8;32] 32  (all zeros = no nomination)
+// 109  paused              u8      1   (1 = no creates or claims against this mint)
+// 110  version             u8      1   (absent before v2: reads as 1)
+// 111  min_expiry_secs     i64     8   (absent before v3: MIN_EXPIRY_SECONDS)
+// 119  max_expiry_secs     i64     8   (absent before v3: MAX_EXPIRY_SECONDS)
+
+const TREASURY_DISCRIMINATOR_OFFSET: usize = 0;
+const TREASURY_BUMP_OFFSET: usize = 1;
+const TREASURY_VAULT_BUMP_OFFSET: usize = 2;
+const TREASURY_MINT_OFFSET: usize = 3;
+const SOL_FEES_OFFSET: usize = 35;
+const TREASURY_FEE_BPS_OFFSET: usize = 43;
+const TREASURY_ADMIN_OFFSET: usize = 45;
+const TREASURY_PENDING_ADMIN_OFFSET: usize = 77;
+const TREASURY_PAUSED_OFFSET: usize = 109;
+const TREASURY_VERSION_OFFSET: usize = 110;
+const TREASURY_MIN_EXPIRY_OFFSET: usize = 111;
+const TREASURY_MAX_EXPIRY_OFFSET: usize = 119;
+
+// ========================
+// Treasury views
+// ========================
+// Same scheme as the red packet views. Older treasury layouts are still
+// accepted: the getters default the fields they don't have yet.
+
+/// Zero-copy view of a treasury's data
+#[derive(Clone, Copy)]
+pub struct TreasuryView<D>(D);
+
+/// TreasuryView over writable data
+pub type TreasuryViewMut<'a> = TreasuryView<&'a mut [u8]>;
+
+fn check_treasury_data(data: &[u8]) -> Result<(), ProgramError> {
+    if data.len() < TREASURY_LEGACY_SIZE {
+        return Err(ProgramError::InvalidAccountData);
     }
     if data[TREASURY_DISCRIMINATOR_OFFSET] != TREASURY_DISCRIMINATOR {
         return Err(RedPacketError::TreasuryNotInitialized.into());
     }
+    if get_treasury_version(data) > TREASURY_VERSION {
+        return Err(RedPacketError::UnsupportedVersion.into());
+    }
+    Ok(())
+}
+
+view_try_from!(TreasuryView, check_treasury_data);
+
+fn check_treasury_account(account: &AccountView, program_id: &Address) -> Result<(), ProgramError> {
+    // Never created, or closed by close_treasury
+    if account.lamports() == 0 {
+        return Err(RedPacketError::TreasuryNotInitialized.into());
+    }
+    if !account.owned_by(program_id) {
+        return Err(RedPacketError::InvalidAccountOwner.into());
+    }
     Ok(())
 }
 
-pub fn init_treasury(data: &mut [u8], bump: u8, vault_bump: u8, mint: &[u8]) {
+/// Check that an account is an initialized treasury and view its data
+#[inline]
+pub fn load_treasury<'a>(
+    account: &'a AccountView,
+    program_id: &Address,
+) -> Result<TreasuryView<Ref<'a, [u8]>>, ProgramError> {
+    check_treasury_account(account, program_id)?;
+    TreasuryView::try_from(account.try_borrow()?)
+}
+
+/// Like `load_treasury`, borrowing the data mutably
+#[inline]
+pub fn load_treasury_mut<'a>(
+    account: &'a AccountView,
+    program_id: &Address,
+) -> Result<TreasuryView<RefMut<'a, [u8]>>, ProgramError> {
+    check_treasury_account(account, program_id)?;
+    TreasuryView::try_from(account.try_borrow_mut()?)
+}
+
+pub fn validate_treasury(account: &AccountView, program_id: &Address) -> Result<(), ProgramError> {
+    load_treasury(account, program_id).map(|_| ())
+}
+
+impl<D: Deref<Target = [u8]>> TreasuryView<D> {
+    /// The whole account data
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Reborrow as a view over plain bytes, for helpers that only read
+    #[inline]
+    pub fn as_view(&self) -> TreasuryView<&[u8]> {
+        TreasuryView(&self.0)
+    }
+
+    /// Treasuries without the version byte are v1
+    #[inline]
+    pub fn version(&self) -> u8 {
+        get_treasury_version(&self.0)
+    }
+
+    #[inline]
+    pub fn bump(&self) -> u8 {
+        self.0[TREASURY_BUMP_OFFSET]
+    }
+
+    #[inline]
+    pub fn vault_bump(&self) -> u8 {
+        self.0[TREASURY_VAULT_BUMP_OFFSET]
+    }
+
+    #[inline]
+    pub fn mint(&self) -> &[u8] {
+        get_treasury_mint(&self.0)
+    }
+
+    #[inline]
+    pub fn sol_fees_collected(&self) -> u64 {
+        read_u64(&self.0, SOL_FEES_OFFSET)
+    }
+
+    /// Legacy treasuries predate fee_bps and keep charging the default rate
+    #[inline]
+    pub fn fee_bps(&self) -> u16 {
+        if self.0.len() < TREASURY_ADMIN_OFFSET {
+            return FEE_RATE_BPS;
+        }
+        u16::from_le_bytes([self.0[TREASURY_FEE_BPS_OFFSET], self.0[TREASURY_FEE_BPS_OFFSET + 1]])
+    }
+
+    /// Treasuries that predate the stored admin are run by ADMIN
+    #[inline]
+    pub fn admin(&self) -> &[u8] {
+        get_treasury_admin(&self.0)
+    }
+
+    #[inline]
+    pub fn pending_admin(&self) -> Option<&[u8]> {
+        get_treasury_pending_admin(&self.0)
+    }
+
+    /// Treasuries that predate the flag were never paused
+    #[inline]
+    pub fn paused(&self) -> bool {
+        self.0.get(TREASURY_PAUSED_OFFSET) == Some(&1)
+    }
+
+    /// Treasuries that predate the bounds use the defaults
+    #[inline]
+    pub fn min_expiry_secs(&self) -> i64 {
+        if self.0.len() < TREASURY_MAX_EXPIRY_OFFSET + 8 {
+            return MIN_EXPIRY_SECONDS;
+        }
+        read_i64(&self.0, TREASURY_MIN_EXPIRY_OFFSET)
+    }
+
+    #[inline]
+    pub fn max_expiry_secs(&self) -> i64 {
+        if self.0.len() < TREASURY_MAX_EXPIRY_OFFSET + 8 {
+            return MAX_EXPIRY_SECONDS;
+        }
+        read_i64(&self.0, TREASURY_MAX_EXPIRY_OFFSET)
+    }
+}
+
+impl<D: DerefMut<Target = [u8]>> TreasuryView<D> {
+    /// The whole account data, writable
+    #[inline]
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    #[inline]
+    pub fn tombstone(&mut self) {
+        self.0[TREASURY_DISCRIMINATOR_OFFSET] = CLOSED_DISCRIMINATOR;
+    }
+
+    #[inline]
+    pub fn set_fee_bps(&mut self, fee_bps: u16) {
+        self.0[TREASURY_FEE_BPS_OFFSET..TREASURY_FEE_BPS_OFFSET + 2].copy_from_slice(&fee_bps.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn set_admin(&mut self, admin: &[u8]) {
+        self.0[TREASURY_ADMIN_OFFSET..TREASURY_ADMIN_OFFSET + 32].copy_from_slice(admin);
+    }
+
+    #[inline]
+    pub fn set_pending_admin(&mut self, pending_admin: &[u8]) {
+        self.0[TREASURY_PENDING_ADMIN_OFFSET..TREASURY_PENDING_ADMIN_OFFSET + 32].copy_from_slice(pending_admin);
+    }
+
+    #[inline]
+    pub fn set_paused(&mut self, paused: bool) {
+        self.0[TREASURY_PAUSED_OFFSET] = paused as u8;
+    }
+
+    #[inline]
+    pub fn set_expiry_bounds(&mut self, min_expiry_secs: i64, max_expiry_secs: i64) {
+        write_i64(&mut self.0, TREASURY_MIN_EXPIRY_OFFSET, min_expiry_secs);
+        write_i64(&mut self.0, TREASURY_MAX_EXPIRY_OFFSET, max_expiry_secs);
+    }
+
+    #[inline]
+    pub fn set_sol_fees_collected(&mut self, amount: u64) {
+        write_u64(&mut self.0, SOL_FEES_OFFSET, amount);
+    }
+}
+
+#[inline]
+pub fn tombstone_treasury(data: &mut [u8]) {
+    TreasuryView(data).tombstone();
+}
+
+pub fn init_treasury(
+    data: &mut [u8],
+    bump: u8,
+    vault_bump: u8,
+    mint: &[u8],
+    fee_bps: u16,
+    admin: &[u8],
+) {
     data[TREASURY_DISCRIMINATOR_OFFSET] = TREASURY_DISCRIMINATOR;
     data[TREASURY_BUMP_OFFSET] = bump;
     data[TREASURY_VAULT_BUMP_OFFSET] = vault_bump;
     data[TREASURY_MINT_OFFSET..TREASURY_MINT_OFFSET + 32].copy_from_slice(mint);
+    set_treasury_fee_bps(data, fee_bps);
+    set_treasury_admin(data, admin);
+    data[TREASURY_VERSION_OFFSET] = TREASURY_VERSION;
+    set_treasury_expiry_bounds(data, MIN_EXPIRY_SECONDS, MAX_EXPIRY_SECONDS);
+}
+
+/// Fill the fields a treasury of `old_len` bytes didn't have yet with the
+/// values it was implicitly using. `data` must already be TREASURY_SIZE.
+pub fn upgrade_treasury(data: &mut [u8], old_len: usize) {
+    if old_len < TREASURY_ADMIN_OFFSET {
+        set_treasury_fee_bps(data, FEE_RATE_BPS);
+    }
+    if old_len < TREASURY_PAUSED_OFFSET {
+        set_treasury_admin(data, ADMIN.as_ref());
+        set_treasury_pending_admin(data, &[0u8; 32]);
+    }
+    if old_len < TREASURY_VERSION_OFFSET {
+        set_treasury_paused(data, false);
+    }
+    if old_len < TREASURY_MAX_EXPIRY_OFFSET {
+        set_treasury_expiry_bounds(data, MIN_EXPIRY_SECONDS, MAX_EXPIRY_SECONDS);
+    }
+    data[TREASURY_VERSION_OFFSET] = TREASURY_VERSION;
+}
+
+/// Treasuries without the version byte are v1
+#[inline]
+pub fn get_treasury_version(data: &[u8]) -> u8 {
+    data.get(TREASURY_VERSION_OFFSET).copied().unwrap_or(1)
 }
 
 #[inline]
 pub fn get_treasury_bump(data: &[u8]) -> u8 {
-    data[TREASURY_BUMP_OFFSET]
+    TreasuryView(data).bump()
 }
 
 #[inline]
 pub fn get_treasury_vault_bump(data: &[u8]) -> u8 {
-    data[TREASURY_VAULT_BUMP_OFFSET]
+    TreasuryView(data).vault_bump()
 }
 
 #[inline]
@@ -251,12 +1590,81 @@ pub fn get_treasury_mint(data: &[u8]) -> &[u8] {
 
 #[inline]
 pub fn get_sol_fees_collected(data: &[u8]) -> u64 {
-    read_u64(data, SOL_FEES_OFFSET)
+    TreasuryView(data).sol_fees_collected()
+}
+
+/// Legacy treasuries predate fee_bps and keep charging the default rate
+#[inline]
+pub fn get_treasury_fee_bps(data: &[u8]) -> u16 {
+    TreasuryView(data).fee_bps()
+}
+
+#[inline]
+pub fn set_treasury_fee_bps(data: &mut [u8], fee_bps: u16) {
+    TreasuryView(data).set_fee_bps(fee_bps);
+}
+
+/// Treasuries that predate the stored admin are run by ADMIN
+#[inline]
+pub fn get_treasury_admin(data: &[u8]) -> &[u8] {
+    if data.len() < TREASURY_PAUSED_OFFSET {
+        return ADMIN.as_ref();
+    }
+    &data[TREASURY_ADMIN_OFFSET..TREASURY_ADMIN_OFFSET + 32]
+}
+
+#[inline]
+pub fn get_treasury_pending_admin(data: &[u8]) -> Option<&[u8]> {
+    if data.len() < TREASURY_PAUSED_OFFSET {
+        return None;
+    }
+    let pending = &data[TREASURY_PENDING_ADMIN_OFFSET..TREASURY_PENDING_ADMIN_OFFSET + 32];
+    if pending.iter().all(|&b| b == 0) {
+        return None;
+    }
+    Some(pending)
+}
+
+#[inline]
+pub fn set_treasury_admin(data: &mut [u8], admin: &[u8]) {
+    TreasuryView(data).set_admin(admin);
+}
+
+#[inline]
+pub fn set_treasury_pending_admin(data: &mut [u8], pending_admin: &[u8]) {
+    TreasuryView(data).set_pending_admin(pending_admin);
+}
+
+/// Treasuries that predate the flag were never paused
+#[inline]
+pub fn get_treasury_paused(data: &[u8]) -> bool {
+    TreasuryView(data).paused()
+}
+
+#[inline]
+pub fn set_treasury_paused(data: &mut [u8], paused: bool) {
+    TreasuryView(data).set_paused(paused);
+}
+
+/// Treasuries that predate the bounds use the defaults
+#[inline]
+pub fn get_treasury_min_expiry_secs(data: &[u8]) -> i64 {
+    TreasuryView(data).min_expiry_secs()
+}
+
+#[inline]
+pub fn get_treasury_max_expiry_secs(data: &[u8]) -> i64 {
+    TreasuryView(data).max_expiry_secs()
+}
+
+#[inline]
+pub fn set_treasury_expiry_bounds(data: &mut [u8], min_expiry_secs: i64, max_expiry_secs: i64) {
+    TreasuryView(data).set_expiry_bounds(min_expiry_secs, max_expiry_secs);
 }
 
 #[inline]
 pub fn set_sol_fees_collected(data: &mut [u8], amount: u64) {
-    write_u64(data, SOL_FEES_OFFSET, amount);
+    TreasuryView(data).set_sol_fees_collected(amount);
 }
 
 #[inline]
@@ -266,3 +1674,317 @@ pub fn validate_token_type(token_type: u8) -> Result<(), ProgramError> {
     }
     Ok(())
 }
+
+// ========================
+// Fixed-capacity buffers
+// ========================
+// The program has no heap (`no_allocator!()`, `no_std` on-chain), so features
+// that need scratch space — hash preimages, merkle proofs, log lines — build
+// into a stack buffer whose capacity is fixed at compile time. Keep N small:
+// the BPF stack frame is 4KB.
+
+pub struct FixedBuf<const N: usize> {
+    data: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> FixedBuf<N> {
+    #[inline]
+    pub const fn new() -> Self {
+        Self { data: [0u8; N], len: 0 }
+    }
+
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    #[inline]
+    pub fn push(&mut self, byte: u8) -> Result<(), ProgramError> {
+        self.extend_from_slice(&[byte])
+    }
+
+    /// Append `bytes`, failing without writing anything if they don't fit
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) -> Result<(), ProgramError> {
+        let end = self
+            .len
+            .checked_add(bytes.len())
+            .ok_or(ProgramError::InvalidArgument)?;
+        if end > N {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.data[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Append `value` in decimal (for log messages)
+    pub fn push_i64(&mut self, value: i64) -> Result<(), ProgramError> {
+        if value < 0 {
+            self.push(b'-')?;
+        }
+        self.push_u64(value.unsigned_abs())
+    }
+
+    /// Append `value` in decimal (for log messages)
+    pub fn push_u64(&mut self, value: u64) -> Result<(), ProgramError> {
+        let mut digits = [0u8; 20];
+        let mut i = digits.len();
+        let mut n = value;
+        loop {
+            i -= 1;
+            digits[i] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        self.extend_from_slice(&digits[i..])
+    }
+
+    /// Append up to 32 bytes (an address) in base58, as wallets display it
+    pub fn push_base58(&mut self, bytes: &[u8]) -> Result<(), ProgramError> {
+        const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+        if bytes.len() > 32 {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Base-58 digits, least significant first; 32 bytes need at most 44
+        let mut digits = [0u8; 44];
+        let mut len = 0;
+        for &byte in bytes {
+            let mut carry = byte as u32;
+            for digit in digits[..len].iter_mut() {
+                carry += (*digit as u32) << 8;
+                *digit = (carry % 58) as u8;
+                carry /= 58;
+            }
+            while carry > 0 {
+                digits[len] = (carry % 58) as u8;
+                len += 1;
+                carry /= 58;
+            }
+        }
+
+        // Each leading zero byte is a leading '1'
+        let mut out = [0u8; 44];
+        let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+        out[..zeros].fill(b'1');
+        for (i, &digit) in digits[..len].iter().rev().enumerate() {
+            out[zeros + i] = ALPHABET[digit as usize];
+        }
+        self.extend_from_slice(&out[..zeros + len])
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        // Only ever filled from &str / ASCII digits / base58
+        core::str::from_utf8(self.as_slice()).unwrap_or("")
+    }
+}
+
+impl<const N: usize> Default for FixedBuf<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ========================
+// Receipt account layout
+// ========================
+// 0    discriminator  u8  1   (= 3)
+// 1    bump           u8  1
+//
+// PDA [RECEIPT_SEED, red_packet, claimer] created by a large packet's claim;
+// the claimer pays its rent and gets it back through close_receipt.
+
+const RECEIPT_DISCRIMINATOR_OFFSET: usize = 0;
+const RECEIPT_BUMP_OFFSET: usize = 1;
+
+/// An account at a receipt address only counts once the program owns and
+/// initialized it; lamports sent there beforehand don't block a claim
+pub fn is_receipt(account: &AccountView, program_id: &Address) -> Result<bool, ProgramError> {
+    if !account.owned_by(program_id) {
+        return Ok(false);
+    }
+    let data = account.try_borrow()?;
+    Ok(data.first() == Some(&RECEIPT_DISCRIMINATOR))
+}
+
+pub fn init_receipt(data: &mut [u8], bump: u8) {
+    data[RECEIPT_DISCRIMINATOR_OFFSET] = RECEIPT_DISCRIMINATOR;
+    data[RECEIPT_BUMP_OFFSET] = bump;
+}
+
+#[inline]
+pub fn get_receipt_bump(data: &[u8]) -> u8 {
+    data[RECEIPT_BUMP_OFFSET]
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::constants::{redpacket_size, TREASURY_SIZE};
+
+    const CREATOR: [u8; 32] = [7; 32];
+    const CLAIMER: [u8; 32] = [9; 32];
+
+    /// A fresh 3-slot even SOL packet of 1000 lamports
+    fn packet() -> Vec<u8> {
+        let mut data = vec![0u8; redpacket_size(3, false, false)];
+        let args = CreateArgs {
+            token_type: TOKEN_TYPE_SOL,
+            id: 42,
+            total_amount: 1000,
+            num_recipients: 3,
+            split_mode: SPLIT_EVEN,
+            expires_at: 2_000_000,
+            rp_bump: 254,
+            vault_bump: 253,
+            flags: FLAG_ALLOW_REPEAT,
+            options: 0,
+            secret_hash: [0; 32],
+            voucher_authority: [0; 32],
+            gate_mint: [0; 32],
+            gate_min_amount: 0,
+            recipients: &[],
+            reveal_commitment: [0; 32],
+            reveal_deadline: 0,
+            memo: None,
+            vesting_start: 0,
+            weights: &[],
+        };
+        init_redpacket(&mut data, &CREATOR, &args, 1_000_000, 1, FLAG_ALLOW_REPEAT);
+        data
+    }
+
+    fn error(view: Result<RedPacketView<&[u8]>, ProgramError>) -> ProgramError {
+        view.err().unwrap()
+    }
+
+    #[test]
+    fn view_reads_the_fields_init_wrote() {
+        let data = packet();
+        let rp = RedPacketView::try_from(&data[..]).unwrap();
+        assert_eq!(rp.creator(), CREATOR);
+        assert_eq!(rp.original_creator(), CREATOR);
+        assert_eq!((rp.id(), rp.total_amount(), rp.remaining_amount()), (42, 1000, 1000));
+        assert_eq!((rp.num_recipients(), rp.num_claimed(), rp.split_mode()), (3, 0, SPLIT_EVEN));
+        assert_eq!((rp.bump(), rp.vault_bump(), rp.token_type()), (254, 253, TOKEN_TYPE_SOL));
+        assert_eq!((rp.expires_at(), rp.created_at(), rp.fee_paid()), (2_000_000, 1_000_000, 1));
+        assert!(rp.allow_repeat() && !rp.no_self_claim() && !rp.large() && !rp.vesting());
+        assert_eq!((rp.secret_hash(), rp.gate(), rp.winner()), (None, None, None));
+        assert!(rp.memo().is_empty());
+        // The last slot takes the rounding remainder
+        assert_eq!([rp.amount_at(0), rp.amount_at(1), rp.amount_at(2)], [333, 333, 334]);
+
+        // The free functions read the same bytes
+        assert_eq!(get_id(&data), rp.id());
+        assert_eq!(get_amount_at(&data, 2), rp.amount_at(2));
+        assert_eq!(get_claimer_at(&data, 3, 1), rp.claimer_at(1));
+    }
+
+    #[test]
+    fn view_rejects_what_validate_redpacket_rejects() {
+        let mut data = packet();
+        assert_eq!(error(RedPacketView::try_from(&data[..REDPACKET_BASE_SIZE - 1])), ProgramError::InvalidAccountData);
+
+        // A length no layout version has
+        assert_eq!(
+            error(RedPacketView::try_from(&data[..data.len() - 1])),
+            RedPacketError::UnsupportedVersion.into()
+        );
+
+        data[0] = TREASURY_DISCRIMINATOR;
+        assert_eq!(error(RedPacketView::try_from(&data[..])), RedPacketError::InvalidDiscriminator.into());
+
+        tombstone_redpacket(&mut data);
+        assert_eq!(error(RedPacketView::try_from(&data[..])), RedPacketError::AccountClosed.into());
+    }
+
+    #[test]
+    fn mut_view_records_a_claim() {
+        let mut data = packet();
+        let mut rp = RedPacketViewMut::try_from(&mut data[..]).unwrap();
+        rp.set_claimer_at(1, &CLAIMER);
+        rp.set_claimed_at(1, 1_500_000);
+        rp.set_claimed(1);
+        rp.set_remaining_amount(667);
+
+        assert_eq!(rp.num_claimed(), 1);
+        assert!(rp.is_claimed(1) && !rp.is_claimed(0));
+        assert_eq!(rp.claimed_at(1), 1_500_000);
+        assert_eq!(rp.find_claimed_slot(&CLAIMER), Some(1));
+        assert!(rp.has_claimed(&CLAIMER) && !rp.has_claimed(&CREATOR));
+        assert_eq!(rp.unclaimed_slots().collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(rp.remaining_amount(), 667);
+
+        rp.tombstone();
+        assert_eq!(
+            RedPacketViewMut::try_from(&mut data[..]).err(),
+            Some(RedPacketError::AccountClosed.into())
+        );
+    }
+
+    #[test]
+    fn treasury_view_defaults_the_fields_legacy_layouts_lack() {
+        let mut data = vec![0u8; TREASURY_SIZE];
+        init_treasury(&mut data, 255, 254, &[5; 32], 25, &CLAIMER);
+        let treasury = TreasuryView::try_from(&data[..]).unwrap();
+        assert_eq!((treasury.bump(), treasury.vault_bump(), treasury.mint()), (255, 254, &[5u8; 32][..]));
+        assert_eq!((treasury.fee_bps(), treasury.admin(), treasury.version()), (25, &CLAIMER[..], TREASURY_VERSION));
+        assert_eq!(
+            (treasury.min_expiry_secs(), treasury.max_expiry_secs()),
+            (MIN_EXPIRY_SECONDS, MAX_EXPIRY_SECONDS)
+        );
+
+        let legacy = TreasuryView::try_from(&data[..TREASURY_LEGACY_SIZE]).unwrap();
+        assert_eq!((legacy.fee_bps(), legacy.admin(), legacy.version()), (FEE_RATE_BPS, ADMIN.as_ref(), 1));
+        assert_eq!((legacy.pending_admin(), legacy.paused()), (None, false));
+
+        let mut tr = TreasuryViewMut::try_from(&mut data[..]).unwrap();
+        tr.set_paused(true);
+        tr.set_sol_fees_collected(600);
+        assert!(tr.paused());
+        assert_eq!(get_sol_fees_collected(&data), 600);
+
+        assert_eq!(
+            TreasuryView::try_from(&data[..TREASURY_LEGACY_SIZE - 1]).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+        data[TREASURY_VERSION_OFFSET] = TREASURY_VERSION + 1;
+        assert_eq!(
+            TreasuryView::try_from(&data[..]).err(),
+            Some(RedPacketError::UnsupportedVersion.into())
+        );
+        data[0] = REDPACKET_DISCRIMINATOR;
+        assert_eq!(
+            TreasuryView::try_from(&data[..]).err(),
+            Some(RedPacketError::TreasuryNotInitialized.into())
+        );
+    }
+}
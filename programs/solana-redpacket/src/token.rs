@@ -0,0 +1,214 @@
+//! Token CPIs against either token program. pinocchio_token's builders always
+//! target the legacy program, so these carry the caller-supplied program id;
+//! the instruction encodings are shared by Token and Token-2022.
+
+use pinocchio::{
+    cpi::{invoke, invoke_signed, Signer},
+    error::ProgramError,
+    instruction::{InstructionAccount, InstructionView},
+    AccountView, Address, ProgramResult,
+};
+use crate::constants::{TOKEN_2022_PROGRAM_ID, TOKEN_ACCOUNT_SIZE, TOKEN_PROGRAM_ID};
+use crate::error::RedPacketError;
+
+/// Legacy mint size; Token-2022 mints with extensions are longer
+const MINT_SIZE: usize = 82;
+/// Token-2022 writes the account type at offset 165, then TLV extensions
+const ACCOUNT_TYPE_OFFSET: usize = 165;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+/// Token account state byte: 0 uninitialized, 1 initialized, 2 frozen
+const ACCOUNT_STATE_OFFSET: usize = 108;
+const ACCOUNT_STATE_INITIALIZED: u8 = 1;
+const ACCOUNT_STATE_FROZEN: u8 = 2;
+
+/// Token-2022 mint extensions that leave transfers and token accounts alone:
+/// MintCloseAuthority, InterestBearingConfig, MetadataPointer, TokenMetadata,
+/// GroupPointer, TokenGroup, GroupMemberPointer, TokenGroupMember,
+/// ScaledUiAmount
+const ALLOWED_MINT_EXTENSIONS: [u16; 9] = [3, 10, 18, 19, 20, 21, 22, 23, 25];
+
+pub fn is_token_program(address: &Address) -> bool {
+    address == &TOKEN_PROGRAM_ID || address == &TOKEN_2022_PROGRAM_ID
+}
+
+pub fn owned_by_token_program(account: &AccountView) -> bool {
+    account.owned_by(&TOKEN_PROGRAM_ID) || account.owned_by(&TOKEN_2022_PROGRAM_ID)
+}
+
+/// Validate a token_program account, returning its id for the CPIs
+pub fn validate_token_program(account: &AccountView) -> Result<&Address, ProgramError> {
+    if !is_token_program(account.address()) {
+        return Err(RedPacketError::InvalidTokenProgram.into());
+    }
+    Ok(account.address())
+}
+
+/// Reject Token-2022 mints whose extensions change transfers (fees, hooks,
+/// non-transferable, pausing), can freeze or seize the vault (default
+/// account state, permanent delegate), or need account extensions a
+/// TOKEN_ACCOUNT_SIZE vault has no room for. Legacy mints always pass.
+pub fn check_mint(mint: &AccountView) -> ProgramResult {
+    let data = mint.try_borrow()?;
+    if data.len() == MINT_SIZE {
+        return Ok(());
+    }
+    if data.len() <= ACCOUNT_TYPE_OFFSET || data[ACCOUNT_TYPE_OFFSET] != ACCOUNT_TYPE_MINT {
+        return Err(RedPacketError::InvalidMint.into());
+    }
+
+    let mut offset = ACCOUNT_TYPE_OFFSET + 1;
+    while offset + 4 <= data.len() {
+        let extension = u16::from_le_bytes([data[offset], data[offset + 1]]);
+        let len = u16::from_le_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        // Type 0 is unused trailing space
+        if extension == 0 {
+            break;
+        }
+        if !ALLOWED_MINT_EXTENSIONS.contains(&extension) {
+            return Err(RedPacketError::UnsupportedMintExtension.into());
+        }
+        offset += 4 + len;
+    }
+    Ok(())
+}
+
+/// Check a vault before moving tokens out of it, so what would fail inside
+/// the token program fails here with an error clients can explain: a
+/// TOKEN_ACCOUNT_SIZE token account owned by `authority`, initialized and not
+/// frozen, and of `mint` when the caller knows it.
+pub fn check_vault(vault: &AccountView, authority: &AccountView, mint: Option<&[u8]>) -> ProgramResult {
+    if !owned_by_token_program(vault) {
+        return Err(RedPacketError::InvalidAccountOwner.into());
+    }
+    let data = vault.try_borrow()?;
+    if data.len() != TOKEN_ACCOUNT_SIZE {
+        return Err(RedPacketError::InvalidTokenAccount.into());
+    }
+    match data[ACCOUNT_STATE_OFFSET] {
+        ACCOUNT_STATE_INITIALIZED => {}
+        ACCOUNT_STATE_FROZEN => return Err(RedPacketError::VaultFrozen.into()),
+        _ => return Err(RedPacketError::InvalidTokenAccount.into()),
+    }
+    if &data[32..64] != authority.address().as_ref() {
+        return Err(RedPacketError::InvalidTokenAccount.into());
+    }
+    if mint.is_some_and(|mint| &data[0..32] != mint) {
+        return Err(RedPacketError::InvalidMint.into());
+    }
+    Ok(())
+}
+
+pub struct Transfer<'a> {
+    pub from: &'a AccountView,
+    pub to: &'a AccountView,
+    pub authority: &'a AccountView,
+    pub amount: u64,
+    pub token_program: &'a Address,
+}
+
+impl Transfer<'_> {
+    #[inline(always)]
+    pub fn invoke(&self) -> ProgramResult {
+        self.invoke_signed(&[])
+    }
+
+    #[inline(always)]
+    pub fn invoke_signed(&self, signers: &[Signer]) -> ProgramResult {
+        let accounts = [
+            InstructionAccount::writable(self.from.address()),
+            InstructionAccount::writable(self.to.address()),
+            InstructionAccount::readonly_signer(self.authority.address()),
+        ];
+        let mut data = [0u8; 9];
+        data[0] = 3;
+        data[1..].copy_from_slice(&self.amount.to_le_bytes());
+
+        let instruction = InstructionView {
+            program_id: self.token_program,
+            accounts: &accounts,
+            data: &data,
+        };
+        invoke_signed(&instruction, &[self.from, self.to, self.authority], signers)
+    }
+}
+
+pub struct Burn<'a> {
+    pub account: &'a AccountView,
+    pub mint: &'a AccountView,
+    pub authority: &'a AccountView,
+    pub amount: u64,
+    pub token_program: &'a Address,
+}
+
+impl Burn<'_> {
+    #[inline(always)]
+    pub fn invoke_signed(&self, signers: &[Signer]) -> ProgramResult {
+        let accounts = [
+            InstructionAccount::writable(self.account.address()),
+            InstructionAccount::writable(self.mint.address()),
+            InstructionAccount::readonly_signer(self.authority.address()),
+        ];
+        let mut data = [0u8; 9];
+        data[0] = 8;
+        data[1..].copy_from_slice(&self.amount.to_le_bytes());
+
+        let instruction = InstructionView {
+            program_id: self.token_program,
+            accounts: &accounts,
+            data: &data,
+        };
+        invoke_signed(&instruction, &[self.account, self.mint, self.authority], signers)
+    }
+}
+
+pub struct InitializeAccount3<'a> {
+    pub account: &'a AccountView,
+    pub mint: &'a AccountView,
+    pub owner: &'a Address,
+    pub token_program: &'a Address,
+}
+
+impl InitializeAccount3<'_> {
+    #[inline(always)]
+    pub fn invoke(&self) -> ProgramResult {
+        let accounts = [
+            InstructionAccount::writable(self.account.address()),
+            InstructionAccount::readonly(self.mint.address()),
+        ];
+        let mut data = [0u8; 33];
+        data[0] = 18;
+        data[1..].copy_from_slice(self.owner.as_ref());
+
+        let instruction = InstructionView {
+            program_id: self.token_program,
+            accounts: &accounts,
+            data: &data,
+        };
+        invoke(&instruction, &[self.account, self.mint])
+    }
+}
+
+pub struct CloseAccount<'a> {
+    pub account: &'a AccountView,
+    pub destination: &'a AccountView,
+    pub authority: &'a AccountView,
+    pub token_program: &'a Address,
+}
+
+impl CloseAccount<'_> {
+    #[inline(always)]
+    pub fn invoke_signed(&self, signers: &[Signer]) -> ProgramResult {
+        let accounts = [
+            InstructionAccount::writable(self.account.address()),
+            InstructionAccount::writable(self.destination.address()),
+            InstructionAccount::readonly_signer(self.authority.address()),
+        ];
+
+        let instruction = InstructionView {
+            program_id: self.token_program,
+            accounts: &accounts,
+            data: &[9],
+        };
+        invoke_signed(&instruction, &[self.account, self.destination, self.authority], signers)
+    }
+}
@@ -0,0 +1,32 @@
+#!/usr/bin/env sh
+# Fails if any on-chain build of the program links a heap allocation.
+#
+# `no_std` makes `Vec`, `String` and `format!` a compile error in our own
+# code, but a dependency (or a feature-gated path) can still pull in the
+# `alloc` crate. Under `no_allocator!()` that compiles and links, then
+# aborts at runtime, so look for the allocator shim rustc emits
+# (`__rust_alloc` and friends) in the unstripped build of each feature set.
+# build-sbf overwrites that build every time, hence one check per build.
+set -eu
+
+manifest=programs/solana-redpacket/Cargo.toml
+out=target/deploy/no-alloc
+failed=0
+
+for features in "" cpi-events logging logging,cpi-events; do
+  name=${features:-default}
+  cargo build-sbf --manifest-path "$manifest" ${features:+--features "$features"} --sbf-out-dir "$out/$name"
+
+  # Newest first: older toolchains built under target/sbf-solana-solana
+  builds=$(find target -path '*-solana-solana/release/solana_redpacket.so')
+  unstripped=${builds:+$(ls -t $builds | head -n 1)}
+  if [ -z "$unstripped" ]; then
+    echo "error: no unstripped build of solana_redpacket.so under target/" >&2
+    exit 1
+  fi
+  if grep -a -q -e __rust_alloc -e __rg_alloc "$unstripped"; then
+    echo "error: the $name build links a heap allocation" >&2
+    failed=1
+  fi
+done
+exit $failed